@@ -0,0 +1,115 @@
+//! Measures how long a router needs to find the route of a request depending on the number of routes,
+//! compared to scanning all routes with `Routeable::matches` like the router did before it had a route tree.
+//! Run with `cargo bench --bench routing`.
+
+use std::collections::HashSet;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use tii::{HttpMethod, HttpVersion, QValue, RequestContext, Response, Routeable, RoutingDecision};
+use tii::{Router, RouterBuilder, TiiResult, TypeSystem};
+
+const ITERATIONS: u32 = 10_000;
+
+fn endpoint(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::no_content())
+}
+
+fn request(path: &str) -> TiiResult<RequestContext> {
+  RequestContext::new(
+    0,
+    "peer",
+    "local",
+    HttpMethod::Put,
+    HttpVersion::Http11,
+    path,
+    Vec::<(String, String)>::new(),
+    Vec::new(),
+    None,
+    None,
+    TypeSystem::empty(),
+  )
+}
+
+/// The way the router used to route before it had a route tree.
+fn linear_scan<'a>(routes: &'a [Routeable], request: &RequestContext) -> Option<&'a Routeable> {
+  let mut best_decision = RoutingDecision::PathMismatch;
+  let mut best_route = None;
+  for route in routes {
+    let decision = route.matches(request);
+    if best_decision >= decision {
+      continue;
+    }
+
+    best_decision = decision;
+    if let RoutingDecision::Match(qv, _) = &best_decision {
+      best_route = Some(route);
+      if qv == &QValue::MAX {
+        break;
+      }
+    }
+  }
+
+  best_route
+}
+
+fn measure(mut run: impl FnMut() -> TiiResult<()>) -> TiiResult<Duration> {
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    run()?;
+  }
+  Ok(start.elapsed() / ITERATIONS)
+}
+
+fn main() -> TiiResult<()> {
+  for count in [10, 100, 500, 1000] {
+    let mut builder = RouterBuilder::default();
+    let mut routes = Vec::new();
+    for i in 0..count {
+      builder = builder
+        .route_get(&format!("/api/resource{i}"), endpoint)?
+        .route_get(&format!("/api/resource{i}/{{id}}"), endpoint)?
+        .route_put(&format!("/api/resource{i}/{{id}}"), endpoint)?;
+      routes.push(Routeable::new(
+        format!("/api/resource{i}"),
+        HttpMethod::Get,
+        HashSet::new(),
+        HashSet::new(),
+      )?);
+      routes.push(Routeable::new(
+        format!("/api/resource{i}/{{id}}"),
+        HttpMethod::Get,
+        HashSet::new(),
+        HashSet::new(),
+      )?);
+      routes.push(Routeable::new(
+        format!("/api/resource{i}/{{id}}"),
+        HttpMethod::Put,
+        HashSet::new(),
+        HashSet::new(),
+      )?);
+    }
+    let router = builder.build();
+
+    for (name, path) in [
+      ("first", "/api/resource0/42".to_string()),
+      ("last", format!("/api/resource{}/42", count - 1)),
+    ] {
+      let mut request = request(&path)?;
+      let scan = measure(|| {
+        black_box(linear_scan(black_box(&routes), black_box(&request)));
+        Ok(())
+      })?;
+      // The router also runs the endpoint, which does nothing but create an empty response.
+      let tree = measure(|| {
+        black_box(router.serve(black_box(&mut request))?);
+        Ok(())
+      })?;
+      println!(
+        "{} routes, {name} route: linear scan {scan:?}/request, router {tree:?}/request",
+        count * 3
+      );
+    }
+  }
+
+  Ok(())
+}
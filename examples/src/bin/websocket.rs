@@ -83,6 +83,10 @@ fn echo_handler(
         WebsocketMessage::Pong => {
           info!("Received pong");
         }
+        WebsocketMessage::Close(reason) => {
+          info!("Connection closed by {addr} with {reason:?}");
+          return Ok(());
+        }
       },
       Ok(ReadMessageTimeoutResult::Timeout) => {
         info!("No message received in 5s sending ping...");
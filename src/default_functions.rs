@@ -1,9 +1,10 @@
+use crate::Routeable;
+use crate::WebSocketHandshakeError;
 use crate::{error_log, info_log};
 use crate::{trace_log, RequestContext};
+use crate::{RequestBodyError, RequestHeadParsingError, TiiError, TiiResult};
 use crate::{Response, StatusCode};
-use crate::{Routeable, RoutingDecision};
-use crate::{TiiError, TiiResult};
-use std::collections::HashSet;
+use std::io::ErrorKind;
 
 pub(crate) fn default_pre_routing_filter(_request: &RequestContext) -> TiiResult<bool> {
   Ok(true)
@@ -15,6 +16,56 @@ pub(crate) fn default_error_handler(
   request: &mut RequestContext,
   error: TiiError,
 ) -> TiiResult<Response> {
+  match error.downcast_ref::<RequestBodyError>() {
+    Some(RequestBodyError::TooLarge(max_size)) => {
+      info_log!(
+        "Request {} Content Too Large {} {} request body exceeds {} bytes",
+        request.id(),
+        &request.get_method(),
+        request.get_path(),
+        max_size
+      );
+      return Ok(Response::content_too_large_no_body());
+    }
+    Some(RequestBodyError::MultipartPartTooLarge(_))
+    | Some(RequestBodyError::MultipartTooLarge(_)) => {
+      info_log!(
+        "Request {} Content Too Large {} {} {:?}",
+        request.id(),
+        &request.get_method(),
+        request.get_path(),
+        error
+      );
+      return Ok(Response::content_too_large_no_body());
+    }
+    Some(RequestBodyError::InvalidMultipart(_))
+    | Some(RequestBodyError::InvalidUrlEncodedForm(_)) => {
+      info_log!(
+        "Request {} Bad Request {} {} {:?}",
+        request.id(),
+        &request.get_method(),
+        request.get_path(),
+        error
+      );
+      return Ok(Response::bad_request_no_body());
+    }
+    _ => {}
+  }
+
+  match error.downcast_ref::<WebSocketHandshakeError>() {
+    Some(WebSocketHandshakeError::UnsupportedProtocol(offered)) => {
+      info_log!(
+        "Request {} Bad Request {} {} no supported websocket subprotocol in '{}'",
+        request.id(),
+        &request.get_method(),
+        request.get_path(),
+        offered
+      );
+      return Ok(Response::bad_request_no_body());
+    }
+    None => {}
+  }
+
   error_log!(
     "Request {} Internal Server Error {} {} {:?}",
     request.id(),
@@ -60,7 +111,7 @@ pub(crate) fn default_not_acceptable_handler(
 
 pub(crate) fn default_method_not_allowed_handler(
   request: &mut RequestContext,
-  routes: &[Routeable],
+  _: &[Routeable],
 ) -> TiiResult<Response> {
   info_log!(
     "Request {} Method not allowed {} {}",
@@ -68,17 +119,8 @@ pub(crate) fn default_method_not_allowed_handler(
     request.get_method(),
     request.get_path()
   );
-  let mut methods = HashSet::new();
-  for route in routes {
-    if matches!(route.matches(request), RoutingDecision::MethodMismatch) {
-      methods.insert(route.get_method().clone());
-    }
-  }
-
-  let mut methods = methods.into_iter().collect::<Vec<_>>();
-  methods.sort();
-
-  Ok(Response::method_not_allowed(methods.as_slice()))
+  // The router adds the Allow header, it knows which methods are handled automatically.
+  Ok(Response::method_not_allowed(&[]))
 }
 
 pub(crate) fn default_unsupported_media_type_handler(
@@ -104,3 +146,31 @@ pub(crate) fn default_continue_handler(request: &mut RequestContext) -> TiiResul
   );
   Ok(true)
 }
+
+/// The default handler for requests that could not be parsed.
+/// This can be overridden by using the `with_parse_error_handler` method when building the app.
+pub(crate) fn default_parse_error_handler(error: &TiiError) -> TiiResult<Option<Response>> {
+  let Some(parsing_error) = error.downcast_ref::<RequestHeadParsingError>() else {
+    return Ok(match error.kind() {
+      ErrorKind::TimedOut | ErrorKind::WouldBlock => {
+        info_log!("Request Timeout while reading request head {:?}", error);
+        Some(Response::new(StatusCode::RequestTimeout))
+      }
+      // The client is gone or sent garbage, there is nobody to respond to.
+      _ => None,
+    });
+  };
+
+  let status = match parsing_error {
+    RequestHeadParsingError::StatusLineTooLong(_) => StatusCode::RequestURITooLong,
+    RequestHeadParsingError::HeaderLineTooLong(_) => StatusCode::RequestHeaderFieldsTooLarge,
+    RequestHeadParsingError::HttpVersionNotSupported(_)
+    | RequestHeadParsingError::HttpVersionDisabled(_) => StatusCode::VersionNotSupported,
+    RequestHeadParsingError::TransferEncodingNotSupported(_) => StatusCode::NotImplemented,
+    RequestHeadParsingError::ContentEncodingNotSupported(_) => StatusCode::UnsupportedMediaType,
+    _ => StatusCode::BadRequest,
+  };
+
+  info_log!("Unparseable request {} {:?}", status.code(), parsing_error);
+  Ok(Some(Response::new(status)))
+}
@@ -1,6 +1,6 @@
 //! Provides a number of useful handlers for Tii apps.
-use crate::HttpHeaderName;
 use crate::ResponseBody;
+use crate::{format_http_date, HttpHeaderName};
 use crate::{Response, StatusCode};
 
 use crate::MimeType;
@@ -9,6 +9,7 @@ use crate::TiiResult;
 use std::fs::{metadata, File};
 use std::io::ErrorKind;
 use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
 
 const INDEX_FILES: [&str; 2] = ["index.html", "index.htm"];
 
@@ -24,18 +25,33 @@ fn try_file_open(path: &PathBuf) -> TiiResult<Response> {
   let mime = MimeType::from_extension(
     path.extension().map(|a| a.to_string_lossy().to_string()).unwrap_or("".to_string()).as_str(),
   );
-  Ok(File::open(path).and_then(ResponseBody::from_file).map(|a| Response::ok(a, mime)).or_else(
-    |e| {
-      if e.kind() == ErrorKind::NotFound {
-        Ok(Response::not_found_no_body())
-      } else {
-        Err(e)
-      }
-    },
-  )?)
+
+  let file = match File::open(path) {
+    Ok(file) => file,
+    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Response::not_found_no_body()),
+    Err(e) => return Err(e.into()),
+  };
+
+  let modified = file.metadata()?.modified().ok();
+  let mut response = Response::ok(ResponseBody::from_file(file)?, mime);
+
+  if let Some(modified) = modified {
+    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let size = response.body.as_ref().and_then(ResponseBody::content_length).unwrap_or_default();
+    response.add_header(
+      HttpHeaderName::ETag,
+      format!("\"{:x}-{:x}-{:x}\"", size, since_epoch.as_secs(), since_epoch.subsec_nanos()),
+    )?;
+    response.add_header(HttpHeaderName::LastModified, format_http_date(modified))?;
+  }
+
+  Ok(response)
 }
 
 /// Serve the specified file, or a default error 404 if not found.
+///
+/// The response carries an `ETag` and a `Last-Modified` header derived from the file metadata,
+/// conditional requests are answered with 304 Not Modified.
 pub fn serve_file(file_path: &'static str) -> impl Fn(&RequestContext) -> TiiResult<Response> {
   let path_buf = PathBuf::from(file_path);
 
@@ -65,6 +81,7 @@ pub fn serve_as_file_path(
 }
 
 /// Serves a directory of files.
+/// Like `serve_file` the responses carry an `ETag` and a `Last-Modified` header.
 ///
 /// Respects index files with the following rules:
 ///   - requests to `/directory` will return either the file `directory`, 301 redirect to `/directory/` if it is a directory, or return 404
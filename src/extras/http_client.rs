@@ -0,0 +1,686 @@
+//! Minimal blocking HTTP/1.1 client that reuses the parsing and stream layer of tii.
+
+use crate::http::{invalid_response, is_token, ResponseHead};
+use crate::util::unwrap_poison;
+use crate::{
+  ConnectionStream, HttpHeader, HttpHeaderName, HttpMethod, HttpVersion, IntoConnectionStream,
+  RequestBody, RequestBodyError, StatusCode, TiiError, TiiResult,
+};
+use std::collections::HashMap;
+use std::io;
+use std::io::{Cursor, ErrorKind, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Where a connection for an url goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+  Tcp { host: String, port: u16, tls: bool },
+  Unix(PathBuf),
+}
+
+/// The parts of an url the client needs.
+///
+/// Supported schemes are `http`, `https` and `http+unix`.
+/// The authority of a `http+unix` url is the percent encoded path of the unix socket,
+/// for example `http+unix://%2Frun%2Fapp.sock/path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Url {
+  scheme: String,
+  authority: String,
+  target: Target,
+  path: String,
+}
+
+impl Url {
+  pub(super) fn parse(url: &str) -> TiiResult<Self> {
+    let invalid = || TiiError::new_io(ErrorKind::InvalidInput, format!("invalid url: {url}"));
+    let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+    let scheme = scheme.to_ascii_lowercase();
+    let rest = rest.split('#').next().unwrap_or_default();
+    let split = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(split);
+    if authority.is_empty() {
+      return Err(invalid());
+    }
+
+    let path = match path.starts_with('/') {
+      true => path.to_string(),
+      false => format!("/{path}"),
+    };
+
+    let target = match scheme.as_str() {
+      "http" | "https" => {
+        let tls = scheme == "https";
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = match authority.rfind(':') {
+          Some(index) if !authority.ends_with(']') => {
+            let (host, port) = authority.split_at(index);
+            (host, port.trim_start_matches(':').parse::<u16>().map_err(|_| invalid())?)
+          }
+          _ => (authority, default_port),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+          return Err(invalid());
+        }
+        Target::Tcp { host: host.to_string(), port, tls }
+      }
+      "http+unix" => Target::Unix(PathBuf::from(
+        urlencoding::decode(authority).map_err(|_| invalid())?.into_owned(),
+      )),
+      _ => return Err(TiiError::new_io(ErrorKind::Unsupported, format!("unsupported url: {url}"))),
+    };
+
+    Ok(Self { scheme, authority: authority.to_string(), target, path })
+  }
+
+  /// Resolves the value of a `Location` header relative to this url.
+  fn resolve(&self, location: &str) -> TiiResult<Self> {
+    if location.contains("://") {
+      return Self::parse(location);
+    }
+
+    if location.starts_with("//") {
+      return Self::parse(&format!("{}:{}", self.scheme, location));
+    }
+
+    let location = location.split('#').next().unwrap_or_default();
+    let path = if location.starts_with('/') {
+      location.to_string()
+    } else {
+      let base = self.path.split('?').next().unwrap_or_default();
+      let directory = base.rfind('/').map(|index| base.split_at(index + 1).0).unwrap_or("/");
+      format!("{directory}{location}")
+    };
+
+    Ok(self.with_path(path))
+  }
+
+  /// Returns the path and query.
+  pub(super) fn path(&self) -> &str {
+    &self.path
+  }
+
+  /// Returns the url with the path and query replaced.
+  pub(super) fn with_path(&self, path: String) -> Self {
+    Self { path, ..self.clone() }
+  }
+
+  /// Connections are pooled by this key.
+  fn key(&self) -> String {
+    format!("{}://{}", self.scheme, self.authority)
+  }
+
+  pub(super) fn host_header(&self) -> &str {
+    match self.target {
+      Target::Unix(_) => "localhost",
+      Target::Tcp { .. } => &self.authority,
+    }
+  }
+}
+
+/// Returns true if the error indicates that the server closed an idle connection.
+fn is_closed_connection(result: &io::Result<bool>) -> bool {
+  match result {
+    Ok(readable) => !readable,
+    Err(err) => matches!(
+      err.kind(),
+      ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+    ),
+  }
+}
+
+/// Error for a server that closed the connection after receiving the request.
+fn no_response() -> TiiError {
+  TiiError::new_io(
+    ErrorKind::UnexpectedEof,
+    "server closed the connection without sending a response",
+  )
+}
+
+/// Blocking HTTP/1.1 client.
+///
+/// The client keeps connections alive and reuses them for further requests to the same scheme, host and port.
+/// Responses are always read completely, bodies with chunked transfer encoding and gzip or deflate content
+/// encoding are decoded. Redirects are followed.
+///
+/// Urls with the schemes `http`, `https` and `http+unix` are supported.
+/// The authority of a `http+unix` url is the percent encoded path of the unix socket,
+/// for example `http+unix://%2Frun%2Fapp.sock/path`.
+/// `https` requires the `tls` feature and a client config, see `HttpClient::with_tls_config`.
+///
+/// The client can be shared between threads. Each request uses its own connection.
+///
+/// If the server closed an idle connection without answering, requests with idempotent methods are sent again
+/// on a new connection. Requests with other methods, for example POST, are never sent twice, they fail instead.
+#[derive(Debug)]
+pub struct HttpClient {
+  pool: Mutex<HashMap<String, Vec<Box<dyn ConnectionStream>>>>,
+  timeout: Option<Duration>,
+  max_redirects: usize,
+  max_idle_connections: usize,
+  max_response_body_size: Option<u64>,
+  #[cfg(feature = "tls")]
+  tls_config: Option<std::sync::Arc<rustls::ClientConfig>>,
+}
+
+impl Default for HttpClient {
+  fn default() -> Self {
+    Self {
+      pool: Mutex::new(HashMap::new()),
+      timeout: Some(Duration::from_secs(30)),
+      max_redirects: 10,
+      max_idle_connections: 8,
+      max_response_body_size: None,
+      #[cfg(feature = "tls")]
+      tls_config: None,
+    }
+  }
+}
+
+impl HttpClient {
+  /// Creates a client that follows up to 10 redirects and times out connecting, reading or writing after 30 seconds.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the timeout for connecting and for each read and write operation. None means no timeout.
+  pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Sets how many redirects are followed for a single request before the request fails.
+  /// 0 disables following redirects, the redirect response is returned instead.
+  pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+    self.max_redirects = max_redirects;
+    self
+  }
+
+  /// Sets how many idle connections are kept alive per scheme, host and port. 0 disables keep-alive.
+  pub fn with_max_idle_connections(mut self, max_idle_connections: usize) -> Self {
+    self.max_idle_connections = max_idle_connections;
+    self
+  }
+
+  /// Sets the maximum size of a decoded response body. Larger responses fail the request.
+  /// None means no limit.
+  pub fn with_max_response_body_size(mut self, max_size: Option<u64>) -> Self {
+    self.max_response_body_size = max_size;
+    self
+  }
+
+  /// Sets the tls config that is used for `https` urls.
+  #[cfg(feature = "tls")]
+  pub fn with_tls_config(mut self, config: std::sync::Arc<rustls::ClientConfig>) -> Self {
+    self.tls_config = Some(config);
+    self
+  }
+
+  /// Creates a request with the given method for the url.
+  pub fn request(&self, method: HttpMethod, url: impl ToString) -> HttpClientRequest<'_> {
+    HttpClientRequest {
+      client: self,
+      method,
+      url: url.to_string(),
+      headers: Vec::new(),
+      body: None,
+    }
+  }
+
+  /// Creates a GET request.
+  pub fn get(&self, url: impl ToString) -> HttpClientRequest<'_> {
+    self.request(HttpMethod::Get, url)
+  }
+
+  /// Creates a HEAD request.
+  pub fn head(&self, url: impl ToString) -> HttpClientRequest<'_> {
+    self.request(HttpMethod::Head, url)
+  }
+
+  /// Creates a POST request.
+  pub fn post(&self, url: impl ToString) -> HttpClientRequest<'_> {
+    self.request(HttpMethod::Post, url)
+  }
+
+  /// Creates a PUT request.
+  pub fn put(&self, url: impl ToString) -> HttpClientRequest<'_> {
+    self.request(HttpMethod::Put, url)
+  }
+
+  /// Creates a PATCH request.
+  pub fn patch(&self, url: impl ToString) -> HttpClientRequest<'_> {
+    self.request(HttpMethod::Patch, url)
+  }
+
+  /// Creates a DELETE request.
+  pub fn delete(&self, url: impl ToString) -> HttpClientRequest<'_> {
+    self.request(HttpMethod::Delete, url)
+  }
+
+  /// Closes all idle connections.
+  pub fn close_idle_connections(&self) {
+    if let Ok(mut pool) = unwrap_poison(self.pool.lock()) {
+      pool.clear();
+    }
+  }
+
+  /// Returns the number of idle connections to all hosts.
+  pub fn idle_connections(&self) -> usize {
+    unwrap_poison(self.pool.lock())
+      .map(|pool| pool.values().map(Vec::len).sum())
+      .unwrap_or_default()
+  }
+
+  fn take_idle(&self, key: &str) -> TiiResult<Option<Box<dyn ConnectionStream>>> {
+    Ok(unwrap_poison(self.pool.lock())?.get_mut(key).and_then(Vec::pop))
+  }
+
+  fn put_idle(&self, key: String, stream: Box<dyn ConnectionStream>) -> TiiResult<()> {
+    let mut pool = unwrap_poison(self.pool.lock())?;
+    let idle = pool.entry(key).or_default();
+    if idle.len() < self.max_idle_connections {
+      idle.push(stream);
+    }
+    Ok(())
+  }
+
+  fn connect_tcp(&self, host: &str, port: u16) -> TiiResult<TcpStream> {
+    let Some(timeout) = self.timeout else {
+      return Ok(TcpStream::connect((host, port))?);
+    };
+
+    let mut last_err = None;
+    for addr in (host, port).to_socket_addrs()? {
+      match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(stream) => return Ok(stream),
+        Err(err) => last_err = Some(err),
+      }
+    }
+
+    Err(
+      last_err
+        .unwrap_or_else(|| io::Error::new(ErrorKind::NotFound, format!("could not resolve {host}")))
+        .into(),
+    )
+  }
+
+  #[cfg(feature = "tls")]
+  fn connect_tls(&self, stream: TcpStream, host: &str) -> TiiResult<Box<dyn ConnectionStream>> {
+    let Some(config) = self.tls_config.clone() else {
+      return Err(TiiError::new_io(ErrorKind::Unsupported, "https requires a tls client config"));
+    };
+
+    let name = rustls::pki_types::ServerName::try_from(host.to_string())
+      .map_err(|err| TiiError::new_io(ErrorKind::InvalidInput, err))?;
+    let connection = rustls::ClientConnection::new(config, name)
+      .map_err(|err| TiiError::new_io(ErrorKind::Other, err))?;
+    Ok(crate::TlsStream::create_client_unpooled(stream, connection)?)
+  }
+
+  #[cfg(not(feature = "tls"))]
+  fn connect_tls(&self, _: TcpStream, _: &str) -> TiiResult<Box<dyn ConnectionStream>> {
+    Err(TiiError::new_io(ErrorKind::Unsupported, "https requires the tls feature"))
+  }
+
+  pub(super) fn connect(&self, url: &Url) -> TiiResult<Box<dyn ConnectionStream>> {
+    let stream = match &url.target {
+      Target::Tcp { host, port, tls } => {
+        let stream = self.connect_tcp(host, *port)?;
+        stream.set_nodelay(true)?;
+        match tls {
+          true => self.connect_tls(stream, host)?,
+          false => stream.into_connection_stream(),
+        }
+      }
+      #[cfg(unix)]
+      Target::Unix(path) => std::os::unix::net::UnixStream::connect(path)?.into_connection_stream(),
+      #[cfg(not(unix))]
+      Target::Unix(_) => {
+        return Err(TiiError::new_io(ErrorKind::Unsupported, "unix sockets are not supported"))
+      }
+    };
+
+    stream.set_read_timeout(self.timeout)?;
+    stream.set_write_timeout(self.timeout)?;
+    Ok(stream)
+  }
+
+  /// Returns true if the server has neither closed the idle connection nor sent anything on it.
+  fn is_idle_open(&self, stream: &dyn ConnectionStream) -> io::Result<bool> {
+    stream.set_read_timeout(Some(Duration::from_millis(1)))?;
+    let result = stream.ensure_readable();
+    stream.set_read_timeout(self.timeout)?;
+    match result {
+      Ok(_) => Ok(false),
+      Err(err) => match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => Ok(true),
+        ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => {
+          Ok(false)
+        }
+        _ => Err(err),
+      },
+    }
+  }
+
+  /// Connects to the server and sends the request on the new connection.
+  fn connect_and_transmit(&self, url: &Url, data: &[u8]) -> TiiResult<Box<dyn ConnectionStream>> {
+    let stream = self.connect(url)?;
+    if !Self::transmit(stream.as_ref(), data)? {
+      return Err(no_response());
+    }
+    Ok(stream)
+  }
+
+  /// Writes the request and waits until the response can be read.
+  /// Returns Ok(false) if the server closed the connection instead.
+  fn transmit(stream: &dyn ConnectionStream, data: &[u8]) -> io::Result<bool> {
+    stream.write_all(data)?;
+    stream.flush()?;
+    stream.ensure_readable()
+  }
+
+  /// Sends a single request without following redirects.
+  fn execute(
+    &self,
+    method: &HttpMethod,
+    url: &Url,
+    headers: &[HttpHeader],
+    body: Option<&[u8]>,
+  ) -> TiiResult<HttpClientResponse> {
+    let data = serialize_request(method, url, headers, body, self.max_idle_connections > 0);
+    let key = url.key();
+
+    //An idle connection may have been closed by the server in the meantime.
+    //Idempotent requests are sent again on a new connection if the server did not answer at all.
+    //Other requests may have been processed even though no response arrived, they are never sent twice.
+    let stream = match self.take_idle(&key)? {
+      Some(idle) if method.is_idempotent() => {
+        match is_closed_connection(&Self::transmit(idle.as_ref(), &data)) {
+          false => idle,
+          true => self.connect_and_transmit(url, &data)?,
+        }
+      }
+      Some(idle) if self.is_idle_open(idle.as_ref())? => {
+        if !Self::transmit(idle.as_ref(), &data)? {
+          return Err(no_response());
+        }
+        idle
+      }
+      _ => self.connect_and_transmit(url, &data)?,
+    };
+
+    let head = loop {
+      let head = ResponseHead::read(stream.as_ref())?;
+      let code = head.status_code.code();
+      //Interim responses like 100 Continue are skipped.
+      if code >= 200 || code == 101 {
+        break head;
+      }
+    };
+
+    let mut reusable = head.keeps_alive() && head.status_code.code() != 101;
+    let response_body = if !head.has_body(*method == HttpMethod::Head) {
+      Vec::new()
+    } else {
+      let codings = head.content_codings()?;
+      let body = if head.is_chunked() {
+        RequestBody::new_encoded_chunked(stream.new_ref_read(), &codings)?
+      } else if let Some(length) = head.content_length()? {
+        RequestBody::new_encoded_with_content_length(stream.new_ref_read(), length, &codings)?
+      } else {
+        //The body ends when the server closes the connection.
+        reusable = false;
+        let mut raw = Vec::new();
+        match self.max_response_body_size {
+          Some(max_size) => {
+            stream.new_ref_read().take(max_size.saturating_add(1)).read_to_end(&mut raw)?;
+            if raw.len() as u64 > max_size {
+              return Err(io::Error::from(RequestBodyError::TooLarge(max_size)).into());
+            }
+          }
+          None => _ = stream.new_ref_read().read_to_end(&mut raw)?,
+        }
+        let length = raw.len() as u64;
+        RequestBody::new_encoded_with_content_length(Cursor::new(raw), length, &codings)?
+      };
+
+      body.set_max_size(self.max_response_body_size)?;
+      body.read_to_vec()?
+    };
+
+    if reusable {
+      self.put_idle(key, stream)?;
+    }
+
+    let ResponseHead { version, status_code, headers } = head;
+    Ok(HttpClientResponse { url: url.clone(), version, status_code, headers, body: response_body })
+  }
+}
+
+fn serialize_request(
+  method: &HttpMethod,
+  url: &Url,
+  headers: &[HttpHeader],
+  body: Option<&[u8]>,
+  keep_alive: bool,
+) -> Vec<u8> {
+  let has_header = |name: &HttpHeaderName| headers.iter().any(|header| &header.name == name);
+
+  let mut data = Vec::new();
+  data.extend_from_slice(format!("{} {} HTTP/1.1\r\n", method.as_str(), url.path).as_bytes());
+  if !has_header(&HttpHeaderName::Host) {
+    data.extend_from_slice(format!("Host: {}\r\n", url.host_header()).as_bytes());
+  }
+  if !has_header(&HttpHeaderName::AcceptEncoding) {
+    data.extend_from_slice(b"Accept-Encoding: gzip, deflate\r\n");
+  }
+  if !has_header(&HttpHeaderName::Connection) {
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    data.extend_from_slice(format!("Connection: {connection}\r\n").as_bytes());
+  }
+  for header in headers {
+    data.extend_from_slice(format!("{}: {}\r\n", header.name, header.value).as_bytes());
+  }
+
+  let Some(body) = body else {
+    data.extend_from_slice(b"\r\n");
+    return data;
+  };
+
+  data.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+  data.extend_from_slice(body);
+  data
+}
+
+/// A request that is built and then sent by a `HttpClient`.
+#[derive(Debug)]
+pub struct HttpClientRequest<'a> {
+  client: &'a HttpClient,
+  method: HttpMethod,
+  url: String,
+  headers: Vec<HttpHeader>,
+  body: Option<Vec<u8>>,
+}
+
+impl HttpClientRequest<'_> {
+  /// Adds a request header.
+  /// `Host`, `Accept-Encoding: gzip, deflate` and `Connection` are added unless they are set explicitly.
+  /// `Content-Length` headers are ignored, the client sets it from the body.
+  /// # Errors
+  /// If the name is not a valid token or the value contains a line break.
+  pub fn with_header(mut self, name: impl AsRef<str>, value: impl ToString) -> TiiResult<Self> {
+    let header = HttpHeader::new(name, value);
+    if !is_token(header.name.to_str()) || header.value.contains(['\r', '\n']) {
+      return Err(TiiError::new_io(
+        ErrorKind::InvalidInput,
+        format!("invalid header {}", header.name),
+      ));
+    }
+
+    if header.name != HttpHeaderName::ContentLength {
+      self.headers.push(header);
+    }
+    Ok(self)
+  }
+
+  /// Sets the request body.
+  pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+    self.body = Some(body.into());
+    self
+  }
+
+  /// Sends the request, follows redirects and reads the response.
+  ///
+  /// 303 See Other responses, as well as 301 and 302 responses to POST requests, are followed with a GET request
+  /// without body. 307 and 308 responses are followed with the same method and body.
+  /// `Authorization` and `Cookie` headers are not sent to a different scheme, host or port.
+  pub fn send(self) -> TiiResult<HttpClientResponse> {
+    let client = self.client;
+    let mut url = Url::parse(&self.url)?;
+    let mut method = self.method;
+    let mut headers = self.headers;
+    let mut body = self.body;
+
+    let mut redirects = 0;
+    loop {
+      let response = client.execute(&method, &url, &headers, body.as_deref())?;
+      let code = response.get_status_code_number();
+      if !matches!(code, 301 | 302 | 303 | 307 | 308) || client.max_redirects == 0 {
+        return Ok(response);
+      }
+
+      let Some(location) = response.get_header(HttpHeaderName::Location) else {
+        return Ok(response);
+      };
+
+      if redirects >= client.max_redirects {
+        return Err(invalid_response("too many redirects"));
+      }
+
+      let next = url.resolve(location)?;
+      if code == 303 || (code != 307 && code != 308 && method == HttpMethod::Post) {
+        if method != HttpMethod::Head {
+          method = HttpMethod::Get;
+        }
+        body = None;
+        headers.retain(|header| header.name != HttpHeaderName::ContentType);
+      }
+
+      if next.key() != url.key() {
+        headers.retain(|header| {
+          header.name != HttpHeaderName::Authorization && header.name != HttpHeaderName::Cookie
+        });
+      }
+
+      url = next;
+      redirects += 1;
+    }
+  }
+}
+
+/// A response received by a `HttpClient`.
+#[derive(Debug, Clone)]
+pub struct HttpClientResponse {
+  url: Url,
+  version: HttpVersion,
+  status_code: StatusCode,
+  headers: Vec<HttpHeader>,
+  body: Vec<u8>,
+}
+
+impl HttpClientResponse {
+  /// Returns the url of the response. This differs from the url of the request if redirects were followed.
+  pub fn get_url(&self) -> String {
+    format!("{}{}", self.url.key(), self.url.path)
+  }
+
+  /// Returns the http version of the response.
+  pub fn get_version(&self) -> HttpVersion {
+    self.version
+  }
+
+  /// Returns the status code.
+  pub fn get_status_code(&self) -> &StatusCode {
+    &self.status_code
+  }
+
+  /// Returns the numeric status code.
+  pub fn get_status_code_number(&self) -> u16 {
+    self.status_code.code()
+  }
+
+  /// Returns the first value of the header or None if the response has no such header.
+  /// Header names are compared case-insensitively.
+  pub fn get_header(&self, name: impl AsRef<str>) -> Option<&str> {
+    self.get_headers(name).into_iter().next()
+  }
+
+  /// Returns all values of the header in order of appearance.
+  /// Header names are compared case-insensitively.
+  pub fn get_headers(&self, name: impl AsRef<str>) -> Vec<&str> {
+    let name = name.as_ref();
+    self
+      .headers
+      .iter()
+      .filter(|header| header.name.to_str().eq_ignore_ascii_case(name))
+      .map(|header| header.value.as_str())
+      .collect()
+  }
+
+  /// Returns all headers in order of appearance.
+  pub fn get_all_headers(&self) -> &[HttpHeader] {
+    &self.headers
+  }
+
+  /// Returns the body after the transfer encoding and content encoding has been removed.
+  pub fn get_body(&self) -> &[u8] {
+    &self.body
+  }
+
+  /// Returns the body as text or None if it is not valid utf-8.
+  pub fn get_body_text(&self) -> Option<&str> {
+    std::str::from_utf8(&self.body).ok()
+  }
+
+  /// Returns the body after the transfer encoding and content encoding has been removed.
+  pub fn into_body(self) -> Vec<u8> {
+    self.body
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::extras::http_client::{Target, Url};
+  use std::path::PathBuf;
+
+  #[test]
+  fn test_url() {
+    let url = Url::parse("http://localhost:8080/a/b?c=d#e").unwrap();
+    assert_eq!(url.target, Target::Tcp { host: "localhost".to_string(), port: 8080, tls: false });
+    assert_eq!(url.path, "/a/b?c=d");
+    assert_eq!(url.key(), "http://localhost:8080");
+
+    let url = Url::parse("HTTPS://[::1]?x").unwrap();
+    assert_eq!(url.target, Target::Tcp { host: "::1".to_string(), port: 443, tls: true });
+    assert_eq!(url.path, "/?x");
+
+    let url = Url::parse("http+unix://%2Ftmp%2Fapp.sock/x").unwrap();
+    assert_eq!(url.target, Target::Unix(PathBuf::from("/tmp/app.sock")));
+    assert_eq!(url.host_header(), "localhost");
+
+    assert!(Url::parse("localhost/x").is_err());
+    assert!(Url::parse("http:///x").is_err());
+    assert!(Url::parse("http://host:port/").is_err());
+    assert!(Url::parse("ftp://host/").is_err());
+
+    let base = Url::parse("http://host/a/b?q").unwrap();
+    assert_eq!(base.resolve("c").unwrap().path, "/a/c");
+    assert_eq!(base.resolve("/c?d").unwrap().path, "/c?d");
+    assert_eq!(base.resolve("//other/c").unwrap().key(), "http://other");
+    assert_eq!(base.resolve("https://other:1/").unwrap().key(), "https://other:1");
+  }
+}
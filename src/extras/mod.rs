@@ -36,3 +36,9 @@ pub(crate) mod tls_connector_impl;
 #[cfg(feature = "tls")]
 #[cfg(unix)]
 pub use tls_unix_connector::*;
+
+mod http_client;
+pub use http_client::*;
+
+mod reverse_proxy;
+pub use reverse_proxy::*;
@@ -0,0 +1,336 @@
+//! Endpoint that forwards requests to an upstream http server.
+
+use crate::extras::http_client::Url;
+use crate::extras::{ConnectorMeta, HttpClient};
+use crate::functional_traits::HttpEndpoint;
+use crate::http::ResponseHead;
+use crate::util::unwrap_some;
+use crate::{
+  error_log, ConnectionStream, HttpHeaderName, HttpMethod, RequestBody, RequestContext, Response,
+  ResponseBody, StatusCode, TiiError, TiiResult,
+};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Headers that only apply to a single connection and are never forwarded.
+/// Headers named in the `Connection` header are not forwarded either.
+const HOP_BY_HOP_HEADERS: [&str; 9] = [
+  "Connection",
+  "Keep-Alive",
+  "Proxy-Connection",
+  "Proxy-Authenticate",
+  "Proxy-Authorization",
+  "TE",
+  "Trailer",
+  "Transfer-Encoding",
+  "Upgrade",
+];
+
+/// Returns the names of all headers that must not be forwarded.
+fn hop_by_hop_headers<'a>(connection: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+  let mut names = HOP_BY_HOP_HEADERS.to_vec();
+  names.extend(connection.flat_map(|value| value.split(',')).map(str::trim));
+  names
+}
+
+fn contains_name(names: &[&str], name: &str) -> bool {
+  names.iter().any(|candidate| candidate.eq_ignore_ascii_case(name))
+}
+
+/// Formats the ip address of the client for the `Forwarded` header.
+fn forwarded_node(peer: Option<SocketAddr>) -> String {
+  match peer {
+    Some(SocketAddr::V4(addr)) => addr.ip().to_string(),
+    Some(SocketAddr::V6(addr)) => format!("\"[{}]\"", addr.ip()),
+    None => "unknown".to_string(),
+  }
+}
+
+/// Endpoint that forwards requests to an upstream http server and streams the response back.
+///
+/// The path and query of the request are appended to the path of the upstream url,
+/// optionally after a prefix was stripped from the path.
+/// All headers except hop-by-hop headers are forwarded.
+/// `X-Forwarded-For`, `X-Forwarded-Host`, `X-Forwarded-Proto` and `Forwarded` are added,
+/// the `Host` header is replaced with the host of the upstream unless the host is preserved.
+///
+/// The request body is streamed to the upstream, with chunked transfer encoding if its length is not known.
+/// Request bodies are forwarded decoded because tii decodes their content encoding.
+/// The response body of the upstream is streamed back with `ResponseBody::chunked`.
+///
+/// Each request uses its own upstream connection.
+/// If the upstream cannot be reached or sends an invalid response then 502 Bad Gateway is returned,
+/// if it does not answer in time then 504 Gateway Timeout is returned.
+///
+/// ```no_run
+/// use tii::extras::ReverseProxy;
+/// use tii::ServerBuilder;
+///
+/// let proxy = ReverseProxy::new("http://127.0.0.1:8080").unwrap().with_strip_prefix("/api");
+/// let server = ServerBuilder::default().router(|rt| rt.route_any("/api/*", proxy)).unwrap().build();
+/// ```
+#[derive(Debug)]
+pub struct ReverseProxy {
+  client: HttpClient,
+  upstream: Url,
+  strip_prefix: Option<String>,
+  preserve_host: bool,
+}
+
+impl ReverseProxy {
+  /// Creates a proxy for the upstream url. See `HttpClient` for the supported urls.
+  /// The upstream times out after 30 seconds.
+  pub fn new(upstream: impl AsRef<str>) -> TiiResult<Self> {
+    let upstream = Url::parse(upstream.as_ref())?;
+    let path = upstream.path().split('?').next().unwrap_or_default().trim_end_matches('/');
+    let upstream = upstream.with_path(path.to_string());
+    Ok(Self { client: HttpClient::new(), upstream, strip_prefix: None, preserve_host: false })
+  }
+
+  /// Sets the timeout for connecting to the upstream and for each read and write operation.
+  /// None means no timeout.
+  pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+    self.client = self.client.with_timeout(timeout);
+    self
+  }
+
+  /// Strips the prefix from the path of the request before it is appended to the upstream path.
+  /// Requests whose path does not start with the prefix are forwarded unchanged.
+  pub fn with_strip_prefix(mut self, prefix: impl ToString) -> Self {
+    self.strip_prefix = Some(prefix.to_string());
+    self
+  }
+
+  /// Forwards the `Host` header of the request instead of replacing it with the host of the upstream.
+  pub fn with_preserve_host(mut self, preserve_host: bool) -> Self {
+    self.preserve_host = preserve_host;
+    self
+  }
+
+  /// Sets the tls config that is used for `https` upstreams.
+  #[cfg(feature = "tls")]
+  pub fn with_tls_config(mut self, config: std::sync::Arc<rustls::ClientConfig>) -> Self {
+    self.client = self.client.with_tls_config(config);
+    self
+  }
+
+  /// Returns the path and query that is requested from the upstream.
+  fn upstream_path(&self, request: &RequestContext) -> String {
+    let mut path = request.get_path();
+    if let Some(stripped) = self.strip_prefix.as_ref().and_then(|prefix| path.strip_prefix(prefix))
+    {
+      path = stripped;
+    }
+
+    let path = path.split('/').map(urlencoding::encode).collect::<Vec<_>>().join("/");
+    let mut result = format!("{}/{}", self.upstream.path(), path.trim_start_matches('/'));
+    for (index, (key, value)) in request.get_query().iter().enumerate() {
+      result.push(if index == 0 { '?' } else { '&' });
+      result.push_str(&urlencoding::encode(key));
+      result.push('=');
+      result.push_str(&urlencoding::encode(value));
+    }
+
+    result
+  }
+
+  fn request_head(&self, request: &RequestContext, url: &Url, length: Option<u64>) -> String {
+    let skipped = hop_by_hop_headers(request.get_headers(HttpHeaderName::Connection).into_iter());
+    let host = request.get_header(HttpHeaderName::Host);
+
+    let mut head = format!("{} {} HTTP/1.1\r\n", request.get_method().as_str(), url.path());
+    match host.filter(|_| self.preserve_host) {
+      Some(host) => head.push_str(&format!("Host: {host}\r\n")),
+      None => head.push_str(&format!("Host: {}\r\n", url.host_header())),
+    }
+
+    for header in request.iter_headers() {
+      let name = header.name.to_str();
+      if contains_name(&skipped, name)
+        || contains_name(&["Host", "Content-Length", "Content-Encoding"], name)
+        || contains_name(&["X-Forwarded-For", "X-Forwarded-Host", "X-Forwarded-Proto"], name)
+        || header.name == HttpHeaderName::Forwarded
+      {
+        continue;
+      }
+      head.push_str(&format!("{}: {}\r\n", header.name, header.value));
+    }
+
+    let peer = request.peer_address().parse::<SocketAddr>().ok();
+    let tls = match request.get_stream_meta::<ConnectorMeta>() {
+      #[cfg(feature = "tls")]
+      Some(ConnectorMeta::TlsTcp) => true,
+      #[cfg(all(unix, feature = "tls"))]
+      Some(ConnectorMeta::TlsUnix) => true,
+      _ => false,
+    };
+    let proto = if tls { "https" } else { "http" };
+
+    let mut forwarded_for = request.get_headers("X-Forwarded-For");
+    let client = peer.map(|peer| peer.ip().to_string()).unwrap_or("unknown".to_string());
+    forwarded_for.push(&client);
+    head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded_for.join(", ")));
+    if let Some(host) = host {
+      head.push_str(&format!("X-Forwarded-Host: {host}\r\n"));
+    }
+    head.push_str(&format!("X-Forwarded-Proto: {proto}\r\n"));
+
+    let mut forwarded = request.get_headers(HttpHeaderName::Forwarded);
+    let mut node = format!("for={};proto={proto}", forwarded_node(peer));
+    if let Some(host) = host {
+      node.push_str(&format!(";host=\"{host}\""));
+    }
+    forwarded.push(&node);
+    head.push_str(&format!("Forwarded: {}\r\n", forwarded.join(", ")));
+
+    head.push_str("Connection: close\r\n");
+    match length {
+      Some(length) => head.push_str(&format!("Content-Length: {length}\r\n\r\n")),
+      None => head.push_str("Transfer-Encoding: chunked\r\n\r\n"),
+    }
+
+    head
+  }
+
+  /// Sends the request and its body to the upstream.
+  fn send_request(&self, request: &RequestContext, stream: &dyn ConnectionStream) -> TiiResult<()> {
+    let url = self.upstream.with_path(self.upstream_path(request));
+    let body = request.request_body();
+    let length = match body {
+      Some(body) => body.remaining()?,
+      None => Some(0),
+    };
+
+    stream.write_all(self.request_head(request, &url, length).as_bytes())?;
+
+    let Some(body) = body else {
+      return Ok(stream.flush()?);
+    };
+
+    let mut buffer = vec![0u8; 0x4000];
+    loop {
+      let count = body.read(&mut buffer)?;
+      if length.is_none() {
+        stream.write_all(format!("{count:X}\r\n").as_bytes())?;
+      }
+      stream.write_all(unwrap_some(buffer.get(..count)))?;
+      if length.is_none() {
+        stream.write_all(b"\r\n")?;
+      }
+      if count == 0 {
+        return Ok(stream.flush()?);
+      }
+    }
+  }
+
+  /// Reads the response head of the upstream and turns it into a response that streams the body.
+  fn receive_response(
+    &self,
+    request: &RequestContext,
+    stream: Box<dyn ConnectionStream>,
+  ) -> TiiResult<Response> {
+    let head = loop {
+      let head = ResponseHead::read(stream.as_ref())?;
+      let code = head.status_code.code();
+      //Interim responses like 100 Continue are not forwarded.
+      if code >= 200 {
+        break head;
+      }
+    };
+
+    let skipped =
+      hop_by_hop_headers(head.get_headers(HttpHeaderName::Connection.to_str()).into_iter());
+    let mut response = Response::new(head.status_code.clone());
+    for header in &head.headers {
+      let name = header.name.to_str();
+      if contains_name(&skipped, name) || header.name == HttpHeaderName::ContentLength {
+        continue;
+      }
+      response.add_header(&header.name, &header.value)?;
+    }
+
+    let head_request = *request.get_method() == HttpMethod::Head;
+    if !head.has_body(head_request) {
+      //The Content-Length of these responses is the length of the body that a GET would have returned.
+      if head_request || head.status_code == StatusCode::NotModified {
+        if let Some(length) = head.content_length()? {
+          response.set_body(Some(ResponseBody::omitted(length)));
+        }
+      }
+      return Ok(response);
+    }
+
+    //Without chunked transfer encoding or length the body ends when the upstream closes the connection.
+    let body = if head.is_chunked() {
+      Some(RequestBody::new_chunked(stream.new_ref_read()))
+    } else {
+      head
+        .content_length()?
+        .map(|length| RequestBody::new_with_content_length(stream.new_ref_read(), length))
+    };
+
+    Ok(response.with_body(ResponseBody::chunked(move |sink| {
+      let mut buffer = vec![0u8; 0x4000];
+      loop {
+        let count = match body.as_ref() {
+          Some(body) => body.read(&mut buffer)?,
+          None => stream.read(&mut buffer)?,
+        };
+        if count == 0 {
+          return Ok(());
+        }
+        sink.write_all(unwrap_some(buffer.get(..count)))?;
+      }
+    })))
+  }
+
+  fn forward(&self, request: &RequestContext) -> TiiResult<Response> {
+    let stream = self.client.connect(&self.upstream)?;
+    self.send_request(request, stream.as_ref())?;
+    self.receive_response(request, stream)
+  }
+}
+
+/// Maps an error while talking to the upstream to the status code of the response.
+fn upstream_error_status(err: &TiiError) -> StatusCode {
+  if let TiiError::IO(err) = err {
+    if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) {
+      return StatusCode::GatewayTimeout;
+    }
+  }
+  StatusCode::BadGateway
+}
+
+impl HttpEndpoint for ReverseProxy {
+  fn serve(&self, request: &RequestContext) -> TiiResult<Response> {
+    match self.forward(request) {
+      Ok(response) => Ok(response),
+      Err(err) => {
+        error_log!(
+          "tii: reverse_proxy: request {} to {} failed: {}",
+          request.id(),
+          self.upstream.host_header(),
+          err
+        );
+        Ok(Response::new(upstream_error_status(&err)))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::extras::reverse_proxy::{forwarded_node, hop_by_hop_headers};
+
+  #[test]
+  fn test_headers() {
+    let names = hop_by_hop_headers(["keep-alive, X-Secret"].into_iter());
+    assert!(names.contains(&"X-Secret"));
+    assert!(names.contains(&"Upgrade"));
+
+    assert_eq!(forwarded_node("127.0.0.1:80".parse().ok()), "127.0.0.1");
+    assert_eq!(forwarded_node("[::1]:80".parse().ok()), "\"[::1]\"");
+    assert_eq!(forwarded_node(None), "unknown");
+  }
+}
@@ -447,7 +447,8 @@ fn exec(es: ExecState) {
                 break;
               }
             }
-            WebsocketMessage::Pong => (), // do nothing
+            WebsocketMessage::Pong => (),     // do nothing
+            WebsocketMessage::Close(_) => (), // the next read returns None
           }
         }
         None => {
@@ -0,0 +1,300 @@
+//! Negotiated response compression as specified in [RFC 9110 Section 8.4](https://datatracker.ietf.org/doc/html/rfc9110#section-8.4)
+//! and [Section 12.5.3](https://datatracker.ietf.org/doc/html/rfc9110#section-12.5.3).
+
+use crate::functional_traits::ResponseFilter;
+use crate::{
+  HttpHeaderName, MimeGroup, MimeType, MimeTypeWithCharset, QValue, Response, ResponseContext,
+  TiiResult,
+};
+use std::fmt::{Display, Formatter};
+
+/// A content coding that tii can compress response bodies with.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ContentCoding {
+  /// The gzip format.
+  Gzip,
+  /// The zlib format. Called "deflate" in http.
+  Deflate,
+}
+
+impl ContentCoding {
+  /// Returns the name of the coding as used in the `Content-Encoding` and `Accept-Encoding` headers.
+  pub const fn as_str(&self) -> &'static str {
+    match self {
+      ContentCoding::Gzip => "gzip",
+      ContentCoding::Deflate => "deflate",
+    }
+  }
+
+  /// Parses the name of a coding. The deprecated alias `x-gzip` is parsed as gzip.
+  /// Returns None for unknown codings.
+  pub fn parse(value: impl AsRef<str>) -> Option<Self> {
+    let value = value.as_ref().trim();
+    if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+      return Some(ContentCoding::Gzip);
+    }
+
+    if value.eq_ignore_ascii_case("deflate") {
+      return Some(ContentCoding::Deflate);
+    }
+
+    None
+  }
+}
+
+impl Display for ContentCoding {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// Parses the value of a `Content-Encoding` header into the codings in the order they were applied.
+/// The `identity` coding is skipped. Returns None if any coding is not supported.
+pub(crate) fn parse_content_codings(value: &str) -> Option<Vec<ContentCoding>> {
+  value
+    .split(',')
+    .map(str::trim)
+    .filter(|coding| !coding.eq_ignore_ascii_case("identity"))
+    .map(ContentCoding::parse)
+    .collect()
+}
+
+/// Returns the q-value that the values of the `Accept-Encoding` headers give the coding.
+/// An explicit entry for the coding takes precedence over the `*` wildcard.
+/// Codings that are not mentioned at all are not acceptable and have a q-value of 0.
+/// Entries with an invalid q-value are not acceptable.
+pub(crate) fn accept_encoding_qvalue(values: &[&str], coding: ContentCoding) -> QValue {
+  let mut wildcard = None;
+  for entry in values.iter().flat_map(|value| value.split(',')) {
+    let mut parts = entry.split(';');
+    let name = parts.next().unwrap_or_default().trim();
+    if name.is_empty() {
+      continue;
+    }
+
+    let mut qvalue = QValue::MAX;
+    for param in parts {
+      let Some((key, value)) = param.split_once('=') else {
+        continue;
+      };
+      if key.trim().eq_ignore_ascii_case("q") {
+        qvalue = QValue::parse(value.trim()).unwrap_or(QValue::MIN);
+      }
+    }
+
+    if name == "*" {
+      wildcard = Some(qvalue);
+      continue;
+    }
+
+    if ContentCoding::parse(name) == Some(coding) {
+      return qvalue;
+    }
+  }
+
+  wildcard.unwrap_or(QValue::MIN)
+}
+
+/// Response filter that compresses response bodies with the content coding the client prefers.
+/// Install it with `RouterBuilder::with_response_filter`. It should be the last response filter
+/// because filters that run after it only see the compressed body.
+///
+/// A response is eligible for compression if:
+/// - its status is neither informational nor 204 No Content, 206 Partial Content or 304 Not Modified.
+/// - it has a body that is not already encoded and no `Content-Encoding` or `Content-Range` header.
+/// - its `Content-Type` is one of the compressible mime types.
+/// - its size is at least the minimum size. Chunked bodies have no known size and are always eligible.
+///
+/// Eligible responses get a `Vary: Accept-Encoding` header, even if the client does not accept any coding.
+/// Data held in memory is compressed immediately, files and chunked streams are compressed on the fly.
+/// File responses to range requests are not compressed so the range request can be answered.
+/// A strong `ETag` of a compressed response gets the name of the coding appended,
+/// because the compressed representation is not byte for byte equal to the uncompressed one.
+#[derive(Debug, Clone)]
+pub struct CompressionFilter {
+  codings: Vec<ContentCoding>,
+  min_size: u64,
+  mime_groups: Vec<MimeGroup>,
+  mime_types: Vec<MimeType>,
+}
+
+impl Default for CompressionFilter {
+  fn default() -> Self {
+    Self {
+      codings: vec![ContentCoding::Gzip, ContentCoding::Deflate],
+      min_size: 1024,
+      mime_groups: vec![MimeGroup::Text],
+      mime_types: vec![
+        MimeType::ApplicationJson,
+        MimeType::ApplicationJsonLd,
+        MimeType::ApplicationXml,
+        MimeType::ApplicationXHtml,
+        MimeType::ImageSvg,
+      ],
+    }
+  }
+}
+
+impl CompressionFilter {
+  /// Creates a filter that compresses text, json, xml and svg bodies of at least 1024 bytes with gzip or deflate.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the codings that may be used in order of preference.
+  /// If the client accepts several codings with the same q-value then the first one is used.
+  pub fn with_codings(mut self, codings: impl IntoIterator<Item = ContentCoding>) -> Self {
+    self.codings = codings.into_iter().collect();
+    self
+  }
+
+  /// Sets the minimum size in bytes of a body that is compressed. Smaller bodies are sent as is.
+  pub fn with_min_size(mut self, min_size: u64) -> Self {
+    self.min_size = min_size;
+    self
+  }
+
+  /// Compress bodies of all mime types of the given group.
+  pub fn with_mime_group(mut self, group: MimeGroup) -> Self {
+    if !self.mime_groups.contains(&group) {
+      self.mime_groups.push(group);
+    }
+    self
+  }
+
+  /// Compress bodies of the given mime type.
+  pub fn with_mime_type(mut self, mime: MimeType) -> Self {
+    if !self.mime_types.contains(&mime) {
+      self.mime_types.push(mime);
+    }
+    self
+  }
+
+  /// Returns true if bodies of the given mime type are compressed.
+  pub fn is_compressible_mime(&self, mime: &MimeType) -> bool {
+    self.mime_types.contains(mime) || self.mime_groups.contains(mime.mime_group())
+  }
+
+  /// Picks the coding with the highest q-value that the client accepts.
+  /// Returns None if the client accepts none of the codings of this filter.
+  pub fn negotiate(&self, accept_encoding: &[&str]) -> Option<ContentCoding> {
+    if accept_encoding.is_empty() {
+      return None;
+    }
+
+    let mut best: Option<(ContentCoding, QValue)> = None;
+    for coding in self.codings.iter().copied() {
+      let qvalue = accept_encoding_qvalue(accept_encoding, coding);
+      if qvalue > QValue::MIN && best.is_none_or(|(_, best)| qvalue > best) {
+        best = Some((coding, qvalue));
+      }
+    }
+
+    best.map(|(coding, _)| coding)
+  }
+
+  /// Returns the mime type of the response if the response is eligible for compression, ignoring its size.
+  fn eligible_mime(&self, response: &Response) -> Option<MimeTypeWithCharset> {
+    let code = response.status_code.code();
+    if code < 200 || code == 204 || code == 206 || code == 304 {
+      return None;
+    }
+
+    if response.get_header(HttpHeaderName::ContentEncoding).is_some()
+      || response.get_header(HttpHeaderName::ContentRange).is_some()
+    {
+      return None;
+    }
+
+    let body = response.get_body()?;
+    if body.get_content_encoding().is_some() {
+      return None;
+    }
+
+    let mime = response
+      .get_header(HttpHeaderName::ContentType)
+      .and_then(MimeTypeWithCharset::parse_from_content_type_header)?;
+
+    self.is_compressible_mime(mime.mime()).then_some(mime)
+  }
+}
+
+impl ResponseFilter for CompressionFilter {
+  fn filter(&self, context: &mut ResponseContext<'_>) -> TiiResult<()> {
+    let Some(mime) = self.eligible_mime(context.get_response()) else {
+      return Ok(());
+    };
+
+    let coding =
+      self.negotiate(&context.get_request().get_headers(&HttpHeaderName::AcceptEncoding));
+    let range_request = context.get_request().get_header(&HttpHeaderName::Range).is_some();
+
+    let response = context.get_response_mut();
+    let Some(mut body) = response.body.take() else {
+      return Ok(());
+    };
+
+    if body.is_entity() {
+      body = body.serialize_entity(&mime)?;
+    }
+
+    let compressible =
+      body.can_compress() && body.content_length().is_none_or(|len| len >= self.min_size);
+    let file = body.file_size().is_some();
+    response.body = Some(body);
+    if !compressible {
+      return Ok(());
+    }
+
+    response.add_header(HttpHeaderName::Vary, "Accept-Encoding")?;
+
+    let Some(coding) = coding else {
+      return Ok(());
+    };
+
+    if range_request && file {
+      return Ok(());
+    }
+
+    if let Some(body) = response.body.take() {
+      response.body = Some(body.compress(coding).unwrap_or_else(|body| body));
+    }
+
+    if let Some(etag) = response.get_header(HttpHeaderName::ETag) {
+      if !etag.starts_with("W/") {
+        if let Some(opaque) = etag.strip_suffix('"') {
+          let etag = format!("{opaque}-{coding}\"");
+          response.set_header(HttpHeaderName::ETag, etag)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::http::compression::accept_encoding_qvalue;
+  use crate::{CompressionFilter, ContentCoding, QValue};
+
+  #[test]
+  fn test_accept_encoding() {
+    let gzip = ContentCoding::Gzip;
+    let deflate = ContentCoding::Deflate;
+    assert_eq!(accept_encoding_qvalue(&["gzip, deflate"], gzip), QValue::MAX);
+    assert_eq!(accept_encoding_qvalue(&["x-gzip"], gzip), QValue::MAX);
+    assert_eq!(accept_encoding_qvalue(&["br"], gzip), QValue::MIN);
+    assert_eq!(accept_encoding_qvalue(&["*;q=0.5"], deflate), QValue::parse("0.5").unwrap());
+    assert_eq!(accept_encoding_qvalue(&["gzip;q=0", "*"], gzip), QValue::MIN);
+    assert_eq!(accept_encoding_qvalue(&["gzip;q=2"], gzip), QValue::MIN);
+
+    let filter = CompressionFilter::new();
+    assert_eq!(filter.negotiate(&[]), None);
+    assert_eq!(filter.negotiate(&["identity"]), None);
+    assert_eq!(filter.negotiate(&["deflate, gzip"]), Some(gzip));
+    assert_eq!(filter.negotiate(&["gzip;q=0.5, deflate"]), Some(deflate));
+    assert_eq!(filter.negotiate(&["gzip;q=0, *"]), Some(deflate));
+    assert_eq!(filter.with_codings([deflate, gzip]).negotiate(&["gzip, deflate"]), Some(deflate));
+  }
+}
@@ -0,0 +1,391 @@
+//! Implements conditional requests as specified in [RFC 9110 Section 13](https://datatracker.ietf.org/doc/html/rfc9110#section-13).
+//!
+//! GET and HEAD requests are evaluated automatically, using the validators of the 200 OK response.
+//! Requests that modify a resource, such as PUT, POST, DELETE or PATCH, must be evaluated before the endpoint runs.
+//! tii cannot know the current validators of the resource, so they are evaluated by the `precondition_filter`,
+//! which asks a user supplied fn for the validators, or by endpoints that call `RequestContext::evaluate_preconditions`.
+
+use crate::{
+  HttpHeaderName, HttpMethod, RequestContext, Response, ResponseContext, StatusCode, TiiResult,
+};
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] =
+  ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Headers of a 200 OK response that are also sent in a 304 Not Modified response.
+const NOT_MODIFIED_HEADERS: [HttpHeaderName; 7] = [
+  HttpHeaderName::CacheControl,
+  HttpHeaderName::ContentLocation,
+  HttpHeaderName::Date,
+  HttpHeaderName::ETag,
+  HttpHeaderName::Expires,
+  HttpHeaderName::LastModified,
+  HttpHeaderName::Vary,
+];
+
+/// Formats a time as a HTTP date, for example `Sun, 06 Nov 1994 08:49:37 GMT`.
+/// Sub-second precision is discarded. Times before 1970 are formatted as the 1st of January 1970.
+pub fn format_http_date(time: SystemTime) -> String {
+  let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+  let days = secs / 86400;
+  let secs_of_day = secs % 86400;
+  let (year, month, day) = civil_from_days(days);
+
+  format!(
+    "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS.get((days % 7) as usize).copied().unwrap_or_default(),
+    day,
+    MONTHS.get((month - 1) as usize).copied().unwrap_or_default(),
+    year,
+    secs_of_day / 3600,
+    secs_of_day % 3600 / 60,
+    secs_of_day % 60
+  )
+}
+
+/// Parses a HTTP date.
+/// Accepts the IMF-fixdate format (`Sun, 06 Nov 1994 08:49:37 GMT`)
+/// as well as the obsolete RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime (`Sun Nov  6 08:49:37 1994`) formats.
+/// Returns None if the value is not a valid date or before 1970.
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+  let tokens = value.split_ascii_whitespace().collect::<Vec<_>>();
+  let (day, month, year, time) = match tokens.as_slice() {
+    [weekday, day, month, year, time, "GMT"] if weekday.ends_with(',') => {
+      (*day, *month, parse_number(year)?, *time)
+    }
+    [weekday, date, time, "GMT"] if weekday.ends_with(',') => {
+      let mut date = date.split('-');
+      let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+      if date.next().is_some() || year.len() != 2 {
+        return None;
+      }
+      let year = parse_number(year)?;
+      (day, month, if year < 70 { year + 2000 } else { year + 1900 }, *time)
+    }
+    [_, month, day, time, year] => (*day, *month, parse_number(year)?, *time),
+    _ => return None,
+  };
+
+  let day = parse_number(day)?;
+  let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+  if year < 1970 || !(1..=31).contains(&day) {
+    return None;
+  }
+
+  let mut time = time.split(':');
+  let (hour, minute, second) =
+    (parse_number(time.next()?)?, parse_number(time.next()?)?, parse_number(time.next()?)?);
+  if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+    return None;
+  }
+
+  let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+  Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn parse_number(value: &str) -> Option<u64> {
+  if value.is_empty() || value.len() > 4 || !value.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+
+  value.parse().ok()
+}
+
+/// Days since 1970-01-01 to (year, month, day). See <http://howardhinnant.github.io/date_algorithms.html>
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+  let z = days + 719468;
+  let era = z / 146097;
+  let doe = z % 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + u64::from(month <= 2);
+  (year, month, day)
+}
+
+/// (year, month, day) to days since 1970-01-01. The year must not be before 1970.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year / 400;
+  let yoe = year % 400;
+  let mp = if month > 2 { month - 3 } else { month + 9 };
+  let doy = (153 * mp + 2) / 5 + day - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  era * 146097 + doe - 719468
+}
+
+/// Parses a list of entity tags, for example the value of a `If-None-Match` header.
+/// Returns the tags with their quotes and a flag that indicates weak tags.
+/// Returns None for `*`.
+fn parse_entity_tags(value: &str) -> Option<Vec<(bool, &str)>> {
+  if value.trim() == "*" {
+    return None;
+  }
+
+  let mut tags = Vec::new();
+  let mut rest = value;
+  loop {
+    rest = rest.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+    if rest.is_empty() {
+      return Some(tags);
+    }
+
+    let weak = rest.starts_with("W/");
+    if weak {
+      rest = rest.get(2..).unwrap_or_default();
+    }
+
+    //Malformed tags end the list
+    if !rest.starts_with('"') {
+      return Some(tags);
+    }
+
+    let Some(end) = rest.get(1..).and_then(|tag| tag.find('"')) else {
+      return Some(tags);
+    };
+
+    tags.push((weak, rest.get(..end + 2).unwrap_or_default()));
+    rest = rest.get(end + 2..).unwrap_or_default();
+  }
+}
+
+/// Splits an entity tag into the weak flag and the tag with its quotes.
+fn split_entity_tag(etag: &str) -> (bool, &str) {
+  let etag = etag.trim();
+  match etag.strip_prefix("W/") {
+    Some(tag) => (true, tag),
+    None => (false, etag),
+  }
+}
+
+/// Evaluates the conditional headers of the request in the order defined by RFC 9110 Section 13.2.2.
+/// Returns the status code of the response if a condition failed.
+fn evaluate(
+  request: &RequestContext,
+  etag: Option<&str>,
+  last_modified: Option<SystemTime>,
+) -> Option<StatusCode> {
+  let etag = etag.map(split_entity_tag);
+  //HTTP dates have a precision of one second.
+  let last_modified = last_modified
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map(|time| UNIX_EPOCH + Duration::from_secs(time.as_secs()));
+  let exists = etag.is_some() || last_modified.is_some();
+  let safe = matches!(request.get_method(), HttpMethod::Get | HttpMethod::Head);
+
+  if let Some(if_match) = request.get_header(&HttpHeaderName::IfMatch) {
+    let matched = match (parse_entity_tags(if_match), etag) {
+      (None, _) => exists,
+      (Some(tags), Some((false, etag))) => tags.iter().any(|(weak, tag)| !weak && *tag == etag),
+      (Some(_), _) => false,
+    };
+
+    if !matched {
+      return Some(StatusCode::PreconditionFailed);
+    }
+  } else if let Some(if_unmodified_since) =
+    request.get_header(&HttpHeaderName::IfUnmodifiedSince).and_then(parse_http_date)
+  {
+    if last_modified.is_some_and(|last_modified| last_modified > if_unmodified_since) {
+      return Some(StatusCode::PreconditionFailed);
+    }
+  }
+
+  let failed = if let Some(if_none_match) = request.get_header(&HttpHeaderName::IfNoneMatch) {
+    match (parse_entity_tags(if_none_match), etag) {
+      (None, _) => exists,
+      (Some(tags), Some((_, etag))) => tags.iter().any(|(_, tag)| *tag == etag),
+      (Some(_), None) => false,
+    }
+  } else if let (true, Some(last_modified), Some(if_modified_since)) = (
+    safe,
+    last_modified,
+    request.get_header(&HttpHeaderName::IfModifiedSince).and_then(parse_http_date),
+  ) {
+    last_modified <= if_modified_since
+  } else {
+    false
+  };
+
+  match (failed, safe) {
+    (false, _) => None,
+    (true, true) => Some(StatusCode::NotModified),
+    (true, false) => Some(StatusCode::PreconditionFailed),
+  }
+}
+
+impl RequestContext {
+  /// Evaluates the `If-Match`, `If-None-Match`, `If-Modified-Since` and `If-Unmodified-Since` headers of this request
+  /// against the current entity tag and modification time of the requested resource.
+  ///
+  /// Returns the 304 Not Modified or 412 Precondition Failed response that should be sent instead of processing the request,
+  /// or None if the request should be processed.
+  /// Pass None for both validators if the resource does not exist.
+  ///
+  /// Responses to GET and HEAD requests are checked automatically if they carry an `ETag` or `Last-Modified` header.
+  /// Requests of other methods such as PUT, POST, DELETE or PATCH are only checked by the `precondition_filter`.
+  /// Without it, their endpoints must call this fn before modifying the resource, otherwise the preconditions are ignored.
+  pub fn evaluate_preconditions(
+    &self,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+  ) -> TiiResult<Option<Response>> {
+    let Some(status) = evaluate(self, etag, last_modified) else {
+      return Ok(None);
+    };
+
+    let not_modified = status == StatusCode::NotModified;
+    let mut response = Response::new(status);
+    if not_modified {
+      if let Some(etag) = etag {
+        response.add_header(HttpHeaderName::ETag, etag)?;
+      }
+      if let Some(last_modified) = last_modified {
+        response.add_header(HttpHeaderName::LastModified, format_http_date(last_modified))?;
+      }
+    }
+
+    Ok(Some(response))
+  }
+}
+
+/// Answers a conditional GET or HEAD request if the response is a 200 OK that carries an `ETag` or `Last-Modified` header.
+/// The response is returned unchanged if all conditions hold.
+/// Responses to other methods are returned unchanged, see `RequestContext::evaluate_preconditions`.
+pub(crate) fn apply_conditional_request(
+  request: &RequestContext,
+  response: Response,
+) -> TiiResult<Response> {
+  if response.status_code != StatusCode::OK
+    || !matches!(request.get_method(), HttpMethod::Get | HttpMethod::Head)
+  {
+    return Ok(response);
+  }
+
+  let etag = response.get_header(HttpHeaderName::ETag);
+  let last_modified = response.get_header(HttpHeaderName::LastModified);
+  if etag.is_none() && last_modified.is_none() {
+    return Ok(response);
+  }
+
+  let Some(status) = evaluate(request, etag, last_modified.and_then(parse_http_date)) else {
+    return Ok(response);
+  };
+
+  let not_modified = status == StatusCode::NotModified;
+  let mut failed = Response::new(status);
+  failed.omit_body = response.omit_body;
+  if not_modified {
+    for header in response.get_all_headers() {
+      if NOT_MODIFIED_HEADERS.contains(&header.name) {
+        failed.add_header(&header.name, &header.value)?;
+      }
+    }
+  }
+
+  Ok(failed)
+}
+
+/// Creates a request filter that evaluates the preconditions of requests that modify a resource,
+/// such as PUT, POST, DELETE or PATCH, before their endpoint is called.
+/// Requests whose `If-Match`, `If-None-Match` or `If-Unmodified-Since` header does not hold are answered with 412 Precondition Failed.
+///
+/// `validators` returns the current entity tag and modification time of the requested resource,
+/// or None for both if the resource does not exist.
+/// It is only called for requests that carry one of these headers.
+///
+/// Register it with `RouterBuilder::with_request_filter(precondition_filter(...))`.
+pub fn precondition_filter<F>(
+  validators: F,
+) -> impl Fn(&mut RequestContext) -> TiiResult<Option<Response>>
+where
+  F: Fn(&RequestContext) -> TiiResult<(Option<String>, Option<SystemTime>)> + Send + Sync + 'static,
+{
+  move |request: &mut RequestContext| {
+    if matches!(
+      request.get_method(),
+      HttpMethod::Get | HttpMethod::Head | HttpMethod::Options | HttpMethod::Trace
+    ) {
+      return Ok(None);
+    }
+
+    if [HttpHeaderName::IfMatch, HttpHeaderName::IfNoneMatch, HttpHeaderName::IfUnmodifiedSince]
+      .iter()
+      .all(|name| request.get_header(name).is_none())
+    {
+      return Ok(None);
+    }
+
+    let (etag, last_modified) = validators(request)?;
+    request.evaluate_preconditions(etag.as_deref(), last_modified)
+  }
+}
+
+/// Response filter that adds a strong `ETag` to 200 OK responses with a body that is held in memory.
+/// The entity tag is derived from a hash of the body.
+/// Responses that already have an `ETag` header are not modified.
+///
+/// Register it with `RouterBuilder::with_response_filter(etag_filter)`.
+/// Conditional GET and HEAD requests are then answered with 304 Not Modified automatically.
+/// This does not protect PUT or DELETE endpoints, use the `precondition_filter` for them.
+pub fn etag_filter(context: &mut ResponseContext<'_>) -> TiiResult<()> {
+  let response = context.get_response_mut();
+  if response.status_code != StatusCode::OK || response.get_header(HttpHeaderName::ETag).is_some() {
+    return Ok(());
+  }
+
+  let Some(data) = response.body.as_ref().and_then(|body| body.fixed_size_data()) else {
+    return Ok(());
+  };
+
+  let hash = Sha1::new().chain_update(data).finalize();
+  let etag = format!("\"{}\"", base64::prelude::BASE64_URL_SAFE_NO_PAD.encode(hash));
+  response.add_header(HttpHeaderName::ETag, etag)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::http::conditional::{format_http_date, parse_entity_tags, parse_http_date};
+  use std::time::{Duration, UNIX_EPOCH};
+
+  #[test]
+  fn test_http_date() {
+    let time = UNIX_EPOCH + Duration::from_secs(784111777);
+    assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert_eq!(format_http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+    assert_eq!(
+      format_http_date(UNIX_EPOCH + Duration::from_secs(951782400)),
+      "Tue, 29 Feb 2000 00:00:00 GMT"
+    );
+
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+    assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(time));
+    assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(time));
+    assert_eq!(
+      parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"),
+      Some(UNIX_EPOCH + Duration::from_secs(951782400))
+    );
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 CET"), None);
+    assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+    assert_eq!(parse_http_date("Sun, 06 Nov 1960 08:49:37 GMT"), None);
+    assert_eq!(parse_http_date("yesterday"), None);
+  }
+
+  #[test]
+  fn test_entity_tags() {
+    assert_eq!(parse_entity_tags("*"), None);
+    assert_eq!(parse_entity_tags("\"a\""), Some(vec![(false, "\"a\"")]));
+    assert_eq!(
+      parse_entity_tags("\"a\", W/\"b,c\" ,\"\""),
+      Some(vec![(false, "\"a\""), (true, "\"b,c\""), (false, "\"\"")])
+    );
+    assert_eq!(parse_entity_tags("\"a\", b"), Some(vec![(false, "\"a\"")]));
+  }
+}
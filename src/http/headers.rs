@@ -22,6 +22,15 @@ pub struct HttpHeader {
   pub value: String,
 }
 
+/// Returns true if one of the header values is a comma separated list of tokens that contains the token.
+/// Tokens are compared case-insensitively, for example `keep-alive` is found in `Upgrade, Keep-Alive`.
+pub(crate) fn has_header_token(values: &[&str], token: &str) -> bool {
+  values
+    .iter()
+    .flat_map(|value| value.split(','))
+    .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
 //TODO later move this impl to RequestContext and get rid of this unneeded wrapper for the Vec
 impl Headers {
   /// Create an empty collection of headers.
@@ -142,6 +151,8 @@ pub enum HttpHeaderName {
   CacheControl,
   /// Indicates what should happen to the connection after the request is served.
   Connection,
+  /// Contains hints on how long and for how many requests a persistent connection is kept open.
+  KeepAlive,
   /// Lists any encodings used on the payload.
   ContentEncoding,
   /// Indicates the length of the payload body.
@@ -162,8 +173,22 @@ pub enum HttpHeaderName {
   Host,
   /// Indicates the origin that caused the request.
   Origin,
+  /// Makes the request conditional on the resource matching one of the given entity tags.
+  IfMatch,
+  /// Makes the request conditional on the resource having been modified after the given date.
+  IfModifiedSince,
+  /// Makes the request conditional on the resource matching none of the given entity tags.
+  IfNoneMatch,
+  /// Makes the request conditional on the resource not having been modified after the given date.
+  IfUnmodifiedSince,
+  /// Makes a range request conditional on the resource not having changed.
+  IfRange,
+  /// Contains the id of the last server-sent event the client received before it reconnected.
+  LastEventId,
   /// Contains backwards-compatible caching information.
   Pragma,
+  /// Indicates the part of the resource that the client wants to receive.
+  Range,
   /// Indicates the absolute or partial address of the page making the request.
   Referer,
   /// Indicates that the connection is to be upgraded to a different protocol, e.g. WebSocket.
@@ -181,6 +206,14 @@ pub enum HttpHeaderName {
   AccessControlAllowHeaders,
   /// Indicates whether certain methods can be used.
   AccessControlAllowMethods,
+  /// Indicates whether the response can be shared when the request includes credentials.
+  AccessControlAllowCredentials,
+  /// Indicates which response headers can be read by scripts of other origins.
+  AccessControlExposeHeaders,
+  /// Indicates how long the result of a preflight request can be cached.
+  AccessControlMaxAge,
+  /// Indicates that the server supports range requests for the resource.
+  AcceptRanges,
   /// Contains the time in seconds that the object has been cached.
   Age,
   /// The set of methods supported by the resource.
@@ -191,6 +224,8 @@ pub enum HttpHeaderName {
   ContentLanguage,
   /// Indicates an alternative location for the returned data.
   ContentLocation,
+  /// Indicates which part of the resource the payload body contains.
+  ContentRange,
   /// Identifies a specific version of a resource.
   ETag,
   /// Contains the date and time at which the response is considered expired.
@@ -207,6 +242,8 @@ pub enum HttpHeaderName {
   SetCookie,
   /// Indicates the encoding used in the transfer of the payload body.
   TransferEncoding,
+  /// Indicates which request headers were used to select the response.
+  Vary,
 
   /// <https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/TE>
   TE,
@@ -232,6 +269,7 @@ static WELL_KNOWN: &[HttpHeaderName] = &[
   HttpHeaderName::Authorization,
   HttpHeaderName::CacheControl,
   HttpHeaderName::Connection,
+  HttpHeaderName::KeepAlive,
   HttpHeaderName::ContentEncoding,
   HttpHeaderName::ContentLength,
   HttpHeaderName::ContentType,
@@ -242,7 +280,14 @@ static WELL_KNOWN: &[HttpHeaderName] = &[
   HttpHeaderName::From,
   HttpHeaderName::Host,
   HttpHeaderName::Origin,
+  HttpHeaderName::IfMatch,
+  HttpHeaderName::IfModifiedSince,
+  HttpHeaderName::IfNoneMatch,
+  HttpHeaderName::IfUnmodifiedSince,
+  HttpHeaderName::IfRange,
+  HttpHeaderName::LastEventId,
   HttpHeaderName::Pragma,
+  HttpHeaderName::Range,
   HttpHeaderName::Referer,
   HttpHeaderName::Upgrade,
   HttpHeaderName::UserAgent,
@@ -251,11 +296,16 @@ static WELL_KNOWN: &[HttpHeaderName] = &[
   HttpHeaderName::AccessControlAllowOrigin,
   HttpHeaderName::AccessControlAllowHeaders,
   HttpHeaderName::AccessControlAllowMethods,
+  HttpHeaderName::AccessControlAllowCredentials,
+  HttpHeaderName::AccessControlExposeHeaders,
+  HttpHeaderName::AccessControlMaxAge,
+  HttpHeaderName::AcceptRanges,
   HttpHeaderName::Age,
   HttpHeaderName::Allow,
   HttpHeaderName::ContentDisposition,
   HttpHeaderName::ContentLanguage,
   HttpHeaderName::ContentLocation,
+  HttpHeaderName::ContentRange,
   HttpHeaderName::ETag,
   HttpHeaderName::Expires,
   HttpHeaderName::LastModified,
@@ -264,6 +314,7 @@ static WELL_KNOWN: &[HttpHeaderName] = &[
   HttpHeaderName::Server,
   HttpHeaderName::SetCookie,
   HttpHeaderName::TransferEncoding,
+  HttpHeaderName::Vary,
   HttpHeaderName::Trailer,
   HttpHeaderName::TE,
   HttpHeaderName::ProxyAuthenticate,
@@ -303,6 +354,7 @@ impl HttpHeaderName {
       HttpHeaderName::Authorization => "Authorization",
       HttpHeaderName::CacheControl => "Cache-Control",
       HttpHeaderName::Connection => "Connection",
+      HttpHeaderName::KeepAlive => "Keep-Alive",
       HttpHeaderName::ContentEncoding => "Content-Encoding",
       HttpHeaderName::ContentLength => "Content-Length",
       HttpHeaderName::ContentType => "Content-Type",
@@ -313,7 +365,14 @@ impl HttpHeaderName {
       HttpHeaderName::From => "From",
       HttpHeaderName::Host => "Host",
       HttpHeaderName::Origin => "Origin",
+      HttpHeaderName::IfMatch => "If-Match",
+      HttpHeaderName::IfModifiedSince => "If-Modified-Since",
+      HttpHeaderName::IfNoneMatch => "If-None-Match",
+      HttpHeaderName::IfUnmodifiedSince => "If-Unmodified-Since",
+      HttpHeaderName::IfRange => "If-Range",
+      HttpHeaderName::LastEventId => "Last-Event-ID",
       HttpHeaderName::Pragma => "Pragma",
+      HttpHeaderName::Range => "Range",
       HttpHeaderName::Referer => "Referer",
       HttpHeaderName::Upgrade => "Upgrade",
       HttpHeaderName::UserAgent => "User-Agent",
@@ -322,11 +381,16 @@ impl HttpHeaderName {
       HttpHeaderName::AccessControlAllowOrigin => "Access-Control-Allow-Origin",
       HttpHeaderName::AccessControlAllowHeaders => "Access-Control-Allow-Headers",
       HttpHeaderName::AccessControlAllowMethods => "Access-Control-Allow-Methods",
+      HttpHeaderName::AccessControlAllowCredentials => "Access-Control-Allow-Credentials",
+      HttpHeaderName::AccessControlExposeHeaders => "Access-Control-Expose-Headers",
+      HttpHeaderName::AccessControlMaxAge => "Access-Control-Max-Age",
+      HttpHeaderName::AcceptRanges => "Accept-Ranges",
       HttpHeaderName::Age => "Age",
       HttpHeaderName::Allow => "Allow",
       HttpHeaderName::ContentDisposition => "Content-Disposition",
       HttpHeaderName::ContentLanguage => "Content-Language",
       HttpHeaderName::ContentLocation => "Content-Location",
+      HttpHeaderName::ContentRange => "Content-Range",
       HttpHeaderName::ETag => "ETag",
       HttpHeaderName::Expires => "Expires",
       HttpHeaderName::LastModified => "Last-Modified",
@@ -335,6 +399,7 @@ impl HttpHeaderName {
       HttpHeaderName::Server => "Server",
       HttpHeaderName::SetCookie => "Set-Cookie",
       HttpHeaderName::TransferEncoding => "Transfer-Encoding",
+      HttpHeaderName::Vary => "Vary",
       HttpHeaderName::ProxyAuthenticate => "Proxy-Authenticate",
       HttpHeaderName::TE => "TE",
       HttpHeaderName::Trailer => "Trailer",
@@ -357,6 +422,7 @@ impl HttpHeaderName {
       HttpHeaderName::Authorization => "Authorization",
       HttpHeaderName::CacheControl => "Cache-Control",
       HttpHeaderName::Connection => "Connection",
+      HttpHeaderName::KeepAlive => "Keep-Alive",
       HttpHeaderName::ContentEncoding => "Content-Encoding",
       HttpHeaderName::ContentLength => "Content-Length",
       HttpHeaderName::ContentType => "Content-Type",
@@ -367,7 +433,14 @@ impl HttpHeaderName {
       HttpHeaderName::From => "From",
       HttpHeaderName::Host => "Host",
       HttpHeaderName::Origin => "Origin",
+      HttpHeaderName::IfMatch => "If-Match",
+      HttpHeaderName::IfModifiedSince => "If-Modified-Since",
+      HttpHeaderName::IfNoneMatch => "If-None-Match",
+      HttpHeaderName::IfUnmodifiedSince => "If-Unmodified-Since",
+      HttpHeaderName::IfRange => "If-Range",
+      HttpHeaderName::LastEventId => "Last-Event-ID",
       HttpHeaderName::Pragma => "Pragma",
+      HttpHeaderName::Range => "Range",
       HttpHeaderName::Referer => "Referer",
       HttpHeaderName::Upgrade => "Upgrade",
       HttpHeaderName::UserAgent => "User-Agent",
@@ -376,11 +449,16 @@ impl HttpHeaderName {
       HttpHeaderName::AccessControlAllowOrigin => "Access-Control-Allow-Origin",
       HttpHeaderName::AccessControlAllowHeaders => "Access-Control-Allow-Headers",
       HttpHeaderName::AccessControlAllowMethods => "Access-Control-Allow-Methods",
+      HttpHeaderName::AccessControlAllowCredentials => "Access-Control-Allow-Credentials",
+      HttpHeaderName::AccessControlExposeHeaders => "Access-Control-Expose-Headers",
+      HttpHeaderName::AccessControlMaxAge => "Access-Control-Max-Age",
+      HttpHeaderName::AcceptRanges => "Accept-Ranges",
       HttpHeaderName::Age => "Age",
       HttpHeaderName::Allow => "Allow",
       HttpHeaderName::ContentDisposition => "Content-Disposition",
       HttpHeaderName::ContentLanguage => "Content-Language",
       HttpHeaderName::ContentLocation => "Content-Location",
+      HttpHeaderName::ContentRange => "Content-Range",
       HttpHeaderName::ETag => "ETag",
       HttpHeaderName::Expires => "Expires",
       HttpHeaderName::LastModified => "Last-Modified",
@@ -389,6 +467,7 @@ impl HttpHeaderName {
       HttpHeaderName::Server => "Server",
       HttpHeaderName::SetCookie => "Set-Cookie",
       HttpHeaderName::TransferEncoding => "Transfer-Encoding",
+      HttpHeaderName::Vary => "Vary",
       HttpHeaderName::ProxyAuthenticate => "Proxy-Authenticate",
       HttpHeaderName::Trailer => "Trailer",
       HttpHeaderName::TE => "TE",
@@ -422,6 +501,7 @@ impl From<&str> for HttpHeaderName {
       "authorization" => Self::Authorization,
       "cache-control" => Self::CacheControl,
       "connection" => Self::Connection,
+      "keep-alive" => Self::KeepAlive,
       "content-encoding" => Self::ContentEncoding,
       "content-length" => Self::ContentLength,
       "content-type" => Self::ContentType,
@@ -432,7 +512,14 @@ impl From<&str> for HttpHeaderName {
       "from" => Self::From,
       "host" => Self::Host,
       "origin" => Self::Origin,
+      "if-match" => Self::IfMatch,
+      "if-modified-since" => Self::IfModifiedSince,
+      "if-none-match" => Self::IfNoneMatch,
+      "if-unmodified-since" => Self::IfUnmodifiedSince,
+      "if-range" => Self::IfRange,
+      "last-event-id" => Self::LastEventId,
       "pragma" => Self::Pragma,
+      "range" => Self::Range,
       "referer" => Self::Referer,
       "upgrade" => Self::Upgrade,
       "user-agent" => Self::UserAgent,
@@ -441,11 +528,16 @@ impl From<&str> for HttpHeaderName {
       "access-control-allow-origin" => Self::AccessControlAllowOrigin,
       "access-control-allow-headers" => Self::AccessControlAllowHeaders,
       "access-control-allow-methods" => Self::AccessControlAllowMethods,
+      "access-control-allow-credentials" => Self::AccessControlAllowCredentials,
+      "access-control-expose-headers" => Self::AccessControlExposeHeaders,
+      "access-control-max-age" => Self::AccessControlMaxAge,
+      "accept-ranges" => Self::AcceptRanges,
       "age" => Self::Age,
       "allow" => Self::Allow,
       "content-disposition" => Self::ContentDisposition,
       "content-language" => Self::ContentLanguage,
       "content-location" => Self::ContentLocation,
+      "content-range" => Self::ContentRange,
       "etag" => Self::ETag,
       "expires" => Self::Expires,
       "last-modified" => Self::LastModified,
@@ -454,6 +546,7 @@ impl From<&str> for HttpHeaderName {
       "server" => Self::Server,
       "set-cookie" => Self::SetCookie,
       "transfer-encoding" => Self::TransferEncoding,
+      "vary" => Self::Vary,
       "proxy-authenticate" => Self::ProxyAuthenticate,
       "te" => Self::TE,
       "trailer" => Self::Trailer,
@@ -121,6 +121,21 @@ impl HttpMethod {
     }
   }
 
+  /// returns true if sending the request more than once has the same effect as sending it once,
+  /// as specified in [RFC 9110 Section 9.2.2](https://www.rfc-editor.org/rfc/rfc9110#section-9.2.2).
+  /// Custom methods are assumed not to be idempotent.
+  pub fn is_idempotent(&self) -> bool {
+    matches!(
+      self,
+      HttpMethod::Get
+        | HttpMethod::Head
+        | HttpMethod::Put
+        | HttpMethod::Delete
+        | HttpMethod::Options
+        | HttpMethod::Trace
+    )
+  }
+
   /// returns true if the server expects that a request that does NOT have a Content-Length: 0 header
   /// with this header may have a body anyway. If this returns true then Tii will be forced to emit
   /// a 'Connection: close' since there may be a body that Tii cannot parse. The body is never parsed in
@@ -2,6 +2,7 @@
 
 use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
 
 /// QValue is defined as a fixed point number with up to 3 digits
 /// after comma. with a valid range from 0 to 1.
@@ -540,11 +541,7 @@ impl AcceptMimeCharset {
       let charset = MimeCharset::parse(charset_name)?;
       let mut q = None;
 
-      loop {
-        let Some(next) = iter.next().map(str::trim) else {
-          break;
-        };
-
+      while let Some(next) = iter.next().map(str::trim) {
         if let Some(raw_q) = next.strip_prefix("q=") {
           if q.is_some() {
             // Multiple Q
@@ -579,10 +576,41 @@ impl AcceptMimeCharset {
 }
 
 /// Mime type with charset, suitable for use in a Content-Type header.
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+///
+/// The boundary parameter is not part of equality, ordering and hashing,
+/// two multipart mime types with different boundaries are the same mime type.
+#[derive(Clone, Debug)]
 pub struct MimeTypeWithCharset {
   mime: MimeType,
   charset: MimeCharset,
+  boundary: Option<String>,
+}
+
+impl PartialEq for MimeTypeWithCharset {
+  fn eq(&self, other: &Self) -> bool {
+    self.mime == other.mime && self.charset == other.charset
+  }
+}
+
+impl Eq for MimeTypeWithCharset {}
+
+impl PartialOrd for MimeTypeWithCharset {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for MimeTypeWithCharset {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.mime.cmp(&other.mime).then_with(|| self.charset.cmp(&other.charset))
+  }
+}
+
+impl Hash for MimeTypeWithCharset {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.mime.hash(state);
+    self.charset.hash(state);
+  }
 }
 
 impl AsRef<MimeType> for MimeTypeWithCharset {
@@ -598,12 +626,18 @@ impl MimeTypeWithCharset {
 
   /// Constructor
   pub const fn new(mime: MimeType, charset: MimeCharset) -> Self {
-    Self { mime, charset }
+    Self { mime, charset, boundary: None }
   }
 
   /// Constructor, unspecified charset
   pub const fn from_mime(mime: MimeType) -> Self {
-    Self { mime, charset: MimeCharset::Unspecified }
+    Self { mime, charset: MimeCharset::Unspecified, boundary: None }
+  }
+
+  /// Sets the boundary parameter of a multipart mime type.
+  pub fn with_boundary(mut self, boundary: impl ToString) -> Self {
+    self.boundary = Some(boundary.to_string());
+    self
   }
 
   /// This fn parses the mime type and assumes that its in the format of a valid Content-Type header.
@@ -611,19 +645,27 @@ impl MimeTypeWithCharset {
     let mut iter = value.as_ref().split(";");
     let ct = iter.next()?;
     let mt = MimeType::parse(ct.trim())?;
-    let charset = loop {
-      let Some(next) = iter.next().map(str::trim) else { break MimeCharset::Unspecified };
-
-      let mut stack_buffer = [0u8; 8];
-      if !crate::util::ascii_to_lower_first_n(&mut stack_buffer, next).starts_with("charset=") {
-        //boundry=..... for example
-        continue;
+    let mut charset = None;
+    let mut boundary = None;
+    for next in iter.map(str::trim) {
+      let mut stack_buffer = [0u8; 9];
+      let lower = crate::util::ascii_to_lower_first_n(&mut stack_buffer, next);
+      if lower.starts_with("charset=") {
+        if charset.is_none() {
+          charset = Some(MimeCharset::parse(next.get(8..)?)?);
+        }
+      } else if lower.starts_with("boundary=") {
+        let value = next.get(9..)?;
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+        boundary = Some(value.to_string());
       }
+    }
 
-      break MimeCharset::parse(next.get(8..)?)?;
-    };
-
-    Some(MimeTypeWithCharset::new(mt, charset))
+    Some(MimeTypeWithCharset {
+      mime: mt,
+      charset: charset.unwrap_or(MimeCharset::Unspecified),
+      boundary,
+    })
   }
 
   /// Ref to the mime type
@@ -640,15 +682,27 @@ impl MimeTypeWithCharset {
   pub const fn has_charset(&self) -> bool {
     !matches!(self.charset, MimeCharset::Unspecified)
   }
+
+  /// The boundary parameter of a multipart mime type such as multipart/form-data.
+  pub fn boundary(&self) -> Option<&str> {
+    self.boundary.as_deref()
+  }
 }
 
 impl Display for MimeTypeWithCharset {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.mime.as_str())?;
     if let Some(charset) = self.charset.as_str() {
-      f.write_fmt(format_args!("{}; charset={}", self.mime.as_str(), charset))
-    } else {
-      f.write_str(self.mime.as_str())
+      f.write_fmt(format_args!("; charset={charset}"))?;
+    }
+    if let Some(boundary) = self.boundary.as_deref() {
+      if boundary.bytes().all(|b| b.is_ascii_alphanumeric() || b"'+_-.".contains(&b)) {
+        f.write_fmt(format_args!("; boundary={boundary}"))?;
+      } else {
+        f.write_fmt(format_args!("; boundary=\"{boundary}\""))?;
+      }
     }
+    Ok(())
   }
 }
 
@@ -1066,6 +1120,8 @@ pub enum MimeType {
   TextCsv,
   /// text/calendar
   TextCalendar,
+  /// text/event-stream
+  TextEventStream,
 
   ///Anything else
   Other(MimeGroup, String),
@@ -1165,6 +1221,7 @@ const WELL_KNOWN_TYPES: &[MimeType] = &[
   MimeType::TextPlain,
   MimeType::TextCsv,
   MimeType::TextCalendar,
+  MimeType::TextEventStream,
   MimeType::ApplicationYaml,
   MimeType::TextLua,
   MimeType::ApplicationLuaBytecode,
@@ -1403,6 +1460,7 @@ impl MimeType {
       MimeType::TextPlain => "txt",
       MimeType::TextCsv => "csv",
       MimeType::TextCalendar => "cal",
+      MimeType::TextEventStream => "txt",
       MimeType::ApplicationYaml => "yaml",
       MimeType::TextLua => "lua",
       MimeType::ApplicationLuaBytecode => "luac",
@@ -1508,6 +1566,7 @@ impl MimeType {
       MimeType::TextPlain => &MimeGroup::Text,
       MimeType::TextCsv => &MimeGroup::Text,
       MimeType::TextCalendar => &MimeGroup::Text,
+      MimeType::TextEventStream => &MimeGroup::Text,
       MimeType::Other(group, _) => group,
     }
   }
@@ -1520,6 +1579,7 @@ impl MimeType {
       MimeType::Video3gpp | MimeType::Audio3gpp => false,   //3gp is shared
       MimeType::AudioMp4 | MimeType::VideoMp4 | MimeType::AudioAac => false, //.mp4 can also only contain audio, aac is always in an mp4 container.
       MimeType::AudioMpeg | MimeType::VideoMpeg => false, //mpeg container can contain both audio and video with the same extension
+      MimeType::TextEventStream => false,                 //Event streams are not stored in files.
       MimeType::Other(_, _) => false, //We don't know what the extension even is.
       _ => true,
     }
@@ -1643,6 +1703,7 @@ impl MimeType {
       MimeType::AudioMp3 => "audio/mpeg",
       MimeType::TextCsv => "text/csv",
       MimeType::TextCalendar => "text/calendar",
+      MimeType::TextEventStream => "text/event-stream",
       MimeType::ApplicationYaml => "application/yaml",
       MimeType::TextLua => "text/x-lua",
       MimeType::ApplicationLuaBytecode => "application/x-lua-bytecode",
@@ -1751,6 +1812,7 @@ impl MimeType {
       MimeType::AudioMp3 => "audio/mpeg",
       MimeType::TextCsv => "text/csv",
       MimeType::TextCalendar => "text/calendar",
+      MimeType::TextEventStream => "text/event-stream",
       MimeType::ApplicationYaml => "application/yaml",
       MimeType::TextLua => "text/x-lua",
       MimeType::ApplicationLuaBytecode => "application/x-lua-bytecode",
@@ -1872,6 +1934,7 @@ impl MimeType {
       "audio/webm" => MimeType::AudioWebm,
       "text/csv" => MimeType::TextCsv,
       "text/calendar" => MimeType::TextCalendar,
+      "text/event-stream" => MimeType::TextEventStream,
       "application/yaml" => MimeType::ApplicationYaml,
       "text/x-lua" => MimeType::TextLua,
       "application/x-lua-bytecode" => MimeType::ApplicationLuaBytecode,
@@ -1998,6 +2061,8 @@ impl From<AcceptQualityMimeType> for AcceptMimeType {
 #[cfg(test)]
 mod tests {
   use crate::http::mime::QValue;
+  use crate::{MimeType, MimeTypeWithCharset};
+  use std::collections::HashSet;
 
   /// Shutup clippy.
   #[macro_export]
@@ -2017,4 +2082,16 @@ mod tests {
     test_qvalue!(999, "0.999");
     test_qvalue!(1000, "1.0");
   }
+
+  #[test]
+  fn boundary_is_not_part_of_equality() {
+    let a = MimeTypeWithCharset::parse_from_content_type_header("multipart/form-data; boundary=a")
+      .unwrap();
+    let b = MimeTypeWithCharset::from_mime(MimeType::parse("multipart/form-data").unwrap())
+      .with_boundary("b");
+    assert_eq!(a.boundary(), Some("a"));
+    assert_eq!(a, b);
+    assert_eq!(a.cmp(&b), std::cmp::Ordering::Equal);
+    assert_eq!(HashSet::from([a, b]).len(), 1);
+  }
 }
@@ -1,5 +1,11 @@
 //! Contains the Tii HTTP implementation.
 
+mod compression;
+pub(crate) use compression::{accept_encoding_qvalue, parse_content_codings};
+pub use compression::{CompressionFilter, ContentCoding};
+mod conditional;
+pub(crate) use conditional::apply_conditional_request;
+pub use conditional::{etag_filter, format_http_date, parse_http_date, precondition_filter};
 mod cookie;
 pub use cookie::*;
 
@@ -11,6 +17,10 @@ pub use method::*;
 
 mod mime;
 pub use mime::*;
+mod multipart;
+pub use multipart::*;
+mod range;
+pub(crate) use range::apply_range_request;
 mod request;
 pub use request::*;
 mod request_body;
@@ -25,8 +35,15 @@ mod response_context;
 pub use response_context::*;
 mod response_entity;
 pub use response_entity::*;
+mod response_head;
+pub(crate) use response_head::{invalid_response, read_line, ResponseHead};
+mod sse;
+pub use sse::{sse_endpoint, SseSender};
 mod status;
 pub use status::*;
 mod type_handler;
+mod urlencoded;
+pub(crate) use urlencoded::decode_query;
+pub use urlencoded::UrlEncodedForm;
 
 pub use type_handler::*;
@@ -0,0 +1,617 @@
+//! Streaming parser for multipart/form-data request bodies as specified in [RFC 7578](https://datatracker.ietf.org/doc/html/rfc7578).
+
+use crate::{
+  EntityDeserializer, HttpHeader, HttpHeaderName, MimeTypeWithCharset, RequestBody,
+  RequestBodyError, TiiResult,
+};
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::io::Read;
+
+/// Size of the internal read buffer. Must be larger than `MAX_PART_HEADER_SIZE`.
+const BUFFER_SIZE: usize = 0x4000;
+
+/// Maximum size of the headers of a single part.
+const MAX_PART_HEADER_SIZE: usize = 0x2000;
+
+/// Maximum length of a boundary as specified in RFC 2046.
+const MAX_BOUNDARY_LENGTH: usize = 70;
+
+fn invalid(message: &str) -> io::Error {
+  RequestBodyError::InvalidMultipart(message.to_string()).into()
+}
+
+/// Reads the parts of a multipart body one after another without buffering the entire body.
+///
+/// Parts that are not read completely are skipped when the next part is requested.
+pub struct MultipartReader<R: Read> {
+  read: R,
+  /// "\r\n--" followed by the boundary.
+  delimiter: Vec<u8>,
+  buffer: Box<[u8]>,
+  start: usize,
+  end: usize,
+  in_part: bool,
+  done: bool,
+  max_part_size: Option<u64>,
+  max_total_size: Option<u64>,
+  part_size: u64,
+  total_size: u64,
+}
+
+impl<R: Read> Debug for MultipartReader<R> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("MultipartReader")
+      .field("delimiter", &String::from_utf8_lossy(&self.delimiter))
+      .field("in_part", &self.in_part)
+      .field("done", &self.done)
+      .field("max_part_size", &self.max_part_size)
+      .field("max_total_size", &self.max_total_size)
+      .field("part_size", &self.part_size)
+      .field("total_size", &self.total_size)
+      .finish()
+  }
+}
+
+impl<'a> MultipartReader<&'a RequestBody> {
+  /// Creates a reader for a request body with the given content type.
+  /// Fails if the content type is not a multipart type or has no boundary.
+  pub fn from_request_body(
+    content_type: &MimeTypeWithCharset,
+    body: &'a RequestBody,
+  ) -> TiiResult<Self> {
+    if !content_type.mime().as_str().starts_with("multipart/") {
+      return Err(invalid("content type is not multipart").into());
+    }
+
+    let Some(boundary) = content_type.boundary() else {
+      return Err(invalid("content type has no boundary").into());
+    };
+
+    Self::new(body, boundary)
+  }
+}
+
+impl<R: Read> MultipartReader<R> {
+  /// Creates a reader for a multipart body with the given boundary.
+  pub fn new(read: R, boundary: &str) -> TiiResult<Self> {
+    if boundary.is_empty() || boundary.len() > MAX_BOUNDARY_LENGTH {
+      return Err(invalid("boundary must be between 1 and 70 characters long").into());
+    }
+
+    let mut delimiter = b"\r\n--".to_vec();
+    delimiter.extend_from_slice(boundary.as_bytes());
+
+    let mut buffer = vec![0u8; BUFFER_SIZE].into_boxed_slice();
+    //The first delimiter is not preceded by a line break, pretend it is so every delimiter looks the same.
+    buffer.get_mut(..2).unwrap_or_default().copy_from_slice(b"\r\n");
+
+    Ok(Self {
+      read,
+      delimiter,
+      buffer,
+      start: 0,
+      end: 2,
+      //Everything before the first delimiter is a preamble that is skipped like an unread part.
+      in_part: true,
+      done: false,
+      max_part_size: None,
+      max_total_size: None,
+      part_size: 0,
+      total_size: 0,
+    })
+  }
+
+  /// Sets the maximum size of the content of a single part.
+  /// Reading a larger part fails with `RequestBodyError::MultipartPartTooLarge`.
+  pub fn with_max_part_size(mut self, max_part_size: Option<u64>) -> Self {
+    self.max_part_size = max_part_size;
+    self
+  }
+
+  /// Sets the maximum size of the entire multipart body including the part headers.
+  /// Reading a larger body fails with `RequestBodyError::MultipartTooLarge`.
+  pub fn with_max_total_size(mut self, max_total_size: Option<u64>) -> Self {
+    self.max_total_size = max_total_size;
+    self
+  }
+
+  /// Returns the next part or None if all parts have been read.
+  pub fn next_part(&mut self) -> TiiResult<Option<MultipartPart<'_, R>>> {
+    if self.done {
+      return Ok(None);
+    }
+
+    let mut skip = [0u8; 0x400];
+    while self.read_part(&mut skip)? > 0 {}
+
+    self.ensure(2)?;
+    if self.buffered().starts_with(b"--") {
+      self.done = true;
+      return Ok(None);
+    }
+
+    //Skip transport padding after the delimiter.
+    loop {
+      self.ensure(1)?;
+      match self.buffered().first() {
+        Some(b' ') | Some(b'\t') => self.start += 1,
+        _ => break,
+      }
+    }
+
+    self.ensure(2)?;
+    if !self.buffered().starts_with(b"\r\n") {
+      return Err(invalid("delimiter is not followed by a line break").into());
+    }
+    self.start += 2;
+
+    let mut headers = Vec::new();
+    let mut header_size = 0usize;
+    loop {
+      let line = self.read_line(MAX_PART_HEADER_SIZE.saturating_sub(header_size))?;
+      header_size += line.len() + 2;
+      if line.is_empty() {
+        break;
+      }
+
+      let line = String::from_utf8_lossy(&line);
+      let Some((name, value)) = line.split_once(':') else {
+        return Err(invalid("part header has no value").into());
+      };
+
+      let name = name.trim();
+      if name.is_empty() {
+        return Err(invalid("part header name is empty").into());
+      }
+
+      headers.push(HttpHeader::new(name, value.trim()));
+    }
+
+    self.in_part = true;
+    self.part_size = 0;
+    Ok(Some(MultipartPart::new(self, headers)))
+  }
+
+  fn buffered(&self) -> &[u8] {
+    self.buffer.get(self.start..self.end).unwrap_or_default()
+  }
+
+  /// Reads more data into the buffer. Returns false at the end of the body.
+  fn fill(&mut self) -> io::Result<bool> {
+    if self.start > 0 {
+      self.buffer.copy_within(self.start..self.end, 0);
+      self.end -= self.start;
+      self.start = 0;
+    }
+
+    let count = self.read.read(self.buffer.get_mut(self.end..).unwrap_or_default())?;
+    self.end += count;
+    self.total_size = self.total_size.saturating_add(count as u64);
+    if let Some(max_total_size) = self.max_total_size {
+      if self.total_size > max_total_size {
+        return Err(RequestBodyError::MultipartTooLarge(max_total_size).into());
+      }
+    }
+
+    Ok(count > 0)
+  }
+
+  /// Ensures that at least the given amount of bytes is buffered.
+  fn ensure(&mut self, count: usize) -> io::Result<()> {
+    while self.end - self.start < count {
+      if !self.fill()? {
+        return Err(invalid("unexpected end of body"));
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Reads a line that ends with a CRLF and returns it without the CRLF.
+  fn read_line(&mut self, max_length: usize) -> io::Result<Vec<u8>> {
+    loop {
+      if let Some(index) = self.buffered().windows(2).position(|w| w == b"\r\n") {
+        if index > max_length {
+          break;
+        }
+
+        let line = self.buffered().get(..index).unwrap_or_default().to_vec();
+        self.start += index + 2;
+        return Ok(line);
+      }
+
+      if self.end - self.start > max_length {
+        break;
+      }
+
+      if !self.fill()? {
+        return Err(invalid("unexpected end of body"));
+      }
+    }
+
+    Err(invalid("part headers are too large"))
+  }
+
+  /// Reads the content of the current part. Returns 0 once the delimiter that ends the part has been reached.
+  fn read_part(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if !self.in_part || buf.is_empty() {
+      return Ok(0);
+    }
+
+    loop {
+      let buffered = self.buffered();
+      let available = match buffered.windows(self.delimiter.len()).position(|w| w == self.delimiter)
+      {
+        Some(0) => {
+          self.start += self.delimiter.len();
+          self.in_part = false;
+          return Ok(0);
+        }
+        Some(index) => index,
+        //The end of the buffer may contain the start of the delimiter.
+        None => buffered.len().saturating_sub(self.delimiter.len() - 1),
+      };
+
+      if available > 0 {
+        let count = available.min(buf.len());
+        buf
+          .get_mut(..count)
+          .unwrap_or_default()
+          .copy_from_slice(buffered.get(..count).unwrap_or_default());
+        self.start += count;
+        self.part_size = self.part_size.saturating_add(count as u64);
+        if let Some(max_part_size) = self.max_part_size {
+          if self.part_size > max_part_size {
+            return Err(RequestBodyError::MultipartPartTooLarge(max_part_size).into());
+          }
+        }
+
+        return Ok(count);
+      }
+
+      if !self.fill()? {
+        return Err(invalid("unexpected end of body"));
+      }
+    }
+  }
+}
+
+/// A single part of a multipart body. The content of the part is read with the `Read` impl.
+pub struct MultipartPart<'a, R: Read> {
+  reader: &'a mut MultipartReader<R>,
+  headers: Vec<HttpHeader>,
+  name: Option<String>,
+  filename: Option<String>,
+  content_type: Option<MimeTypeWithCharset>,
+}
+
+impl<R: Read> Debug for MultipartPart<'_, R> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("MultipartPart")
+      .field("headers", &self.headers)
+      .field("name", &self.name)
+      .field("filename", &self.filename)
+      .field("content_type", &self.content_type)
+      .finish()
+  }
+}
+
+impl<'a, R: Read> MultipartPart<'a, R> {
+  fn new(reader: &'a mut MultipartReader<R>, headers: Vec<HttpHeader>) -> Self {
+    let (name, filename) = headers
+      .iter()
+      .find(|hdr| hdr.name == HttpHeaderName::ContentDisposition)
+      .map(|hdr| parse_content_disposition(&hdr.value))
+      .unwrap_or_default();
+
+    let content_type = headers
+      .iter()
+      .find(|hdr| hdr.name == HttpHeaderName::ContentType)
+      .and_then(|hdr| MimeTypeWithCharset::parse_from_content_type_header(&hdr.value));
+
+    Self { reader, headers, name, filename, content_type }
+  }
+
+  /// Returns the first value of the given part header.
+  pub fn get_header(&self, name: impl AsRef<str>) -> Option<&str> {
+    let name = name.as_ref();
+    self
+      .headers
+      .iter()
+      .find(|hdr| hdr.name.to_str().eq_ignore_ascii_case(name))
+      .map(|hdr| hdr.value.as_str())
+  }
+
+  /// Returns all headers of this part.
+  pub fn get_all_headers(&self) -> &[HttpHeader] {
+    &self.headers
+  }
+
+  /// The name of the form field from the `Content-Disposition` header.
+  pub fn name(&self) -> Option<&str> {
+    self.name.as_deref()
+  }
+
+  /// The file name from the `Content-Disposition` header. Only present for file uploads.
+  pub fn filename(&self) -> Option<&str> {
+    self.filename.as_deref()
+  }
+
+  /// The content type of this part.
+  pub fn content_type(&self) -> Option<&MimeTypeWithCharset> {
+    self.content_type.as_ref()
+  }
+
+  /// Reads the remaining content of this part into a Vec.
+  pub fn read_to_vec(&mut self) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    self.read_to_end(&mut data)?;
+    Ok(data)
+  }
+
+  /// Reads this part completely and turns it into a field that owns its content.
+  pub fn into_field(mut self) -> io::Result<MultipartField> {
+    let data = self.read_to_vec()?;
+    Ok(MultipartField {
+      headers: self.headers,
+      name: self.name,
+      filename: self.filename,
+      content_type: self.content_type,
+      data,
+    })
+  }
+}
+
+impl<R: Read> Read for MultipartPart<'_, R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.reader.read_part(buf)
+  }
+}
+
+/// Extracts the name and filename parameters of a `Content-Disposition` header.
+fn parse_content_disposition(value: &str) -> (Option<String>, Option<String>) {
+  let mut name = None;
+  let mut filename = None;
+
+  let Some((_, mut rest)) = value.split_once(';') else {
+    return (None, None);
+  };
+
+  loop {
+    rest = rest.trim_start_matches(|c: char| c == ';' || c.is_ascii_whitespace());
+    let Some((key, value_and_rest)) = rest.split_once('=') else {
+      break;
+    };
+
+    let (value, remaining) = match value_and_rest.strip_prefix('"') {
+      Some(quoted) => {
+        let mut value = String::new();
+        let mut escaped = false;
+        let mut end = quoted.len();
+        for (index, char) in quoted.char_indices() {
+          match (escaped, char) {
+            (false, '\\') => escaped = true,
+            (false, '"') => {
+              end = index + 1;
+              break;
+            }
+            _ => {
+              escaped = false;
+              value.push(char);
+            }
+          }
+        }
+        (value, quoted.get(end..).unwrap_or_default())
+      }
+      None => {
+        let (value, remaining) = value_and_rest.split_once(';').unwrap_or((value_and_rest, ""));
+        (value.trim().to_string(), remaining)
+      }
+    };
+
+    match key.trim().to_ascii_lowercase().as_str() {
+      "name" => name = Some(value),
+      "filename" => filename = Some(value),
+      _ => {}
+    }
+
+    rest = remaining;
+  }
+
+  (name, filename)
+}
+
+/// A part of a multipart/form-data body that has been read into memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartField {
+  headers: Vec<HttpHeader>,
+  name: Option<String>,
+  filename: Option<String>,
+  content_type: Option<MimeTypeWithCharset>,
+  data: Vec<u8>,
+}
+
+impl MultipartField {
+  /// Returns the first value of the given part header.
+  pub fn get_header(&self, name: impl AsRef<str>) -> Option<&str> {
+    let name = name.as_ref();
+    self
+      .headers
+      .iter()
+      .find(|hdr| hdr.name.to_str().eq_ignore_ascii_case(name))
+      .map(|hdr| hdr.value.as_str())
+  }
+
+  /// Returns all headers of this part.
+  pub fn get_all_headers(&self) -> &[HttpHeader] {
+    &self.headers
+  }
+
+  /// The name of the form field from the `Content-Disposition` header.
+  pub fn name(&self) -> Option<&str> {
+    self.name.as_deref()
+  }
+
+  /// The file name from the `Content-Disposition` header. Only present for file uploads.
+  pub fn filename(&self) -> Option<&str> {
+    self.filename.as_deref()
+  }
+
+  /// The content type of this part.
+  pub fn content_type(&self) -> Option<&MimeTypeWithCharset> {
+    self.content_type.as_ref()
+  }
+
+  /// The content of this part.
+  pub fn data(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// The content of this part as text or None if it is not valid utf-8.
+  pub fn text(&self) -> Option<&str> {
+    std::str::from_utf8(&self.data).ok()
+  }
+
+  /// Returns the content of this part.
+  pub fn into_data(self) -> Vec<u8> {
+    self.data
+  }
+}
+
+/// A multipart/form-data body that has been read into memory.
+/// Use `MultipartFormDeserializer` to receive it in an entity endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultipartForm {
+  fields: Vec<MultipartField>,
+}
+
+impl MultipartForm {
+  /// Reads all remaining parts of the reader into memory.
+  pub fn read<R: Read>(reader: &mut MultipartReader<R>) -> TiiResult<Self> {
+    let mut fields = Vec::new();
+    while let Some(part) = reader.next_part()? {
+      fields.push(part.into_field()?);
+    }
+
+    Ok(Self { fields })
+  }
+
+  /// All fields in order of appearance.
+  pub fn fields(&self) -> &[MultipartField] {
+    &self.fields
+  }
+
+  /// Returns the first field with the given name.
+  pub fn get_field(&self, name: impl AsRef<str>) -> Option<&MultipartField> {
+    let name = name.as_ref();
+    self.fields.iter().find(|field| field.name() == Some(name))
+  }
+
+  /// Returns all fields with the given name in order of appearance.
+  pub fn get_fields(&self, name: impl AsRef<str>) -> Vec<&MultipartField> {
+    let name = name.as_ref();
+    self.fields.iter().filter(|field| field.name() == Some(name)).collect()
+  }
+
+  /// Returns the text of the first field with the given name.
+  pub fn get_text(&self, name: impl AsRef<str>) -> Option<&str> {
+    self.get_field(name).and_then(MultipartField::text)
+  }
+
+  /// Returns all fields that are file uploads.
+  pub fn files(&self) -> impl Iterator<Item = &MultipartField> {
+    self.fields.iter().filter(|field| field.filename().is_some())
+  }
+
+  /// Returns the fields.
+  pub fn into_fields(self) -> Vec<MultipartField> {
+    self.fields
+  }
+}
+
+/// Deserializes multipart/form-data request bodies into a `MultipartForm` for entity endpoints.
+///
+/// Each part is read into memory, so the limits should be set to sensible values for the endpoint.
+/// The maximum request body size of the server or route applies in addition to the limits of this deserializer.
+#[derive(Debug, Clone, Default)]
+pub struct MultipartFormDeserializer {
+  max_part_size: Option<u64>,
+  max_total_size: Option<u64>,
+}
+
+impl MultipartFormDeserializer {
+  /// Creates a deserializer without limits.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the maximum size of the content of a single part.
+  pub fn with_max_part_size(mut self, max_part_size: u64) -> Self {
+    self.max_part_size = Some(max_part_size);
+    self
+  }
+
+  /// Sets the maximum size of the entire multipart body.
+  pub fn with_max_total_size(mut self, max_total_size: u64) -> Self {
+    self.max_total_size = Some(max_total_size);
+    self
+  }
+}
+
+impl EntityDeserializer<MultipartForm> for MultipartFormDeserializer {
+  fn deserialize(
+    &self,
+    mime: &MimeTypeWithCharset,
+    body: &RequestBody,
+  ) -> TiiResult<MultipartForm> {
+    let mut reader = MultipartReader::from_request_body(mime, body)?
+      .with_max_part_size(self.max_part_size)
+      .with_max_total_size(self.max_total_size);
+
+    MultipartForm::read(&mut reader)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::http::multipart::{parse_content_disposition, MultipartReader};
+  use std::io::Read;
+
+  #[test]
+  fn test_content_disposition() {
+    assert_eq!(
+      parse_content_disposition("form-data; name=\"field\"; filename=\"a \\\"b\\\".txt\""),
+      (Some("field".to_string()), Some("a \"b\".txt".to_string()))
+    );
+    assert_eq!(
+      parse_content_disposition("form-data; name=plain"),
+      (Some("plain".to_string()), None)
+    );
+    assert_eq!(parse_content_disposition("form-data"), (None, None));
+  }
+
+  #[test]
+  fn test_small_reads() {
+    let body = b"preamble\r\n--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n--xy\r\n--xyz \r\n\r\nsecond\r\n--xyz--\r\nepilogue";
+    //Read the body one byte at a time to test that delimiters spanning multiple reads are detected.
+    struct OneByte<'a>(&'a [u8]);
+    impl Read for OneByte<'_> {
+      fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let (first, rest) = self.0.split_at(self.0.len().min(1).min(buf.len()));
+        buf[..first.len()].copy_from_slice(first);
+        self.0 = rest;
+        Ok(first.len())
+      }
+    }
+
+    let mut reader = MultipartReader::new(OneByte(body), "xyz").unwrap();
+    let mut part = reader.next_part().unwrap().unwrap();
+    assert_eq!(part.name(), Some("a"));
+    assert_eq!(part.read_to_vec().unwrap(), b"--xy");
+    let mut part = reader.next_part().unwrap().unwrap();
+    assert_eq!(part.name(), None);
+    assert_eq!(part.read_to_vec().unwrap(), b"second");
+    assert!(reader.next_part().unwrap().is_none());
+  }
+}
@@ -0,0 +1,212 @@
+//! Implements range requests as specified in [RFC 9110 Section 14](https://datatracker.ietf.org/doc/html/rfc9110#section-14).
+
+use crate::http::response_body::FileSegment;
+use crate::util::unwrap_some;
+use crate::{HttpHeaderName, HttpMethod, RequestContext, Response, StatusCode, TiiResult};
+
+/// Range headers with more ranges than this are ignored and the complete resource is sent.
+const MAX_RANGES: usize = 64;
+
+/// Parses the value of a `Range` header for a resource of the given size.
+/// Returns the first and last byte position (inclusive) of every satisfiable range ordered by position.
+/// Overlapping and adjacent ranges are merged, so every byte is sent at most once.
+///
+/// Returns None if the header is malformed, uses a unit other than bytes or contains too many ranges.
+/// Such a header must be ignored.
+/// Returns an empty Vec if none of the ranges is satisfiable.
+pub(crate) fn parse_range_header(value: &str, size: u64) -> Option<Vec<(u64, u64)>> {
+  let (unit, ranges) = value.split_once('=')?;
+  if !unit.trim().eq_ignore_ascii_case("bytes") {
+    return None;
+  }
+
+  let mut count = 0usize;
+  let mut result = Vec::new();
+  for spec in ranges.split(',').map(str::trim).filter(|spec| !spec.is_empty()) {
+    count += 1;
+    if count > MAX_RANGES {
+      return None;
+    }
+
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+      //Suffix range, the last n bytes.
+      let suffix = parse_position(last)?;
+      if suffix == 0 || size == 0 {
+        continue;
+      }
+      result.push((size.saturating_sub(suffix), size - 1));
+      continue;
+    }
+
+    let first = parse_position(first)?;
+    let last = match last {
+      "" => None,
+      last => Some(parse_position(last)?),
+    };
+
+    if last.is_some_and(|last| last < first) {
+      return None;
+    }
+
+    if first >= size {
+      continue;
+    }
+
+    result.push((first, last.unwrap_or(u64::MAX).min(size - 1)));
+  }
+
+  if count == 0 {
+    return None;
+  }
+
+  Some(merge_ranges(result))
+}
+
+/// Sorts the ranges and merges those that overlap or are adjacent,
+/// see [RFC 9110 section 14.2](https://www.rfc-editor.org/rfc/rfc9110#section-14.2).
+fn merge_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+  ranges.sort_unstable();
+  let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+  for (first, last) in ranges {
+    match merged.last_mut() {
+      Some((_, previous_last)) if first <= previous_last.saturating_add(1) => {
+        *previous_last = last.max(*previous_last);
+      }
+      _ => merged.push((first, last)),
+    }
+  }
+  merged
+}
+
+fn parse_position(value: &str) -> Option<u64> {
+  if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+
+  value.parse().ok()
+}
+
+/// Returns true if the validator of an `If-Range` header matches the response.
+/// Entity tags must match the `ETag` of the response using the strong comparison.
+/// Dates must be equal to the `Last-Modified` header of the response.
+fn if_range_matches(if_range: &str, response: &Response) -> bool {
+  let if_range = if_range.trim();
+  if if_range.starts_with('"') || if_range.starts_with("W/") {
+    return !if_range.starts_with("W/")
+      && response.get_header(HttpHeaderName::ETag).is_some_and(|etag| etag.trim() == if_range);
+  }
+
+  response.get_header(HttpHeaderName::LastModified).is_some_and(|date| date.trim() == if_range)
+}
+
+/// Answers a range request if the response is a 200 OK with an uncompressed file body.
+/// Such responses always advertise `Accept-Ranges: bytes`.
+///
+/// A single range is answered with a 206 Partial Content that contains the range.
+/// Multiple ranges are answered with a 206 Partial Content of type multipart/byteranges.
+/// If no range is satisfiable then the response is replaced by a 416 Range Not Satisfiable.
+/// The response is returned unchanged if the `Range` header is malformed or the `If-Range` validator does not match.
+/// Only GET requests are answered with ranges, the `Range` header of any other method is ignored (RFC 9110 section 14.2).
+pub(crate) fn apply_range_request(
+  request: &RequestContext,
+  mut response: Response,
+) -> TiiResult<Response> {
+  if response.status_code != StatusCode::OK {
+    return Ok(response);
+  }
+
+  let Some(size) = response.body.as_ref().and_then(|body| body.file_size()) else {
+    return Ok(response);
+  };
+
+  if response.get_header(HttpHeaderName::AcceptRanges).is_none() {
+    response.add_header(HttpHeaderName::AcceptRanges, "bytes")?;
+  }
+
+  if request.get_method() != HttpMethod::Get {
+    return Ok(response);
+  }
+
+  let Some(range) = request.get_header(&HttpHeaderName::Range) else {
+    return Ok(response);
+  };
+
+  if let Some(if_range) = request.get_header(&HttpHeaderName::IfRange) {
+    if !if_range_matches(if_range, &response) {
+      return Ok(response);
+    }
+  }
+
+  let Some(ranges) = parse_range_header(range, size) else {
+    return Ok(response);
+  };
+
+  if ranges.is_empty() {
+    return Response::new(StatusCode::RequestedRangeNotSatisfiable)
+      .with_header(HttpHeaderName::AcceptRanges, "bytes")?
+      .with_header(HttpHeaderName::ContentRange, format!("bytes */{size}"));
+  }
+
+  let mut segments = Vec::with_capacity(ranges.len() * 2 + 1);
+  if let [(first, last)] = ranges.as_slice() {
+    response.set_header(HttpHeaderName::ContentRange, format!("bytes {first}-{last}/{size}"))?;
+    segments.push(FileSegment::Range(*first, last - first + 1));
+  } else {
+    let boundary = format!("tii-byteranges-{:032x}", request.id());
+    let content_type = response
+      .get_header(HttpHeaderName::ContentType)
+      .map(|content_type| format!("Content-Type: {content_type}\r\n"))
+      .unwrap_or_default();
+
+    for (index, (first, last)) in ranges.iter().enumerate() {
+      let delimiter = if index == 0 { "" } else { "\r\n" };
+      segments.push(FileSegment::Data(
+        format!("{delimiter}--{boundary}\r\n{content_type}Content-Range: bytes {first}-{last}/{size}\r\n\r\n")
+          .into_bytes(),
+      ));
+      segments.push(FileSegment::Range(*first, last - first + 1));
+    }
+    segments.push(FileSegment::Data(format!("\r\n--{boundary}--\r\n").into_bytes()));
+
+    response.set_header(
+      HttpHeaderName::ContentType,
+      format!("multipart/byteranges; boundary={boundary}"),
+    )?;
+  }
+
+  let body = unwrap_some(response.body.take());
+  response.body = Some(body.into_file_segments(segments).unwrap_or_else(|body| body));
+  response.status_code = StatusCode::PartialContent;
+  Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::http::range::parse_range_header;
+
+  #[test]
+  fn test_parse_range_header() {
+    assert_eq!(parse_range_header("bytes=0-499", 1000), Some(vec![(0, 499)]));
+    assert_eq!(parse_range_header("bytes=500-", 1000), Some(vec![(500, 999)]));
+    assert_eq!(parse_range_header("bytes=-200", 1000), Some(vec![(800, 999)]));
+    assert_eq!(parse_range_header("bytes=-2000", 1000), Some(vec![(0, 999)]));
+    assert_eq!(parse_range_header("bytes=900-2000", 1000), Some(vec![(900, 999)]));
+    assert_eq!(parse_range_header("bytes= 0-1, 5-6 ,", 1000), Some(vec![(0, 1), (5, 6)]));
+    assert_eq!(parse_range_header("bytes=5-6,0-1", 1000), Some(vec![(0, 1), (5, 6)]));
+    assert_eq!(parse_range_header("bytes=0-5,3-8,9-10,20-", 30), Some(vec![(0, 10), (20, 29)]));
+    assert_eq!(parse_range_header("bytes=0-1,0-1,0-1,-1000", 1000), Some(vec![(0, 999)]));
+    assert_eq!(parse_range_header("bytes=1000-", 1000), Some(vec![]));
+    assert_eq!(parse_range_header("bytes=-0", 1000), Some(vec![]));
+    assert_eq!(parse_range_header("bytes=0-0", 0), Some(vec![]));
+    assert_eq!(parse_range_header("bytes=5-1", 1000), None);
+    assert_eq!(parse_range_header("bytes=+1-5", 1000), None);
+    assert_eq!(parse_range_header("bytes=a-5", 1000), None);
+    assert_eq!(parse_range_header("bytes=", 1000), None);
+    assert_eq!(parse_range_header("items=0-5", 1000), None);
+    assert_eq!(parse_range_header("0-5", 1000), None);
+    assert_eq!(parse_range_header(&format!("bytes={}", "0-1,".repeat(65)), 1000), None);
+  }
+}
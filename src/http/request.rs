@@ -1,11 +1,12 @@
 //! Provides functionality for handling HTTP requests.
 
 use crate::{error_log, AcceptMimeCharset, HttpMethod, MimeCharset, MimeTypeWithCharset};
-use crate::{trace_log, Cookie};
+use crate::{trace_log, ContentCoding, Cookie};
 use crate::{Headers, HttpHeader, HttpHeaderName};
 
+use crate::http::{accept_encoding_qvalue, decode_query};
 use crate::tii_error::{RequestHeadParsingError, TiiError, TiiResult, UserError};
-use crate::util::{unwrap_ok, unwrap_some};
+use crate::util::unwrap_some;
 use crate::warn_log;
 use crate::ConnectionStream;
 use crate::{AcceptQualityMimeType, MimeType, QValue};
@@ -80,6 +81,44 @@ impl HttpVersion {
   }
 }
 
+/// Controls which requests are accepted when a request head is parsed.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub(crate) struct RequestParsingOptions {
+  /// Rejects anything that RFC 9112 does not allow or that could make the framing of the request ambiguous.
+  pub(crate) strict: bool,
+  /// Requests with an older http version are rejected.
+  pub(crate) min_version: HttpVersion,
+}
+
+impl Default for RequestParsingOptions {
+  fn default() -> Self {
+    Self { strict: false, min_version: HttpVersion::Http09 }
+  }
+}
+
+/// RFC 9110 Section 5.6.2
+pub(crate) fn is_token(value: &str) -> bool {
+  !value.is_empty()
+    && value.bytes().all(|n| n.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&n))
+}
+
+/// Checks that a line only contains CR and LF as its CRLF terminator.
+fn validate_line_ending(line: &[u8]) -> TiiResult<()> {
+  let Some(content) = line.strip_suffix(b"\n") else {
+    //The line is incomplete, the lenient checks report this.
+    return Ok(());
+  };
+  let Some(content) = content.strip_suffix(b"\r") else {
+    return Err(RequestHeadParsingError::BareLineFeed.into());
+  };
+
+  if content.contains(&b'\r') {
+    return Err(RequestHeadParsingError::BareCarriageReturn.into());
+  }
+
+  Ok(())
+}
+
 /// Represents a request to the server.
 /// Contains parsed information about the request's data.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -189,8 +228,8 @@ fn parse_status_line(start_line_buf: &Vec<u8>) -> TiiResult<&str> {
       b'%' => {}
       b' ' => {}
       b'\\' => {} // curl doesnt escape this character
-      b'\r' => {} // TODO we should check this later... this is only allowed as the second to last char...
-      b'\n' => {} // TODO we should check this later... this is only allowed as the last char...
+      b'\r' => {} // Only allowed as the second to last char, this is checked in strict mode.
+      b'\n' => {} // read_until guarantees that this is the last char.
       other => {
         if other.is_ascii_alphanumeric() {
           continue;
@@ -208,90 +247,78 @@ fn parse_status_line(start_line_buf: &Vec<u8>) -> TiiResult<&str> {
   crate::util::unreachable()
 }
 
-fn parse_raw_query(raw_query: &str) -> TiiResult<Vec<(String, String)>> {
-  if raw_query.is_empty() {
-    return Ok(Vec::new());
-  }
-
-  let mut query = Vec::new();
-  let mut current_key = Vec::new();
-  let mut current_value = Vec::new();
-  let mut matching_value = false;
-  let mut matching_percent = 0;
-  for n in raw_query.as_bytes().iter().copied() {
-    if matching_percent != 0 {
-      match n {
-        b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F' => {
-          matching_percent -= 1;
-        }
-        _ => {
-          return Err(RequestHeadParsingError::InvalidQueryString(raw_query.to_string()).into());
-        }
-      }
-    }
+/// Strict checks of a header line that are not done by the lenient parser.
+fn validate_header_line(line: &str) -> TiiResult<()> {
+  if line == "\r\n" {
+    return Ok(());
+  }
 
-    match n {
-      b'=' => {
-        if matching_value {
-          return Err(RequestHeadParsingError::InvalidQueryString(raw_query.to_string()).into());
-        }
-        matching_value = true;
-        continue;
-      }
-      b'&' => {
-        if !matching_value {
-          return Err(RequestHeadParsingError::InvalidQueryString(raw_query.to_string()).into());
-        }
+  if line.starts_with([' ', '\t']) {
+    //RFC 9112 Section 5.2, obs-fold is deprecated
+    return Err(RequestHeadParsingError::HeaderObsFold.into());
+  }
 
-        let key = urlencoding::decode(unwrap_ok(std::str::from_utf8(current_key.as_slice())))
-          .map_err(|_| RequestHeadParsingError::InvalidQueryString(raw_query.to_string()))?
-          .replace('+', " ");
+  let Some((name, _)) = line.split_once(':') else {
+    return Err(RequestHeadParsingError::MalformedHeaderLine(line.trim().to_string()).into());
+  };
 
-        let value = urlencoding::decode(unwrap_ok(std::str::from_utf8(current_value.as_slice())))
-          .map_err(|_| RequestHeadParsingError::InvalidQueryString(raw_query.to_string()))?
-          .replace('+', " ");
+  if name.ends_with([' ', '\t']) {
+    //RFC 9112 Section 5.1
+    return Err(RequestHeadParsingError::HeaderWhitespaceBeforeColon.into());
+  }
 
-        query.push((key, value));
+  if !is_token(name) {
+    return Err(RequestHeadParsingError::InvalidHeaderNameToken(name.to_string()).into());
+  }
 
-        matching_value = false;
-        current_key = Vec::new();
-        current_value = Vec::new();
-        continue;
-      }
-      b'%' => {
-        matching_percent = 2;
-      }
-      b'!' | b'$' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b'-' | b'.' | b'/' | b':' | b';'
-      | b'@' | b'_' | b'~' => {}
-      other => {
-        if !other.is_ascii_alphanumeric() {
-          return Err(RequestHeadParsingError::InvalidQueryString(raw_query.to_string()).into());
-        }
-      }
+  Ok(())
+}
+
+/// Rejects headers that would make the length of the request body ambiguous.
+/// RFC 9112 Section 6.3
+fn validate_framing_headers(headers: &Headers) -> TiiResult<()> {
+  let transfer_encodings = headers.get_all(HttpHeaderName::TransferEncoding);
+  let content_lengths = headers.get_all(HttpHeaderName::ContentLength);
+  if !transfer_encodings.is_empty() {
+    if !content_lengths.is_empty() {
+      return Err(RequestHeadParsingError::ContentLengthWithTransferEncoding.into());
     }
 
-    if matching_value {
-      current_value.push(n);
-    } else {
-      current_key.push(n);
+    //Only the final coding may be chunked, otherwise the end of the body is unknown.
+    let codings: Vec<&str> =
+      transfer_encodings.iter().flat_map(|value| value.split(',')).map(str::trim).collect();
+    let is_chunked = |coding: &str| coding.eq_ignore_ascii_case("chunked");
+    if transfer_encodings.len() > 1
+      || codings.iter().filter(|coding| is_chunked(coding)).count() != 1
+      || !codings.last().is_some_and(|coding| is_chunked(coding))
+    {
+      let values = transfer_encodings.iter().map(|value| value.to_string()).collect();
+      return Err(RequestHeadParsingError::InvalidTransferEncoding(values).into());
     }
-  }
 
-  if !matching_value || matching_percent != 0 {
-    return Err(RequestHeadParsingError::InvalidQueryString(raw_query.to_string()).into());
+    return Ok(());
   }
 
-  let key = urlencoding::decode(unwrap_ok(std::str::from_utf8(current_key.as_slice())))
-    .map_err(|_| RequestHeadParsingError::InvalidQueryString(raw_query.to_string()))?
-    .replace('+', " ");
+  if content_lengths.is_empty() {
+    return Ok(());
+  }
 
-  let value = urlencoding::decode(unwrap_ok(std::str::from_utf8(current_value.as_slice())))
-    .map_err(|_| RequestHeadParsingError::InvalidQueryString(raw_query.to_string()))?
-    .replace('+', " ");
+  let mut values: Vec<String> = content_lengths
+    .iter()
+    .flat_map(|value| value.split(','))
+    .map(|value| value.trim().to_string())
+    .collect();
+  values.dedup();
+  if values.len() > 1 {
+    return Err(RequestHeadParsingError::ConflictingContentLength(values).into());
+  }
 
-  query.push((key, value));
+  Ok(())
+}
 
-  Ok(query)
+fn parse_raw_query(raw_query: &str) -> TiiResult<Vec<(String, String)>> {
+  decode_query(raw_query)
+    .ok_or_else(|| RequestHeadParsingError::InvalidQueryString(raw_query.to_string()).into())
 }
 
 impl RequestHead {
@@ -413,13 +440,36 @@ impl RequestHead {
   }
 
   /// Attempts to read and parse one HTTP request from the given reader.
+  /// The http version is stored in `parsed_version` as soon as the status line is parsed,
+  /// so it is known even if parsing the rest of the request head fails.
+  /// Versions that are older than the minimum version of the options are not stored.
   pub fn read(
     id: u128,
     stream: &dyn ConnectionStream,
     max_head_buffer_size: usize,
+    options: RequestParsingOptions,
+    parsed_version: &mut Option<HttpVersion>,
   ) -> TiiResult<Self> {
     let mut start_line_buf: Vec<u8> = Vec::with_capacity(256);
-    let count = stream.read_until(0xA, max_head_buffer_size, &mut start_line_buf)?;
+    let mut count = stream.read_until(0xA, max_head_buffer_size, &mut start_line_buf)?;
+
+    // RFC 9112 Section 2.2: Empty lines before the request line are ignored.
+    // Some clients send an extra CRLF after the body of the previous request.
+    let mut skipped_empty_line = false;
+    while count != 0 && matches!(start_line_buf.as_slice(), b"\r\n" | b"\n") {
+      if options.strict {
+        validate_line_ending(&start_line_buf)?;
+      }
+      trace_log!("tii: Request {} ignoring empty line before status line", id);
+      skipped_empty_line = true;
+      start_line_buf.clear();
+      count = stream.read_until(0xA, max_head_buffer_size, &mut start_line_buf)?;
+    }
+
+    if count == 0 && skipped_empty_line {
+      //The client closed the connection after sending empty lines.
+      return Err(TiiError::from_io_kind(ErrorKind::UnexpectedEof));
+    }
 
     if count == 0 {
       //Unreachable unless stream implementation is shit. TC 42 tests this case.
@@ -442,6 +492,10 @@ impl RequestHead {
       count
     );
 
+    if options.strict {
+      validate_line_ending(&start_line_buf)?;
+    }
+
     let start_line_string = parse_status_line(&start_line_buf)?;
 
     let status_line =
@@ -451,7 +505,12 @@ impl RequestHead {
 
     let mut start_line = status_line.split(' ');
 
-    let method = HttpMethod::from(unwrap_some(start_line.next()));
+    let raw_method = unwrap_some(start_line.next());
+    if options.strict && !is_token(raw_method) {
+      return Err(RequestHeadParsingError::InvalidMethodToken(raw_method.to_string()).into());
+    }
+
+    let method = HttpMethod::from(raw_method);
 
     let mut uri_iter =
       start_line.next().ok_or(RequestHeadParsingError::StatusLineNoWhitespace)?.splitn(2, '?');
@@ -466,6 +525,13 @@ impl RequestHead {
       return Err(TiiError::from(RequestHeadParsingError::StatusLineTooManyWhitespaces));
     }
 
+    if version < options.min_version {
+      error_log!("tii: Request {id} uses disabled http version {version}");
+      return Err(TiiError::from(RequestHeadParsingError::HttpVersionDisabled(version)));
+    }
+
+    *parsed_version = Some(version);
+
     let raw_path = unwrap_some(uri_iter.next());
     validate_raw_path(raw_path)?;
 
@@ -522,6 +588,11 @@ impl RequestHead {
       let line = std::str::from_utf8(&line_buf)
         .map_err(|_| RequestHeadParsingError::HeaderLineIsNotUsAscii)?;
 
+      if options.strict {
+        validate_line_ending(&line_buf)?;
+        validate_header_line(line)?;
+      }
+
       if line == "\r\n" {
         trace_log!("tii: Request {id} Client sent CRLF, end of header section");
         break;
@@ -555,6 +626,10 @@ impl RequestHead {
       headers.add(HttpHeaderName::from(name), value);
     }
 
+    if options.strict {
+      validate_framing_headers(&headers)?;
+    }
+
     let accept_hdr = headers.get(HttpHeaderName::Accept).unwrap_or("*/*"); //TODO This is probably also wrong.
     let accept = AcceptQualityMimeType::parse(accept_hdr);
     if accept.is_none() {
@@ -812,14 +887,12 @@ impl RequestHead {
 
   /// Returns true if the client indicates that it accepts gzip.
   pub fn accepts_gzip(&self) -> bool {
-    let Some(accept_enc) = self.get_header(HttpHeaderName::AcceptEncoding) else {
-      return false;
-    };
+    self.accepts_encoding(ContentCoding::Gzip)
+  }
 
-    //we have to match "gzip" " gzip," " gzip" "gzip,"
-    //as well as the same variant with x-gzip
-    //this is not perfect, we should later improve this by parsing the header to an Enum.
-    accept_enc.contains("gzip")
+  /// Returns true if the `Accept-Encoding` header gives the content coding a q-value above 0.
+  pub fn accepts_encoding(&self, coding: ContentCoding) -> bool {
+    accept_encoding_qvalue(&self.get_headers(HttpHeaderName::AcceptEncoding), coding) > QValue::MIN
   }
 
   /// Returns the all header values of empty Vec.
@@ -1,8 +1,8 @@
 //! Provides functionality for http request bodies
 
 use crate::util::{unwrap_poison, unwrap_some};
-use crate::{error_log, TiiResult};
-use libflate::gzip;
+use crate::{error_log, ContentCoding, HttpHeader, RequestBodyError, TiiResult};
+use libflate::{gzip, zlib};
 use std::fmt::{Debug, Formatter};
 use std::io;
 use std::io::{Cursor, Error, ErrorKind, Read, Take};
@@ -17,7 +17,7 @@ use std::sync::{Arc, Mutex};
 /// The content of the stream is not considered when determining equality as that would require consuming the stream.
 #[derive(Debug, Clone)]
 #[repr(transparent)]
-pub struct RequestBody(Arc<Mutex<RequestBodyInner>>);
+pub struct RequestBody(Arc<Mutex<LimitedRequestBody>>);
 
 impl Eq for RequestBody {}
 impl PartialEq for RequestBody {
@@ -27,6 +27,10 @@ impl PartialEq for RequestBody {
 }
 
 impl RequestBody {
+  fn new(inner: RequestBodyInner) -> RequestBody {
+    RequestBody(Arc::new(Mutex::new(LimitedRequestBody { inner, max_size: None, read: 0 })))
+  }
+
   /// For unit tests or mocks, will mimic new_with_content_length
   /// This will call to_vec() on the slice.
   pub fn new_with_data_ref<T: AsRef<[u8]>>(data: T) -> RequestBody {
@@ -42,37 +46,53 @@ impl RequestBody {
 
   /// Uncompressed stream with known length.
   pub fn new_with_content_length<T: Read + Send + 'static>(read: T, len: u64) -> RequestBody {
-    RequestBody(Arc::new(Mutex::new(RequestBodyInner::WithContentLength(
-      RequestBodyWithContentLength {
-        err: false,
-        len,
-        read: 0,
-        data: (Box::new(read) as Box<dyn Read + Send>).take(len),
-      },
-    ))))
+    RequestBody::new(RequestBodyInner::WithContentLength(RequestBodyWithContentLength {
+      err: false,
+      len,
+      read: 0,
+      data: (Box::new(read) as Box<dyn Read + Send>).take(len),
+    }))
   }
 
   /// Uncompressed Chunked stream. Content length is not known.
   pub fn new_chunked<T: Read + Send + 'static>(read: T) -> RequestBody {
-    RequestBody(Arc::new(Mutex::new(RequestBodyInner::Chunked(RequestBodyChunked {
+    RequestBody::new(RequestBodyInner::Chunked(RequestBodyChunked {
       read: Box::new(read) as Box<dyn Read + Send>,
       eof: false,
       err: false,
       remaining_chunk_length: 0,
-    }))))
+      trailers: None,
+    }))
   }
 
   /// Chunked stream that is gzip compressed.
   /// Neither compressed nor uncompressed content lengths are known.
   pub fn new_gzip_chunked<T: Read + Send + 'static>(read: T) -> TiiResult<RequestBody> {
+    Self::new_encoded_chunked(read, &[ContentCoding::Gzip])
+  }
+
+  /// Chunked stream that is deflate (zlib) compressed.
+  /// Neither compressed nor uncompressed content lengths are known.
+  pub fn new_deflate_chunked<T: Read + Send + 'static>(read: T) -> TiiResult<RequestBody> {
+    Self::new_encoded_chunked(read, &[ContentCoding::Deflate])
+  }
+
+  /// Chunked stream that has been encoded with the given content codings in the order they were applied.
+  /// The codings are decoded in reverse order. No codings means that the stream is not encoded.
+  /// Neither compressed nor uncompressed content lengths are known.
+  pub fn new_encoded_chunked<T: Read + Send + 'static>(
+    read: T,
+    codings: &[ContentCoding],
+  ) -> TiiResult<RequestBody> {
     let inner = RequestBodyInner::Chunked(RequestBodyChunked {
       read: Box::new(read) as Box<dyn Read + Send>,
       eof: false,
       err: false,
       remaining_chunk_length: 0,
+      trailers: None,
     });
 
-    Ok(RequestBody(Arc::new(Mutex::new(RequestBodyInner::Gzip(GzipRequestBody::new(inner)?)))))
+    Ok(RequestBody::new(DecodingRequestBody::decode_all(inner, codings)?))
   }
 
   /// GZIP stream with a known length of the uncompressed data.
@@ -88,11 +108,45 @@ impl RequestBody {
     Ok(Self::new_with_content_length(decoder, len))
   }
 
+  /// Deflate (zlib) stream with a known length of the uncompressed data.
+  /// The size of the deflate payload is presumably smaller (not guaranteed) but otherwise unknown.
+  pub fn new_deflate_with_uncompressed_length<T: Read + Send + 'static>(
+    read: T,
+    len: u64,
+  ) -> TiiResult<RequestBody> {
+    let decoder = zlib::Decoder::new(read).inspect_err(|e| {
+      error_log!("Could not decode zlib header of request body: {}", e);
+    })?;
+
+    Ok(Self::new_with_content_length(decoder, len))
+  }
+
   /// GZIP stream with a known length of the compressed data.
   /// The length of the uncompressed data is not known.
   pub fn new_gzip_with_compressed_content_length<T: Read + Send + 'static>(
     read: T,
     len: u64,
+  ) -> TiiResult<RequestBody> {
+    Self::new_encoded_with_content_length(read, len, &[ContentCoding::Gzip])
+  }
+
+  /// Deflate (zlib) stream with a known length of the compressed data.
+  /// The length of the uncompressed data is not known.
+  pub fn new_deflate_with_compressed_content_length<T: Read + Send + 'static>(
+    read: T,
+    len: u64,
+  ) -> TiiResult<RequestBody> {
+    Self::new_encoded_with_content_length(read, len, &[ContentCoding::Deflate])
+  }
+
+  /// Stream with a known length of the encoded data that has been encoded with the given content codings
+  /// in the order they were applied. The codings are decoded in reverse order.
+  /// No codings means that the stream is not encoded.
+  /// The length of the decoded data is not known.
+  pub fn new_encoded_with_content_length<T: Read + Send + 'static>(
+    read: T,
+    len: u64,
+    codings: &[ContentCoding],
   ) -> TiiResult<RequestBody> {
     let inner = RequestBodyInner::WithContentLength(RequestBodyWithContentLength {
       err: false,
@@ -101,7 +155,7 @@ impl RequestBody {
       data: (Box::new(read) as Box<dyn Read + Send>).take(len),
     });
 
-    Ok(RequestBody(Arc::new(Mutex::new(RequestBodyInner::Gzip(GzipRequestBody::new(inner)?)))))
+    Ok(RequestBody::new(DecodingRequestBody::decode_all(inner, codings)?))
   }
 }
 
@@ -139,11 +193,69 @@ impl RequestBody {
   /// # Errors
   /// If the read mutex was poisoned.
   pub fn remaining(&self) -> io::Result<Option<u64>> {
-    Ok(match unwrap_poison(self.0.lock())?.deref_mut() {
-      RequestBodyInner::WithContentLength(wc) => Some(wc.data.limit()),
-      _ => None,
-    })
+    Ok(unwrap_poison(self.0.lock())?.inner.remaining())
   }
+
+  /// Sets the maximum amount of bytes that can be read from this request body.
+  /// For compressed request bodies this is the amount of decompressed bytes.
+  /// Once more bytes than this would be read all read fn's return an `io::Error` containing `RequestBodyError::TooLarge`.
+  /// None means that the size of the request body is not limited.
+  /// # Errors
+  /// If the read mutex was poisoned.
+  pub fn set_max_size(&self, max_size: Option<u64>) -> io::Result<()> {
+    unwrap_poison(self.0.lock())?.max_size = max_size;
+    Ok(())
+  }
+
+  /// Returns the maximum amount of bytes that can be read from this request body.
+  /// # Errors
+  /// If the read mutex was poisoned.
+  pub fn max_size(&self) -> io::Result<Option<u64>> {
+    Ok(unwrap_poison(self.0.lock())?.max_size)
+  }
+
+  /// Returns true if it is already known that this request body is larger than its maximum size.
+  /// This is the case if the length of the request body is known in advance, for example due to a Content-Length header,
+  /// or if reading the request body already exceeded the maximum size.
+  /// # Errors
+  /// If the read mutex was poisoned.
+  pub fn is_too_large(&self) -> io::Result<bool> {
+    Ok(unwrap_poison(self.0.lock())?.is_too_large())
+  }
+
+  /// Returns the trailer fields the client sent after the last chunk of a chunked request body.
+  /// Trailers are only known once the request body has been fully read.
+  /// None is returned before that or if the request body was not chunked.
+  /// # Errors
+  /// If the read mutex was poisoned.
+  pub fn trailers(&self) -> io::Result<Option<Vec<HttpHeader>>> {
+    Ok(unwrap_poison(self.0.lock())?.inner.trailers().map(<[HttpHeader]>::to_vec))
+  }
+
+  /// Returns the value of the first trailer field with the given name.
+  /// See `trailers` for when trailers are known.
+  /// # Errors
+  /// If the read mutex was poisoned.
+  pub fn trailer(&self, name: impl AsRef<str>) -> io::Result<Option<String>> {
+    let name = name.as_ref();
+    Ok(
+      unwrap_poison(self.0.lock())?
+        .inner
+        .trailers()
+        .and_then(|trailers| {
+          trailers.iter().find(|trailer| trailer.name.to_str().eq_ignore_ascii_case(name))
+        })
+        .map(|trailer| trailer.value.clone()),
+    )
+  }
+}
+
+/// Returns true if the error was caused by a request body that exceeded its maximum size.
+pub(crate) fn is_too_large_error(err: &io::Error) -> bool {
+  err
+    .get_ref()
+    .and_then(|inner| inner.downcast_ref::<RequestBodyError>())
+    .is_some_and(|inner| matches!(inner, RequestBodyError::TooLarge(_)))
 }
 
 impl Read for &RequestBody {
@@ -152,12 +264,75 @@ impl Read for &RequestBody {
   }
 }
 
+#[derive(Debug)]
+struct LimitedRequestBody {
+  inner: RequestBodyInner,
+  max_size: Option<u64>,
+  read: u64,
+}
+
+impl LimitedRequestBody {
+  fn is_too_large(&self) -> bool {
+    let Some(max_size) = self.max_size else {
+      return false;
+    };
+
+    self.read > max_size
+      || self
+        .inner
+        .remaining()
+        .is_some_and(|remaining| self.read.saturating_add(remaining) > max_size)
+  }
+}
+
+impl Read for LimitedRequestBody {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let Some(max_size) = self.max_size else {
+      let count = self.inner.read(buf)?;
+      self.read = self.read.saturating_add(count as u64);
+      return Ok(count);
+    };
+
+    if self.is_too_large() {
+      return Err(RequestBodyError::TooLarge(max_size).into());
+    }
+
+    // Reading 1 byte past the maximum is enough to know that the body is too large.
+    // This also stops a decompressing body from decompressing more than that.
+    let allowed = max_size.saturating_sub(self.read).saturating_add(1);
+    let (buf, _) = buf.split_at_mut(u64::min(allowed, buf.len() as u64) as usize);
+    let count = self.inner.read(buf)?;
+    self.read = self.read.saturating_add(count as u64);
+    if self.read > max_size {
+      return Err(RequestBodyError::TooLarge(max_size).into());
+    }
+
+    Ok(count)
+  }
+}
+
 #[derive(Debug)]
 enum RequestBodyInner {
   WithContentLength(RequestBodyWithContentLength),
   Chunked(RequestBodyChunked),
-  Gzip(GzipRequestBody),
-  //Gzipped(...)   //..
+  Decoding(DecodingRequestBody),
+}
+
+impl RequestBodyInner {
+  fn remaining(&self) -> Option<u64> {
+    match self {
+      RequestBodyInner::WithContentLength(wc) => Some(wc.data.limit()),
+      _ => None,
+    }
+  }
+
+  fn trailers(&self) -> Option<&[HttpHeader]> {
+    match self {
+      RequestBodyInner::WithContentLength(_) => None,
+      RequestBodyInner::Chunked(body) => body.trailers.as_deref(),
+      RequestBodyInner::Decoding(body) => body.finished.as_ref().and_then(|inner| inner.trailers()),
+    }
+  }
 }
 
 impl Read for RequestBodyInner {
@@ -165,27 +340,74 @@ impl Read for RequestBodyInner {
     match self {
       RequestBodyInner::WithContentLength(body) => body.read(buf),
       RequestBodyInner::Chunked(body) => body.read(buf),
-      RequestBodyInner::Gzip(body) => body.read(buf),
+      RequestBodyInner::Decoding(body) => body.read(buf),
     }
   }
 }
 
 #[derive(Debug)]
-struct GzipRequestBody {
+enum Decoder {
+  Gzip(gzip::Decoder<Box<RequestBodyInner>>),
+  Deflate(zlib::Decoder<Box<RequestBodyInner>>),
+}
+
+impl Decoder {
+  fn into_inner(self) -> Box<RequestBodyInner> {
+    match self {
+      Decoder::Gzip(decoder) => decoder.into_inner(),
+      Decoder::Deflate(decoder) => decoder.into_inner(),
+    }
+  }
+}
+
+impl Read for Decoder {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    match self {
+      Decoder::Gzip(decoder) => decoder.read(buf),
+      Decoder::Deflate(decoder) => decoder.read(buf),
+    }
+  }
+}
+
+#[derive(Debug)]
+struct DecodingRequestBody {
   err: bool,
-  decoder: Option<gzip::Decoder<Box<RequestBodyInner>>>,
+  decoder: Option<Decoder>,
+  /// The decoded stream once the decoder is done, it is kept for its trailers.
+  finished: Option<Box<RequestBodyInner>>,
 }
 
-impl GzipRequestBody {
-  fn new(inner: RequestBodyInner) -> TiiResult<Self> {
-    let decoder = gzip::Decoder::new(Box::new(inner)).inspect_err(|e| {
-      error_log!("Could not decode gzip header of request body: {}", e);
-    })?;
-    Ok(Self { err: false, decoder: Some(decoder) })
+impl DecodingRequestBody {
+  fn new(inner: RequestBodyInner, coding: ContentCoding) -> TiiResult<Self> {
+    let decoder = match coding {
+      ContentCoding::Gzip => {
+        Decoder::Gzip(gzip::Decoder::new(Box::new(inner)).inspect_err(|e| {
+          error_log!("Could not decode gzip header of request body: {}", e);
+        })?)
+      }
+      ContentCoding::Deflate => {
+        Decoder::Deflate(zlib::Decoder::new(Box::new(inner)).inspect_err(|e| {
+          error_log!("Could not decode zlib header of request body: {}", e);
+        })?)
+      }
+    };
+    Ok(Self { err: false, decoder: Some(decoder), finished: None })
+  }
+
+  /// Wraps the body in one decoder per coding, the last coding that was applied is decoded first.
+  fn decode_all(
+    mut inner: RequestBodyInner,
+    codings: &[ContentCoding],
+  ) -> TiiResult<RequestBodyInner> {
+    for coding in codings.iter().rev() {
+      inner = RequestBodyInner::Decoding(Self::new(inner, *coding)?);
+    }
+
+    Ok(inner)
   }
 }
 
-impl Read for GzipRequestBody {
+impl Read for DecodingRequestBody {
   fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
     if self.err {
       return Err(Error::new(ErrorKind::BrokenPipe, "Previous IO Error reading body"));
@@ -198,13 +420,12 @@ impl Read for GzipRequestBody {
     if count == 0 {
       //This is needed to consume the trailer of chunked stream see tc53_c for this.
       let mut small_buf = [0u8];
-      let count = unwrap_some(self.decoder.take())
-        .into_inner()
-        .read(small_buf.as_mut_slice())
-        .inspect_err(|_| self.err = true)?;
+      let mut inner = unwrap_some(self.decoder.take()).into_inner();
+      let count = inner.read(small_buf.as_mut_slice()).inspect_err(|_| self.err = true)?;
+      self.finished = Some(inner);
       if count != 0 {
         self.err = true;
-        return Err(Error::new(ErrorKind::BrokenPipe, "Gzip decoded did not fully consume data"));
+        return Err(Error::new(ErrorKind::BrokenPipe, "Decoder did not fully consume data"));
       }
     }
     Ok(count)
@@ -258,8 +479,12 @@ struct RequestBodyChunked {
   eof: bool,
   err: bool,
   remaining_chunk_length: u64,
+  trailers: Option<Vec<HttpHeader>>,
 }
 
+/// Maximum size of the trailer section of a chunked request body.
+const MAX_TRAILER_SECTION_SIZE: usize = 0x2000;
+
 impl Debug for RequestBodyChunked {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     f.write_fmt(format_args!(
@@ -337,15 +562,7 @@ impl RequestBodyChunked {
     let chunk_len = u64::from_str_radix(str, 16)
       .map_err(|_| Error::new(io::ErrorKind::InvalidData, "Chunk size is malformed"))?;
     if chunk_len == 0 {
-      self.read.read_exact(&mut small_buffer[n..n + 1])?;
-      if small_buffer[n] != b'\r' {
-        return Err(Error::new(io::ErrorKind::InvalidData, "Chunk trailer is malformed"));
-      }
-      self.read.read_exact(&mut small_buffer[n..n + 1])?;
-      if small_buffer[n] != b'\n' {
-        return Err(Error::new(io::ErrorKind::InvalidData, "Chunk trailer is malformed"));
-      }
-
+      self.trailers = Some(self.read_trailers()?);
       self.eof = true;
       return Ok(0);
     }
@@ -355,6 +572,47 @@ impl RequestBodyChunked {
   }
 }
 
+impl RequestBodyChunked {
+  /// Reads the trailer section that follows the last chunk up to and including the final CRLF.
+  fn read_trailers(&mut self) -> io::Result<Vec<HttpHeader>> {
+    let malformed = || Error::new(io::ErrorKind::InvalidData, "Chunk trailer is malformed");
+    let mut trailers = Vec::new();
+    let mut size = 0usize;
+    loop {
+      let mut line = Vec::new();
+      let mut byte = [0u8; 1];
+      while !line.ends_with(b"\r\n") {
+        if size >= MAX_TRAILER_SECTION_SIZE {
+          return Err(Error::new(io::ErrorKind::InvalidData, "Chunk trailer is too large"));
+        }
+        self.read.read_exact(&mut byte)?;
+        // CR and LF are only allowed together at the end of a line.
+        if (byte[0] == b'\n') != line.ends_with(b"\r") {
+          return Err(malformed());
+        }
+        line.extend_from_slice(&byte);
+        size += 1;
+      }
+
+      let line = std::str::from_utf8(&line).map_err(|_| malformed())?;
+      let line = unwrap_some(line.strip_suffix("\r\n"));
+      if line.is_empty() {
+        return Ok(trailers);
+      }
+
+      let (name, value) = line.split_once(':').ok_or_else(malformed)?;
+      if name.is_empty()
+        || name.contains(|c: char| c.is_whitespace() || c.is_control())
+        || value.contains(|c: char| c.is_control() && c != '\t')
+      {
+        return Err(malformed());
+      }
+
+      trailers.push(HttpHeader::new(name, value.trim()));
+    }
+  }
+}
+
 impl Read for RequestBodyChunked {
   fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
     if self.err {
@@ -3,15 +3,15 @@
 use crate::http::headers::HttpHeaderName;
 use crate::http::request::HttpVersion;
 use crate::http::request_body::RequestBody;
-use crate::http::RequestHead;
+use crate::http::{parse_content_codings, RequestHead, RequestParsingOptions};
 use crate::stream::ConnectionStream;
 use crate::tii_error::{RequestHeadParsingError, TiiError, TiiResult};
 use crate::tii_server::ConnectionStreamMetadata;
 use crate::util::unwrap_some;
 use crate::{
   debug_log, error_log, trace_log, util, warn_log, AcceptMimeCharset, AcceptQualityMimeType,
-  Cookie, HttpHeader, HttpMethod, MimeType, MimeTypeWithCharset, TypeSystem, TypeSystemError,
-  UserError,
+  ContentCoding, Cookie, HttpHeader, HttpMethod, MimeType, MimeTypeWithCharset, TypeSystem,
+  TypeSystemError, UrlEncodedForm, UserError,
 };
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
@@ -37,6 +37,7 @@ pub struct RequestContext {
   force_connection_close: bool,
   stream_meta: Option<Arc<dyn ConnectionStreamMetadata>>,
   routed_path: Option<String>,
+  websocket_protocol: Option<String>,
   path_params: Option<HashMap<String, String>>,
   properties: Option<HashMap<String, Box<dyn Any + Send>>>,
   type_system: TypeSystem,
@@ -75,6 +76,7 @@ impl RequestContext {
       force_connection_close: false,
       stream_meta,
       routed_path: None,
+      websocket_protocol: None,
       path_params: None,
       properties: None,
       type_system,
@@ -105,6 +107,7 @@ impl RequestContext {
       force_connection_close: true,
       properties: None,
       routed_path: None,
+      websocket_protocol: None,
       stream_meta,
       path_params: None,
       type_system,
@@ -139,9 +142,10 @@ impl RequestContext {
           request: req,
           body: None,
           request_entity: None,
-          force_connection_close: true,
+          force_connection_close: false,
           properties: None,
           routed_path: None,
+          websocket_protocol: None,
           stream_meta,
           path_params: None,
           type_system,
@@ -158,9 +162,10 @@ impl RequestContext {
         request: req,
         body: Some(body),
         request_entity: None,
-        force_connection_close: true,
+        force_connection_close: false,
         properties: None,
         routed_path: None,
+        websocket_protocol: None,
         stream_meta,
         path_params: None,
         type_system,
@@ -170,6 +175,8 @@ impl RequestContext {
     trace_log!(
       "tii: Request {id} did not sent Content-Length header. Assuming that it has no request body"
     );
+    //Http 1.0 requires Content-Length for request bodies. Anything the client sent anyway must not be read as the next request.
+    let force_connection_close = req.get_method().is_likely_to_have_request_body();
     Ok(RequestContext {
       id,
       timestamp,
@@ -178,9 +185,10 @@ impl RequestContext {
       request: req,
       body: None,
       request_entity: None,
-      force_connection_close: true,
+      force_connection_close,
       properties: None,
       routed_path: None,
+      websocket_protocol: None,
       stream_meta,
       path_params: None,
       type_system,
@@ -215,30 +223,9 @@ impl RequestContext {
     ) {
       (None, None) => match content_length {
         None => {
-          if req.get_header(&HttpHeaderName::Connection) != Some("keep-alive") {
-            trace_log!(
-              "tii: Request {id} did not sent Content-Length header. Assuming that it has no request body. Connection: keep-alive was not explicitly requested, so will send Connection: close");
-
-            return Ok(RequestContext {
-              id,
-              timestamp,
-              peer_address,
-              local_address,
-              request: req,
-              body: None,
-              request_entity: None,
-              force_connection_close: true,
-              properties: None,
-              routed_path: None,
-              stream_meta,
-              path_params: None,
-              type_system,
-            });
-          }
-
           if req.get_method().is_likely_to_have_request_body() {
             warn_log!(
-            "tii: Request {id} did not sent Content-Length header but did request Connection: keep-alive. Assuming that it has no request body. The request method {} usually has a body, will force Connection: close to be safe.", req.get_method()
+            "tii: Request {id} did not sent Content-Length header. Assuming that it has no request body. The request method {} usually has a body, will force Connection: close to be safe.", req.get_method()
             );
 
             return Ok(RequestContext {
@@ -252,6 +239,7 @@ impl RequestContext {
               force_connection_close: true,
               properties: None,
               routed_path: None,
+              websocket_protocol: None,
               stream_meta,
               path_params: None,
               type_system,
@@ -259,7 +247,7 @@ impl RequestContext {
           }
 
           trace_log!(
-            "tii: Request {id} did not sent Content-Length header. Assuming that it has no request body. Connection: keep-alive was requested, so will trust the client that the request actually has no body.");
+            "tii: Request {id} did not sent Content-Length header. Assuming that it has no request body.");
 
           Ok(RequestContext {
             id,
@@ -272,6 +260,7 @@ impl RequestContext {
             force_connection_close: false,
             properties: None,
             routed_path: None,
+            websocket_protocol: None,
             stream_meta,
             path_params: None,
             type_system,
@@ -290,6 +279,7 @@ impl RequestContext {
             force_connection_close: false,
             properties: None,
             routed_path: None,
+            websocket_protocol: None,
             stream_meta,
             path_params: None,
             type_system,
@@ -308,6 +298,7 @@ impl RequestContext {
             force_connection_close: false,
             properties: None,
             routed_path: None,
+            websocket_protocol: None,
             stream_meta,
             path_params: None,
             type_system,
@@ -328,20 +319,30 @@ impl RequestContext {
           force_connection_close: false,
           properties: None,
           routed_path: None,
+          websocket_protocol: None,
           stream_meta,
           path_params: None,
           type_system,
         })
       }
-      (None, Some("x-gzip")) | (None, Some("gzip")) => {
-        trace_log!("tii: Request {id} has gzip request body with length of uncompressed content");
+      (None, Some(transfer @ ("gzip" | "x-gzip" | "deflate"))) => {
+        trace_log!(
+          "tii: Request {id} has {transfer} request body with length of uncompressed content"
+        );
         let Some(content_length) = content_length else {
-          error_log!("tii: Request {id} not implemented no transfer encoding, Content-Encoding: gzip/x-gzip without Content-Length header");
+          error_log!("tii: Request {id} not implemented no content encoding, Transfer-Encoding: {transfer} without Content-Length header");
           return Err(TiiError::from(RequestHeadParsingError::ContentLengthHeaderMissing));
         };
 
-        let body =
-          RequestBody::new_gzip_with_uncompressed_length(stream.new_ref_read(), content_length)?;
+        let body = match transfer {
+          "deflate" => RequestBody::new_deflate_with_uncompressed_length(
+            stream.new_ref_read(),
+            content_length,
+          )?,
+          _ => {
+            RequestBody::new_gzip_with_uncompressed_length(stream.new_ref_read(), content_length)?
+          }
+        };
 
         Ok(RequestContext {
           id,
@@ -357,27 +358,45 @@ impl RequestContext {
           force_connection_close: true,
           properties: None,
           routed_path: None,
+          websocket_protocol: None,
           stream_meta,
           path_params: None,
           type_system,
         })
       }
-      (Some("gzip"), None) | (Some("x-gzip"), None) => {
-        trace_log!("tii: Request {id} has gzip request body with length of compressed content");
-        //gzip+Content-Length of zipped stuff
-        let Some(content_length) = content_length else {
-          error_log!("tii: Request {id} not implemented Transfer-Encoding: gzip/x-gzip, no Content-Encoding without Content-Length header");
-          return Err(TiiError::from(RequestHeadParsingError::ContentLengthHeaderMissing));
+      (Some(encoding), transfer @ (None | Some("chunked"))) => {
+        let Some(codings) = parse_content_codings(encoding) else {
+          error_log!("tii: Request {id} has unimplemented content encoding: {}", encoding);
+          return Err(TiiError::from(RequestHeadParsingError::ContentEncodingNotSupported(
+            encoding.to_string(),
+          )));
+        };
+
+        let body = if transfer.is_some() {
+          trace_log!("tii: Request {id} has chunked {encoding} request body");
+          RequestBody::new_encoded_chunked(stream.new_ref_read(), &codings)?
+        } else {
+          trace_log!(
+            "tii: Request {id} has {encoding} request body with length of compressed content"
+          );
+          //Content-Encoding+Content-Length of the encoded stuff
+          let Some(content_length) = content_length else {
+            error_log!("tii: Request {id} not implemented Content-Encoding: {encoding}, no Transfer-Encoding without Content-Length header");
+            return Err(TiiError::from(RequestHeadParsingError::ContentLengthHeaderMissing));
+          };
+
+          //TODO curl, hyper and several http server implementation disagree on how this should be handled.
+          //Its safe to assume that no client will ever send this...
+          //We may have to read the full rfc eventually, the rfc only mentions that this exists and
+          //This impl is honestly based upon some forum comments of a obscure http proxy.
+
+          RequestBody::new_encoded_with_content_length(
+            stream.new_ref_read(),
+            content_length,
+            &codings,
+          )?
         };
 
-        //TODO curl, hyper and several http server implementation disagree on how this should be handled.
-        //Its safe to assume that no client will ever send this...
-        //We may have to read the full rfc eventually, the rfc only mentions that this exists and
-        //This impl is honestly based upon some forum comments of a obscure http proxy.
-        let body = RequestBody::new_gzip_with_compressed_content_length(
-          stream.new_ref_read(),
-          content_length,
-        )?;
         Ok(RequestContext {
           id,
           timestamp,
@@ -389,17 +408,18 @@ impl RequestContext {
           force_connection_close: false,
           properties: None,
           routed_path: None,
+          websocket_protocol: None,
           stream_meta,
           path_params: None,
           type_system,
         })
       }
-      (Some("gzip"), Some("chunked"))
-      | (Some("x-gzip"), Some("chunked"))
-      | (None, Some("gzip, chunked"))
-      | (None, Some("x-gzip, chunked")) => {
-        trace_log!("tii: Request {id} has chunked gzip request body");
-        let body = RequestBody::new_gzip_chunked(stream.new_ref_read())?;
+      (None, Some(transfer @ ("gzip, chunked" | "x-gzip, chunked" | "deflate, chunked"))) => {
+        trace_log!("tii: Request {id} has chunked {transfer} request body");
+        let body = match transfer {
+          "deflate, chunked" => RequestBody::new_deflate_chunked(stream.new_ref_read())?,
+          _ => RequestBody::new_gzip_chunked(stream.new_ref_read())?,
+        };
         Ok(RequestContext {
           id,
           timestamp,
@@ -411,35 +431,16 @@ impl RequestContext {
           force_connection_close: false,
           properties: None,
           routed_path: None,
+          websocket_protocol: None,
           stream_meta,
           path_params: None,
           type_system,
         })
       }
-      (other_encoding, other_transfer) => {
-        match other_transfer {
-          Some("chunked") | None => (),
-          Some(other) => {
-            error_log!("tii: Request {id} has unimplemented transfer encoding: {}", other);
-            return Err(TiiError::from(RequestHeadParsingError::TransferEncodingNotSupported(
-              other.to_string(),
-            )));
-          }
-        }
-
-        let Some(other_encoding) = other_encoding else {
-          error_log!(
-            "tii: BUG! Fatal unreachable syntax/encoding reached {:?} {:?}",
-            other_encoding,
-            other_transfer
-          );
-          util::unreachable();
-        };
-
-        error_log!("tii: Request {id} has unimplemented content encoding: {}", other_encoding);
-
-        Err(TiiError::from(RequestHeadParsingError::ContentEncodingNotSupported(
-          other_encoding.to_string(),
+      (_, Some(other)) => {
+        error_log!("tii: Request {id} has unimplemented transfer encoding: {}", other);
+        Err(TiiError::from(RequestHeadParsingError::TransferEncodingNotSupported(
+          other.to_string(),
         )))
       }
     }
@@ -452,6 +453,25 @@ impl RequestContext {
     stream_meta: Option<Arc<dyn ConnectionStreamMetadata>>,
     max_head_buffer_size: usize,
     type_system: TypeSystem,
+  ) -> TiiResult<RequestContext> {
+    Self::read_with_options(
+      stream,
+      stream_meta,
+      max_head_buffer_size,
+      RequestParsingOptions::default(),
+      type_system,
+      &mut None,
+    )
+  }
+
+  /// Same as `read` but the request head is parsed with the given options.
+  pub(crate) fn read_with_options(
+    stream: &dyn ConnectionStream,
+    stream_meta: Option<Arc<dyn ConnectionStreamMetadata>>,
+    max_head_buffer_size: usize,
+    options: RequestParsingOptions,
+    type_system: TypeSystem,
+    parsed_version: &mut Option<HttpVersion>,
   ) -> TiiResult<RequestContext> {
     let now: u128 =
       SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|a| a.as_millis()).unwrap_or(0);
@@ -460,7 +480,7 @@ impl RequestContext {
     let local_address = stream.local_addr()?;
     debug_log!("tii: Request {id} local: {} peer: {}", &local_address, &peer_address);
 
-    let req = RequestHead::read(id, stream, max_head_buffer_size)?;
+    let req = RequestHead::read(id, stream, max_head_buffer_size, options, parsed_version)?;
 
     match req.get_version() {
       HttpVersion::Http09 => Self::new_http09(
@@ -789,6 +809,28 @@ impl RequestContext {
     self.request.get_query_params(key)
   }
 
+  /// Reads the request body as an application/x-www-form-urlencoded form.
+  /// The form is decoded with the same rules as the query string and the charset of the content type.
+  /// This consumes the request body.
+  ///
+  /// # Returns
+  /// Ok(None) if the request has no body.
+  /// Ok(Some) if the body is a valid form.
+  /// Err(...) if the content type is not application/x-www-form-urlencoded, the body is malformed or exceeds the maximum request body size.
+  pub fn read_urlencoded_form(&self) -> TiiResult<Option<UrlEncodedForm>> {
+    let Some(body) = self.request_body() else {
+      return Ok(None);
+    };
+
+    match self.get_content_type() {
+      Some(mime) => UrlEncodedForm::from_request_body(mime, body).map(Some),
+      None => {
+        UrlEncodedForm::from_request_body(&MimeTypeWithCharset::APPLICATION_OCTET_STREAM, body)
+          .map(Some)
+      }
+    }
+  }
+
   /// gets the method of the request.
   pub fn get_method(&self) -> &HttpMethod {
     self.request.get_method()
@@ -854,11 +896,22 @@ impl RequestContext {
     self.request.get_header(name)
   }
 
+  /// Returns the id of the last server-sent event the client received,
+  /// browsers send it in the `Last-Event-ID` header when they reconnect to an event stream.
+  pub fn last_event_id(&self) -> Option<&str> {
+    self.request.get_header(HttpHeaderName::LastEventId)
+  }
+
   /// Returns true if the client indicates that it accepts gzip.
   pub fn accepts_gzip(&self) -> bool {
     self.request.accepts_gzip()
   }
 
+  /// Returns true if the client indicates that it accepts the content coding.
+  pub fn accepts_encoding(&self, coding: ContentCoding) -> bool {
+    self.request.accepts_encoding(coding)
+  }
+
   /// Returns the all header values or empty Vec.
   pub fn get_headers(&self, name: impl AsRef<str>) -> Vec<&str> {
     self.request.get_headers(name)
@@ -889,6 +942,16 @@ impl RequestContext {
     self.routed_path.as_deref().unwrap_or("")
   }
 
+  /// Returns the subprotocol that was selected during the websocket handshake.
+  /// None if this is not a websocket request or if no subprotocol was selected.
+  pub fn websocket_protocol(&self) -> Option<&str> {
+    self.websocket_protocol.as_deref()
+  }
+
+  pub(crate) fn set_websocket_protocol(&mut self, protocol: Option<String>) {
+    self.websocket_protocol = protocol;
+  }
+
   /// get the path param keys.
   pub fn get_path_param_keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
     match self.path_params.as_ref() {
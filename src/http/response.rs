@@ -9,9 +9,10 @@ use crate::http::request::HttpVersion;
 use crate::http::response_body::ResponseBody;
 use crate::stream::ConnectionStreamWrite;
 use crate::tii_error::{TiiResult, UserError};
-use crate::{EntitySerializer, MimeTypeWithCharset};
+use crate::{EntitySerializer, MimeType, MimeTypeWithCharset, SseSender};
 use std::fmt::Debug;
 use std::io::{Read, Seek};
+use std::time::Duration;
 use std::{io, mem};
 
 /// Represents a response from the server.
@@ -93,6 +94,18 @@ impl Response {
     Ok(Self::ok(bytes.try_into()?, mime))
   }
 
+  /// HTTP 200 OK with a `text/event-stream` body of server-sent events.
+  /// The streamer runs when the response is written to the client, the response ends when it returns.
+  /// If `keep_alive` is set then a ping is sent whenever no event was sent for that long,
+  /// this also detects clients that have disconnected while no events were sent.
+  pub fn event_stream<T: FnOnce(&SseSender<'_>) -> io::Result<()> + Send + 'static>(
+    keep_alive: Option<Duration>,
+    streamer: T,
+  ) -> Response {
+    Self::ok(ResponseBody::event_stream(keep_alive, streamer), MimeType::TextEventStream)
+      .with_header_unchecked(HttpHeaderName::CacheControl, "no-cache")
+  }
+
   /// HTTP 201 Created with body.
   pub fn created(bytes: impl Into<ResponseBody>, mime: impl Into<MimeTypeWithCharset>) -> Response {
     Self::new(StatusCode::Created)
@@ -140,7 +153,9 @@ impl Response {
   }
 
   /// HTTP 206 Partial Content
-  /// Note: Content-Range header must still be set by the caller. TODO
+  /// Note: Content-Range header must still be set by the caller.
+  /// Range requests for responses with a body created by `ResponseBody::from_file`
+  /// are answered automatically, there is no need to call this fn for them.
   pub fn partial_content(
     body: impl Into<ResponseBody>,
     mime: impl Into<MimeTypeWithCharset>,
@@ -635,7 +650,7 @@ impl Response {
 
     if version == HttpVersion::Http09 {
       if let Some(body) = self.body {
-        body.write_to_http(request_id, destination)?;
+        body.write_to_http(request_id, version, destination)?;
         destination.flush()?;
       }
 
@@ -665,8 +680,15 @@ impl Response {
     }
 
     if let Some(body) = self.body {
-      if body.is_chunked() {
+      // Older clients do not understand chunks, they get the raw data and the connection is closed afterward.
+      if body.is_chunked() && version == HttpVersion::Http11 {
         destination.write_all(b"\r\nTransfer-Encoding: chunked\r\n")?;
+        if !body.trailer_names().is_empty() {
+          let names: Vec<&str> = body.trailer_names().iter().map(HttpHeaderName::to_str).collect();
+          destination.write_all(b"Trailer: ")?;
+          destination.write_all(names.join(", ").as_bytes())?;
+          destination.write_all(b"\r\n")?;
+        }
         if let Some(enc) = body.get_content_encoding() {
           if self.headers.get(HttpHeaderName::ContentEncoding).is_none() {
             destination.write_all(b"Content-Encoding: ")?;
@@ -676,7 +698,7 @@ impl Response {
         }
         destination.write_all(b"\r\n")?;
         if !self.omit_body {
-          body.write_to_http(request_id, destination)?;
+          body.write_to_http(request_id, version, destination)?;
         }
         destination.flush()?;
         return Ok(());
@@ -699,7 +721,7 @@ impl Response {
       destination.write_all(b"\r\n")?;
 
       if !self.omit_body {
-        body.write_to_http(request_id, destination)?;
+        body.write_to_http(request_id, version, destination)?;
       }
       destination.flush()?;
       return Ok(());
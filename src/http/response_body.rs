@@ -3,14 +3,15 @@
 #![allow(missing_docs)]
 
 use crate::http::response_entity::ResponseEntity;
+use crate::http::sse::{stream_events, SseHandler, SseSender};
 use crate::stream::ConnectionStreamWrite;
-use crate::util::unwrap_some;
+use crate::util::{unwrap_poison, unwrap_some};
 use crate::{
-  trace_log, EntitySerializer, MimeTypeWithCharset, TiiError, TiiResult, TypeSystem,
-  TypeSystemError,
+  trace_log, ContentCoding, EntitySerializer, HttpHeader, HttpHeaderName, HttpVersion,
+  MimeTypeWithCharset, TiiError, TiiResult, TypeSystem, TypeSystemError, UserError,
 };
 use defer_heavy::defer;
-use libflate::gzip;
+use libflate::{gzip, zlib};
 use std::any::{Any, TypeId};
 use std::cell::RefCell;
 use std::fmt::{Debug, Formatter};
@@ -18,12 +19,13 @@ use std::fs::File;
 use std::io;
 use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 
 pub(crate) type ResponseBodyHandler = dyn FnOnce(&dyn ResponseBodySink) -> io::Result<()> + Send;
 
-#[repr(transparent)]
 #[derive(Debug)]
-pub struct ResponseBody(ResponseBodyInner);
+pub struct ResponseBody(ResponseBodyInner, Vec<HttpHeaderName>);
 
 // We don't want to expose this enum.
 enum ResponseBodyInner {
@@ -42,6 +44,10 @@ enum ResponseBodyInner {
   //Content length header will be set automatically
   FixedSizeFile(Box<dyn ReadAndSeek>, u64),
 
+  //Streams parts of a file, for example to answer a range request.
+  //Content length header will be set automatically
+  FileSegments(Box<dyn ReadAndSeek>, Vec<FileSegment>),
+
   //Content length header will not be set.
   //This forces Connection-Close after the request has been processed.
   //The caused overhead is that the client has to redo the connection.
@@ -52,6 +58,10 @@ enum ResponseBodyInner {
   //All required headers for this will be set automatically.
   ChunkedStream(Option<Box<ResponseBodyHandler>>),
 
+  //Server-sent events sent as chunked transfer encoding, every event is flushed immediately.
+  //The optional duration is the keep-alive ping interval.
+  EventStream(Option<Duration>, Option<Box<SseHandler>>),
+
   //Data that has been externally gzipped. The Vec contains data in gzip format
   ExternallyGzippedData(Vec<u8>),
 
@@ -63,6 +73,38 @@ enum ResponseBodyInner {
 
   //File that will be gzipped on the fly and sent in chunks
   ChunkedGzipFile(Box<dyn ReadAndSeek>),
+
+  //Data that has been externally deflated. The Vec contains data in zlib format
+  ExternallyDeflatedData(Vec<u8>),
+
+  //Chunked Stream that will be deflated on the fly
+  ChunkedDeflateStream(Option<Box<ResponseBodyHandler>>),
+
+  //File that will be deflated on the fly and sent in chunks
+  ChunkedDeflateFile(Box<dyn ReadAndSeek>),
+
+  //Body of a response to a HEAD request or a 304 Not Modified that is never sent.
+  //Content length header will be set to the length of the body that would have been sent.
+  #[cfg_attr(not(feature = "extras"), allow(dead_code))] //For now only used by the reverse proxy.
+  Omitted(u64),
+}
+
+/// A part of a `ResponseBodyInner::FileSegments` body.
+#[derive(Debug)]
+pub(crate) enum FileSegment {
+  /// Data that is written as is. Used for the part headers of multipart/byteranges bodies.
+  Data(Vec<u8>),
+  /// Range of the file given as start offset and length.
+  Range(u64, u64),
+}
+
+impl FileSegment {
+  fn len(&self) -> u64 {
+    match self {
+      FileSegment::Data(data) => data.len() as u64,
+      FileSegment::Range(_, len) => *len,
+    }
+  }
 }
 
 impl Debug for ResponseBodyInner {
@@ -80,8 +122,14 @@ impl Debug for ResponseBodyInner {
       ResponseBodyInner::FixedSizeFile(_, size) => {
         f.write_fmt(format_args!("ResponseBody::FixedSizeFile(file, {size})"))
       }
+      ResponseBodyInner::FileSegments(_, segments) => {
+        f.write_fmt(format_args!("ResponseBody::FileSegments(file, {segments:?})"))
+      }
       ResponseBodyInner::Stream(_) => f.write_str("ResponseBody::Stream(...)"),
       ResponseBodyInner::ChunkedStream(_) => f.write_str("ResponseBody::ChunkedStream(...)"),
+      ResponseBodyInner::EventStream(keep_alive, _) => {
+        f.write_fmt(format_args!("ResponseBody::EventStream({keep_alive:?}, ...)"))
+      }
       ResponseBodyInner::ExternallyGzippedData(_) => {
         f.write_str("ResponseBody::ExternallyGzippedData(...)")
       }
@@ -92,6 +140,18 @@ impl Debug for ResponseBodyInner {
         f.write_str("ResponseBody::ChunkedGzipStream(...)")
       }
       ResponseBodyInner::ChunkedGzipFile(_) => f.write_str("ResponseBody::ChunkedGzipFile(...)"),
+      ResponseBodyInner::ExternallyDeflatedData(_) => {
+        f.write_str("ResponseBody::ExternallyDeflatedData(...)")
+      }
+      ResponseBodyInner::ChunkedDeflateStream(_) => {
+        f.write_str("ResponseBody::ChunkedDeflateStream(...)")
+      }
+      ResponseBodyInner::ChunkedDeflateFile(_) => {
+        f.write_str("ResponseBody::ChunkedDeflateFile(...)")
+      }
+      ResponseBodyInner::Omitted(length) => {
+        f.write_fmt(format_args!("ResponseBody::Omitted({length})"))
+      }
       ResponseBodyInner::Entity(entity) => {
         f.write_fmt(format_args!("ResponseBody::Entity({entity:?})"))
       }
@@ -109,21 +169,117 @@ pub trait ResponseBodySink {
   fn write_all(&self, buffer: &[u8]) -> io::Result<()>;
 
   fn as_write(&self) -> ResponseBodySinkAsWrite<'_>;
+
+  /// Pushes everything written so far to the client.
+  /// Without this data may remain in a buffer until the response is complete.
+  fn flush(&self) -> io::Result<()> {
+    Ok(())
+  }
+
+  /// Sets a trailer field that is sent after the last chunk of the body.
+  /// Trailers are discarded if the body is not sent in chunks, for example to http 1.0 clients.
+  /// The field must have been announced with `ResponseBody::with_trailer`.
+  /// Fields that frame, route or authenticate the message such as Content-Length cannot be sent as trailers.
+  fn set_trailer(&self, name: &str, value: &str) -> io::Result<()> {
+    validate_trailer(name, value).map(drop)
+  }
 }
+
+/// Fields that are needed before the body is processed and must not be sent as trailers.
+/// See [RFC 9110 section 6.5.1](https://www.rfc-editor.org/rfc/rfc9110#section-6.5.1).
+const FORBIDDEN_TRAILERS: &[&str] = &[
+  // Message framing
+  "Content-Length",
+  "Transfer-Encoding",
+  "Trailer",
+  "Connection",
+  "Keep-Alive",
+  "Upgrade",
+  // Routing
+  "Host",
+  // Request modifiers
+  "Cache-Control",
+  "Expect",
+  "Max-Forwards",
+  "Pragma",
+  "Range",
+  "TE",
+  "If-Match",
+  "If-None-Match",
+  "If-Modified-Since",
+  "If-Unmodified-Since",
+  "If-Range",
+  // Authentication
+  "Authorization",
+  "Proxy-Authorization",
+  "WWW-Authenticate",
+  "Proxy-Authenticate",
+  "Cookie",
+  "Set-Cookie",
+  // Response control data
+  "Age",
+  "Date",
+  "Expires",
+  "Location",
+  "Retry-After",
+  "Vary",
+  "Warning",
+  // Content processing
+  "Content-Encoding",
+  "Content-Type",
+  "Content-Range",
+];
+
+/// Returns the trailer if it is allowed to be sent after the last chunk of a body.
+fn validate_trailer(name: &str, value: &str) -> io::Result<HttpHeader> {
+  let header = HttpHeader::new(name, value);
+  if FORBIDDEN_TRAILERS.iter().any(|forbidden| forbidden.eq_ignore_ascii_case(name))
+    || name.is_empty()
+    || name.contains(|c: char| c.is_whitespace() || c.is_control() || c == ':')
+    || value.contains(['\r', '\n'])
+  {
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid trailer {name}")));
+  }
+
+  Ok(header)
+}
+
 impl ResponseBody {
+  fn new(inner: ResponseBodyInner) -> Self {
+    Self(inner, Vec::new())
+  }
+
+  /// Announces a trailer field in the `Trailer` header of the response.
+  /// The value is set by the producer of the body with `ResponseBodySink::set_trailer`.
+  /// Trailers are only sent if the body is sent in chunks.
+  /// # Errors
+  /// If the field cannot be sent as a trailer because it frames, routes or authenticates the message,
+  /// for example Content-Length, Host or Authorization.
+  pub fn with_trailer(mut self, name: impl AsRef<str>) -> TiiResult<Self> {
+    let header = validate_trailer(name.as_ref(), "")
+      .map_err(|_| UserError::ImmutableResponseHeaderModified(name.as_ref().into()))?;
+    self.1.push(header.name);
+    Ok(self)
+  }
+
+  /// Returns the names of the trailer fields announced for this body.
+  pub fn trailer_names(&self) -> &[HttpHeaderName] {
+    self.1.as_slice()
+  }
+
   pub fn from_entity<T: Any + Send + Debug + 'static>(
     entity: T,
     serializer: impl EntitySerializer<T> + 'static,
   ) -> Self {
-    Self(ResponseBodyInner::Entity(ResponseEntity::new(entity, serializer)))
+    Self::new(ResponseBodyInner::Entity(ResponseEntity::new(entity, serializer)))
   }
   pub fn from_data(data: Vec<u8>) -> Self {
-    Self(ResponseBodyInner::FixedSizeBinaryData(data))
+    Self::new(ResponseBodyInner::FixedSizeBinaryData(data))
   }
 
   /// Will send data that has been externally gzipped. the data is assumed to be in gzip format and this is not checked.
   pub fn from_externally_gzipped_data(data_in_gzip_format: Vec<u8>) -> Self {
-    Self(ResponseBodyInner::ExternallyGzippedData(data_in_gzip_format))
+    Self::new(ResponseBodyInner::ExternallyGzippedData(data_in_gzip_format))
   }
 
   /// Will gzip the data in memory and then send the compressed version of the data.
@@ -134,15 +290,31 @@ impl ResponseBody {
       crate::util::unwrap_ok(crate::util::new_gzip_encoder(Vec::with_capacity(data.len() + 128)));
     crate::util::unwrap_ok(encoder.write_all(data));
     let buffer = crate::util::unwrap_ok(encoder.finish().into_result());
-    Self(ResponseBodyInner::ExternallyGzippedData(buffer))
+    Self::new(ResponseBodyInner::ExternallyGzippedData(buffer))
+  }
+
+  /// Will send data that has been externally deflated. the data is assumed to be in zlib format and this is not checked.
+  pub fn from_externally_deflated_data(data_in_zlib_format: Vec<u8>) -> Self {
+    Self::new(ResponseBodyInner::ExternallyDeflatedData(data_in_zlib_format))
+  }
+
+  /// Will deflate the data in memory and then send the compressed version of the data.
+  pub fn from_data_with_deflate_in_memory(data: impl AsRef<[u8]>) -> Self {
+    let data = data.as_ref();
+    //We don't do any IO here, this should be infallible unless we run out of memory to enlarge the Vec in which case we might as well die.
+    let mut encoder =
+      crate::util::unwrap_ok(zlib::Encoder::new(Vec::with_capacity(data.len() + 128)));
+    crate::util::unwrap_ok(encoder.write_all(data));
+    let buffer = crate::util::unwrap_ok(encoder.finish().into_result());
+    Self::new(ResponseBodyInner::ExternallyDeflatedData(buffer))
   }
 
   pub fn from_string(data: impl ToString) -> Self {
-    Self(ResponseBodyInner::FixedSizeTextData(data.to_string()))
+    Self::new(ResponseBodyInner::FixedSizeTextData(data.to_string()))
   }
 
   pub fn from_slice<T: AsRef<[u8]> + ?Sized>(data: &T) -> Self {
-    Self(ResponseBodyInner::FixedSizeBinaryData(data.as_ref().to_vec()))
+    Self::new(ResponseBodyInner::FixedSizeBinaryData(data.as_ref().to_vec()))
   }
 
   /// Creates a response body from a static slice.
@@ -151,17 +323,116 @@ impl ResponseBody {
   ///
   /// This is useful for usage with include_bytes!.
   pub fn from_static_slice(data: &'static [u8]) -> Self {
-    Self(ResponseBodyInner::FixedSizeBinaryDataStaticSlice(data))
+    Self::new(ResponseBodyInner::FixedSizeBinaryDataStaticSlice(data))
   }
 
   pub fn from_file<T: Read + Seek + Send + 'static>(mut file: T) -> io::Result<Self> {
     file.seek(SeekFrom::End(0))?;
     let size = file.stream_position()?;
-    Ok(Self(ResponseBodyInner::FixedSizeFile(Box::new(file), size)))
+    Ok(Self::new(ResponseBodyInner::FixedSizeFile(Box::new(file), size)))
+  }
+
+  /// Creates a response body that contains `length` bytes of the file starting at `start`.
+  /// The range must be within the file, otherwise writing the body fails.
+  pub fn from_file_range<T: Read + Seek + Send + 'static>(
+    file: T,
+    start: u64,
+    length: u64,
+  ) -> Self {
+    Self::new(ResponseBodyInner::FileSegments(
+      Box::new(file),
+      vec![FileSegment::Range(start, length)],
+    ))
+  }
+
+  /// Creates a body that is never sent, for responses that only announce the length of a body.
+  /// This is used for the response to a HEAD request or a 304 Not Modified.
+  #[cfg(feature = "extras")] //For now only used by the reverse proxy.
+  pub(crate) fn omitted(length: u64) -> Self {
+    Self::new(ResponseBodyInner::Omitted(length))
+  }
+
+  /// Returns the size of the file if this body is an uncompressed file that is sent completely.
+  pub(crate) fn file_size(&self) -> Option<u64> {
+    match &self.0 {
+      ResponseBodyInner::FixedSizeFile(_, size) => Some(*size),
+      _ => None,
+    }
+  }
+
+  /// Returns the body if it is held in memory and sent as is.
+  pub(crate) fn fixed_size_data(&self) -> Option<&[u8]> {
+    match &self.0 {
+      ResponseBodyInner::FixedSizeBinaryData(data) => Some(data.as_slice()),
+      ResponseBodyInner::FixedSizeBinaryDataStaticSlice(data) => Some(data),
+      ResponseBodyInner::FixedSizeTextData(data) => Some(data.as_bytes()),
+      ResponseBodyInner::ExternallyGzippedData(data) => Some(data.as_slice()),
+      ResponseBodyInner::ExternallyDeflatedData(data) => Some(data.as_slice()),
+      _ => None,
+    }
+  }
+
+  /// Returns true if `compress` can compress this body.
+  pub(crate) fn can_compress(&self) -> bool {
+    matches!(
+      self.0,
+      ResponseBodyInner::FixedSizeBinaryData(_)
+        | ResponseBodyInner::FixedSizeBinaryDataStaticSlice(_)
+        | ResponseBodyInner::FixedSizeTextData(_)
+        | ResponseBodyInner::FixedSizeFile(_, _)
+        | ResponseBodyInner::ChunkedStream(_)
+    )
+  }
+
+  /// Turns an unencoded body into a body that is compressed with the given content coding.
+  /// Data held in memory is compressed immediately, files and chunked streams are compressed on the fly.
+  /// Entities, streams without chunked transfer encoding, file segments and already encoded bodies are returned unchanged as Err.
+  pub(crate) fn compress(self, coding: ContentCoding) -> Result<Self, Self> {
+    let trailers = self.1;
+    let data = match self.0 {
+      ResponseBodyInner::FixedSizeBinaryData(data) => data,
+      ResponseBodyInner::FixedSizeBinaryDataStaticSlice(data) => data.to_vec(),
+      ResponseBodyInner::FixedSizeTextData(data) => data.into_bytes(),
+      ResponseBodyInner::FixedSizeFile(file, _) => {
+        let inner = match coding {
+          ContentCoding::Gzip => ResponseBodyInner::ChunkedGzipFile(file),
+          ContentCoding::Deflate => ResponseBodyInner::ChunkedDeflateFile(file),
+        };
+        return Ok(Self(inner, trailers));
+      }
+      ResponseBodyInner::ChunkedStream(handler) => {
+        let inner = match coding {
+          ContentCoding::Gzip => ResponseBodyInner::ChunkedGzipStream(handler),
+          ContentCoding::Deflate => ResponseBodyInner::ChunkedDeflateStream(handler),
+        };
+        return Ok(Self(inner, trailers));
+      }
+      other => return Err(Self(other, trailers)),
+    };
+
+    Ok(match coding {
+      ContentCoding::Gzip => Self::from_data_with_gzip_in_memory(data),
+      ContentCoding::Deflate => Self::from_data_with_deflate_in_memory(data),
+    })
+  }
+
+  /// Turns an uncompressed file body into a body that only contains the given segments of the file.
+  /// Bodies of any other type are returned unchanged as Err.
+  pub(crate) fn into_file_segments(self, segments: Vec<FileSegment>) -> Result<Self, Self> {
+    match self.0 {
+      ResponseBodyInner::FixedSizeFile(file, _) => {
+        Ok(Self(ResponseBodyInner::FileSegments(file, segments), self.1))
+      }
+      other => Err(Self(other, self.1)),
+    }
   }
 
   pub fn from_file_with_chunked_gzip<T: Read + Seek + Send + 'static>(file: T) -> Self {
-    Self(ResponseBodyInner::ChunkedGzipFile(Box::new(file)))
+    Self::new(ResponseBodyInner::ChunkedGzipFile(Box::new(file)))
+  }
+
+  pub fn from_file_with_chunked_deflate<T: Read + Seek + Send + 'static>(file: T) -> Self {
+    Self::new(ResponseBodyInner::ChunkedDeflateFile(Box::new(file)))
   }
 
   pub fn from_externally_gzipped_file<T: Read + Seek + Send + 'static>(
@@ -169,19 +440,30 @@ impl ResponseBody {
   ) -> io::Result<Self> {
     file_in_gzip_format.seek(SeekFrom::End(0))?;
     let size = file_in_gzip_format.stream_position()?;
-    Ok(Self(ResponseBodyInner::ExternallyGzippedFile(Box::new(file_in_gzip_format), size)))
+    Ok(Self::new(ResponseBodyInner::ExternallyGzippedFile(Box::new(file_in_gzip_format), size)))
   }
 
   pub fn chunked<T: FnOnce(&dyn ResponseBodySink) -> io::Result<()> + Send + 'static>(
     streamer: T,
   ) -> Self {
-    Self(ResponseBodyInner::ChunkedStream(Some(Box::new(streamer))))
+    Self::new(ResponseBodyInner::ChunkedStream(Some(Box::new(streamer))))
   }
 
   pub fn streamed<T: FnOnce(&dyn ResponseBodySink) -> io::Result<()> + Send + 'static>(
     streamer: T,
   ) -> Self {
-    Self(ResponseBodyInner::Stream(Some(Box::new(streamer))))
+    Self::new(ResponseBodyInner::Stream(Some(Box::new(streamer))))
+  }
+
+  /// Creates a response body that streams server-sent events.
+  /// The streamer runs when the response is written to the client, the response ends when it returns.
+  /// If `keep_alive` is set then a ping is sent whenever no event was sent for that long.
+  /// Such bodies are never compressed since that would delay the delivery of events.
+  pub fn event_stream<T: FnOnce(&SseSender<'_>) -> io::Result<()> + Send + 'static>(
+    keep_alive: Option<Duration>,
+    streamer: T,
+  ) -> Self {
+    Self::new(ResponseBodyInner::EventStream(keep_alive, Some(Box::new(streamer))))
   }
 
   /// Creates a response body that streams data from a sink and will on the fly gzip it.
@@ -190,7 +472,16 @@ impl ResponseBody {
   pub fn chunked_gzip<T: FnOnce(&dyn ResponseBodySink) -> io::Result<()> + Send + 'static>(
     streamer: T,
   ) -> Self {
-    Self(ResponseBodyInner::ChunkedGzipStream(Some(Box::new(streamer))))
+    Self::new(ResponseBodyInner::ChunkedGzipStream(Some(Box::new(streamer))))
+  }
+
+  /// Creates a response body that streams data from a sink and will on the fly deflate it.
+  /// Due to deflate encoding the implementation does not guarantee that each written chunk
+  /// corresponds to exactly one http chunk and also does not guarantee that any such chunk is written immediately.
+  pub fn chunked_deflate<T: FnOnce(&dyn ResponseBodySink) -> io::Result<()> + Send + 'static>(
+    streamer: T,
+  ) -> Self {
+    Self::new(ResponseBodyInner::ChunkedDeflateStream(Some(Box::new(streamer))))
   }
 
   /// This fn causes entity data to be serialized into a Vec
@@ -200,9 +491,9 @@ impl ResponseBody {
   pub fn serialize_entity(self, mime: &MimeTypeWithCharset) -> TiiResult<ResponseBody> {
     Ok(match self.0 {
       ResponseBodyInner::Entity(entity) => {
-        ResponseBody(ResponseBodyInner::FixedSizeBinaryData(entity.serialize(mime)?))
+        ResponseBody(ResponseBodyInner::FixedSizeBinaryData(entity.serialize(mime)?), self.1)
       }
-      other => ResponseBody(other),
+      other => ResponseBody(other, self.1),
     })
   }
 
@@ -276,7 +567,8 @@ impl ResponseBody {
       ResponseBodyInner::FixedSizeBinaryData(data) => stream.write_all(data.as_ref())?,
       ResponseBodyInner::FixedSizeTextData(text) => stream.write_all(text.as_ref())?,
       ResponseBodyInner::FixedSizeFile(mut data, _)
-      | ResponseBodyInner::ChunkedGzipFile(mut data) => {
+      | ResponseBodyInner::ChunkedGzipFile(mut data)
+      | ResponseBodyInner::ChunkedDeflateFile(mut data) => {
         let mut io_buf = [0u8; 0x1_00_00];
         loop {
           let len = data.read(&mut io_buf)?;
@@ -286,14 +578,25 @@ impl ResponseBody {
           stream.write_all(unwrap_some(io_buf.get(..len)))?
         }
       }
+      ResponseBodyInner::FileSegments(mut file, segments) => {
+        write_file_segments(&mut file, segments.as_slice(), |buf| stream.write_all(buf))?
+      }
       ResponseBodyInner::Stream(mut handler)
       | ResponseBodyInner::ChunkedStream(mut handler)
-      | ResponseBodyInner::ChunkedGzipStream(mut handler) => {
+      | ResponseBodyInner::ChunkedGzipStream(mut handler)
+      | ResponseBodyInner::ChunkedDeflateStream(mut handler) => {
         let sink = RawSink(RefCell::new(stream));
         handler.take().ok_or_else(|| {
           io::Error::new(io::ErrorKind::UnexpectedEof, "stream can only be written once")
         })?(&sink)?;
       }
+      ResponseBodyInner::EventStream(_, mut handler) => {
+        let sink = RawSink(RefCell::new(stream));
+        let handler = handler.take().ok_or_else(|| {
+          io::Error::new(io::ErrorKind::UnexpectedEof, "stream can only be written once")
+        })?;
+        stream_events(&sink, None, handler)?;
+      }
       ResponseBodyInner::ExternallyGzippedData(data) => {
         let mut io_buf = [0u8; 0x1_00_00];
         let mut dec = gzip::Decoder::new(&*data)?;
@@ -316,7 +619,19 @@ impl ResponseBody {
           stream.write_all(unwrap_some(io_buf.get(..len)))?
         }
       }
+      ResponseBodyInner::ExternallyDeflatedData(data) => {
+        let mut io_buf = [0u8; 0x1_00_00];
+        let mut dec = zlib::Decoder::new(&*data)?;
+        loop {
+          let len = dec.read(&mut io_buf)?;
+          if len == 0 {
+            return Ok(());
+          }
+          stream.write_all(unwrap_some(io_buf.get(..len)))?
+        }
+      }
       ResponseBodyInner::Entity(entity) => stream.write_all(&entity.serialize(mime)?)?,
+      ResponseBodyInner::Omitted(_) => {}
     };
 
     Ok(())
@@ -326,15 +641,22 @@ impl ResponseBody {
   /// Its expected that the stream just finished writing the last header.
   /// This fn will handle things like transfer/content encoding (not the header part)
   /// for the body transparently. So a chunked stream will be in the http chunked format for example.
+  /// Chunked bodies are only framed as chunks for http 1.1, older clients get the raw data
+  /// and the end of the body is signaled by closing the connection.
   pub fn write_to_http<T: ConnectionStreamWrite + ?Sized>(
     self,
     request_id: u128,
+    version: HttpVersion,
     stream: &T,
   ) -> TiiResult<()> {
+    let self_coding = self.content_coding();
+    let chunked = version == HttpVersion::Http11;
+    let trailer_names = self.1;
     match self.0 {
       ResponseBodyInner::FixedSizeBinaryDataStaticSlice(data) => stream.write_all(data)?,
       ResponseBodyInner::FixedSizeBinaryData(data)
-      | ResponseBodyInner::ExternallyGzippedData(data) => stream.write_all(data.as_slice())?,
+      | ResponseBodyInner::ExternallyGzippedData(data)
+      | ResponseBodyInner::ExternallyDeflatedData(data) => stream.write_all(data.as_slice())?,
       ResponseBodyInner::FixedSizeTextData(text) => stream.write_all(text.as_bytes())?,
       ResponseBodyInner::FixedSizeFile(mut file, size)
       | ResponseBodyInner::ExternallyGzippedFile(mut file, size) => {
@@ -363,6 +685,9 @@ impl ResponseBody {
           stream.write_all(io_buf.get_mut(..read).ok_or(io::Error::other("buffer overflow"))?)?;
         }
       }
+      ResponseBodyInner::FileSegments(mut file, segments) => {
+        write_file_segments(&mut file, segments.as_slice(), |buf| stream.write_all(buf))?
+      }
       ResponseBodyInner::Stream(mut handler) => {
         handler.take().ok_or_else(|| TiiError::from_io_kind(io::ErrorKind::UnexpectedEof))?(
           &StreamSink(stream.as_stream_write()),
@@ -370,22 +695,46 @@ impl ResponseBody {
       }
 
       ResponseBodyInner::ChunkedStream(mut handler) => {
-        let sink = ChunkedSink(request_id, stream.as_stream_write());
+        let sink = ChunkedSink::new(request_id, stream.as_stream_write(), chunked, trailer_names);
         handler.take().ok_or_else(|| {
           io::Error::new(io::ErrorKind::UnexpectedEof, "stream can only be written once")
         })?(&sink)?;
         sink.finish()?
       }
-      ResponseBodyInner::ChunkedGzipStream(mut handler) => {
-        let sink = GzipChunkedSink::new(request_id, stream.as_stream_write())?;
+      ResponseBodyInner::EventStream(keep_alive, mut handler) => {
+        let sink = ChunkedSink::new(request_id, stream.as_stream_write(), chunked, trailer_names);
+        let handler = handler.take().ok_or_else(|| {
+          io::Error::new(io::ErrorKind::UnexpectedEof, "stream can only be written once")
+        })?;
+        stream_events(&sink, keep_alive.map(|interval| (&sink as _, interval)), handler)?;
+        sink.finish()?
+      }
+      ResponseBodyInner::ChunkedGzipStream(mut handler)
+      | ResponseBodyInner::ChunkedDeflateStream(mut handler) => {
+        let coding = unwrap_some(self_coding);
+        let sink = EncodingChunkedSink::new(
+          request_id,
+          coding,
+          stream.as_stream_write(),
+          chunked,
+          trailer_names,
+        )?;
         handler.take().ok_or_else(|| {
           io::Error::new(io::ErrorKind::UnexpectedEof, "stream can only be written once")
         })?(&sink)?;
         sink.finish()?
       }
-      ResponseBodyInner::ChunkedGzipFile(mut file) => {
+      ResponseBodyInner::ChunkedGzipFile(mut file)
+      | ResponseBodyInner::ChunkedDeflateFile(mut file) => {
         file.seek(io::SeekFrom::Start(0))?;
-        let sink = GzipChunkedSink::new(request_id, stream.as_stream_write())?;
+        let coding = unwrap_some(self_coding);
+        let sink = EncodingChunkedSink::new(
+          request_id,
+          coding,
+          stream.as_stream_write(),
+          chunked,
+          trailer_names,
+        )?;
         let mut io_buf = [0u8; 0x1_00_00];
         loop {
           let count = file.read(io_buf.as_mut_slice())?;
@@ -398,11 +747,12 @@ impl ResponseBody {
       }
       ResponseBodyInner::Entity(entity) => {
         // This should be unreachable under normal circumstances,
-        // if we got here anyway we are writing it in Chunked Transfer Encoding.
-        let sink = ChunkedSink(request_id, stream.as_stream_write());
+        // if we got here anyway we are writing it like any other chunked body.
+        let sink = ChunkedSink::new(request_id, stream.as_stream_write(), chunked, trailer_names);
         sink.write_all(&entity.serialize(&MimeTypeWithCharset::APPLICATION_OCTET_STREAM)?)?;
         sink.finish()?
       }
+      ResponseBodyInner::Omitted(_) => {}
     };
 
     Ok(())
@@ -416,18 +766,41 @@ impl ResponseBody {
     matches!(
       self.0,
       ResponseBodyInner::ChunkedStream(_)
+        | ResponseBodyInner::EventStream(_, _)
         | ResponseBodyInner::ChunkedGzipStream(_)
         | ResponseBodyInner::ChunkedGzipFile(_)
+        | ResponseBodyInner::ChunkedDeflateStream(_)
+        | ResponseBodyInner::ChunkedDeflateFile(_)
         | ResponseBodyInner::Entity(_)
     )
   }
 
+  /// Returns true if the end of this body can only be signaled by closing the connection
+  /// when it is sent to a client that speaks the given http version.
+  /// That is the case for bodies without a known length unless they can be sent in chunks.
+  pub fn requires_connection_close(&self, version: HttpVersion) -> bool {
+    if self.is_entity() {
+      // Entities are serialized into fixed size data before they are written.
+      return false;
+    }
+
+    self.content_length().is_none() && !(version == HttpVersion::Http11 && self.is_chunked())
+  }
+
   pub fn get_content_encoding(&self) -> Option<&'static str> {
+    self.content_coding().map(|coding| coding.as_str())
+  }
+
+  /// Returns the content coding of the body or None if the body is not encoded.
+  pub fn content_coding(&self) -> Option<ContentCoding> {
     Some(match self.0 {
-      ResponseBodyInner::ExternallyGzippedData(_) => "gzip",
-      ResponseBodyInner::ExternallyGzippedFile(_, _) => "gzip",
-      ResponseBodyInner::ChunkedGzipStream(_) => "gzip",
-      ResponseBodyInner::ChunkedGzipFile(_) => "gzip",
+      ResponseBodyInner::ExternallyGzippedData(_) => ContentCoding::Gzip,
+      ResponseBodyInner::ExternallyGzippedFile(_, _) => ContentCoding::Gzip,
+      ResponseBodyInner::ChunkedGzipStream(_) => ContentCoding::Gzip,
+      ResponseBodyInner::ChunkedGzipFile(_) => ContentCoding::Gzip,
+      ResponseBodyInner::ExternallyDeflatedData(_) => ContentCoding::Deflate,
+      ResponseBodyInner::ChunkedDeflateStream(_) => ContentCoding::Deflate,
+      ResponseBodyInner::ChunkedDeflateFile(_) => ContentCoding::Deflate,
       _ => return None,
     })
   }
@@ -439,12 +812,46 @@ impl ResponseBody {
       ResponseBodyInner::FixedSizeTextData(data) => u64::try_from(data.len()).ok(),
       ResponseBodyInner::FixedSizeFile(_, sz) => Some(*sz),
       ResponseBodyInner::ExternallyGzippedData(data) => u64::try_from(data.len()).ok(),
+      ResponseBodyInner::ExternallyDeflatedData(data) => u64::try_from(data.len()).ok(),
       ResponseBodyInner::ExternallyGzippedFile(_, sz) => Some(*sz),
+      ResponseBodyInner::Omitted(length) => Some(*length),
+      ResponseBodyInner::FileSegments(_, segments) => {
+        Some(segments.iter().map(FileSegment::len).sum())
+      }
       _ => None,
     }
   }
 }
 
+/// Writes the segments of a file to a stream.
+fn write_file_segments(
+  file: &mut Box<dyn ReadAndSeek>,
+  segments: &[FileSegment],
+  mut write_all: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+  let mut io_buf = [0u8; 0x1_00_00];
+  for segment in segments {
+    match segment {
+      FileSegment::Data(data) => write_all(data.as_slice())?,
+      FileSegment::Range(start, len) => {
+        file.seek(SeekFrom::Start(*start))?;
+        let mut remaining = *len;
+        while remaining > 0 {
+          let chunk = usize::try_from(remaining).unwrap_or(usize::MAX).min(io_buf.len());
+          let read = file.read(unwrap_some(io_buf.get_mut(..chunk)))?;
+          if read == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+          }
+          write_all(unwrap_some(io_buf.get(..read)))?;
+          remaining -= read as u64;
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
 pub struct ResponseBodySinkAsWrite<'a>(&'a dyn ResponseBodySink);
 
 impl Write for ResponseBodySinkAsWrite<'_> {
@@ -472,49 +879,95 @@ impl ResponseBodySink for StreamSink<'_> {
   fn as_write(&self) -> ResponseBodySinkAsWrite<'_> {
     ResponseBodySinkAsWrite(self)
   }
+
+  fn flush(&self) -> io::Result<()> {
+    self.0.flush()
+  }
+}
+
+enum ChunkedEncoder<'a> {
+  Gzip(gzip::Encoder<BufWriter<ChunkedSink<'a>>>),
+  Deflate(zlib::Encoder<BufWriter<ChunkedSink<'a>>>),
+}
+
+impl ChunkedEncoder<'_> {
+  fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+    match self {
+      ChunkedEncoder::Gzip(encoder) => encoder.write(buffer),
+      ChunkedEncoder::Deflate(encoder) => encoder.write(buffer),
+    }
+  }
+
+  fn write_all(&mut self, buffer: &[u8]) -> io::Result<()> {
+    match self {
+      ChunkedEncoder::Gzip(encoder) => encoder.write_all(buffer),
+      ChunkedEncoder::Deflate(encoder) => encoder.write_all(buffer),
+    }
+  }
+
+  fn finish(self) -> io::Result<()> {
+    match self {
+      ChunkedEncoder::Gzip(encoder) => encoder.finish().into_result()?.into_inner()?.finish(),
+      ChunkedEncoder::Deflate(encoder) => encoder.finish().into_result()?.into_inner()?.finish(),
+    }
+  }
+
+  fn chunked_sink(&self) -> &ChunkedSink<'_> {
+    match self {
+      ChunkedEncoder::Gzip(encoder) => encoder.as_inner_ref().get_ref(),
+      ChunkedEncoder::Deflate(encoder) => encoder.as_inner_ref().get_ref(),
+    }
+  }
 }
 
-struct GzipChunkedSink<'a>(u128, RefCell<Option<gzip::Encoder<BufWriter<ChunkedSink<'a>>>>>);
+struct EncodingChunkedSink<'a>(u128, RefCell<Option<ChunkedEncoder<'a>>>);
 
-impl<'a> GzipChunkedSink<'a> {
+impl<'a> EncodingChunkedSink<'a> {
   fn new(
     request_id: u128,
+    coding: ContentCoding,
     stream: &'a dyn ConnectionStreamWrite,
-  ) -> io::Result<GzipChunkedSink<'a>> {
-    // We need BufWriter here because the gzip encoder calls write with like 2-4 bytes at a time.
-    // We don't want to emit a http chunk every single time the gzip encoder writes a single symbol
+    chunked: bool,
+    trailer_names: Vec<HttpHeaderName>,
+  ) -> io::Result<EncodingChunkedSink<'a>> {
+    // We need BufWriter here because the encoder calls write with like 2-4 bytes at a time.
+    // We don't want to emit a http chunk every single time the encoder writes a single symbol
     // the overhead would be several 100%.
-    // If we use the BufWriter the overhead only exist when gzip calls flush().
+    // If we use the BufWriter the overhead only exist when the encoder calls flush().
     // This only happens when there is significant data buffered so it's reasonable to emit a chunk then.
-    Ok(Self(
-      request_id,
-      RefCell::new(Some(crate::util::new_gzip_encoder(BufWriter::new(ChunkedSink(
-        request_id, stream,
-      )))?)),
-    ))
+    let buffer = BufWriter::new(ChunkedSink::new(request_id, stream, chunked, trailer_names));
+    let encoder = match coding {
+      ContentCoding::Gzip => ChunkedEncoder::Gzip(crate::util::new_gzip_encoder(buffer)?),
+      ContentCoding::Deflate => ChunkedEncoder::Deflate(zlib::Encoder::new(buffer)?),
+    };
+    Ok(Self(request_id, RefCell::new(Some(encoder))))
   }
 }
 
-impl GzipChunkedSink<'_> {
+impl EncodingChunkedSink<'_> {
   fn finish(&self) -> io::Result<()> {
-    trace_log!("tii: Request {} GzipChunkedSink::finish", self.0);
+    trace_log!("tii: Request {} EncodingChunkedSink::finish", self.0);
     defer! {
-      trace_log!("tii: Request {} GzipChunkedSink::finish done", self.0);
+      trace_log!("tii: Request {} EncodingChunkedSink::finish done", self.0);
     }
     //Safety, this function will panic/abort if called more than once
-    unwrap_some(self.1.borrow_mut().take()).finish().into_result()?.into_inner()?.finish()
+    unwrap_some(self.1.borrow_mut().take()).finish()
   }
 }
 
-impl ResponseBodySink for GzipChunkedSink<'_> {
+impl ResponseBodySink for EncodingChunkedSink<'_> {
   fn write(&self, buffer: &[u8]) -> io::Result<usize> {
-    trace_log!("tii: Request {} GzipChunkedSink::write with {} bytes", self.0, buffer.len());
+    trace_log!("tii: Request {} EncodingChunkedSink::write with {} bytes", self.0, buffer.len());
     //Safety, this function will panic/abort if called after finish
     unwrap_some(self.1.borrow_mut().as_mut()).write(buffer)
   }
 
   fn write_all(&self, buffer: &[u8]) -> io::Result<()> {
-    trace_log!("tii: Request {} GzipChunkedSink::write_all with {} bytes", self.0, buffer.len());
+    trace_log!(
+      "tii: Request {} EncodingChunkedSink::write_all with {} bytes",
+      self.0,
+      buffer.len()
+    );
     //Safety, this function will panic/abort if called after finish
     unwrap_some(self.1.borrow_mut().as_mut()).write_all(buffer)
   }
@@ -522,6 +975,11 @@ impl ResponseBodySink for GzipChunkedSink<'_> {
   fn as_write(&self) -> ResponseBodySinkAsWrite<'_> {
     ResponseBodySinkAsWrite(self)
   }
+
+  fn set_trailer(&self, name: &str, value: &str) -> io::Result<()> {
+    //Safety, this function will panic/abort if called after finish
+    unwrap_some(self.1.borrow().as_ref()).chunked_sink().set_trailer(name, value)
+  }
 }
 
 struct RawSink<'a>(RefCell<&'a mut dyn Write>);
@@ -541,7 +999,17 @@ impl ResponseBodySink for RawSink<'_> {
 }
 static CHUNK_LUT: [&[u8]; 8096] = tii_procmacro::hex_chunked_lut!(8096);
 
-struct ChunkedSink<'a>(u128, &'a dyn ConnectionStreamWrite);
+/// Writes a body in the http chunked format.
+struct ChunkedSink<'a> {
+  request_id: u128,
+  stream: &'a dyn ConnectionStreamWrite,
+  /// If false the data is written as is, this is used for clients that do not understand chunks.
+  chunked: bool,
+  /// The names of the trailers that were announced in the `Trailer` header.
+  trailer_names: Vec<HttpHeaderName>,
+  /// The trailers that are written after the last chunk.
+  trailers: Mutex<Vec<HttpHeader>>,
+}
 
 impl Write for ChunkedSink<'_> {
   fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
@@ -565,31 +1033,79 @@ impl ResponseBodySink for ChunkedSink<'_> {
       return Ok(());
     }
 
+    if !self.chunked {
+      return self.stream.write_all(buffer);
+    }
+
     trace_log!(
       "tii: Request {} ChunkedSink -> Emitting a HTTP chunk with {} bytes",
-      self.0,
+      self.request_id,
       buffer.len()
     );
 
     if let Some(lut) = CHUNK_LUT.get(buffer.len()) {
-      self.1.write_all(lut)?;
+      self.stream.write_all(lut)?;
     } else {
-      self.1.write_all(format!("{:X}\r\n", buffer.len()).as_bytes())?;
+      self.stream.write_all(format!("{:X}\r\n", buffer.len()).as_bytes())?;
     }
 
-    self.1.write_all(buffer)?;
-    self.1.write_all(b"\r\n")
+    self.stream.write_all(buffer)?;
+    self.stream.write_all(b"\r\n")
   }
 
   fn as_write(&self) -> ResponseBodySinkAsWrite<'_> {
     ResponseBodySinkAsWrite(self)
   }
+
+  fn flush(&self) -> io::Result<()> {
+    self.stream.flush()
+  }
+
+  fn set_trailer(&self, name: &str, value: &str) -> io::Result<()> {
+    let trailer = validate_trailer(name, value)?;
+    if !self.trailer_names.contains(&trailer.name) {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("trailer {name} was not announced"),
+      ));
+    }
+
+    let mut trailers = unwrap_poison(self.trailers.lock())?;
+    trailers.retain(|other| !other.name.to_str().eq_ignore_ascii_case(trailer.name.to_str()));
+    trailers.push(trailer);
+    Ok(())
+  }
 }
 
-impl ChunkedSink<'_> {
+impl<'a> ChunkedSink<'a> {
+  fn new(
+    request_id: u128,
+    stream: &'a dyn ConnectionStreamWrite,
+    chunked: bool,
+    trailer_names: Vec<HttpHeaderName>,
+  ) -> Self {
+    Self { request_id, stream, chunked, trailer_names, trailers: Mutex::new(Vec::new()) }
+  }
+
   fn finish(&self) -> io::Result<()> {
-    trace_log!("tii: Request {} ChunkedSink -> Emitting trailer", self.0);
-    self.1.write_all(b"0\r\n\r\n")
+    if !self.chunked {
+      return Ok(());
+    }
+
+    trace_log!("tii: Request {} ChunkedSink -> Emitting trailer", self.request_id);
+    let trailers = unwrap_poison(self.trailers.lock())?;
+    if trailers.is_empty() {
+      return self.stream.write_all(b"0\r\n\r\n");
+    }
+
+    self.stream.write_all(b"0\r\n")?;
+    for trailer in trailers.iter() {
+      self.stream.write_all(trailer.name.to_str().as_bytes())?;
+      self.stream.write_all(b": ")?;
+      self.stream.write_all(trailer.value.as_bytes())?;
+      self.stream.write_all(b"\r\n")?;
+    }
+    self.stream.write_all(b"\r\n")
   }
 }
 
@@ -0,0 +1,128 @@
+//! Parsing of response heads for the clients that ship with tii.
+
+use crate::http::{has_header_token, parse_content_codings};
+use crate::{
+  ConnectionStream, ContentCoding, HttpHeader, HttpHeaderName, HttpVersion, StatusCode, TiiError,
+  TiiResult,
+};
+use std::io::ErrorKind;
+
+/// Maximum length of the status line and of each header line of a response.
+const MAX_LINE_LENGTH: usize = 0x1_00_00;
+
+/// Status line and headers of a response.
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseHead {
+  pub(crate) version: HttpVersion,
+  pub(crate) status_code: StatusCode,
+  pub(crate) headers: Vec<HttpHeader>,
+}
+
+pub(crate) fn invalid_response(message: &str) -> TiiError {
+  TiiError::new_io(ErrorKind::InvalidData, format!("invalid response: {message}"))
+}
+
+/// Reads a line terminated by `\n` or `\r\n`, the terminator is not part of the returned line.
+pub(crate) fn read_line(stream: &dyn ConnectionStream) -> TiiResult<String> {
+  let mut line = Vec::new();
+  stream.read_until(b'\n', MAX_LINE_LENGTH, &mut line)?;
+  if line.pop() != Some(b'\n') {
+    return Err(TiiError::from_io_kind(ErrorKind::UnexpectedEof));
+  }
+  if line.last() == Some(&b'\r') {
+    line.pop();
+  }
+
+  String::from_utf8(line).map_err(|_| invalid_response("line is not utf-8"))
+}
+
+impl ResponseHead {
+  /// Reads the status line and the headers.
+  pub(crate) fn read(stream: &dyn ConnectionStream) -> TiiResult<Self> {
+    let status_line = read_line(stream)?;
+    let mut parts = status_line.splitn(3, ' ');
+    let (Some(version), Some(code)) = (parts.next(), parts.next()) else {
+      return Err(invalid_response("malformed status line"));
+    };
+    let version = match HttpVersion::try_from_net_str(version) {
+      Ok(HttpVersion::Http09) | Err(_) => return Err(invalid_response("unsupported version")),
+      Ok(version) => version,
+    };
+    let code = code.parse::<u16>().map_err(|_| invalid_response("malformed status code"))?;
+    let reason = parts.next().unwrap_or_default();
+    let status_code = StatusCode::from_custom_string(code, &reason)
+      .unwrap_or_else(|| StatusCode::from_well_known_code_or_500(code));
+
+    let mut headers = Vec::new();
+    loop {
+      let line = read_line(stream)?;
+      if line.is_empty() {
+        break;
+      }
+      let Some((name, value)) = line.split_once(':') else {
+        return Err(invalid_response("malformed header"));
+      };
+      headers.push(HttpHeader::new(name.trim(), value.trim()));
+    }
+
+    Ok(Self { version, status_code, headers })
+  }
+
+  /// Returns all values of the header in order of appearance.
+  /// Header names are compared case-insensitively.
+  pub(crate) fn get_headers(&self, name: &str) -> Vec<&str> {
+    self
+      .headers
+      .iter()
+      .filter(|header| header.name.to_str().eq_ignore_ascii_case(name))
+      .map(|header| header.value.as_str())
+      .collect()
+  }
+
+  /// Returns the first value of the header. Header names are compared case-insensitively.
+  pub(crate) fn get_header(&self, name: &str) -> Option<&str> {
+    self.get_headers(name).into_iter().next()
+  }
+
+  /// Returns true if a response to a request with the given method has a body.
+  pub(crate) fn has_body(&self, head_request: bool) -> bool {
+    let code = self.status_code.code();
+    !head_request && code >= 200 && code != 204 && code != 304
+  }
+
+  /// Returns true if the body is sent with chunked transfer encoding.
+  pub(crate) fn is_chunked(&self) -> bool {
+    self
+      .get_header(HttpHeaderName::TransferEncoding.to_str())
+      .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+  }
+
+  /// Returns the parsed `Content-Length` header.
+  pub(crate) fn content_length(&self) -> TiiResult<Option<u64>> {
+    self
+      .get_header(HttpHeaderName::ContentLength.to_str())
+      .map(|length| length.parse::<u64>().map_err(|_| invalid_response("malformed length")))
+      .transpose()
+  }
+
+  /// Returns the content codings of the body in the order they were applied.
+  pub(crate) fn content_codings(&self) -> TiiResult<Vec<ContentCoding>> {
+    Ok(
+      self
+        .get_headers(HttpHeaderName::ContentEncoding.to_str())
+        .iter()
+        .map(|value| parse_content_codings(value).ok_or_else(|| invalid_response("unknown coding")))
+        .collect::<TiiResult<Vec<_>>>()?
+        .concat(),
+    )
+  }
+
+  /// Returns true if the server keeps the connection open after this response.
+  pub(crate) fn keeps_alive(&self) -> bool {
+    let connection = self.get_headers(HttpHeaderName::Connection.to_str());
+    match self.version {
+      HttpVersion::Http11 => !has_header_token(&connection, "close"),
+      _ => has_header_token(&connection, "keep-alive") && !has_header_token(&connection, "close"),
+    }
+  }
+}
@@ -0,0 +1,213 @@
+//! Server-Sent Events (`text/event-stream`) response bodies.
+
+use crate::http::response_body::ResponseBodySink;
+use crate::util::unwrap_poison;
+use crate::{RequestContext, Response, TiiResult};
+use defer_heavy::defer;
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::io::ErrorKind;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub(crate) type SseHandler = dyn FnOnce(&SseSender<'_>) -> io::Result<()> + Send;
+
+/// State shared between the sender and the keep-alive thread.
+struct SseState {
+  last_write: Instant,
+  disconnected: bool,
+  finished: bool,
+}
+
+struct SseShared {
+  state: Mutex<SseState>,
+  condition: Condvar,
+}
+
+/// Writes server-sent events to the client.
+///
+/// Every event is flushed to the client immediately.
+/// Once a write fails the client is considered disconnected and all further writes fail with `BrokenPipe`.
+pub struct SseSender<'a> {
+  sink: &'a dyn ResponseBodySink,
+  shared: &'a SseShared,
+}
+
+impl Debug for SseSender<'_> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!("SseSender(connected={})", self.is_connected()))
+  }
+}
+
+fn check_field(name: &str, value: &str) -> io::Result<()> {
+  if value.contains(['\r', '\n', '\0']) {
+    return Err(io::Error::new(
+      ErrorKind::InvalidInput,
+      format!("server-sent event {name} must not contain line breaks or NUL"),
+    ));
+  }
+  Ok(())
+}
+
+fn push_lines(buffer: &mut String, prefix: &str, value: &str) {
+  for line in value.split("\r\n").flat_map(|line| line.split(['\r', '\n'])) {
+    buffer.push_str(prefix);
+    buffer.push_str(line);
+    buffer.push('\n');
+  }
+}
+
+impl SseSender<'_> {
+  /// Sends an event. `data` may contain multiple lines, each line is sent as its own `data` field.
+  /// The client uses `id` as `Last-Event-ID` when it reconnects.
+  /// Events without a `name` are dispatched as `message` events by browsers.
+  ///
+  /// # Errors
+  /// If `id` or `name` contain line breaks or if writing to the client fails.
+  pub fn event(
+    &self,
+    id: Option<&str>,
+    name: Option<&str>,
+    data: impl AsRef<str>,
+  ) -> io::Result<()> {
+    let mut buffer = String::new();
+    if let Some(id) = id {
+      check_field("id", id)?;
+      push_lines(&mut buffer, "id: ", id);
+    }
+    if let Some(name) = name {
+      check_field("name", name)?;
+      push_lines(&mut buffer, "event: ", name);
+    }
+    push_lines(&mut buffer, "data: ", data.as_ref());
+    buffer.push('\n');
+    self.send(buffer.as_bytes())
+  }
+
+  /// Sends an unnamed event without id.
+  ///
+  /// # Errors
+  /// If writing to the client fails.
+  pub fn data(&self, data: impl AsRef<str>) -> io::Result<()> {
+    self.event(None, None, data)
+  }
+
+  /// Tells the client how long it should wait before reconnecting after the connection was lost.
+  ///
+  /// # Errors
+  /// If writing to the client fails.
+  pub fn retry(&self, delay: Duration) -> io::Result<()> {
+    self.send(format!("retry: {}\n\n", delay.as_millis()).as_bytes())
+  }
+
+  /// Sends a comment, clients ignore comments.
+  ///
+  /// # Errors
+  /// If writing to the client fails.
+  pub fn comment(&self, comment: impl AsRef<str>) -> io::Result<()> {
+    let mut buffer = String::new();
+    push_lines(&mut buffer, ": ", comment.as_ref());
+    buffer.push('\n');
+    self.send(buffer.as_bytes())
+  }
+
+  /// Sends an empty comment. This keeps proxies from closing an idle connection
+  /// and detects clients that have disconnected.
+  ///
+  /// # Errors
+  /// If writing to the client fails.
+  pub fn ping(&self) -> io::Result<()> {
+    self.send(b":\n\n")
+  }
+
+  /// Returns false once a write to the client has failed.
+  pub fn is_connected(&self) -> bool {
+    unwrap_poison(self.shared.state.lock()).is_ok_and(|state| !state.disconnected)
+  }
+
+  fn send(&self, data: &[u8]) -> io::Result<()> {
+    let mut state = unwrap_poison(self.shared.state.lock())?;
+    if state.disconnected {
+      return Err(io::Error::new(ErrorKind::BrokenPipe, "client disconnected"));
+    }
+
+    let result = self.sink.write_all(data).and_then(|_| self.sink.flush());
+    state.last_write = Instant::now();
+    state.disconnected = result.is_err();
+    result
+  }
+}
+
+/// Runs the handler of an event stream.
+/// If a keep-alive interval is given then a thread sends a ping whenever no data was written for that long.
+pub(crate) fn stream_events(
+  sink: &dyn ResponseBodySink,
+  keep_alive: Option<(&(dyn ResponseBodySink + Sync), Duration)>,
+  handler: Box<SseHandler>,
+) -> io::Result<()> {
+  let shared = SseShared {
+    state: Mutex::new(SseState {
+      last_write: Instant::now(),
+      disconnected: false,
+      finished: false,
+    }),
+    condition: Condvar::new(),
+  };
+
+  let Some((ping_sink, interval)) = keep_alive else {
+    return handler(&SseSender { sink, shared: &shared });
+  };
+
+  thread::scope(|scope| {
+    let pinger = scope.spawn(|| -> io::Result<()> {
+      let sender = SseSender { sink: ping_sink, shared: &shared };
+      let mut state = unwrap_poison(shared.state.lock())?;
+      while !state.finished && !state.disconnected {
+        let idle = state.last_write.elapsed();
+        if idle < interval {
+          state = unwrap_poison(shared.condition.wait_timeout(state, interval - idle))?.0;
+          continue;
+        }
+
+        drop(state);
+        //The error is seen by the handler on its next write.
+        let _ = sender.ping();
+        state = unwrap_poison(shared.state.lock())?;
+      }
+      Ok(())
+    });
+
+    let result = {
+      //Stops the keep-alive thread even if the handler panics.
+      defer! {
+        if let Ok(mut state) = unwrap_poison(shared.state.lock()) {
+          state.finished = true;
+        }
+        shared.condition.notify_all();
+      }
+      handler(&SseSender { sink, shared: &shared })
+    };
+
+    pinger.join().map_err(|_| io::Error::other("sse keep-alive thread panicked"))??;
+    result
+  })
+}
+
+/// Creates an endpoint that answers every request with an event stream.
+/// The handler receives the `Last-Event-ID` sent by the client, and it runs when the response body is written.
+/// See `Response::event_stream` for the meaning of `keep_alive`.
+pub fn sse_endpoint<F>(
+  keep_alive: Option<Duration>,
+  handler: F,
+) -> impl Fn(&RequestContext) -> TiiResult<Response>
+where
+  F: Fn(Option<&str>, &SseSender<'_>) -> io::Result<()> + Send + Sync + 'static,
+{
+  let handler = Arc::new(handler);
+  move |request: &RequestContext| {
+    let handler = handler.clone();
+    let last_event_id = request.last_event_id().map(ToString::to_string);
+    Ok(Response::event_stream(keep_alive, move |sender| handler(last_event_id.as_deref(), sender)))
+  }
+}
@@ -80,6 +80,8 @@ pub enum StatusCode {
   RequestedRangeNotSatisfiable,
   /// `417 Expectation Failed`: The expectation given in the `Expect` header could not be met by the server.
   ExpectationFailed,
+  /// `431 Request Header Fields Too Large`: The headers of the request are too large for the server to process.
+  RequestHeaderFieldsTooLarge,
   /// `500 Internal Server Error`: The server encountered an unexpected error which prevented it from fulfilling the request.
   InternalServerError,
   /// `501 Not Implemented`: The server does not support the functionality required to fulfill the request.
@@ -181,6 +183,7 @@ impl StatusCode {
       415 => StatusCode::UnsupportedMediaType,
       416 => StatusCode::RequestedRangeNotSatisfiable,
       417 => StatusCode::ExpectationFailed,
+      431 => StatusCode::RequestHeaderFieldsTooLarge,
       501 => StatusCode::NotImplemented,
       502 => StatusCode::BadGateway,
       503 => StatusCode::ServiceUnavailable,
@@ -229,6 +232,7 @@ impl StatusCode {
       415 => StatusCode::UnsupportedMediaType,
       416 => StatusCode::RequestedRangeNotSatisfiable,
       417 => StatusCode::ExpectationFailed,
+      431 => StatusCode::RequestHeaderFieldsTooLarge,
       500 => StatusCode::InternalServerError,
       501 => StatusCode::NotImplemented,
       502 => StatusCode::BadGateway,
@@ -278,6 +282,7 @@ impl StatusCode {
       StatusCode::UnsupportedMediaType => "Unsupported Media Type",
       StatusCode::RequestedRangeNotSatisfiable => "Requested Range Not Satisfiable",
       StatusCode::ExpectationFailed => "Expectation Failed",
+      StatusCode::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
       StatusCode::InternalServerError => "Internal Server Error",
       StatusCode::NotImplemented => "Not Implemented",
       StatusCode::BadGateway => "Bad Gateway",
@@ -329,6 +334,7 @@ impl StatusCode {
       StatusCode::UnsupportedMediaType => "Unsupported Media Type",
       StatusCode::RequestedRangeNotSatisfiable => "Requested Range Not Satisfiable",
       StatusCode::ExpectationFailed => "Expectation Failed",
+      StatusCode::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
       StatusCode::InternalServerError => "Internal Server Error",
       StatusCode::NotImplemented => "Not Implemented",
       StatusCode::BadGateway => "Bad Gateway",
@@ -382,6 +388,7 @@ impl StatusCode {
       StatusCode::UnsupportedMediaType => b"415",
       StatusCode::RequestedRangeNotSatisfiable => b"416",
       StatusCode::ExpectationFailed => b"417",
+      StatusCode::RequestHeaderFieldsTooLarge => b"431",
       StatusCode::InternalServerError => b"500",
       StatusCode::NotImplemented => b"501",
       StatusCode::BadGateway => b"502",
@@ -433,6 +440,7 @@ impl StatusCode {
       StatusCode::UnsupportedMediaType => 415,
       StatusCode::RequestedRangeNotSatisfiable => 416,
       StatusCode::ExpectationFailed => 417,
+      StatusCode::RequestHeaderFieldsTooLarge => 431,
       StatusCode::InternalServerError => 500,
       StatusCode::NotImplemented => 501,
       StatusCode::BadGateway => 502,
@@ -0,0 +1,232 @@
+//! Decoding of application/x-www-form-urlencoded data, used for query strings and form bodies.
+
+use crate::{
+  MimeCharset, MimeTypeWithCharset, RequestBody, RequestBodyError, TiiError, TiiResult, UserError,
+};
+use std::any::{Any, TypeId};
+use std::error::Error;
+use std::str::FromStr;
+
+/// Decodes urlencoded key value pairs in order of appearance.
+/// A `+` is decoded as a space, percent encoded bytes are decoded with the given charset.
+///
+/// Returns None if the data contains a character that must be percent encoded, an invalid percent encoding,
+/// a key without a value or bytes that are not valid in the charset.
+fn decode_urlencoded(raw: &str, charset: &MimeCharset) -> Option<Vec<(String, String)>> {
+  split_urlencoded(raw)?
+    .into_iter()
+    .map(|(key, value)| Some((decode_component(key, charset)?, decode_component(value, charset)?)))
+    .collect()
+}
+
+/// Decodes the key value pairs of a utf-8 query string in order of appearance.
+/// Unlike form bodies the percent encoded bytes are decoded before `+` is replaced with a space,
+/// so `%2B` is decoded as a space as well. Query strings have always been decoded like this.
+///
+/// Returns None for the same malformed data as `decode_urlencoded`.
+pub(crate) fn decode_query(raw: &str) -> Option<Vec<(String, String)>> {
+  split_urlencoded(raw)?
+    .into_iter()
+    .map(|(key, value)| Some((decode_query_component(key)?, decode_query_component(value)?)))
+    .collect()
+}
+
+/// Validates urlencoded data and splits it into the raw key value pairs.
+fn split_urlencoded(raw: &str) -> Option<Vec<(&str, &str)>> {
+  if raw.is_empty() {
+    return Some(Vec::new());
+  }
+
+  let mut bytes = raw.bytes();
+  while let Some(n) = bytes.next() {
+    match n {
+      b'%' => {
+        if !bytes.next().is_some_and(|b| b.is_ascii_hexdigit())
+          || !bytes.next().is_some_and(|b| b.is_ascii_hexdigit())
+        {
+          return None;
+        }
+      }
+      b'!' | b'$' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b'-' | b'.' | b'/' | b':' | b';'
+      | b'@' | b'_' | b'~' | b'=' | b'&' => {}
+      other => {
+        if !other.is_ascii_alphanumeric() {
+          return None;
+        }
+      }
+    }
+  }
+
+  let mut result = Vec::new();
+  for pair in raw.split('&') {
+    let (key, value) = pair.split_once('=')?;
+    if value.contains('=') {
+      return None;
+    }
+
+    result.push((key, value));
+  }
+
+  Some(result)
+}
+
+fn decode_query_component(raw: &str) -> Option<String> {
+  Some(urlencoding::decode(raw).ok()?.replace('+', " "))
+}
+
+fn decode_component(raw: &str, charset: &MimeCharset) -> Option<String> {
+  let raw = raw.replace('+', " ");
+  let bytes = urlencoding::decode_binary(raw.as_bytes());
+  match charset {
+    MimeCharset::Utf8 | MimeCharset::Unspecified => String::from_utf8(bytes.into_owned()).ok(),
+    MimeCharset::UsAscii => {
+      if !bytes.is_ascii() {
+        return None;
+      }
+      String::from_utf8(bytes.into_owned()).ok()
+    }
+    MimeCharset::Iso88591 => Some(bytes.iter().copied().map(char::from).collect()),
+    MimeCharset::Other(_) => None,
+  }
+}
+
+/// A decoded application/x-www-form-urlencoded request body.
+///
+/// Read it with `RequestContext::read_urlencoded_form`
+/// or receive it in an entity endpoint by using `UrlEncodedForm::from_request_body` as the deserializer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlEncodedForm {
+  params: Vec<(String, String)>,
+}
+
+impl UrlEncodedForm {
+  /// Reads and decodes a request body with the given content type.
+  /// The body is decoded with the charset of the content type, utf-8 is assumed if there is none.
+  ///
+  /// The maximum request body size applies, larger bodies fail with `RequestBodyError::TooLarge`.
+  /// Malformed bodies and unsupported content types or charsets fail with `RequestBodyError::InvalidUrlEncodedForm`.
+  pub fn from_request_body(mime: &MimeTypeWithCharset, body: &RequestBody) -> TiiResult<Self> {
+    if mime.mime().as_str() != "application/x-www-form-urlencoded" {
+      return Err(
+        RequestBodyError::InvalidUrlEncodedForm(format!("unsupported content type {mime}")).into(),
+      );
+    }
+
+    if let MimeCharset::Other(charset) = mime.charset() {
+      return Err(
+        RequestBodyError::InvalidUrlEncodedForm(format!("unsupported charset {charset}")).into(),
+      );
+    }
+
+    let data = body.read_to_vec()?;
+    let Some(params) = std::str::from_utf8(&data)
+      .ok()
+      .and_then(|raw| decode_urlencoded(raw.trim_end(), mime.charset()))
+    else {
+      return Err(RequestBodyError::InvalidUrlEncodedForm("malformed body".to_string()).into());
+    };
+
+    Ok(Self { params })
+  }
+
+  /// Gets all parameters in order of appearance.
+  pub fn get_all_params(&self) -> &[(String, String)] {
+    &self.params
+  }
+
+  /// Gets the first parameter with the given key.
+  pub fn get_param(&self, key: impl AsRef<str>) -> Option<&str> {
+    let key = key.as_ref();
+    self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+  }
+
+  /// Gets all parameters in order of appearance that contain the given key. Returns empty vec if the key doesn’t exist.
+  pub fn get_params(&self, key: impl AsRef<str>) -> Vec<&str> {
+    let key = key.as_ref();
+    self.params.iter().filter(|(k, _)| k == key).map(|(_, v)| v.as_str()).collect()
+  }
+
+  /// Parses a parameter into a given type.
+  ///
+  /// This function only looks at the first parameter with the given name.
+  ///
+  /// # Returns
+  /// Ok(None) if no such parameter exists.
+  /// Ok(Some) if the parameter exists and can be parsed
+  /// Err(...) if the parameter exists but parsing fails.
+  pub fn parse_param<T: Any + FromStr<Err = E>, E: Error + Send + Sync + 'static>(
+    &self,
+    name: impl AsRef<str>,
+  ) -> TiiResult<Option<T>> {
+    let name = name.as_ref();
+    let Some(param) = self.get_param(name) else {
+      return Ok(None);
+    };
+
+    param.parse::<T>().map(Some).map_err(|e| {
+      TiiError::UserError(UserError::InvalidFormParameter(
+        name.to_string(),
+        TypeId::of::<T>(),
+        Box::new(e),
+      ))
+    })
+  }
+
+  /// Parses a parameter into a given type or returns the default value if no such parameter exists.
+  pub fn parse_param_or<T: Any + FromStr<Err = E>, E: Error + Send + Sync + 'static>(
+    &self,
+    name: impl AsRef<str>,
+    default_value: T,
+  ) -> TiiResult<T> {
+    Ok(self.parse_param(name)?.unwrap_or(default_value))
+  }
+
+  /// Parses a parameter into a given type or calls the fallback constructor function if no such parameter exists.
+  pub fn parse_param_or_else<T: Any + FromStr<Err = E>, E: Error + Send + Sync + 'static>(
+    &self,
+    name: impl AsRef<str>,
+    default_value: impl FnOnce() -> T,
+  ) -> TiiResult<T> {
+    Ok(self.parse_param(name)?.unwrap_or_else(default_value))
+  }
+
+  /// Returns the parameters.
+  pub fn into_params(self) -> Vec<(String, String)> {
+    self.params
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::http::urlencoded::decode_urlencoded;
+  use crate::MimeCharset;
+
+  #[test]
+  fn test_decode_urlencoded() {
+    let utf8 = &MimeCharset::Utf8;
+    assert_eq!(decode_urlencoded("", utf8), Some(vec![]));
+    assert_eq!(
+      decode_urlencoded("a=1&b=x+y%2B&c=", utf8),
+      Some(vec![
+        ("a".to_string(), "1".to_string()),
+        ("b".to_string(), "x y+".to_string()),
+        ("c".to_string(), "".to_string())
+      ])
+    );
+    assert_eq!(
+      decode_urlencoded("k=%E2%80%A2", utf8),
+      Some(vec![("k".to_string(), "•".to_string())])
+    );
+    assert_eq!(
+      decode_urlencoded("k=%E4", &MimeCharset::Iso88591),
+      Some(vec![("k".to_string(), "ä".to_string())])
+    );
+    assert_eq!(decode_urlencoded("k=%E4", utf8), None);
+    assert_eq!(decode_urlencoded("k=%E4", &MimeCharset::UsAscii), None);
+    assert_eq!(decode_urlencoded("a", utf8), None);
+    assert_eq!(decode_urlencoded("a=1&", utf8), None);
+    assert_eq!(decode_urlencoded("a=1=2", utf8), None);
+    assert_eq!(decode_urlencoded("a=%2", utf8), None);
+    assert_eq!(decode_urlencoded("a=b c", utf8), None);
+  }
+}
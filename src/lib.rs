@@ -18,6 +18,9 @@ pub use stream::*;
 mod tii_router;
 pub use tii_router::*;
 
+mod tii_cors;
+pub use tii_cors::*;
+
 mod tii_router_builder;
 pub use tii_router_builder::*;
 mod tii_server;
@@ -33,6 +36,9 @@ pub use tls_stream::{TlsCapableStream, TlsStream};
 #[cfg(feature = "extras")]
 pub mod extras;
 
+/// In-process test client that drives a server without opening any sockets.
+pub mod testing;
+
 //Private modules
 mod default_functions;
 mod functional_traits;
@@ -0,0 +1,699 @@
+//! In-process client for testing applications built with tii.
+//!
+//! The `TestClient` drives a `Server` through an in-memory connection.
+//! No sockets are opened, requests and responses are exchanged as raw http over the connection
+//! so every part of the server, including filters, keep-alive and web socket upgrades, is exercised.
+//!
+//! ```rust
+//! use tii::testing::TestClient;
+//! use tii::{MimeType, Response, ServerBuilder};
+//!
+//! let server = ServerBuilder::default()
+//!   .router(|rt| rt.route_get("/hello", |_: &tii::RequestContext| Response::ok("Hello", MimeType::TextPlain)))
+//!   .unwrap()
+//!   .build();
+//!
+//! let mut client = TestClient::new(server);
+//! let response = client.get("/hello").send().unwrap();
+//! assert_eq!(response.get_status_code_number(), 200);
+//! assert_eq!(response.get_body_text(), Some("Hello"));
+//! ```
+
+use crate::http::{invalid_response, read_line, ResponseHead};
+use crate::util::{unwrap_poison, unwrap_some};
+use crate::websocket::frame::{Frame, Opcode};
+use crate::{
+  ConnectionStream, ConnectionStreamRead, ContentCoding, HttpHeader, HttpHeaderName, HttpMethod,
+  IntoConnectionStream, RequestHeadParsingError, Server, StatusCode, TiiError, TiiResult,
+  WebsocketMessage,
+};
+use base64::Engine;
+use libflate::{gzip, zlib};
+use sha1::{Digest, Sha1};
+use std::collections::VecDeque;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// One direction of an in-memory connection.
+#[derive(Debug, Default)]
+struct Pipe {
+  state: Mutex<(VecDeque<u8>, bool)>,
+  condition: Condvar,
+}
+
+#[derive(Debug)]
+struct PipeReader(Arc<Pipe>, Option<Duration>);
+
+impl Read for PipeReader {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let deadline = self.1.map(|timeout| Instant::now() + timeout);
+    let mut state = unwrap_poison(self.0.state.lock())?;
+    loop {
+      if !state.0.is_empty() || buf.is_empty() {
+        let count = buf.len().min(state.0.len());
+        for (dst, src) in buf.iter_mut().zip(state.0.drain(..count)) {
+          *dst = src;
+        }
+        return Ok(count);
+      }
+
+      if state.1 {
+        return Ok(0);
+      }
+
+      state = match deadline {
+        None => unwrap_poison(self.0.condition.wait(state))?,
+        Some(deadline) => {
+          let now = Instant::now();
+          if now >= deadline {
+            return Err(io::Error::from(ErrorKind::TimedOut));
+          }
+          unwrap_poison(self.0.condition.wait_timeout(state, deadline - now))?.0
+        }
+      };
+    }
+  }
+}
+
+#[derive(Debug)]
+struct PipeWriter(Arc<Pipe>);
+
+impl Write for PipeWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let mut state = unwrap_poison(self.0.state.lock())?;
+    if state.1 {
+      return Err(io::Error::from(ErrorKind::BrokenPipe));
+    }
+    state.0.extend(buf);
+    self.0.condition.notify_all();
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Drop for PipeWriter {
+  fn drop(&mut self) {
+    if let Ok(mut state) = self.0.state.lock() {
+      state.1 = true;
+    }
+    self.0.condition.notify_all();
+  }
+}
+
+/// A connection to the server that is handled by a thread.
+#[derive(Debug)]
+struct TestConnection {
+  stream: Box<dyn ConnectionStream>,
+  handle: JoinHandle<TiiResult<()>>,
+}
+
+impl TestConnection {
+  fn open(server: &Arc<Server>, timeout: Option<Duration>) -> TiiResult<Self> {
+    let to_server = Arc::new(Pipe::default());
+    let to_client = Arc::new(Pipe::default());
+
+    let server_stream = (
+      Box::new(PipeReader(to_server.clone(), None)) as Box<dyn Read + Send>,
+      Box::new(PipeWriter(to_client.clone())) as Box<dyn Write + Send>,
+    )
+      .into_connection_stream();
+
+    let stream = (
+      Box::new(PipeReader(to_client, timeout)) as Box<dyn Read + Send>,
+      Box::new(PipeWriter(to_server)) as Box<dyn Write + Send>,
+    )
+      .into_connection_stream();
+
+    let server = server.clone();
+    let handle = std::thread::Builder::new()
+      .name("tii-test-connection".to_string())
+      .spawn(move || server.handle_connection(server_stream))?;
+
+    Ok(Self { stream, handle })
+  }
+
+  /// Closes the connection and waits for the server to finish handling it.
+  fn close(self) -> TiiResult<()> {
+    drop(self.stream);
+    match self.handle.join() {
+      Ok(result) => result,
+      Err(_) => Err(TiiError::new_io(ErrorKind::Other, "server thread panicked")),
+    }
+  }
+}
+
+/// Client that sends requests to a `Server` without opening any sockets.
+///
+/// Requests are sent over a single in-memory connection that is kept alive,
+/// a new connection is opened once the server closes the previous one.
+/// The server handles each connection in its own thread.
+#[derive(Debug)]
+pub struct TestClient {
+  server: Arc<Server>,
+  timeout: Option<Duration>,
+  connection: Option<TestConnection>,
+}
+
+impl TestClient {
+  /// Creates a client for the server. Reading a response times out after 10 seconds.
+  pub fn new(server: impl Into<Arc<Server>>) -> Self {
+    Self { server: server.into(), timeout: Some(Duration::from_secs(10)), connection: None }
+  }
+
+  /// Sets how long the client waits for the server to send data before failing with `TimedOut`.
+  /// None means the client waits forever.
+  pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Creates a request with the given method for the path. The path must not contain a query string.
+  pub fn request(&mut self, method: HttpMethod, path: impl ToString) -> TestRequest<'_> {
+    TestRequest {
+      client: self,
+      method,
+      path: path.to_string(),
+      query: Vec::new(),
+      headers: Vec::new(),
+      body: None,
+      chunked: false,
+      gzip: false,
+    }
+  }
+
+  /// Creates a GET request.
+  pub fn get(&mut self, path: impl ToString) -> TestRequest<'_> {
+    self.request(HttpMethod::Get, path)
+  }
+
+  /// Creates a HEAD request.
+  pub fn head(&mut self, path: impl ToString) -> TestRequest<'_> {
+    self.request(HttpMethod::Head, path)
+  }
+
+  /// Creates a POST request.
+  pub fn post(&mut self, path: impl ToString) -> TestRequest<'_> {
+    self.request(HttpMethod::Post, path)
+  }
+
+  /// Creates a PUT request.
+  pub fn put(&mut self, path: impl ToString) -> TestRequest<'_> {
+    self.request(HttpMethod::Put, path)
+  }
+
+  /// Creates a PATCH request.
+  pub fn patch(&mut self, path: impl ToString) -> TestRequest<'_> {
+    self.request(HttpMethod::Patch, path)
+  }
+
+  /// Creates a DELETE request.
+  pub fn delete(&mut self, path: impl ToString) -> TestRequest<'_> {
+    self.request(HttpMethod::Delete, path)
+  }
+
+  /// Closes the current connection, if there is one, and waits for the server to finish handling it.
+  /// Returns the result of `Server::handle_connection` for the connection.
+  pub fn close(&mut self) -> TiiResult<()> {
+    match self.connection.take() {
+      Some(connection) => connection.close(),
+      None => Ok(()),
+    }
+  }
+
+  fn connection(&mut self) -> TiiResult<&TestConnection> {
+    if self.connection.as_ref().is_some_and(|connection| connection.handle.is_finished()) {
+      //The server closed the connection without us noticing, for example after a timeout.
+      let _ = self.close();
+    }
+
+    if self.connection.is_none() {
+      self.connection = Some(TestConnection::open(&self.server, self.timeout)?);
+    }
+
+    Ok(unwrap_some(self.connection.as_ref()))
+  }
+}
+
+impl Drop for TestClient {
+  fn drop(&mut self) {
+    // Dropping the stream is enough for the server thread to finish, we don't wait for it.
+    self.connection.take();
+  }
+}
+
+/// A request that is built and then sent by a `TestClient`.
+#[derive(Debug)]
+pub struct TestRequest<'a> {
+  client: &'a mut TestClient,
+  method: HttpMethod,
+  path: String,
+  query: Vec<(String, String)>,
+  headers: Vec<HttpHeader>,
+  body: Option<Vec<u8>>,
+  chunked: bool,
+  gzip: bool,
+}
+
+impl TestRequest<'_> {
+  /// Adds a query parameter. Key and value are percent encoded.
+  pub fn with_query_param(mut self, key: impl ToString, value: impl ToString) -> Self {
+    self.query.push((key.to_string(), value.to_string()));
+    self
+  }
+
+  /// Adds a request header.
+  pub fn with_header(mut self, name: impl AsRef<str>, value: impl ToString) -> Self {
+    self.headers.push(HttpHeader::new(name, value));
+    self
+  }
+
+  /// Sets the request body.
+  pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+    self.body = Some(body.into());
+    self
+  }
+
+  /// Sends the body with chunked transfer encoding instead of a `Content-Length` header.
+  pub fn with_chunked(mut self, chunked: bool) -> Self {
+    self.chunked = chunked;
+    self
+  }
+
+  /// Compresses the body with gzip and sets the `Content-Encoding` header.
+  pub fn with_gzip(mut self, gzip: bool) -> Self {
+    self.gzip = gzip;
+    self
+  }
+
+  fn has_header(&self, name: &HttpHeaderName) -> bool {
+    self.headers.iter().any(|header| &header.name == name)
+  }
+
+  fn to_bytes(&self) -> TiiResult<Vec<u8>> {
+    let mut data = Vec::new();
+    data.extend_from_slice(self.method.as_str().as_bytes());
+    data.push(b' ');
+    data.extend_from_slice(self.path.as_bytes());
+    for (index, (key, value)) in self.query.iter().enumerate() {
+      data.push(if index == 0 { b'?' } else { b'&' });
+      data.extend_from_slice(urlencoding::encode(key).as_bytes());
+      data.push(b'=');
+      data.extend_from_slice(urlencoding::encode(value).as_bytes());
+    }
+    data.extend_from_slice(b" HTTP/1.1\r\n");
+
+    for header in &self.headers {
+      data.extend_from_slice(format!("{}: {}\r\n", header.name, header.value).as_bytes());
+    }
+
+    if !self.has_header(&HttpHeaderName::Connection) {
+      data.extend_from_slice(b"Connection: keep-alive\r\n");
+    }
+
+    let Some(body) = self.body.as_ref() else {
+      data.extend_from_slice(b"\r\n");
+      return Ok(data);
+    };
+
+    let body = if self.gzip {
+      data.extend_from_slice(b"Content-Encoding: gzip\r\n");
+      let mut encoder = crate::util::new_gzip_encoder(Vec::new())?;
+      encoder.write_all(body)?;
+      encoder.finish().into_result()?
+    } else {
+      body.clone()
+    };
+
+    if !self.chunked {
+      data.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+      data.extend_from_slice(&body);
+      return Ok(data);
+    }
+
+    data.extend_from_slice(b"Transfer-Encoding: chunked\r\n\r\n");
+    if !body.is_empty() {
+      data.extend_from_slice(format!("{:X}\r\n", body.len()).as_bytes());
+      data.extend_from_slice(&body);
+      data.extend_from_slice(b"\r\n");
+    }
+    data.extend_from_slice(b"0\r\n\r\n");
+    Ok(data)
+  }
+
+  /// Sends the request and reads the response.
+  ///
+  /// If the server closes the connection without sending a complete response
+  /// then the error returned by the server is returned.
+  pub fn send(self) -> TiiResult<TestResponse> {
+    let data = self.to_bytes()?;
+    let head = self.method == HttpMethod::Head;
+    let client = self.client;
+
+    let connection = client.connection()?;
+    connection.stream.as_stream_write().write_all(&data)?;
+    connection.stream.as_stream_write().flush()?;
+
+    let response = match read_response(connection.stream.as_ref(), head) {
+      Ok(response) => response,
+      Err(err) => {
+        return match client.close() {
+          Err(server_err) => Err(server_err),
+          Ok(()) => Err(err),
+        }
+      }
+    };
+
+    if response.closes_connection() {
+      let _ = client.close();
+    }
+
+    Ok(response)
+  }
+
+  /// Sends the request as a web socket handshake and returns the web socket if the server switches protocols.
+  /// Fails with `InvalidData` if the server answers with any other status.
+  ///
+  /// The connection is taken over by the web socket, the next request of the client opens a new connection.
+  pub fn websocket(mut self) -> TiiResult<TestWebSocket> {
+    let key = base64::prelude::BASE64_STANDARD.encode(crate::util::next_id().to_be_bytes());
+    for (name, value) in [
+      (HttpHeaderName::Connection, "Upgrade"),
+      (HttpHeaderName::Upgrade, "websocket"),
+      (HttpHeaderName::from("Sec-WebSocket-Key"), key.as_str()),
+      (HttpHeaderName::from("Sec-WebSocket-Version"), "13"),
+    ] {
+      if !self.has_header(&name) {
+        self.headers.push(HttpHeader::new(name, value));
+      }
+    }
+
+    let data = self.to_bytes()?;
+    let client = self.client;
+    let connection = client.connection()?;
+    connection.stream.as_stream_write().write_all(&data)?;
+    connection.stream.as_stream_write().flush()?;
+    let response = read_response(connection.stream.as_ref(), false);
+    let connection = unwrap_some(client.connection.take());
+    let response = match response {
+      Ok(response) => response,
+      Err(err) => return Err(connection.close().err().unwrap_or(err)),
+    };
+
+    if response.get_status_code_number() != 101 {
+      if !response.closes_connection() {
+        client.connection = Some(connection);
+      }
+      return Err(TiiError::new_io(
+        ErrorKind::InvalidData,
+        format!("web socket handshake failed with status {}", response.get_status_code_number()),
+      ));
+    }
+
+    let accept = base64::prelude::BASE64_STANDARD.encode(
+      Sha1::new()
+        .chain_update(&key)
+        .chain_update("258EAFA5-E914-47DA-95CA-C5AB0DC85B11")
+        .finalize(),
+    );
+    if response.get_header("Sec-WebSocket-Accept") != Some(accept.as_str()) {
+      return Err(TiiError::new_io(ErrorKind::InvalidData, "invalid Sec-WebSocket-Accept header"));
+    }
+
+    Ok(TestWebSocket { connection: Some(connection), response, closed: false })
+  }
+}
+
+/// A response received by a `TestClient`.
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+  status_code: StatusCode,
+  headers: Vec<HttpHeader>,
+  raw_body: Vec<u8>,
+  body: Vec<u8>,
+  keep_alive: bool,
+}
+
+impl TestResponse {
+  /// Returns the status code.
+  pub fn get_status_code(&self) -> &StatusCode {
+    &self.status_code
+  }
+
+  /// Returns the numeric status code.
+  pub fn get_status_code_number(&self) -> u16 {
+    self.status_code.code()
+  }
+
+  /// Returns the first value of the header or None if the response has no such header.
+  /// Header names are compared case-insensitively.
+  pub fn get_header(&self, name: impl AsRef<str>) -> Option<&str> {
+    self.get_headers(name).into_iter().next()
+  }
+
+  /// Returns all values of the header in order of appearance.
+  /// Header names are compared case-insensitively.
+  pub fn get_headers(&self, name: impl AsRef<str>) -> Vec<&str> {
+    let name = name.as_ref();
+    self
+      .headers
+      .iter()
+      .filter(|header| header.name.to_str().eq_ignore_ascii_case(name))
+      .map(|header| header.value.as_str())
+      .collect()
+  }
+
+  /// Returns all headers in order of appearance.
+  pub fn get_all_headers(&self) -> &[HttpHeader] {
+    &self.headers
+  }
+
+  /// Returns the body after the transfer encoding and content encoding has been removed.
+  pub fn get_body(&self) -> &[u8] {
+    &self.body
+  }
+
+  /// Returns the body as text or None if it is not valid utf-8.
+  pub fn get_body_text(&self) -> Option<&str> {
+    std::str::from_utf8(&self.body).ok()
+  }
+
+  /// Returns the body as it was sent, after the transfer encoding but not the content encoding has been removed.
+  pub fn get_raw_body(&self) -> &[u8] {
+    &self.raw_body
+  }
+
+  /// Returns the body after the transfer encoding and content encoding has been removed.
+  pub fn into_body(self) -> Vec<u8> {
+    self.body
+  }
+
+  fn closes_connection(&self) -> bool {
+    !self.keep_alive
+  }
+}
+
+fn read_response(stream: &dyn ConnectionStream, head: bool) -> TiiResult<TestResponse> {
+  let response_head = ResponseHead::read(stream)?;
+  let keep_alive = response_head.keeps_alive();
+  if !response_head.has_body(head) {
+    let ResponseHead { status_code, headers, .. } = response_head;
+    return Ok(TestResponse {
+      status_code,
+      headers,
+      raw_body: Vec::new(),
+      body: Vec::new(),
+      keep_alive,
+    });
+  }
+
+  let raw_body = if response_head.is_chunked() {
+    read_chunked(stream)?
+  } else if let Some(length) = response_head.content_length()? {
+    let length = usize::try_from(length).map_err(|_| invalid_response("malformed length"))?;
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body)?;
+    body
+  } else {
+    let mut body = Vec::new();
+    let mut buffer = [0u8; 0x1000];
+    loop {
+      let count = ConnectionStreamRead::read(stream.as_stream_read(), &mut buffer)?;
+      if count == 0 {
+        break;
+      }
+      body.extend_from_slice(unwrap_some(buffer.get(..count)));
+    }
+    body
+  };
+
+  let mut body = raw_body.clone();
+  for coding in response_head.content_codings()?.iter().rev() {
+    let mut decoded = Vec::new();
+    match coding {
+      ContentCoding::Gzip => gzip::Decoder::new(body.as_slice())?.read_to_end(&mut decoded)?,
+      ContentCoding::Deflate => zlib::Decoder::new(body.as_slice())?.read_to_end(&mut decoded)?,
+    };
+    body = decoded;
+  }
+
+  let ResponseHead { status_code, headers, .. } = response_head;
+  Ok(TestResponse { status_code, headers, raw_body, body, keep_alive })
+}
+
+fn read_chunked(stream: &dyn ConnectionStream) -> TiiResult<Vec<u8>> {
+  let mut body = Vec::new();
+  loop {
+    let line = read_line(stream)?;
+    let size = line.split(';').next().unwrap_or_default().trim();
+    let size = usize::from_str_radix(size, 16).map_err(|_| invalid_response("malformed chunk"))?;
+    if size == 0 {
+      //Trailer fields are ignored.
+      while !read_line(stream)?.is_empty() {}
+      return Ok(body);
+    }
+
+    let start = body.len();
+    body.resize(start + size, 0);
+    stream.read_exact(unwrap_some(body.get_mut(start..)))?;
+    if !read_line(stream)?.is_empty() {
+      return Err(invalid_response("malformed chunk"));
+    }
+  }
+}
+
+/// Client side of a web socket connection to the server, created by `TestRequest::websocket`.
+///
+/// Frames sent by the client are masked as required for clients.
+/// Dropping the web socket closes the connection without sending a close frame.
+#[derive(Debug)]
+pub struct TestWebSocket {
+  connection: Option<TestConnection>,
+  response: TestResponse,
+  closed: bool,
+}
+
+impl TestWebSocket {
+  /// Returns the response of the server to the handshake.
+  pub fn get_handshake_response(&self) -> &TestResponse {
+    &self.response
+  }
+
+  fn stream(&self) -> &dyn ConnectionStream {
+    unwrap_some(self.connection.as_ref()).stream.as_ref()
+  }
+
+  fn write_frame(&self, opcode: Opcode, mut payload: Vec<u8>) -> TiiResult<()> {
+    let masking_key = (crate::util::next_id() as u32).to_be_bytes();
+    for (index, byte) in payload.iter_mut().enumerate() {
+      *byte ^= unwrap_some(masking_key.get(index % 4));
+    }
+
+    let mut frame = Frame::new(opcode, payload);
+    frame.mask = true;
+    frame.masking_key = masking_key;
+    frame.write_to(self.stream().as_stream_write())
+  }
+
+  /// Sends a message to the server.
+  pub fn send(&self, message: WebsocketMessage) -> TiiResult<()> {
+    match message {
+      WebsocketMessage::Text(text) => self.text(text),
+      WebsocketMessage::Binary(binary) => self.binary(binary),
+      WebsocketMessage::Ping => self.ping(),
+      WebsocketMessage::Pong => self.pong(),
+      WebsocketMessage::Close(reason) => {
+        let payload = reason
+          .map(|(code, reason)| [code.to_be_bytes().as_slice(), reason.as_bytes()].concat())
+          .unwrap_or_default();
+        self.write_frame(Opcode::Close, payload)
+      }
+    }
+  }
+
+  /// Sends a text message to the server.
+  pub fn text(&self, message: impl ToString) -> TiiResult<()> {
+    self.write_frame(Opcode::Text, message.to_string().into_bytes())
+  }
+
+  /// Sends a binary message to the server.
+  pub fn binary(&self, message: impl Into<Vec<u8>>) -> TiiResult<()> {
+    self.write_frame(Opcode::Binary, message.into())
+  }
+
+  /// Sends a ping to the server.
+  pub fn ping(&self) -> TiiResult<()> {
+    self.write_frame(Opcode::Ping, Vec::new())
+  }
+
+  /// Sends a pong to the server.
+  pub fn pong(&self) -> TiiResult<()> {
+    self.write_frame(Opcode::Pong, Vec::new())
+  }
+
+  /// Receives the next complete message. Pings, pongs and the close frame of the server are returned as messages.
+  /// Ok(None) indicates that the web socket or the connection is closed.
+  pub fn read_message(&mut self) -> TiiResult<Option<WebsocketMessage>> {
+    if self.closed {
+      return Ok(None);
+    }
+
+    let mut frames: Vec<Frame> = Vec::new();
+    while frames.last().is_none_or(|frame| !frame.fin) {
+      let stream = self.stream().as_stream_read();
+      if frames.is_empty() && !stream.ensure_readable()? {
+        //The server closed the connection without sending a close frame.
+        self.closed = true;
+        return Ok(None);
+      }
+
+      let mut frame = Frame::header_from_stream(stream)?;
+      frame.read_payload(stream)?;
+      match frame.opcode {
+        Opcode::Ping => return Ok(Some(WebsocketMessage::Ping)),
+        Opcode::Pong => return Ok(Some(WebsocketMessage::Pong)),
+        Opcode::Close => {
+          self.closed = true;
+          let reason = frame.payload.split_first_chunk::<2>().map(|(code, reason)| {
+            (u16::from_be_bytes(*code), String::from_utf8_lossy(reason).to_string())
+          });
+          return Ok(Some(WebsocketMessage::Close(reason)));
+        }
+        _ => frames.push(frame),
+      }
+    }
+
+    let opcode = unwrap_some(frames.first()).opcode;
+    let payload = mem::take(&mut frames).into_iter().flat_map(|frame| frame.payload).collect();
+    match opcode {
+      Opcode::Text => String::from_utf8(payload)
+        .map(|text| Some(WebsocketMessage::Text(text)))
+        .map_err(|e| RequestHeadParsingError::WebSocketTextMessageIsNotUtf8(e.into_bytes()).into()),
+      Opcode::Binary => Ok(Some(WebsocketMessage::Binary(payload))),
+      _ => Err(RequestHeadParsingError::UnexpectedWebSocketOpcode.into()),
+    }
+  }
+
+  /// Sends a close frame, waits for the server to close the connection and returns the result of
+  /// `Server::handle_connection` for the connection. Messages that the server sent in the meantime are discarded.
+  pub fn close(mut self) -> TiiResult<()> {
+    let Some(connection) = self.connection.take() else {
+      return Ok(());
+    };
+
+    if !self.closed {
+      self.connection = Some(connection);
+      self.write_frame(Opcode::Close, Vec::new())?;
+      while self.read_message()?.is_some() {}
+      return unwrap_some(self.connection.take()).close();
+    }
+
+    connection.close()
+  }
+}
@@ -1,6 +1,7 @@
 //! Provides the core Tii app functionality.
 
-use crate::{Response, TypeSystemBuilder};
+use crate::http::RequestParsingOptions;
+use crate::{HttpVersion, Response, TypeSystemBuilder};
 
 use std::sync::Arc;
 use std::time::Duration;
@@ -11,10 +12,14 @@ pub struct ServerBuilder {
   routers: Vec<Box<dyn Router>>,
   error_handler: ErrorHandler,
   not_found_handler: NotFoundHandler,
+  parse_error_handler: ParseErrorHandler,
   max_head_buffer_size: usize,
+  parsing_options: RequestParsingOptions,
+  max_request_body_size: Option<u64>,
   connection_timeout: Option<Duration>,
   read_timeout: Option<Duration>,
   keep_alive_timeout: Option<Duration>,
+  max_requests_per_connection: Option<u64>,
   request_body_io_timeout: Option<Duration>,
   write_timeout: Option<Duration>,
   continue_handler: ContinueHandler,
@@ -22,6 +27,7 @@ pub struct ServerBuilder {
 
 use crate::default_functions::{
   default_continue_handler, default_error_handler, default_fallback_not_found_handler,
+  default_parse_error_handler,
 };
 pub use crate::functional_traits::*;
 use crate::tii_error::{TiiError, TiiResult, UserError};
@@ -45,6 +51,11 @@ pub type NotRouteableHandler = fn(&mut RequestContext, &[Routeable]) -> TiiResul
 /// Fallback handler if no router handled the request.
 pub type NotFoundHandler = fn(&mut RequestContext) -> TiiResult<Response>;
 
+/// Handler for requests whose head could not be read or parsed, there is no RequestContext for such requests.
+/// The returned response is sent to the client before the connection is closed.
+/// Returning None closes the connection without a response.
+pub type ParseErrorHandler = fn(&TiiError) -> TiiResult<Option<Response>>;
+
 impl Default for ServerBuilder {
   /// Initialises a new Tii app.
   fn default() -> Self {
@@ -53,9 +64,13 @@ impl Default for ServerBuilder {
       routers: Vec::new(),
       error_handler: default_error_handler,
       not_found_handler: default_fallback_not_found_handler,
+      parse_error_handler: default_parse_error_handler,
       connection_timeout: None,
       max_head_buffer_size: 8192,
+      parsing_options: RequestParsingOptions::default(),
+      max_request_body_size: None,
       keep_alive_timeout: None,
+      max_requests_per_connection: None,
       read_timeout: None,
       request_body_io_timeout: None,
       write_timeout: None,
@@ -86,10 +101,14 @@ impl ServerBuilder {
       self.routers,
       self.error_handler,
       self.not_found_handler,
+      self.parse_error_handler,
       self.max_head_buffer_size,
+      self.parsing_options,
+      self.max_request_body_size,
       self.connection_timeout,
       self.read_timeout,
       self.keep_alive_timeout,
+      self.max_requests_per_connection,
       self.request_body_io_timeout,
       self.write_timeout,
       self.continue_handler,
@@ -141,6 +160,18 @@ impl ServerBuilder {
     Ok(self)
   }
 
+  /// Sets the handler for requests that could not be parsed.
+  ///
+  /// The default handler responds with 400 Bad Request for malformed requests, 414 URI Too Long for overlong request lines,
+  /// 431 Request Header Fields Too Large for overlong header lines, 505 HTTP Version Not Supported for unsupported or disabled
+  /// http versions, 501 Not Implemented for unknown transfer encodings, 415 Unsupported Media Type for unknown content encodings
+  /// and 408 Request Timeout if the client was too slow to send the request head.
+  /// The connection is closed without a response for other io errors.
+  pub fn with_parse_error_handler(mut self, handler: ParseErrorHandler) -> TiiResult<Self> {
+    self.parse_error_handler = handler;
+    Ok(self)
+  }
+
   /// Sets the maximum head buffer size. Default value is 8192.
   ///
   /// This affects the maximum permitted length of a header name + value pair as well
@@ -159,6 +190,47 @@ impl ServerBuilder {
     Ok(self)
   }
 
+  /// Enables the strict parsing mode. Default value is false.
+  ///
+  /// In strict mode tii rejects request heads that RFC 9112 does not allow instead of guessing what the client meant.
+  /// This prevents request smuggling when tii runs behind a proxy that may interpret such requests differently.
+  /// The following requests are rejected:
+  /// - Requests with both a Content-Length and a Transfer-Encoding header.
+  /// - Requests with Content-Length headers that have different values.
+  /// - Header lines that continue the previous line (obs-fold).
+  /// - Whitespace between a header name and the colon.
+  /// - Lines that end with LF instead of CRLF or that contain a CR elsewhere.
+  /// - Methods and header names that contain characters not allowed in a token.
+  ///
+  /// Each rejection causes a distinct `RequestHeadParsingError`.
+  pub fn with_strict_parsing(mut self, strict: bool) -> TiiResult<Self> {
+    self.parsing_options.strict = strict;
+    Ok(self)
+  }
+
+  /// Sets the oldest http version tii accepts. Default value is `HttpVersion::Http09` = all versions are accepted.
+  ///
+  /// Requests that use an older version are rejected with `RequestHeadParsingError::HttpVersionDisabled`.
+  /// For example `HttpVersion::Http11` disables HTTP/0.9 and HTTP/1.0 entirely.
+  pub fn with_min_http_version(mut self, version: HttpVersion) -> TiiResult<Self> {
+    self.parsing_options.min_version = version;
+    Ok(self)
+  }
+
+  /// Sets the maximum size of request bodies in bytes. Default value is None = no limit.
+  ///
+  /// For compressed request bodies this limits the size of the decompressed data.
+  /// Routes can override this value, see `RouteBuilder::max_request_body_size`.
+  ///
+  /// If the size of the request body is known before it is read, then the request is rejected
+  /// before the endpoint is called. Otherwise, reading the request body will fail once
+  /// the limit is exceeded. Both cases cause the error handler to be called with `RequestBodyError::TooLarge`,
+  /// the default error handler responds with 413 Content Too Large.
+  pub fn with_max_request_body_size(mut self, size: Option<u64>) -> TiiResult<Self> {
+    self.max_request_body_size = size;
+    Ok(self)
+  }
+
   /// Sets the connection timeout,
   /// the amount of time before tii will close the connection if it sends no data to tii.
   /// If this value is not set then Tii will use the read_timeout for this purpose
@@ -190,11 +262,21 @@ impl ServerBuilder {
   /// that keep-alives are not supported by setting "Connection: Close" on every HTTP1/1 response.
   ///
   /// Otherwise, tii will wait this amount of time for the client to send at least 1 byte of the next request.
+  /// The timeout is hinted to the client in whole seconds with the "Keep-Alive" response header.
   pub fn with_keep_alive_timeout(mut self, timeout: Option<Duration>) -> TiiResult<Self> {
     self.keep_alive_timeout = timeout;
     Ok(self)
   }
 
+  /// Sets the maximum number of requests tii serves on a single connection.
+  /// The response to the last request signals "Connection: Close" and the connection is closed afterward.
+  /// Responses on persistent connections carry the remaining number of requests in the "Keep-Alive" header.
+  /// Default is None = Unlimited.
+  pub fn with_max_requests_per_connection(mut self, max: Option<u64>) -> TiiResult<Self> {
+    self.max_requests_per_connection = max;
+    Ok(self)
+  }
+
   /// Sets the amount of time tii will wait for the client to produce at least a single byte of a request
   /// body before returning the `TimedOut` error.
   /// A value of None will cause the read timeout to be used.
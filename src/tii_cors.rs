@@ -0,0 +1,211 @@
+//! Cross-Origin Resource Sharing (CORS) support for routers.
+//! See [the fetch standard](https://fetch.spec.whatwg.org/#http-cors-protocol) for details on the protocol.
+
+use crate::{HttpHeaderName, HttpMethod, RequestContext, Response, ResponseContext, TiiResult};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// A CORS policy for a router. Set it with `RouterBuilder::with_cors_policy`.
+///
+/// The router answers CORS preflight requests for every path that has a route and adds the
+/// `Access-Control-Allow-Origin` family of headers to all responses to requests from allowed origins.
+///
+/// A new policy allows nothing, every origin, method and header has to be allowed explicitly.
+#[derive(Debug, Clone, Default)]
+pub struct CorsPolicy {
+  allow_any_origin: bool,
+  allowed_origins: HashSet<String>,
+  allowed_methods: Option<Vec<HttpMethod>>,
+  allow_any_header: bool,
+  allowed_headers: Vec<HttpHeaderName>,
+  allow_credentials: bool,
+  max_age: Option<Duration>,
+  exposed_headers: Vec<HttpHeaderName>,
+}
+
+impl CorsPolicy {
+  /// Creates a new policy that allows nothing.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Allow requests from any origin.
+  pub fn with_any_origin(mut self) -> Self {
+    self.allow_any_origin = true;
+    self
+  }
+
+  /// Allow requests from the given origin, for example `https://example.com`.
+  pub fn with_allowed_origin(mut self, origin: impl ToString) -> Self {
+    self.allowed_origins.insert(origin.to_string());
+    self
+  }
+
+  /// Allow the given method in cross-origin requests.
+  /// If no method is set then the methods of the routes of the requested path are allowed.
+  pub fn with_allowed_method(mut self, method: HttpMethod) -> Self {
+    let methods = self.allowed_methods.get_or_insert_with(Vec::new);
+    if !methods.contains(&method) {
+      methods.push(method);
+    }
+    self
+  }
+
+  /// Allow any request header in cross-origin requests.
+  pub fn with_any_header(mut self) -> Self {
+    self.allow_any_header = true;
+    self
+  }
+
+  /// Allow the given request header in cross-origin requests.
+  pub fn with_allowed_header(mut self, header: impl AsRef<str>) -> Self {
+    let header = HttpHeaderName::from(header.as_ref());
+    if !self.allowed_headers.contains(&header) {
+      self.allowed_headers.push(header);
+    }
+    self
+  }
+
+  /// Allow cross-origin requests to include credentials such as cookies.
+  pub fn with_credentials(mut self, allow: bool) -> Self {
+    self.allow_credentials = allow;
+    self
+  }
+
+  /// How long browsers may cache the result of a preflight request. Sub-second precision is ignored.
+  pub fn with_max_age(mut self, max_age: Duration) -> Self {
+    self.max_age = Some(max_age);
+    self
+  }
+
+  /// Allow scripts of other origins to read the given response header.
+  pub fn with_exposed_header(mut self, header: impl AsRef<str>) -> Self {
+    let header = HttpHeaderName::from(header.as_ref());
+    if !self.exposed_headers.contains(&header) {
+      self.exposed_headers.push(header);
+    }
+    self
+  }
+
+  /// Returns true if the origin is allowed by this policy.
+  pub fn is_origin_allowed(&self, origin: &str) -> bool {
+    self.allow_any_origin || self.allowed_origins.contains(origin)
+  }
+
+  /// Returns true if the request is a CORS preflight request.
+  pub fn is_preflight(request: &RequestContext) -> bool {
+    request.get_method() == HttpMethod::Options
+      && request.get_header(&HttpHeaderName::Origin).is_some()
+      && request.get_header(&HttpHeaderName::AccessControlRequestMethod).is_some()
+  }
+
+  /// Creates the response to a preflight request.
+  /// `route_methods` contains the methods of the routes of the requested path.
+  /// If the preflight is not allowed by this policy then the response contains no CORS headers,
+  /// which causes the browser to reject the actual request.
+  pub(crate) fn preflight(
+    &self,
+    request: &RequestContext,
+    route_methods: &[HttpMethod],
+  ) -> TiiResult<Response> {
+    let mut response = Response::no_content();
+    response.add_header(
+      HttpHeaderName::Vary,
+      "Access-Control-Request-Method, Access-Control-Request-Headers",
+    )?;
+
+    if !request.get_header(&HttpHeaderName::Origin).is_some_and(|o| self.is_origin_allowed(o)) {
+      return Ok(response);
+    }
+
+    let allowed_methods = self.allowed_methods.as_deref().unwrap_or(route_methods);
+    let requested_method = request
+      .get_header(&HttpHeaderName::AccessControlRequestMethod)
+      .map(HttpMethod::from)
+      .unwrap_or(HttpMethod::Options);
+    if !allowed_methods.contains(&requested_method) {
+      return Ok(response);
+    }
+
+    let requested_headers = request
+      .get_headers(&HttpHeaderName::AccessControlRequestHeaders)
+      .into_iter()
+      .flat_map(|value| value.split(','))
+      .map(str::trim)
+      .filter(|name| !name.is_empty())
+      .collect::<Vec<_>>();
+
+    if !self.allow_any_header
+      && !requested_headers.iter().all(|name| {
+        self.allowed_headers.iter().any(|allowed| allowed.to_str().eq_ignore_ascii_case(name))
+      })
+    {
+      return Ok(response);
+    }
+
+    response.add_header(
+      HttpHeaderName::AccessControlAllowMethods,
+      join(allowed_methods.iter().map(HttpMethod::as_str)),
+    )?;
+
+    if !requested_headers.is_empty() {
+      if self.allow_any_header {
+        response
+          .add_header(HttpHeaderName::AccessControlAllowHeaders, requested_headers.join(", "))?;
+      } else {
+        response.add_header(
+          HttpHeaderName::AccessControlAllowHeaders,
+          join(self.allowed_headers.iter().map(HttpHeaderName::to_str)),
+        )?;
+      }
+    }
+
+    if let Some(max_age) = self.max_age {
+      response.add_header(HttpHeaderName::AccessControlMaxAge, max_age.as_secs())?;
+    }
+
+    Ok(response)
+  }
+
+  /// Adds the CORS headers to a response. Called as a response filter by the router.
+  pub(crate) fn filter(&self, context: &mut ResponseContext<'_>) -> TiiResult<()> {
+    let Some(origin) = context.get_request().get_header(&HttpHeaderName::Origin) else {
+      return Ok(());
+    };
+
+    let vary = !self.allow_any_origin || self.allow_credentials;
+    let allow_origin = match self.is_origin_allowed(origin) {
+      false => None,
+      true if self.allow_any_origin && !self.allow_credentials => Some("*".to_string()),
+      true => Some(origin.to_string()),
+    };
+    let preflight = CorsPolicy::is_preflight(context.get_request());
+
+    let response = context.get_response_mut();
+    if vary {
+      response.add_header(HttpHeaderName::Vary, "Origin")?;
+    }
+
+    let Some(allow_origin) = allow_origin else {
+      return Ok(());
+    };
+
+    response.set_header(HttpHeaderName::AccessControlAllowOrigin, allow_origin)?;
+    if self.allow_credentials {
+      response.set_header(HttpHeaderName::AccessControlAllowCredentials, "true")?;
+    }
+
+    if !preflight && !self.exposed_headers.is_empty() {
+      response.set_header(
+        HttpHeaderName::AccessControlExposeHeaders,
+        join(self.exposed_headers.iter().map(HttpHeaderName::to_str)),
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a str>) -> String {
+  values.collect::<Vec<_>>().join(", ")
+}
@@ -57,6 +57,43 @@ pub enum RequestHeadParsingError {
   UnexpectedWebSocketOpcode,
   WebSocketClosedDuringPendingMessage,
   WebSocketTextMessageIsNotUtf8(Vec<u8>),
+  /// A web socket frame or message exceeded the configured maximum size. Contains the size of the offending frame or message.
+  WebSocketMessageTooBig(u64),
+  /// A web socket frame set reserved bits that no negotiated extension defines.
+  WebSocketReservedBitsSet,
+  /// A web socket control frame was fragmented or its payload was longer than 125 bytes.
+  InvalidWebSocketControlFrame,
+  /// The payload of a web socket close frame was malformed. Contains the payload.
+  InvalidWebSocketClosePayload(Vec<u8>),
+  /// The payload of a web socket message compressed with `permessage-deflate` could not be decompressed.
+  InvalidWebSocketCompressedPayload,
+  /// The client did not answer a web socket ping within the pong timeout.
+  WebSocketPongTimeout,
+  /// The client did not send a web socket message within the idle timeout.
+  WebSocketIdleTimeout,
+  /// The http version of the request is older than the minimum http version of the server.
+  HttpVersionDisabled(HttpVersion),
+  /// Strict parsing: The request has both a Content-Length and a Transfer-Encoding header.
+  ContentLengthWithTransferEncoding,
+  /// Strict parsing: The request has Content-Length headers with different values. Contains the values.
+  ConflictingContentLength(Vec<String>),
+  /// Strict parsing: A header line continues the previous line by starting with whitespace (obs-fold).
+  HeaderObsFold,
+  /// Strict parsing: A header name is followed by whitespace before the colon.
+  HeaderWhitespaceBeforeColon,
+  /// Strict parsing: A line of the request head ends with LF instead of CRLF.
+  BareLineFeed,
+  /// Strict parsing: A line of the request head contains a CR that is not followed by LF.
+  BareCarriageReturn,
+  /// Strict parsing: The method contains characters that are not allowed in a token. Contains the method.
+  InvalidMethodToken(String),
+  /// Strict parsing: A header name contains characters that are not allowed in a token. Contains the name.
+  InvalidHeaderNameToken(String),
+  /// Strict parsing: A header line does not contain a colon. Contains the line without its line ending.
+  MalformedHeaderLine(String),
+  /// Strict parsing: The transfer codings of the request do not end with a single chunked coding,
+  /// or the Transfer-Encoding header was sent more than once. Contains the values.
+  InvalidTransferEncoding(Vec<String>),
 }
 
 impl Display for RequestHeadParsingError {
@@ -67,6 +104,52 @@ impl Display for RequestHeadParsingError {
 }
 impl Error for RequestHeadParsingError {}
 
+/// Errors that occur while reading a request body.
+/// These are returned wrapped in an `io::Error` by the read functions of `RequestBody`.
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum RequestBodyError {
+  /// The request body is larger than the maximum request body size. Contains the maximum size.
+  TooLarge(u64),
+  /// A part of a multipart body is larger than the maximum part size. Contains the maximum size.
+  MultipartPartTooLarge(u64),
+  /// A multipart body is larger than the maximum size for multipart bodies. Contains the maximum size.
+  MultipartTooLarge(u64),
+  /// The request body is not a valid multipart body. Contains a description of the problem.
+  InvalidMultipart(String),
+  /// The request body is not a valid application/x-www-form-urlencoded body. Contains a description of the problem.
+  InvalidUrlEncodedForm(String),
+}
+
+impl Display for RequestBodyError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    Debug::fmt(self, f)
+  }
+}
+impl Error for RequestBodyError {}
+
+/// Errors that prevent a request from being upgraded to a web socket.
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum WebSocketHandshakeError {
+  /// The client did not offer any of the subprotocols of a web socket route that requires one.
+  /// Contains the offered subprotocols.
+  UnsupportedProtocol(String),
+}
+
+impl Display for WebSocketHandshakeError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    Debug::fmt(self, f)
+  }
+}
+impl Error for WebSocketHandshakeError {}
+
+impl From<RequestBodyError> for io::Error {
+  fn from(value: RequestBodyError) -> Self {
+    io::Error::new(ErrorKind::InvalidData, value)
+  }
+}
+
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum UserError {
@@ -86,6 +169,10 @@ pub enum UserError {
   InvalidPathParameter(String, TypeId, Box<dyn Error + Send + Sync>),
   /// name of the path parameter, TypeId for which parsing was attempted, error returned by FromStr trait.
   InvalidQueryParameter(String, TypeId, Box<dyn Error + Send + Sync>),
+  /// name of the form parameter, TypeId for which parsing was attempted, error returned by FromStr trait.
+  InvalidFormParameter(String, TypeId, Box<dyn Error + Send + Sync>),
+  /// A router can only have one CORS policy.
+  CorsPolicyAlreadySet,
 }
 
 impl Display for UserError {
@@ -158,7 +245,12 @@ impl TiiError {
   }
   pub fn downcast_mut<T: Error + Send + 'static>(&mut self) -> Option<&mut T> {
     match self {
-      TiiError::IO(err) => (err as &mut dyn Error).downcast_mut::<T>(),
+      TiiError::IO(err) => {
+        if (err as &dyn Error).is::<T>() {
+          return (err as &mut dyn Error).downcast_mut::<T>();
+        }
+        err.get_mut().and_then(|inner| inner.downcast_mut::<T>())
+      }
       TiiError::RequestHeadParsing(err) => (err as &mut dyn Error).downcast_mut::<T>(),
       TiiError::UserError(err) => (err as &mut dyn Error).downcast_mut::<T>(),
       TiiError::InvalidPathError(err) => (err as &mut dyn Error).downcast_mut::<T>(),
@@ -169,7 +261,9 @@ impl TiiError {
 
   pub fn downcast_ref<T: Error + Send + 'static>(&self) -> Option<&T> {
     match self {
-      TiiError::IO(err) => (err as &dyn Error).downcast_ref::<T>(),
+      TiiError::IO(err) => (err as &dyn Error)
+        .downcast_ref::<T>()
+        .or_else(|| err.get_ref().and_then(|inner| inner.downcast_ref::<T>())),
       TiiError::RequestHeadParsing(err) => (err as &dyn Error).downcast_ref::<T>(),
       TiiError::UserError(err) => (err as &dyn Error).downcast_ref::<T>(),
       TiiError::InvalidPathError(err) => (err as &dyn Error).downcast_ref::<T>(),
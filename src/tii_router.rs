@@ -6,10 +6,16 @@ use crate::functional_traits::{
 };
 use crate::stream::ConnectionStream;
 use crate::tii_builder::{ErrorHandler, NotRouteableHandler};
-use crate::tii_error::{InvalidPathError, RequestHeadParsingError, TiiError, TiiResult};
+use crate::tii_error::{
+  InvalidPathError, RequestBodyError, RequestHeadParsingError, TiiError, TiiResult,
+  WebSocketHandshakeError,
+};
 use crate::util::unwrap_some;
+use crate::websocket::deflate::DeflateParameters;
+use crate::CorsPolicy;
 use crate::QValue;
 use crate::RequestContext;
+use crate::{new_web_socket_stream_with_deflate, PerMessageDeflate};
 use crate::{trace_log, util};
 use crate::{warn_log, HttpHeaderName};
 use crate::{AcceptMimeTypeWithCharset, HttpVersion, MimeCharset, MimeTypeWithCharset};
@@ -22,6 +28,7 @@ use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 enum PathPart {
@@ -147,6 +154,184 @@ impl PathPart {
   }
 }
 
+/// Prefix tree over the path parts of all routes of a router.
+/// This is built once when the router is built and yields the routes whose path matches a request
+/// path without calling `Routeable::matches` for every route.
+#[derive(Debug, Default)]
+struct RouteTree {
+  /// Indices of the routes whose path ends at this node.
+  terminal: Vec<usize>,
+  literals: HashMap<String, RouteTree>,
+  variables: Vec<(String, RouteTree)>,
+  regex_variables: Vec<(String, Regex, RouteTree)>,
+  /// Wildcards and regex tail variables, those always end the path of a route.
+  tails: Vec<(PathPart, usize)>,
+}
+
+impl RouteTree {
+  fn new<'a>(routeables: impl Iterator<Item = &'a Routeable>) -> Self {
+    let mut tree = RouteTree::default();
+    for (index, routeable) in routeables.enumerate() {
+      tree.insert(routeable.parts.as_slice(), index);
+    }
+    tree
+  }
+
+  fn insert(&mut self, parts: &[PathPart], index: usize) {
+    let Some((part, rest)) = parts.split_first() else {
+      self.terminal.push(index);
+      return;
+    };
+
+    match part {
+      PathPart::Literal(literal) => {
+        self.literals.entry(literal.clone()).or_default().insert(rest, index);
+      }
+      PathPart::Variable(name) => {
+        if let Some((_, child)) = self.variables.iter_mut().find(|(n, _)| n == name) {
+          child.insert(rest, index);
+          return;
+        }
+
+        let mut child = RouteTree::default();
+        child.insert(rest, index);
+        self.variables.push((name.clone(), child));
+      }
+      PathPart::RegexVariable(name, regex) => {
+        if let Some((_, _, child)) = self
+          .regex_variables
+          .iter_mut()
+          .find(|(n, r, _)| n == name && r.as_str() == regex.as_str())
+        {
+          child.insert(rest, index);
+          return;
+        }
+
+        let mut child = RouteTree::default();
+        child.insert(rest, index);
+        self.regex_variables.push((name.clone(), regex.clone(), child));
+      }
+      PathPart::Wildcard | PathPart::RegexTailVariable(_, _) => {
+        self.tails.push((part.clone(), index));
+      }
+    }
+  }
+
+  /// Returns the indices of all routes whose path matches the request path together with their path parameters.
+  /// The result is ordered by index. This yields the same as calling `Routeable::matches_path` on every route.
+  fn candidates(&self, request_path: &str) -> Vec<(usize, Option<HashMap<String, String>>)> {
+    let mut result = Vec::new();
+    if let Some(request_path) = request_path.strip_prefix("/") {
+      self.collect(request_path, &mut Vec::new(), &mut result);
+    }
+    result.sort_unstable_by_key(|(index, _)| *index);
+    result
+  }
+
+  fn collect<'a>(
+    &'a self,
+    request_path: &'a str,
+    params: &mut Vec<(&'a str, &'a str)>,
+    result: &mut Vec<(usize, Option<HashMap<String, String>>)>,
+  ) {
+    if request_path.is_empty() {
+      for index in &self.terminal {
+        result.push((*index, Self::to_path_params(params)));
+      }
+    }
+
+    // Once the request path is exhausted the remaining parts of a route are matched against "".
+    let (part, remaining) = request_path.split_once("/").unwrap_or((request_path, ""));
+
+    if let Some(child) = self.literals.get(part) {
+      child.collect(remaining, params, result);
+    }
+
+    for (name, child) in &self.variables {
+      params.push((name.as_str(), part));
+      child.collect(remaining, params, result);
+      params.pop();
+    }
+
+    for (name, regex, child) in &self.regex_variables {
+      if regex.is_match(part) {
+        params.push((name.as_str(), part));
+        child.collect(remaining, params, result);
+        params.pop();
+      }
+    }
+
+    for (tail, index) in &self.tails {
+      match tail {
+        PathPart::RegexTailVariable(name, regex) => {
+          if regex.is_match(request_path) {
+            params.push((name.as_str(), request_path));
+            result.push((*index, Self::to_path_params(params)));
+            params.pop();
+          }
+        }
+        _ => result.push((*index, Self::to_path_params(params))),
+      }
+    }
+  }
+
+  fn to_path_params(params: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+    if params.is_empty() {
+      return None;
+    }
+
+    Some(params.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect())
+  }
+
+  /// Returns the methods of all routes whose path matches the request path.
+  fn methods<T>(
+    &self,
+    routes: &[T],
+    routeable: impl Fn(&T) -> &Routeable,
+    request_path: &str,
+  ) -> Vec<HttpMethod> {
+    self
+      .candidates(request_path)
+      .into_iter()
+      .filter_map(|(index, _)| routes.get(index))
+      .map(|route| routeable(route).method.clone())
+      .collect()
+  }
+
+  /// Finds the best route for the request.
+  /// Ties are resolved in favor of the route that was added first, just like a linear scan would.
+  fn find<'r, T>(
+    &self,
+    routes: &'r [T],
+    routeable: impl Fn(&T) -> &Routeable,
+    request: &RequestContext,
+  ) -> (RoutingDecision, Option<&'r T>) {
+    let mut best_decision = RoutingDecision::PathMismatch;
+    let mut best_route = None;
+
+    for (index, path_params) in self.candidates(request.get_path()) {
+      let Some(route) = routes.get(index) else {
+        continue;
+      };
+
+      let decision = routeable(route).matches_request(request, path_params);
+      if best_decision >= decision {
+        continue;
+      }
+
+      best_decision = decision;
+      if let RoutingDecision::Match(qv, _) = &best_decision {
+        best_route = Some(route);
+        if qv == &QValue::MAX {
+          break;
+        }
+      }
+    }
+
+    (best_decision, best_route)
+  }
+}
+
 #[derive(Debug, Clone)]
 /// Encapsulates a route and its handler.
 pub struct Routeable {
@@ -172,6 +357,9 @@ pub(crate) struct HttpRoute {
 
   /// The handler to run when the route is matched.
   pub(crate) handler: Box<dyn HttpEndpoint>,
+
+  /// Overrides the maximum request body size of the server for this route.
+  pub(crate) max_request_body_size: Option<u64>,
 }
 
 pub(crate) struct WebSocketRoute {
@@ -179,6 +367,31 @@ pub(crate) struct WebSocketRoute {
 
   /// The handler to run when the route is matched.
   pub(crate) handler: Box<dyn WebsocketEndpoint>,
+
+  /// Options of the websocket handshake.
+  pub(crate) options: WebSocketRouteOptions,
+}
+
+/// Options of a websocket route that affect the handshake and the connection.
+#[derive(Debug, Default)]
+pub(crate) struct WebSocketRouteOptions {
+  /// Enables the `permessage-deflate` extension for clients that offer it.
+  pub(crate) deflate: Option<PerMessageDeflate>,
+
+  /// The subprotocols the endpoint supports.
+  pub(crate) protocols: Vec<String>,
+
+  /// Reject the upgrade if the client does not offer any of the subprotocols.
+  pub(crate) protocol_required: bool,
+
+  /// Interval of the keep-alive pings, see `WebsocketReceiver::set_ping_interval`.
+  pub(crate) ping_interval: Option<Duration>,
+
+  /// See `WebsocketReceiver::set_pong_timeout`.
+  pub(crate) pong_timeout: Option<Duration>,
+
+  /// See `WebsocketReceiver::set_idle_timeout`.
+  pub(crate) idle_timeout: Option<Duration>,
 }
 
 impl HttpRoute {
@@ -187,11 +400,13 @@ impl HttpRoute {
     method: impl Into<HttpMethod>,
     consumes: HashSet<AcceptMimeTypeWithCharset>,
     produces: HashSet<AcceptMimeTypeWithCharset>,
+    max_request_body_size: Option<u64>,
     route: impl HttpEndpoint + 'static,
   ) -> TiiResult<Self> {
     Ok(HttpRoute {
       routeable: Routeable::new(path, method, consumes, produces)?,
       handler: Box::new(route) as Box<dyn HttpEndpoint>,
+      max_request_body_size,
     })
   }
 }
@@ -202,11 +417,13 @@ impl WebSocketRoute {
     method: impl Into<HttpMethod>,
     consumes: HashSet<AcceptMimeTypeWithCharset>,
     produces: HashSet<AcceptMimeTypeWithCharset>,
+    options: WebSocketRouteOptions,
     route: impl WebsocketEndpoint + 'static,
   ) -> TiiResult<Self> {
     Ok(WebSocketRoute {
       routeable: Routeable::new(path, method, consumes, produces)?,
       handler: Box::new(route) as Box<dyn WebsocketEndpoint>,
+      options,
     })
   }
 }
@@ -276,7 +493,9 @@ impl Ord for RoutingDecision {
 }
 
 impl Routeable {
-  pub(crate) fn new(
+  /// Creates a route that can be matched against requests with `matches`.
+  /// Returns an error if the path is not a valid route path.
+  pub fn new(
     path: impl ToString,
     method: impl Into<HttpMethod>,
     consumes: HashSet<AcceptMimeTypeWithCharset>,
@@ -376,6 +595,15 @@ impl Routeable {
       return RoutingDecision::PathMismatch;
     }
 
+    self.matches_request(route, path_params)
+  }
+
+  /// Checks method, content type and accept of a request whose path is already known to match this route.
+  fn matches_request(
+    &self,
+    route: &RequestContext,
+    path_params: Option<HashMap<String, String>>,
+  ) -> RoutingDecision {
     if &self.method != route.get_method() {
       return RoutingDecision::MethodMismatch;
     }
@@ -501,6 +729,26 @@ pub(crate) struct DefaultRouter {
   /// The routes to process WebSocket requests for and their handlers.
   websocket_routes: Vec<WebSocketRoute>,
 
+  /// Prefix tree over the paths of `routes`.
+  route_tree: RouteTree,
+
+  /// Prefix tree over the paths of `websocket_routes`.
+  websocket_route_tree: RouteTree,
+
+  /// Maximum payload size of a single websocket frame received from the client.
+  websocket_max_frame_size: Option<u64>,
+  /// Maximum size of a websocket message received from the client.
+  websocket_max_message_size: Option<u64>,
+
+  /// Serve HEAD requests with the GET route of the path if there is no HEAD route.
+  head_fallback_to_get: bool,
+
+  /// Answer OPTIONS requests with the methods of the path if there is no OPTIONS route.
+  automatic_options: bool,
+
+  /// Answers CORS preflight requests. The response filter part is in `response_filters`.
+  cors_policy: Option<Arc<CorsPolicy>>,
+
   /// Called when no route has been found in the router.
   not_found_handler: NotRouteableHandler,
 
@@ -527,8 +775,36 @@ impl Debug for DefaultRouter {
   }
 }
 
+const SEC_WEBSOCKET_EXTENSIONS: &str = "Sec-WebSocket-Extensions";
+const SEC_WEBSOCKET_PROTOCOL: &str = "Sec-WebSocket-Protocol";
+
+/// Picks the first subprotocol offered by the client that the route supports.
+fn select_websocket_protocol<'a>(
+  request: &RequestContext,
+  supported: &'a [String],
+) -> Option<&'a String> {
+  request
+    .get_headers(SEC_WEBSOCKET_PROTOCOL)
+    .into_iter()
+    .flat_map(|value| value.split(','))
+    .map(str::trim)
+    .find_map(|offered| supported.iter().find(|protocol| protocol.as_str() == offered))
+}
+
 /// Performs the WebSocket handshake.
-fn websocket_handshake(request: &RequestContext) -> TiiResult<Response> {
+/// Returns the `permessage-deflate` parameters if the extension is enabled for the route and the client offered it.
+/// The selected subprotocol is stored in the request.
+fn websocket_handshake(
+  request: &mut RequestContext,
+  options: &WebSocketRouteOptions,
+) -> TiiResult<(Response, Option<DeflateParameters>)> {
+  let protocol = select_websocket_protocol(request, &options.protocols);
+  if protocol.is_none() && options.protocol_required {
+    return Err(TiiError::from(WebSocketHandshakeError::UnsupportedProtocol(
+      request.get_headers(SEC_WEBSOCKET_PROTOCOL).join(", "),
+    )));
+  }
+
   const HANDSHAKE_KEY_CONSTANT: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
   // Get the handshake key header
@@ -544,16 +820,28 @@ fn websocket_handshake(request: &RequestContext) -> TiiResult<Response> {
   //let sec_websocket_accept = sha1.encode();
 
   // Serialise the handshake response
-  let response = Response::new(StatusCode::SwitchingProtocols)
+  let mut response = Response::new(StatusCode::SwitchingProtocols)
     .with_header(HttpHeaderName::Upgrade, "websocket")?
     .with_header(HttpHeaderName::Connection, "Upgrade")?
     .with_header("Sec-WebSocket-Accept", sec_websocket_accept)?;
 
+  if let Some(protocol) = protocol {
+    response = response.with_header(SEC_WEBSOCKET_PROTOCOL, protocol)?;
+  }
+  request.set_websocket_protocol(protocol.cloned());
+
+  let deflate = options.deflate.as_ref().and_then(|config| {
+    DeflateParameters::negotiate(config, &request.get_headers(SEC_WEBSOCKET_EXTENSIONS))
+  });
+  if let Some(deflate) = deflate {
+    response = response.with_header(SEC_WEBSOCKET_EXTENSIONS, deflate.to_header_value())?;
+  }
+
   // Oddly enough I think you can establish a WS connection with a POST request that has data.
   // This will consume that data if it has not already been used by a filter.
   // Some beta versions of Web Sockets used the request body to convey the Sec-WebSocket-Key...
   request.consume_request_body()?;
-  Ok(response)
+  Ok((response, deflate))
 }
 
 impl DefaultRouter {
@@ -565,6 +853,11 @@ impl DefaultRouter {
     response_filters: Vec<Box<dyn ResponseFilter>>,
     routes: Vec<HttpRoute>,
     websocket_routes: Vec<WebSocketRoute>,
+    websocket_max_frame_size: Option<u64>,
+    websocket_max_message_size: Option<u64>,
+    head_fallback_to_get: bool,
+    automatic_options: bool,
+    cors_policy: Option<Arc<CorsPolicy>>,
     not_found_handler: NotRouteableHandler,
     not_acceptable_handler: NotRouteableHandler,
     method_not_allowed_handler: NotRouteableHandler,
@@ -579,6 +872,10 @@ impl DefaultRouter {
       routeables.push(x.routeable.clone());
     }
 
+    let route_tree = RouteTree::new(routes.iter().map(|route| &route.routeable));
+    let websocket_route_tree =
+      RouteTree::new(websocket_routes.iter().map(|route| &route.routeable));
+
     Self {
       router_filter,
       pre_routing_filters,
@@ -587,6 +884,13 @@ impl DefaultRouter {
       routeables,
       routes,
       websocket_routes,
+      route_tree,
+      websocket_route_tree,
+      websocket_max_frame_size,
+      websocket_max_message_size,
+      head_fallback_to_get,
+      automatic_options,
+      cors_policy,
       not_found_handler,
       not_acceptable_handler,
       method_not_allowed_handler,
@@ -618,23 +922,8 @@ impl DefaultRouter {
       return Ok(RouterWebSocketServingResponse::HandledWithoutProtocolSwitch(resp));
     }
 
-    let mut best_decision = RoutingDecision::PathMismatch;
-    let mut best_handler = None;
-
-    for handler in &self.websocket_routes {
-      let decision = handler.routeable.matches(request);
-      if best_decision >= decision {
-        continue;
-      }
-
-      best_decision = decision;
-      if let RoutingDecision::Match(qv, _) = &best_decision {
-        best_handler = Some(handler);
-        if qv == &QValue::MAX {
-          break;
-        }
-      }
-    }
+    let (best_decision, best_handler) =
+      self.websocket_route_tree.find(&self.websocket_routes, |route| &route.routeable, request);
 
     if let Some(handler) = best_handler {
       request.set_routed_path(handler.routeable.path.as_str());
@@ -651,27 +940,35 @@ impl DefaultRouter {
         return Ok(RouterWebSocketServingResponse::HandledWithoutProtocolSwitch(resp));
       }
 
-      return match websocket_handshake(request) {
+      return match websocket_handshake(request, &handler.options) {
         Err(err) => {
           let resp = (self.error_handler)(request, err)?;
           let resp = self.call_response_filters(request, resp)?;
           Ok(RouterWebSocketServingResponse::HandledWithoutProtocolSwitch(resp))
         }
-        Ok(resp) => {
+        Ok((resp, deflate)) => {
           let resp = self.call_response_filters(request, resp)?;
           if resp.status_code != StatusCode::SwitchingProtocols {
             return Ok(RouterWebSocketServingResponse::HandledWithoutProtocolSwitch(resp));
           }
 
-          if let Some(enc) = resp.get_body().and_then(|a| a.get_content_encoding()) {
-            if enc == "gzip" && !request.accepts_gzip() {
-              warn_log!("Request {} responding with gzip even tho client doesnt indicate that it can understand gzip.", request.id());
+          if let Some(enc) = resp.get_body().and_then(|a| a.content_coding()) {
+            if !request.accepts_encoding(enc) {
+              warn_log!("Request {} responding with {} even tho client doesnt indicate that it can understand it.", request.id(), enc);
             }
           }
 
+          //A response filter may have removed the extension from the handshake.
+          let deflate = deflate.filter(|_| resp.get_header(SEC_WEBSOCKET_EXTENSIONS).is_some());
+
           resp.write_to(request.id(), HttpVersion::Http11, stream)?; //Errors here are fatal
 
-          let (sender, receiver) = crate::new_web_socket_stream(stream);
+          let (sender, mut receiver) = new_web_socket_stream_with_deflate(stream, deflate);
+          receiver.set_max_frame_size(self.websocket_max_frame_size);
+          receiver.set_max_message_size(self.websocket_max_message_size);
+          receiver.set_ping_interval(handler.options.ping_interval);
+          receiver.set_pong_timeout(handler.options.pong_timeout);
+          receiver.set_idle_timeout(handler.options.idle_timeout);
           handler.handler.serve(request, receiver, sender)?;
           Ok(RouterWebSocketServingResponse::HandledWithProtocolSwitch)
         }
@@ -725,8 +1022,14 @@ impl DefaultRouter {
       return Ok(None);
     }
 
-    let mut resp = self.serve_inner(request).or_else(|e| self.call_error_handler(request, e))?;
+    let mut head_fallback = false;
+    let mut resp = self
+      .serve_inner(request, &mut head_fallback)
+      .or_else(|e| self.call_error_handler(request, e))?;
     resp = self.call_response_filters(request, resp)?;
+    if head_fallback {
+      resp.omit_body = true;
+    }
 
     Ok(Some(resp))
   }
@@ -750,34 +1053,74 @@ impl DefaultRouter {
     Ok(resp)
   }
 
-  fn serve_inner(&self, request: &mut RequestContext) -> TiiResult<Response> {
+  /// Finds the route for a http request.
+  /// If there is no route for a HEAD request then the GET route of the path is used instead if enabled.
+  /// `head_fallback` is set to true in that case.
+  fn find_route(
+    &self,
+    request: &mut RequestContext,
+    head_fallback: &mut bool,
+  ) -> (RoutingDecision, Option<&HttpRoute>) {
+    let (best_decision, best_handler) =
+      self.route_tree.find(&self.routes, |route| &route.routeable, request);
+
+    if best_handler.is_some()
+      || !self.head_fallback_to_get
+      || *request.get_method() != HttpMethod::Head
+    {
+      return (best_decision, best_handler);
+    }
+
+    request.set_method(HttpMethod::Get);
+    let (get_decision, get_handler) =
+      self.route_tree.find(&self.routes, |route| &route.routeable, request);
+    request.set_method(HttpMethod::Head);
+
+    if get_handler.is_none() {
+      return (best_decision, None);
+    }
+
+    trace_log!("Request {} HEAD is served by the GET route {}", request.id(), request.get_path());
+    *head_fallback = true;
+    (get_decision, get_handler)
+  }
+
+  fn serve_inner(
+    &self,
+    request: &mut RequestContext,
+    head_fallback: &mut bool,
+  ) -> TiiResult<Response> {
     for filter in self.pre_routing_filters.iter() {
       if let Some(resp) = filter.filter(request)? {
         return Ok(resp);
       }
     }
 
-    let mut best_decision = RoutingDecision::PathMismatch;
-    let mut best_handler = None;
-
-    for handler in &self.routes {
-      let decision = handler.routeable.matches(request);
-      if best_decision >= decision {
-        continue;
-      }
-
-      best_decision = decision;
-      if let RoutingDecision::Match(qv, _) = &best_decision {
-        best_handler = Some(handler);
-        if qv == &QValue::MAX {
-          break;
+    if let Some(cors_policy) = self.cors_policy.as_ref() {
+      if CorsPolicy::is_preflight(request) {
+        let methods = self.path_methods(request);
+        if !methods.is_empty() {
+          return cors_policy.preflight(request, methods.as_slice());
         }
       }
     }
 
+    let (best_decision, best_handler) = self.find_route(request, head_fallback);
+
     if let Some(handler) = best_handler {
       request.set_routed_path(handler.routeable.path.as_str());
 
+      if let Some(body) = request.request_body() {
+        if handler.max_request_body_size.is_some() {
+          body.set_max_size(handler.max_request_body_size)?;
+        }
+
+        if body.is_too_large()? {
+          let max_size = unwrap_some(body.max_size()?);
+          return Err(RequestBodyError::TooLarge(max_size).into());
+        }
+      }
+
       if request.get_request_entity().is_none() {
         if let Some(body) = request.request_body() {
           request.set_request_entity(handler.handler.parse_entity(
@@ -798,9 +1141,52 @@ impl DefaultRouter {
       return handler.handler.serve(request);
     }
 
+    if self.automatic_options
+      && best_decision == RoutingDecision::MethodMismatch
+      && *request.get_method() == HttpMethod::Options
+    {
+      return Response::no_content().with_header(HttpHeaderName::Allow, self.allow(request));
+    }
+
     self.invoke_appropriate_fallback_handler(request, &best_decision)
   }
 
+  /// Returns the methods that the path of the request can be served with, except the method of the request.
+  /// This includes HEAD and OPTIONS if they are handled automatically.
+  fn path_methods(&self, request: &RequestContext) -> Vec<HttpMethod> {
+    let mut methods =
+      self.route_tree.methods(&self.routes, |route| &route.routeable, request.get_path());
+    methods.extend(self.websocket_route_tree.methods(
+      &self.websocket_routes,
+      |route| &route.routeable,
+      request.get_path(),
+    ));
+    methods.retain(|method| method != request.get_method());
+    if methods.is_empty() {
+      return methods;
+    }
+
+    if self.head_fallback_to_get
+      && methods.contains(&HttpMethod::Get)
+      && !methods.contains(&HttpMethod::Head)
+    {
+      methods.push(HttpMethod::Head);
+    }
+
+    if self.automatic_options {
+      methods.push(HttpMethod::Options);
+    }
+
+    methods.sort();
+    methods.dedup();
+    methods
+  }
+
+  /// Returns the value of the `Allow` header for an OPTIONS or 405 Method Not Allowed response.
+  fn allow(&self, request: &RequestContext) -> String {
+    self.path_methods(request).iter().map(HttpMethod::as_str).collect::<Vec<_>>().join(", ")
+  }
+
   fn invoke_appropriate_fallback_handler(
     &self,
     request: &mut RequestContext,
@@ -809,7 +1195,13 @@ impl DefaultRouter {
     match best_decision {
       RoutingDecision::PathMismatch => (self.not_found_handler)(request, &self.routeables),
       RoutingDecision::MethodMismatch => {
-        (self.method_not_allowed_handler)(request, &self.routeables)
+        let mut response = (self.method_not_allowed_handler)(request, &self.routeables)?;
+        if response.status_code == StatusCode::MethodNotAllowed
+          && response.get_header(HttpHeaderName::Allow).is_none()
+        {
+          response.set_header(HttpHeaderName::Allow, self.allow(request))?;
+        }
+        Ok(response)
       }
       RoutingDecision::MimeMismatch => {
         (self.unsupported_media_type_handler)(request, &self.routeables)
@@ -851,3 +1243,151 @@ where
     Arc::as_ref(self).serve_websocket(stream, request)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::tii_router::{RouteTree, Routeable, RoutingDecision};
+  use crate::{
+    AcceptMimeTypeWithCharset, HttpHeader, HttpMethod, HttpVersion, MimeType, QValue,
+    RequestContext, TypeSystem,
+  };
+  use std::collections::HashSet;
+
+  fn routeable(
+    path: &str,
+    method: HttpMethod,
+    consumes: &[MimeType],
+    produces: &[MimeType],
+  ) -> Routeable {
+    let to_set = |mimes: &[MimeType]| {
+      mimes.iter().cloned().map(AcceptMimeTypeWithCharset::from).collect::<HashSet<_>>()
+    };
+    Routeable::new(path, method, to_set(consumes), to_set(produces)).unwrap()
+  }
+
+  fn request(method: HttpMethod, path: &str, headers: &[(&str, &str)]) -> RequestContext {
+    // Pre routing filters may set any path, so the path is not validated here.
+    let mut request = RequestContext::new(
+      0,
+      "peer",
+      "local",
+      method,
+      HttpVersion::Http11,
+      "/",
+      Vec::<(String, String)>::new(),
+      headers.iter().map(|(name, value)| HttpHeader::new(name, value)).collect(),
+      None,
+      None,
+      TypeSystem::empty(),
+    )
+    .unwrap();
+    request.set_path(path);
+    request
+  }
+
+  /// The way the router used to route before it had a `RouteTree`.
+  fn linear_scan<'a>(
+    routes: &'a [Routeable],
+    request: &RequestContext,
+  ) -> (RoutingDecision, Option<&'a Routeable>) {
+    let mut best_decision = RoutingDecision::PathMismatch;
+    let mut best_route = None;
+    for route in routes {
+      let decision = route.matches(request);
+      if best_decision >= decision {
+        continue;
+      }
+
+      best_decision = decision;
+      if let RoutingDecision::Match(qv, _) = &best_decision {
+        best_route = Some(route);
+        if qv == &QValue::MAX {
+          break;
+        }
+      }
+    }
+
+    (best_decision, best_route)
+  }
+
+  fn assert_same_decision(routes: &[Routeable], request: &RequestContext) {
+    let tree = RouteTree::new(routes.iter());
+    let (expected_decision, expected_route) = linear_scan(routes, request);
+    let (decision, route) = tree.find(routes, |r| r, request);
+    assert_eq!(expected_decision, decision, "{} {}", request.get_method(), request.get_path());
+    assert_eq!(
+      expected_route.map(|r| r as *const Routeable),
+      route.map(|r| r as *const Routeable),
+      "{} {}",
+      request.get_method(),
+      request.get_path()
+    );
+  }
+
+  #[test]
+  fn tree_matches_linear_scan() {
+    let routes = vec![
+      routeable("/", HttpMethod::Get, &[], &[]),
+      routeable("/users", HttpMethod::Get, &[], &[MimeType::ApplicationJson]),
+      routeable("/users", HttpMethod::Get, &[], &[MimeType::TextHtml]),
+      routeable("/users", HttpMethod::Post, &[MimeType::ApplicationJson], &[]),
+      routeable("/users/{id}", HttpMethod::Get, &[], &[]),
+      routeable("/users/{name}/posts", HttpMethod::Get, &[], &[]),
+      routeable("/users/{id:[0-9]+}", HttpMethod::Delete, &[], &[]),
+      routeable("/users/{id:[0-9]+}/posts/{post}", HttpMethod::Get, &[], &[]),
+      routeable("/users/me", HttpMethod::Get, &[], &[]),
+      routeable("/files/*", HttpMethod::Get, &[], &[]),
+      routeable("/files/static/*", HttpMethod::Get, &[], &[]),
+      routeable("/files/{rest:.*\\.png}", HttpMethod::Get, &[], &[]),
+      routeable("/a//b", HttpMethod::Get, &[], &[]),
+      routeable("/opt/{x}/{y}", HttpMethod::Put, &[], &[]),
+      routeable("/dup/{x}/{x}", HttpMethod::Get, &[], &[]),
+      routeable("/*", HttpMethod::Patch, &[], &[]),
+    ];
+
+    let paths = [
+      "",
+      "/",
+      "//",
+      "/users",
+      "/users/",
+      "/users/12",
+      "/users/12/",
+      "/users/bob",
+      "/users/me",
+      "/users/me/posts",
+      "/users/12/posts/3",
+      "/users/12/posts",
+      "/files",
+      "/files/",
+      "/files/a/b/c",
+      "/files/static/x",
+      "/files/img/a.png",
+      "/a//b",
+      "/a/b",
+      "/opt",
+      "/opt/1",
+      "/opt/1/2",
+      "/opt/1/2/3",
+      "/dup/1/2",
+      "/nothing/here",
+      "users",
+    ];
+
+    let headers: [&[(&str, &str)]; 5] = [
+      &[],
+      &[("Accept", "text/html;q=0.5, application/json;q=0.7")],
+      &[("Accept", "text/html")],
+      &[("Accept", "image/png")],
+      &[("Content-Type", "application/json")],
+    ];
+
+    for method in HttpMethod::well_known() {
+      for path in paths {
+        for header in headers {
+          assert_same_decision(&routes, &request(method.clone(), path, header));
+        }
+      }
+    }
+  }
+}
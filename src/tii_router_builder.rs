@@ -9,17 +9,19 @@ use crate::functional_traits::{
   WebsocketEndpoint,
 };
 use crate::tii_builder::EntityHttpEndpoint;
-use crate::{AcceptMimeType, RequestBody};
+use crate::{AcceptMimeType, PerMessageDeflate, RequestBody};
 use crate::{AcceptMimeTypeWithCharset, MimeCharset, MimeTypeWithCharset, TiiResult};
-use crate::{AsRequestState, RequestContext};
+use crate::{AsRequestState, RequestContext, ResponseContext};
+use crate::{CorsPolicy, UserError};
 use crate::{DefaultRouter, Response, Router};
 use crate::{EntityDeserializer, HttpMethod};
 use crate::{ErrorHandler, NotRouteableHandler};
-use crate::{HttpRoute, WebSocketRoute};
+use crate::{HttpRoute, WebSocketRoute, WebSocketRouteOptions};
 use crate::{WebsocketReceiver, WebsocketSender};
 use std::any::Any;
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Represents a sub-app to run for a specific host.
 pub struct RouterBuilder {
@@ -42,6 +44,20 @@ pub struct RouterBuilder {
   /// The routes to process WebSocket requests for and their handlers.
   websocket_routes: Vec<WebSocketRoute>,
 
+  /// Maximum payload size of a single websocket frame received from the client.
+  websocket_max_frame_size: Option<u64>,
+  /// Maximum size of a websocket message received from the client.
+  websocket_max_message_size: Option<u64>,
+
+  /// Serve HEAD requests with the GET route of the path if there is no HEAD route.
+  head_fallback_to_get: bool,
+
+  /// Answer OPTIONS requests with the methods of the path if there is no OPTIONS route.
+  automatic_options: bool,
+
+  /// Answers CORS preflight requests and adds CORS headers to responses.
+  cors_policy: Option<Arc<CorsPolicy>>,
+
   /// Called when no route has been found in the router.
   not_found_handler: NotRouteableHandler,
 
@@ -103,6 +119,7 @@ pub struct RouteBuilder {
   method: HttpMethod,
   consumes: HashSet<AcceptMimeTypeWithCharset>,
   produces: HashSet<AcceptMimeTypeWithCharset>,
+  max_request_body_size: Option<u64>,
 }
 
 impl RouteBuilder {
@@ -117,6 +134,7 @@ impl RouteBuilder {
       method,
       consumes: Default::default(),
       produces: Default::default(),
+      max_request_body_size: None,
     }
   }
 
@@ -132,6 +150,13 @@ impl RouteBuilder {
     self
   }
 
+  /// Overrides the maximum request body size of the server for this endpoint.
+  /// This can be larger or smaller than the maximum request body size of the server.
+  pub fn max_request_body_size(mut self, size: u64) -> Self {
+    self.max_request_body_size = Some(size);
+    self
+  }
+
   /// Finish building the route by proving the endpoint to call.
   pub fn endpoint<T: HttpEndpoint + 'static>(mut self, handler: T) -> TiiResult<RouterBuilder> {
     self.inner.routes.push(HttpRoute::new(
@@ -139,6 +164,7 @@ impl RouteBuilder {
       self.method,
       self.consumes,
       self.produces,
+      self.max_request_body_size,
       handler,
     )?);
     Ok(self.inner)
@@ -190,6 +216,90 @@ impl RouteBuilder {
   }
 }
 
+/// Builder for a websocket route/endpoint.
+pub struct WebsocketRouteBuilder {
+  inner: RouterBuilder,
+  route: String,
+  method: HttpMethod,
+  options: WebSocketRouteOptions,
+}
+
+impl WebsocketRouteBuilder {
+  pub(crate) fn new(
+    router_builder: RouterBuilder,
+    method: HttpMethod,
+    route: String,
+  ) -> WebsocketRouteBuilder {
+    WebsocketRouteBuilder { inner: router_builder, route, method, options: Default::default() }
+  }
+
+  /// Enables the `permessage-deflate` extension for clients that offer it during the handshake.
+  /// Messages sent to such clients are compressed and compressed messages received from them are decompressed.
+  pub fn permessage_deflate(mut self, config: PerMessageDeflate) -> Self {
+    self.options.deflate = Some(config);
+    self
+  }
+
+  /// Add a subprotocol which the endpoint supports.
+  /// The handshake selects the first subprotocol in the `Sec-WebSocket-Protocol` header of the client
+  /// that was added here and echoes it to the client. The endpoint can get it from `RequestContext::websocket_protocol`.
+  pub fn protocol(mut self, protocol: impl ToString) -> Self {
+    self.options.protocols.push(protocol.to_string());
+    self
+  }
+
+  /// Rejects the upgrade if the client does not offer any of the subprotocols of the endpoint.
+  /// The error handler is called with `WebSocketHandshakeError::UnsupportedProtocol`,
+  /// the default error handler responds with 400 Bad Request.
+  /// If this is false then the endpoint is called without a subprotocol. Default is false.
+  pub fn require_protocol(mut self, required: bool) -> Self {
+    self.options.protocol_required = required;
+    self
+  }
+
+  /// Sends a ping to the client whenever no frame was received from it for the given interval.
+  /// Pings are sent by the `WebsocketReceiver` while the endpoint waits in one of its read functions.
+  pub fn ping_interval(mut self, interval: Duration) -> Self {
+    self.options.ping_interval = Some(interval);
+    self
+  }
+
+  /// Closes the web socket with status 1011 (Internal Error) if the client does not answer a ping
+  /// within the given timeout. The read function of the `WebsocketReceiver` then fails with
+  /// `RequestHeadParsingError::WebSocketPongTimeout`. Only has an effect together with `ping_interval`.
+  pub fn pong_timeout(mut self, timeout: Duration) -> Self {
+    self.options.pong_timeout = Some(timeout);
+    self
+  }
+
+  /// Closes the web socket with status 1001 (Going Away) if the client does not send a text or binary
+  /// message within the given timeout. The read function of the `WebsocketReceiver` then fails with
+  /// `RequestHeadParsingError::WebSocketIdleTimeout`.
+  pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+    self.options.idle_timeout = Some(timeout);
+    self
+  }
+
+  /// Finish building the route by proving the endpoint to call.
+  pub fn endpoint<T: WebsocketEndpoint + 'static>(
+    mut self,
+    handler: T,
+  ) -> TiiResult<RouterBuilder> {
+    self.inner.websocket_routes.push(WebSocketRoute::new(
+      self.route,
+      self.method,
+      HashSet::new(),
+      HashSet::new(),
+      self.options,
+      handler,
+    )?);
+    Ok(self.inner)
+  }
+}
+
+/// Default maximum frame and message size of websockets, 16 MiB.
+const DEFAULT_WEBSOCKET_MAX_SIZE: u64 = 0x100_0000;
+
 impl Default for RouterBuilder {
   fn default() -> Self {
     RouterBuilder {
@@ -199,6 +309,11 @@ impl Default for RouterBuilder {
       response_filters: Vec::default(),
       routes: Vec::new(),
       websocket_routes: Vec::new(),
+      websocket_max_frame_size: Some(DEFAULT_WEBSOCKET_MAX_SIZE),
+      websocket_max_message_size: Some(DEFAULT_WEBSOCKET_MAX_SIZE),
+      head_fallback_to_get: true,
+      automatic_options: true,
+      cors_policy: None,
       not_found_handler: default_not_found_handler,
       not_acceptable_handler: default_not_acceptable_handler,
       method_not_allowed_handler: default_method_not_allowed_handler,
@@ -303,6 +418,7 @@ impl RouterBuilder {
         MimeCharset::Unspecified,
       )]),
       HashSet::new(),
+      None,
       handler,
     )?);
     Ok(self)
@@ -460,6 +576,26 @@ impl RouterBuilder {
     closure(self.method(method, route))
   }
 
+  /// Build a WebSocket endpoint that listens for HTTP upgrade requests with the GET http method.
+  pub fn ws_get(self, route: &str) -> WebsocketRouteBuilder {
+    WebsocketRouteBuilder::new(self, HttpMethod::Get, route.to_string())
+  }
+
+  /// Build a WebSocket endpoint that listens for HTTP upgrade requests with the GET http method.
+  pub fn begin_ws_get<T: FnOnce(WebsocketRouteBuilder) -> TiiResult<Self>>(
+    self,
+    route: &str,
+    closure: T,
+  ) -> TiiResult<Self> {
+    closure(self.ws_get(route))
+  }
+
+  /// Build a WebSocket endpoint that listens for HTTP upgrade requests with the given http method.
+  /// Ordinary Web-Socket clients only use the GET Method.
+  pub fn ws_method(self, method: HttpMethod, route: &str) -> WebsocketRouteBuilder {
+    WebsocketRouteBuilder::new(self, method, route.to_string())
+  }
+
   /// Adds a WebSocket route and associated handler to the sub-app.
   /// Routes can include wildcards, for example `/ws/*`.
   /// The handler is passed a reading and writing end of the websocket.
@@ -496,6 +632,7 @@ impl RouterBuilder {
       method,
       HashSet::new(),
       HashSet::new(),
+      WebSocketRouteOptions::default(),
       handler,
     )?);
     Ok(self)
@@ -573,6 +710,60 @@ impl RouterBuilder {
     self.ws_route_method(HttpMethod::Delete, route, handler)
   }
 
+  /// Enables or disables serving HEAD requests with the GET route of a path if the path has no HEAD route.
+  /// The response of the GET route keeps its headers and Content-Length, but its body is not sent.
+  /// Streamed bodies are never run. See `Response::omit_body` for details.
+  /// Default is true.
+  pub fn with_head_fallback_to_get(mut self, enabled: bool) -> TiiResult<Self> {
+    self.head_fallback_to_get = enabled;
+    Ok(self)
+  }
+
+  /// Enables or disables answering OPTIONS requests to a path without an OPTIONS route.
+  /// The response is a 204 No Content with an `Allow` header that lists the methods of the routes of the path.
+  /// Default is true.
+  pub fn with_automatic_options(mut self, enabled: bool) -> TiiResult<Self> {
+    self.automatic_options = enabled;
+    Ok(self)
+  }
+
+  /// Sets the CORS policy of this router.
+  /// Preflight requests to paths that have a route are answered according to the policy
+  /// before any route is matched, even if the path has an OPTIONS route.
+  /// The CORS headers of all other responses are added by a response filter
+  /// which runs after the response filters that were added before this call.
+  /// Calling this a second time is an error.
+  pub fn with_cors_policy(mut self, policy: CorsPolicy) -> TiiResult<Self> {
+    if self.cors_policy.is_some() {
+      return Err(UserError::CorsPolicyAlreadySet.into());
+    }
+
+    let policy = Arc::new(policy);
+    let filter_policy = Arc::clone(&policy);
+    self.cors_policy = Some(policy);
+    self
+      .with_response_filter(move |context: &mut ResponseContext<'_>| filter_policy.filter(context))
+  }
+
+  /// Sets the maximum payload size of a single frame that clients may send on the websockets of this router.
+  /// A client that sends a larger frame has its websocket closed with status 1009 (Message Too Big)
+  /// without the payload ever being read into memory. None means unlimited. Default is 16 MiB.
+  pub fn with_websocket_max_frame_size(mut self, size: Option<u64>) -> TiiResult<Self> {
+    self.websocket_max_frame_size = size;
+    Ok(self)
+  }
+
+  /// Sets the maximum size of a message that clients may send on the websockets of this router.
+  /// For fragmented messages the payloads of all frames of the message are added up.
+  /// A client that sends a larger message has its websocket closed with status 1009 (Message Too Big).
+  /// Messages compressed with `permessage-deflate` are limited after decompression. If this is set to None
+  /// the maximum frame size, or 16 MiB if that is None as well, limits the decompressed message.
+  /// None means unlimited for uncompressed messages. Default is 16 MiB.
+  pub fn with_websocket_max_message_size(mut self, size: Option<u64>) -> TiiResult<Self> {
+    self.websocket_max_message_size = size;
+    Ok(self)
+  }
+
   /// Sets the error handler for this router.
   pub fn with_error_handler(mut self, handler: ErrorHandler) -> TiiResult<Self> {
     self.error_handler = handler;
@@ -588,6 +779,11 @@ impl RouterBuilder {
       self.response_filters,
       self.routes,
       self.websocket_routes,
+      self.websocket_max_frame_size,
+      self.websocket_max_message_size,
+      self.head_fallback_to_get,
+      self.automatic_options,
+      self.cors_policy,
       self.not_found_handler,
       self.not_acceptable_handler,
       self.method_not_allowed_handler,
@@ -3,9 +3,12 @@
 //! If no router wants to handle the request it also has a 404 handler.
 
 use crate::functional_traits::Router;
-use crate::http::{Response, StatusCode};
+use crate::http::{apply_conditional_request, apply_range_request, is_too_large_error};
+use crate::http::{has_header_token, RequestParsingOptions, Response, StatusCode};
 use crate::stream::{ConnectionStream, IntoConnectionStream};
-use crate::tii_builder::{ErrorHandler, NotFoundHandler, RouterWebSocketServingResponse};
+use crate::tii_builder::{
+  ErrorHandler, NotFoundHandler, ParseErrorHandler, RouterWebSocketServingResponse,
+};
 use crate::tii_error::{TiiError, TiiResult};
 use crate::{error_log, trace_log};
 use crate::{warn_log, HttpHeaderName};
@@ -49,10 +52,14 @@ pub struct Server {
   routers: Vec<Box<dyn Router>>,
   error_handler: ErrorHandler,
   not_found_handler: NotFoundHandler,
+  parse_error_handler: ParseErrorHandler,
   max_head_buffer_size: usize,
+  parsing_options: RequestParsingOptions,
+  max_request_body_size: Option<u64>,
   connection_timeout: Option<Duration>,
   read_timeout: Option<Duration>,
   keep_alive_timeout: Option<Duration>,
+  max_requests_per_connection: Option<u64>,
   request_body_io_timeout: Option<Duration>,
   write_timeout: Option<Duration>,
   continue_handler: ContinueHandler,
@@ -80,10 +87,14 @@ impl Server {
     routers: Vec<Box<dyn Router>>,
     error_handler: ErrorHandler,
     not_found_handler: NotFoundHandler,
+    parse_error_handler: ParseErrorHandler,
     max_head_buffer_size: usize,
+    parsing_options: RequestParsingOptions,
+    max_request_body_size: Option<u64>,
     connection_timeout: Option<Duration>,
     read_timeout: Option<Duration>,
     keep_alive_timeout: Option<Duration>,
+    max_requests_per_connection: Option<u64>,
     request_body_io_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
     continue_handler: ContinueHandler,
@@ -94,10 +105,14 @@ impl Server {
       routers,
       error_handler,
       not_found_handler,
+      parse_error_handler,
       max_head_buffer_size,
+      parsing_options,
+      max_request_body_size,
       read_timeout,
       connection_timeout: connection_timeout.or(read_timeout),
       keep_alive_timeout: keep_alive_timeout.or(read_timeout),
+      max_requests_per_connection,
       request_body_io_timeout: request_body_io_timeout.or(read_timeout),
       write_timeout,
       continue_handler,
@@ -190,16 +205,43 @@ impl Server {
 
       stream.set_read_timeout(self.read_timeout)?;
 
-      let mut context = RequestContext::read(
+      let mut version = None;
+      let mut context = match RequestContext::read_with_options(
         stream.as_ref(),
         meta.as_ref().cloned(),
         self.max_head_buffer_size,
+        self.parsing_options,
         self.type_system.clone(),
-      )?;
+        &mut version,
+      ) {
+        Ok(context) => context,
+        Err(TiiError::IO(err)) if count > 0 && err.kind() == ErrorKind::UnexpectedEof => {
+          trace_log!("tii: Keep-alive client disconnected after sending empty lines.");
+          break;
+        }
+        Err(err) => {
+          self.write_parse_error_response(stream.as_ref(), &err, version);
+          return Err(err);
+        }
+      };
       count += 1;
 
-      if let Some(value) = context.get_header(HttpHeaderName::Expect) {
-        if value == "100-continue" && (self.continue_handler)(&mut context)? {
+      let mut body_too_large = false;
+      if let Some(body) = context.request_body() {
+        body.set_max_size(self.max_request_body_size)?;
+        body_too_large = body.is_too_large()?;
+      }
+
+      if context.get_header(HttpHeaderName::Expect) == Some("100-continue") {
+        if body_too_large {
+          // The client is not asked to send a body that is already known to be too large.
+          // The router answers with 413 unless the route allows a larger body.
+          trace_log!(
+            "tii: Request {} request body too large, not sending 100 Continue",
+            context.id()
+          );
+          context.force_connection_close();
+        } else if (self.continue_handler)(&mut context)? {
           match context.get_version() {
             HttpVersion::Http10 => _ = stream.write("HTTP/1.0 100 Continue\r\n\r\n".as_bytes())?,
             HttpVersion::Http11 => _ = stream.write("HTTP/1.1 100 Continue\r\n\r\n".as_bytes())?,
@@ -226,7 +268,7 @@ impl Server {
           match router.serve_websocket(stream.as_ref(), &mut context)? {
             RouterWebSocketServingResponse::HandledWithProtocolSwitch => return Ok(()),
             RouterWebSocketServingResponse::HandledWithoutProtocolSwitch(response) => {
-              self.write_response(stream.as_ref(), context, false, response)?;
+              self.write_response(stream.as_ref(), context, false, count, response)?;
               return Ok(());
             }
             RouterWebSocketServingResponse::NotHandled => (), // Next router please
@@ -240,21 +282,18 @@ impl Server {
             .unwrap_or_else(|e| self.fallback_error_handler(&mut context, e)),
         };
 
-        self.write_response(stream.as_ref(), context, false, response)?;
+        self.write_response(stream.as_ref(), context, false, count, response)?;
         return Ok(());
       }
 
       // Will we do keep alive?
       let mut keep_alive = !self.is_shutdown()
-          // is this http 1.1 because earlier does not support it.
-          && context.get_version() == HttpVersion::Http11
           // Do we have a keep alive timeout that is not zero?
           && self.keep_alive_timeout.as_ref().map(|a| !a.is_zero()).unwrap_or(true)
-          // did the client tell us not to do keep alive?
-          && context
-            .get_header(&HttpHeaderName::Connection)
-            .map(|e| e.eq_ignore_ascii_case("keep-alive"))
-            .unwrap_or_default();
+          // Did the connection already serve the maximum number of requests?
+          && self.max_requests_per_connection.map(|max| count < max).unwrap_or(true)
+          // Does the client want to keep the connection open?
+          && client_wants_keep_alive(&context);
 
       let mut response = None;
       for router in self.routers.iter() {
@@ -274,15 +313,27 @@ impl Server {
           .unwrap_or_else(|e| self.fallback_error_handler(&mut context, e)),
       });
 
+      let response = apply_conditional_request(&context, response)
+        .and_then(|response| apply_range_request(&context, response))
+        .unwrap_or_else(|error| {
+          (self.error_handler)(&mut context, error)
+            .unwrap_or_else(|e| self.fallback_error_handler(&mut context, e))
+        });
+
       if response.omit_body {
         context.force_connection_close();
       }
 
       keep_alive &= !context.is_connection_close_forced();
 
+      // A body without a length that cannot be sent in chunks is delimited by closing the connection.
+      keep_alive &= !response
+        .get_body()
+        .is_some_and(|body| body.requires_connection_close(context.get_version()));
+
       let id = context.id();
 
-      self.write_response(stream.as_ref(), context, keep_alive, response)?;
+      self.write_response(stream.as_ref(), context, keep_alive, count, response)?;
 
       // Can we do keep alive?
       if !keep_alive {
@@ -338,6 +389,11 @@ impl Server {
     }
   }
 
+  /// Maximum size of request bodies in bytes, unless overridden by a route.
+  pub fn max_request_body_size(&self) -> Option<u64> {
+    self.max_request_body_size
+  }
+
   /// Timeout until bytes from, for example, the headers/status line etc. are read.
   pub fn read_timeout(&self) -> Option<Duration> {
     self.read_timeout
@@ -358,6 +414,38 @@ impl Server {
     self.keep_alive_timeout
   }
 
+  /// Returns true if requests are parsed in strict mode.
+  pub fn strict_parsing(&self) -> bool {
+    self.parsing_options.strict
+  }
+
+  /// Returns the oldest http version that is accepted.
+  pub fn min_http_version(&self) -> HttpVersion {
+    self.parsing_options.min_version
+  }
+
+  /// Returns the maximum number of requests served on a single connection. None means unlimited.
+  pub fn max_requests_per_connection(&self) -> Option<u64> {
+    self.max_requests_per_connection
+  }
+
+  /// Returns the value of the `Keep-Alive` response header or None if there is nothing to hint at.
+  /// `count` is the number of requests served on the connection including the current one.
+  fn keep_alive_hint(&self, count: u64) -> Option<String> {
+    let mut params = Vec::new();
+    if let Some(timeout) = self.keep_alive_timeout {
+      params.push(format!("timeout={}", timeout.as_secs()));
+    }
+    if let Some(max) = self.max_requests_per_connection {
+      params.push(format!("max={}", max.saturating_sub(count)));
+    }
+
+    if params.is_empty() {
+      return None;
+    }
+    Some(params.join(", "))
+  }
+
   /// Returns the read timeout during reading of a request body
   pub fn request_body_io_timeout(&self) -> Option<Duration> {
     self.request_body_io_timeout
@@ -368,14 +456,18 @@ impl Server {
     stream: &dyn ConnectionStream,
     request: RequestContext,
     keep_alive: bool,
+    count: u64,
     mut response: Response,
   ) -> TiiResult<()> {
-    if request.get_version() == HttpVersion::Http11 {
-      let previous_headers = if keep_alive {
-        response.headers.replace_all(HttpHeaderName::Connection, "Keep-Alive")
-      } else {
-        response.headers.replace_all(HttpHeaderName::Connection, "Close")
-      };
+    let connection = match request.get_version() {
+      HttpVersion::Http11 if !keep_alive => Some("Close"),
+      HttpVersion::Http11 | HttpVersion::Http10 if keep_alive => Some("Keep-Alive"),
+      // Http 1.0 connections are closed by default.
+      _ => None,
+    };
+
+    if let Some(connection) = connection {
+      let previous_headers = response.headers.replace_all(HttpHeaderName::Connection, connection);
 
       if !previous_headers.is_empty() {
         error_log!(
@@ -388,6 +480,12 @@ impl Server {
           "Endpoint has set banned header 'Connection'",
         ));
       }
+
+      if keep_alive {
+        if let Some(hint) = self.keep_alive_hint(count) {
+          response.headers.replace_all(HttpHeaderName::KeepAlive, hint);
+        }
+      }
     }
 
     trace_log!(
@@ -396,9 +494,9 @@ impl Server {
       response.status_code.code()
     );
 
-    if let Some(enc) = response.get_body().and_then(|a| a.get_content_encoding()) {
-      if enc == "gzip" && !request.accepts_gzip() {
-        warn_log!("tii: Request {} responding with gzip even tho client doesnt indicate that it can understand gzip.", request.id());
+    if let Some(enc) = response.get_body().and_then(|a| a.content_coding()) {
+      if !request.accepts_encoding(enc) {
+        warn_log!("tii: Request {} responding with {} even tho client doesnt indicate that it can understand it.", request.id(), enc);
       }
     }
 
@@ -417,7 +515,7 @@ impl Server {
         .duration_since(std::time::SystemTime::UNIX_EPOCH)
         .map(|a| a.as_millis())
         .unwrap_or_default();
-      let diff = now.checked_sub(request.get_timestamp()).unwrap_or_default();
+      let diff = now.saturating_sub(request.get_timestamp());
       crate::info_log!(
         "tii: Request {} from {} to {} {} ({}) served in {}ms",
         request.id(),
@@ -429,10 +527,45 @@ impl Server {
       );
     }
 
-    request.consume_request_body()?;
+    request.consume_request_body().or_else(|err| {
+      if request.is_connection_close_forced() && is_too_large_error(&err) {
+        // No point in reading the rest of a body we refused, the connection is closed anyway.
+        trace_log!("tii: Request {} request body too large, not consuming it", request.id());
+        return Ok(());
+      }
+      Err(err)
+    })?;
     Ok(())
   }
 
+  /// Responds to a request whose head could not be read. The connection is closed afterward.
+  /// The response uses the http version of the request if its status line could be parsed, otherwise http 1.1.
+  /// Http 0.9 responses have no status line, so requests of that version are also answered with http 1.1.
+  fn write_parse_error_response(
+    &self,
+    stream: &dyn ConnectionStream,
+    error: &TiiError,
+    version: Option<HttpVersion>,
+  ) {
+    let mut response = match (self.parse_error_handler)(error) {
+      Ok(Some(response)) => response,
+      Ok(None) => return,
+      Err(err) => {
+        error_log!("tii: Parse error handler failed for {:?}. Closing connection {:?}", error, err);
+        return;
+      }
+    };
+
+    response.headers.replace_all(HttpHeaderName::Connection, "Close");
+    trace_log!("tii: Responding to unparseable request with HTTP {}", response.status_code.code());
+    let version =
+      version.filter(|version| *version != HttpVersion::Http09).unwrap_or(HttpVersion::Http11);
+    //The client may not read the response if it is still sending, the connection is closed regardless.
+    if let Err(err) = response.write_to(crate::util::next_id(), version, stream.as_stream_write()) {
+      trace_log!("tii: Failed to write response to unparseable request {}", err);
+    }
+  }
+
   fn fallback_error_handler(&self, request: &mut RequestContext, error: TiiError) -> Response {
     request.force_connection_close();
 
@@ -448,6 +581,20 @@ impl Server {
   }
 }
 
+/// Http 1.1 connections are persistent unless the client sends "Connection: close",
+/// Http 1.0 clients have to ask for a persistent connection with "Connection: keep-alive".
+/// The Connection header is a comma separated list of tokens, for example "keep-alive, Upgrade".
+fn client_wants_keep_alive(request: &RequestContext) -> bool {
+  let connection = request.get_headers(HttpHeaderName::Connection);
+  let has_token = |token: &str| has_header_token(&connection, token);
+
+  match request.get_version() {
+    HttpVersion::Http11 => !has_token("close"),
+    HttpVersion::Http10 => has_token("keep-alive") && !has_token("close"),
+    _ => false,
+  }
+}
+
 impl Drop for Server {
   fn drop(&mut self) {
     self.shutdown();
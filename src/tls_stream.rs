@@ -2,8 +2,9 @@ use crate::functional_traits::{DefaultThreadAdapter, ThreadAdapter};
 use crate::stream::{ConnectionStream, ConnectionStreamRead, ConnectionStreamWrite};
 use crate::util::unwrap_poison;
 use rust_tls_duplex_stream::RustTlsDuplexStream;
+use rustls::client::ClientConnectionData;
 use rustls::server::ServerConnectionData;
-use rustls::ServerConnection;
+use rustls::{ClientConnection, ServerConnection};
 use std::fmt::Debug;
 use std::io;
 use std::io::{Read, Write};
@@ -166,8 +167,6 @@ impl TlsStream {
     tls: ServerConnection,
     spawner: &dyn ThreadAdapter,
   ) -> io::Result<Box<dyn ConnectionStream>> {
-    let peer = stream.peer_addr()?.to_string();
-    let local = stream.local_addr()?.to_string();
     let stream_wrapper = StreamWrapper(Arc::new(stream));
     let tls = RustTlsDuplexStream::new_with_initial_data(
       tls,
@@ -180,6 +179,43 @@ impl TlsStream {
       initial_data.to_vec(),
     )?;
 
+    Self::wrap(stream_wrapper, TlsEngine::Server(tls))
+  }
+
+  /// Create a new TlsStream for the client side of a connection using the given tcp stream.
+  /// Calling this fn will create 2 background threads using `thread::Builder::new()::spawn`
+  /// The threads are automatically stopped if the returned ConnectionStream is dropped.
+  pub fn create_client_unpooled<S: TlsCapableStream + 'static>(
+    tcp: S,
+    tls: ClientConnection,
+  ) -> io::Result<Box<dyn ConnectionStream>> {
+    Self::create_client(tcp, tls, &DefaultThreadAdapter)
+  }
+
+  /// Create a new TlsStream for the client side of a connection using the given tcp stream.
+  /// Calling this fn will create 2 background threads using the provided thread spawn function.
+  /// The tasks automatically return if the returned ConnectionStream is dropped.
+  pub fn create_client<S: TlsCapableStream + 'static>(
+    stream: S,
+    tls: ClientConnection,
+    spawner: &dyn ThreadAdapter,
+  ) -> io::Result<Box<dyn ConnectionStream>> {
+    let stream_wrapper = StreamWrapper(Arc::new(stream));
+    let tls =
+      RustTlsDuplexStream::new(tls, stream_wrapper.clone(), stream_wrapper.clone(), move |task| {
+        spawner.spawn(task)?;
+        Ok(())
+      })?;
+
+    Self::wrap(stream_wrapper, TlsEngine::Client(tls))
+  }
+
+  fn wrap<S: TlsCapableStream + 'static>(
+    stream_wrapper: StreamWrapper<S>,
+    tls: TlsEngine,
+  ) -> io::Result<Box<dyn ConnectionStream>> {
+    let peer = stream_wrapper.0.peer_addr()?.to_string();
+    let local = stream_wrapper.0.local_addr()?.to_string();
     Ok(Box::new(Self(Arc::new(TlsWrapperInner {
       stream_ref: stream_wrapper.0 as Arc<_>,
       tls,
@@ -191,10 +227,72 @@ impl TlsStream {
   }
 }
 
+/// The server or client side of a tls connection.
+#[derive(Debug)]
+enum TlsEngine {
+  Server(RustTlsDuplexStream<ServerConnection, ServerConnectionData>),
+  Client(RustTlsDuplexStream<ClientConnection, ClientConnectionData>),
+}
+
+impl TlsEngine {
+  fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+    match self {
+      TlsEngine::Server(tls) => tls.set_read_timeout(dur),
+      TlsEngine::Client(tls) => tls.set_read_timeout(dur),
+    }
+  }
+
+  fn read_timeout(&self) -> io::Result<Option<Duration>> {
+    match self {
+      TlsEngine::Server(tls) => tls.read_timeout(),
+      TlsEngine::Client(tls) => tls.read_timeout(),
+    }
+  }
+
+  fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+    match self {
+      TlsEngine::Server(tls) => tls.set_write_timeout(dur),
+      TlsEngine::Client(tls) => tls.set_write_timeout(dur),
+    }
+  }
+
+  fn write_timeout(&self) -> io::Result<Option<Duration>> {
+    match self {
+      TlsEngine::Server(tls) => tls.write_timeout(),
+      TlsEngine::Client(tls) => tls.write_timeout(),
+    }
+  }
+}
+
+impl Read for &TlsEngine {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    match self {
+      TlsEngine::Server(tls) => tls.read(buf),
+      TlsEngine::Client(tls) => tls.read(buf),
+    }
+  }
+}
+
+impl Write for &TlsEngine {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match self {
+      TlsEngine::Server(tls) => tls.write(buf),
+      TlsEngine::Client(tls) => tls.write(buf),
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    match self {
+      TlsEngine::Server(tls) => tls.flush(),
+      TlsEngine::Client(tls) => tls.flush(),
+    }
+  }
+}
+
 #[derive(Debug)]
 struct TlsWrapperInner {
   stream_ref: Arc<dyn TlsCapableStream>,
-  tls: RustTlsDuplexStream<ServerConnection, ServerConnectionData>,
+  tls: TlsEngine,
   read: Mutex<UnownedReadBuffer<0x4000>>,
   write: Mutex<UnownedWriteBuffer<0x4000>>,
   peer: String,
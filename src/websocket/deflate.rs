@@ -0,0 +1,336 @@
+//! Provides the `permessage-deflate` extension as specified in [RFC 7692](https://datatracker.ietf.org/doc/html/rfc7692).
+
+use crate::util::unwrap_some;
+use libflate::lz77::DefaultLz77Encoder;
+use libflate::non_blocking::deflate::Decoder;
+use libflate::zlib::{EncodeOptions, Encoder, FlushMode};
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+const EXTENSION_NAME: &str = "permessage-deflate";
+
+/// The empty uncompressed block that terminates every compressed message.
+/// It is removed by the sender and appended again by the receiver.
+const MESSAGE_TAIL: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+/// Length of the zlib header that precedes the deflate data written by libflate.
+const ZLIB_HEADER_LENGTH: usize = 2;
+
+/// Maximum size of a decompressed message if neither a maximum message size nor a maximum frame size is configured.
+/// A few KB of compressed data can decompress to gigabytes, decompression is therefore never unlimited.
+pub(crate) const DEFAULT_MAX_INFLATED_SIZE: u64 = 0x100_0000;
+
+const MIN_WINDOW_BITS: u8 = 8;
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// Configuration of the `permessage-deflate` extension for a websocket route.
+///
+/// The extension is only used if the client offers it during the handshake.
+/// The default accepts any offer of the client without further restrictions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerMessageDeflate {
+  server_no_context_takeover: bool,
+  client_no_context_takeover: bool,
+  server_max_window_bits: Option<u8>,
+  client_max_window_bits: Option<u8>,
+}
+
+impl PerMessageDeflate {
+  /// Creates a new configuration which accepts any offer of the client.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Tells the client that the server compresses every message on its own.
+  /// The server never refers to earlier messages, this parameter only informs the client that it
+  /// does not have to keep its decompression context between messages.
+  pub fn with_server_no_context_takeover(mut self, enabled: bool) -> Self {
+    self.server_no_context_takeover = enabled;
+    self
+  }
+
+  /// Requires the client to compress every message on its own.
+  /// The server then does not have to keep its decompression context between messages.
+  pub fn with_client_no_context_takeover(mut self, enabled: bool) -> Self {
+    self.client_no_context_takeover = enabled;
+    self
+  }
+
+  /// Limits the LZ77 window the server uses to compress messages to 2^bits bytes.
+  /// Values outside 8..=15 are clamped. None uses the window size offered by the client or 15.
+  pub fn with_server_max_window_bits(mut self, bits: Option<u8>) -> Self {
+    self.server_max_window_bits = bits.map(|bits| bits.clamp(MIN_WINDOW_BITS, MAX_WINDOW_BITS));
+    self
+  }
+
+  /// Limits the LZ77 window the client may use to compress messages to 2^bits bytes.
+  /// This only has an effect if the client indicates that it supports the parameter.
+  /// Values outside 8..=15 are clamped. None lets the client choose.
+  pub fn with_client_max_window_bits(mut self, bits: Option<u8>) -> Self {
+    self.client_max_window_bits = bits.map(|bits| bits.clamp(MIN_WINDOW_BITS, MAX_WINDOW_BITS));
+    self
+  }
+}
+
+/// The parameters of the extension that were agreed on during the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DeflateParameters {
+  server_no_context_takeover: bool,
+  client_no_context_takeover: bool,
+  server_max_window_bits: Option<u8>,
+  client_max_window_bits: Option<u8>,
+}
+
+fn parse_window_bits(value: &str) -> Option<u8> {
+  let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+  if value.is_empty() || value.starts_with('0') || !value.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+
+  value.parse::<u8>().ok().filter(|bits| (MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(bits))
+}
+
+fn min_window_bits(offered: Option<u8>, configured: Option<u8>) -> Option<u8> {
+  match (offered, configured) {
+    (Some(offered), Some(configured)) => Some(offered.min(configured)),
+    (offered, configured) => offered.or(configured),
+  }
+}
+
+impl DeflateParameters {
+  /// Picks the first offer in the `Sec-WebSocket-Extensions` headers of the client that can be accepted.
+  /// Offers with unknown, duplicate or invalid parameters are declined as required by RFC 7692.
+  pub(crate) fn negotiate(config: &PerMessageDeflate, header_values: &[&str]) -> Option<Self> {
+    header_values
+      .iter()
+      .flat_map(|value| value.split(','))
+      .find_map(|offer| Self::accept_offer(config, offer))
+  }
+
+  fn accept_offer(config: &PerMessageDeflate, offer: &str) -> Option<Self> {
+    let mut params = offer.split(';').map(str::trim);
+    if !params.next()?.eq_ignore_ascii_case(EXTENSION_NAME) {
+      return None;
+    }
+
+    let mut server_no_context_takeover = false;
+    let mut client_no_context_takeover = false;
+    let mut server_max_window_bits = None;
+    //Outer option: was the parameter offered, inner option: did it have a value.
+    let mut client_max_window_bits: Option<Option<u8>> = None;
+
+    for param in params {
+      let (name, value) = match param.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (param, None),
+      };
+
+      match (name, value) {
+        ("server_no_context_takeover", None) if !server_no_context_takeover => {
+          server_no_context_takeover = true
+        }
+        ("client_no_context_takeover", None) if !client_no_context_takeover => {
+          client_no_context_takeover = true
+        }
+        ("server_max_window_bits", Some(value)) if server_max_window_bits.is_none() => {
+          server_max_window_bits = Some(parse_window_bits(value)?)
+        }
+        ("client_max_window_bits", None) if client_max_window_bits.is_none() => {
+          client_max_window_bits = Some(None)
+        }
+        ("client_max_window_bits", Some(value)) if client_max_window_bits.is_none() => {
+          client_max_window_bits = Some(Some(parse_window_bits(value)?))
+        }
+        _ => return None,
+      }
+    }
+
+    Some(Self {
+      server_no_context_takeover: server_no_context_takeover || config.server_no_context_takeover,
+      client_no_context_takeover: client_no_context_takeover || config.client_no_context_takeover,
+      server_max_window_bits: min_window_bits(
+        server_max_window_bits,
+        config.server_max_window_bits,
+      ),
+      //The server must not send this parameter if the client did not offer it.
+      client_max_window_bits: client_max_window_bits
+        .and_then(|offered| min_window_bits(offered, config.client_max_window_bits)),
+    })
+  }
+
+  /// Returns the value of the `Sec-WebSocket-Extensions` response header.
+  pub(crate) fn to_header_value(self) -> String {
+    let mut value = EXTENSION_NAME.to_string();
+    if self.server_no_context_takeover {
+      value.push_str("; server_no_context_takeover");
+    }
+    if self.client_no_context_takeover {
+      value.push_str("; client_no_context_takeover");
+    }
+    if let Some(bits) = self.server_max_window_bits {
+      value.push_str(&format!("; server_max_window_bits={bits}"));
+    }
+    if let Some(bits) = self.client_max_window_bits {
+      value.push_str(&format!("; client_max_window_bits={bits}"));
+    }
+    value
+  }
+
+  /// The window size the server compresses its messages with.
+  pub(crate) fn server_window_bits(self) -> u8 {
+    self.server_max_window_bits.unwrap_or(MAX_WINDOW_BITS)
+  }
+
+  pub(crate) fn client_no_context_takeover(self) -> bool {
+    self.client_no_context_takeover
+  }
+}
+
+/// Compresses the fragments of a message.
+///
+/// libflate never refers to data from before the last flush, so every message is compressed
+/// on its own. This is valid regardless of whether `server_no_context_takeover` was negotiated.
+pub(crate) struct MessageDeflater {
+  encoder: Encoder<Vec<u8>>,
+  first: bool,
+}
+
+impl Debug for MessageDeflater {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!("MessageDeflater(first={})", self.first))
+  }
+}
+
+impl MessageDeflater {
+  pub(crate) fn new(window_bits: u8) -> io::Result<Self> {
+    let lz77 = DefaultLz77Encoder::with_window_size(1 << window_bits);
+    let options = EncodeOptions::with_lz77(lz77).flush_mode(FlushMode::Sync);
+    Ok(Self { encoder: Encoder::with_options(Vec::new(), options)?, first: true })
+  }
+
+  /// Compresses the next fragment of the message. `fin` must be true for the last fragment.
+  pub(crate) fn compress_fragment(&mut self, fragment: &[u8], fin: bool) -> io::Result<Vec<u8>> {
+    self.encoder.write_all(fragment)?;
+    self.encoder.flush()?;
+
+    let mut compressed = std::mem::take(self.encoder.as_inner_mut());
+    let header_length = if self.first { ZLIB_HEADER_LENGTH } else { 0 };
+    self.first = false;
+    if !compressed.ends_with(&MESSAGE_TAIL) || compressed.len() < header_length + 4 {
+      return Err(io::Error::other("deflate encoder did not produce a sync flush"));
+    }
+    if fin {
+      compressed.truncate(compressed.len() - MESSAGE_TAIL.len());
+    }
+    compressed.drain(..header_length);
+    Ok(compressed)
+  }
+}
+
+/// Compresses the payload of a message that is sent as a single frame.
+pub(crate) fn deflate_message(payload: &[u8], window_bits: u8) -> io::Result<Vec<u8>> {
+  MessageDeflater::new(window_bits)?.compress_fragment(payload, true)
+}
+
+/// Input of the decompressor, reports WouldBlock once the received data is used up.
+#[derive(Debug, Default)]
+struct InflaterInput(VecDeque<u8>);
+
+impl Read for InflaterInput {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.0.is_empty() && !buf.is_empty() {
+      return Err(io::Error::from(ErrorKind::WouldBlock));
+    }
+    self.0.read(buf)
+  }
+}
+
+/// Error of `Inflater::inflate_fragment`.
+#[derive(Debug)]
+pub(crate) enum InflateError {
+  /// The compressed data is invalid.
+  Invalid(io::Error),
+  /// The decompressed message would be larger than this many bytes.
+  TooBig(u64),
+}
+
+/// Decompresses the messages received from the client.
+/// The decompression context is kept between messages unless `client_no_context_takeover` was negotiated.
+pub(crate) struct Inflater {
+  decoder: Decoder<InflaterInput>,
+  no_context_takeover: bool,
+  /// The client ended the deflate stream with a final block, the next message starts a new stream.
+  finished: bool,
+  /// Decompressed size of the current message.
+  inflated: u64,
+}
+
+impl Debug for Inflater {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!("Inflater(no_context_takeover={})", self.no_context_takeover))
+  }
+}
+
+impl Inflater {
+  pub(crate) fn new(parameters: DeflateParameters) -> Self {
+    Self {
+      decoder: Decoder::new(InflaterInput::default()),
+      no_context_takeover: parameters.client_no_context_takeover(),
+      finished: false,
+      inflated: 0,
+    }
+  }
+
+  /// Prepares the decompression of the next message.
+  pub(crate) fn begin_message(&mut self) {
+    if self.no_context_takeover || self.finished {
+      self.decoder = Decoder::new(InflaterInput::default());
+      self.finished = false;
+    }
+    self.decoder.as_inner_mut().0.clear();
+    self.inflated = 0;
+  }
+
+  /// Decompresses the payload of the next frame of a message. `fin` must be true for the last frame.
+  /// The decompressed message must not be larger than `max_size`.
+  pub(crate) fn inflate_fragment(
+    &mut self,
+    payload: &[u8],
+    fin: bool,
+    max_size: u64,
+  ) -> Result<Vec<u8>, InflateError> {
+    let input = &mut self.decoder.as_inner_mut().0;
+    input.extend(payload);
+    if fin {
+      input.extend(MESSAGE_TAIL);
+    }
+
+    let mut fragment = Vec::new();
+    let mut buffer = [0u8; 0x4000];
+    loop {
+      let count = match self.decoder.read(&mut buffer) {
+        Ok(0) => {
+          self.finished = true;
+          break;
+        }
+        Ok(count) => count,
+        Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+        Err(err) => {
+          self.finished = true;
+          return Err(InflateError::Invalid(err));
+        }
+      };
+
+      self.inflated = self.inflated.saturating_add(count as u64);
+      if self.inflated > max_size {
+        self.finished = true;
+        return Err(InflateError::TooBig(self.inflated));
+      }
+      fragment.extend_from_slice(unwrap_some(buffer.get(..count)));
+    }
+
+    Ok(fragment)
+  }
+}
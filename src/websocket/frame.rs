@@ -82,7 +82,17 @@ impl Frame {
   }
 
   /// Attempts to read a frame from the given stream, blocking until the frame is read.
+  #[cfg(test)]
   pub fn from_stream<T: ConnectionStreamRead + ?Sized>(stream: &T) -> TiiResult<Self> {
+    let mut frame = Self::header_from_stream(stream)?;
+    frame.read_payload(stream)?;
+    Ok(frame)
+  }
+
+  /// Attempts to read only the header of a frame from the given stream.
+  /// The returned frame has an empty payload, `read_payload` must be called to read it.
+  /// This allows the caller to inspect the length before any memory for the payload is allocated.
+  pub fn header_from_stream<T: ConnectionStreamRead + ?Sized>(stream: &T) -> TiiResult<Self> {
     let mut header: [u8; 2] = [0; 2];
     stream.read_exact(&mut header)?;
 
@@ -111,18 +121,33 @@ impl Frame {
       buf
     };
 
+    Ok(Self { fin, rsv, opcode, mask, length, masking_key, payload: Vec::new() })
+  }
+
+  /// Reads and unmasks the payload of a frame whose header was read by `header_from_stream`.
+  pub fn read_payload<T: ConnectionStreamRead + ?Sized>(&mut self, stream: &T) -> TiiResult<()> {
+    let length = usize::try_from(self.length)
+      .map_err(|_| RequestHeadParsingError::WebSocketMessageTooBig(self.length))?;
+
     // Read the payload
-    let mut payload: Vec<u8> = vec![0; length as usize];
+    let mut payload: Vec<u8> = vec![0; length];
     stream.read_exact(&mut payload)?;
 
     // Unmask the payload
+    let masking_key = self.masking_key;
     payload
       .iter_mut()
       .enumerate()
       // n % 4 is always 0-3
       .for_each(|(i, tem)| *tem ^= util::unwrap_some(masking_key.get(i % 4)));
 
-    Ok(Self { fin, rsv, opcode, mask, length, masking_key, payload })
+    self.payload = payload;
+    Ok(())
+  }
+
+  /// Returns true if this frame is a control frame (Close, Ping or Pong).
+  pub fn is_control(&self) -> bool {
+    (self.opcode as u8) & 0x8 != 0
   }
 
   pub fn write_to<T: ConnectionStreamWrite + ?Sized>(self, write: &T) -> TiiResult<()> {
@@ -11,6 +11,9 @@ pub enum WebsocketMessage {
   Ping,
   /// Pong message
   Pong,
+  /// Close message. Contains the status code and reason if the close frame had a payload.
+  /// Receiving it means the peer closed the web socket, sending it closes the web socket.
+  Close(Option<(u16, String)>),
 }
 
 impl WebsocketMessage {
@@ -27,6 +30,11 @@ impl WebsocketMessage {
     Self::Text(str.to_string())
   }
 
+  /// Creates a new close message with the given status code and reason.
+  pub fn new_close(code: u16, reason: impl ToString) -> Self {
+    Self::Close(Some((code, reason.to_string())))
+  }
+
   /// Returns whether the sender of this message specified that it contains text.
   pub fn is_text(&self) -> bool {
     matches!(self, Self::Text(_))
@@ -48,6 +56,7 @@ impl WebsocketMessage {
       WebsocketMessage::Binary(bin) => Some(bin.as_slice()),
       WebsocketMessage::Ping => None,
       WebsocketMessage::Pong => None,
+      WebsocketMessage::Close(_) => None,
     }
   }
 }
@@ -4,9 +4,11 @@
 
 #![warn(missing_docs)]
 
+pub(crate) mod deflate;
+pub use deflate::PerMessageDeflate;
 mod message;
 pub use message::*;
 mod ws_stream;
 pub use ws_stream::*;
 
-mod frame;
+pub(crate) mod frame;
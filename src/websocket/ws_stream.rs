@@ -1,5 +1,9 @@
 //! Provides functionality for working with a WebSocket stream.
 
+use crate::websocket::deflate::{
+  deflate_message, DeflateParameters, InflateError, Inflater, MessageDeflater,
+  DEFAULT_MAX_INFLATED_SIZE,
+};
 use crate::websocket::frame::{Frame, Opcode};
 use crate::websocket::message::WebsocketMessage;
 use std::collections::VecDeque;
@@ -12,14 +16,100 @@ use crate::{error_log, trace_log, warn_log};
 use std::io::{Cursor, ErrorKind, Read, Write};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::SeqCst;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Close status code 1001 as specified in [RFC 6455 Section 7.4.1](https://datatracker.ietf.org/doc/html/rfc6455#section-7.4.1).
+const CLOSE_GOING_AWAY: u16 = 1001;
+/// Close status code 1002 as specified in [RFC 6455 Section 7.4.1](https://datatracker.ietf.org/doc/html/rfc6455#section-7.4.1).
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+/// Close status code 1007 as specified in [RFC 6455 Section 7.4.1](https://datatracker.ietf.org/doc/html/rfc6455#section-7.4.1).
+const CLOSE_INVALID_PAYLOAD: u16 = 1007;
+/// Close status code 1009 as specified in [RFC 6455 Section 7.4.1](https://datatracker.ietf.org/doc/html/rfc6455#section-7.4.1).
+const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+/// Close status code 1011 as specified in [RFC 6455 Section 7.4.1](https://datatracker.ietf.org/doc/html/rfc6455#section-7.4.1).
+const CLOSE_INTERNAL_ERROR: u16 = 1011;
+
+/// The payload of a close frame is limited to 125 bytes, 2 of which are the status code.
+const MAX_CLOSE_REASON_LENGTH: usize = 123;
+
+/// Returns true if the status code may appear in a close frame.
+/// 1004, 1005, 1006 and 1015 are reserved and must never be sent, 3000-4999 are for libraries and applications.
+fn is_valid_close_code(code: u16) -> bool {
+  matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+fn close_payload(code: u16, reason: &str) -> TiiResult<Vec<u8>> {
+  if !is_valid_close_code(code) {
+    return Err(TiiError::new_io(
+      ErrorKind::InvalidInput,
+      format!("{code} is not a valid web socket close code"),
+    ));
+  }
+  if reason.len() > MAX_CLOSE_REASON_LENGTH {
+    return Err(TiiError::new_io(
+      ErrorKind::InvalidInput,
+      format!("web socket close reason is longer than {MAX_CLOSE_REASON_LENGTH} bytes"),
+    ));
+  }
+
+  let mut payload = code.to_be_bytes().to_vec();
+  payload.extend_from_slice(reason.as_bytes());
+  Ok(payload)
+}
+
+/// Parses the payload of a received close frame.
+/// Err contains the status code the web socket should be closed with because the payload is malformed.
+fn parse_close_payload(payload: &[u8]) -> Result<Option<(u16, String)>, u16> {
+  let Some((code, reason)) = payload.split_first_chunk::<2>() else {
+    return if payload.is_empty() { Ok(None) } else { Err(CLOSE_PROTOCOL_ERROR) };
+  };
+
+  let code = u16::from_be_bytes(*code);
+  if !is_valid_close_code(code) {
+    return Err(CLOSE_PROTOCOL_ERROR);
+  }
+
+  let reason = std::str::from_utf8(reason).map_err(|_| CLOSE_INVALID_PAYLOAD)?;
+  Ok(Some((code, reason.to_string())))
+}
 
 #[derive(Debug)]
 struct WebSocketGuard {
   closed: AtomicBool,
+  /// Held while a text or binary message is sent so that the frames of messages are not interleaved.
+  /// Control frames only need the write mutex, they may be sent between the frames of a message.
+  message_mutex: Mutex<()>,
   write_mutex: Mutex<()>,
   stream: Box<dyn ConnectionStream>,
+  /// Set if `permessage-deflate` was negotiated during the handshake.
+  deflate: Option<DeflateParameters>,
+}
+
+impl WebSocketGuard {
+  /// Sends a close frame with the given payload unless a close frame was already sent.
+  fn close(&self, payload: Vec<u8>) -> TiiResult<()> {
+    let _g = unwrap_poison(self.write_mutex.lock())?;
+
+    if self.closed.swap(true, SeqCst) {
+      return Ok(()); //ALREADY CLOSED!
+    }
+
+    Frame::new(Opcode::Close, payload).write_to(self.stream.as_stream_write())
+  }
+
+  /// Sends a text or binary message, compressing it if `permessage-deflate` was negotiated.
+  fn send_message(&self, opcode: Opcode, payload: &[u8]) -> TiiResult<()> {
+    let _m = unwrap_poison(self.message_mutex.lock())?;
+    let _g = unwrap_poison(self.write_mutex.lock())?;
+    let Some(deflate) = self.deflate else {
+      return Frame::write_unowned_payload_frame(self.stream.as_stream_write(), opcode, payload);
+    };
+
+    let mut frame = Frame::new(opcode, deflate_message(payload, deflate.server_window_bits())?);
+    frame.rsv[0] = true;
+    frame.write_to(self.stream.as_stream_write())
+  }
 }
 
 /// Sending side of a web socket
@@ -30,20 +120,43 @@ pub struct WebsocketSender(Arc<WebSocketGuard>);
 /// Creates a new WebSocket receiver sender pair.
 pub fn new_web_socket_stream(
   connection: &dyn ConnectionStream,
+) -> (WebsocketSender, WebsocketReceiver) {
+  new_web_socket_stream_with_deflate(connection, None)
+}
+
+/// Creates a new WebSocket receiver sender pair which uses the `permessage-deflate` parameters
+/// that were negotiated during the handshake.
+pub(crate) fn new_web_socket_stream_with_deflate(
+  connection: &dyn ConnectionStream,
+  deflate: Option<DeflateParameters>,
 ) -> (WebsocketSender, WebsocketReceiver) {
   let guard = Arc::new(WebSocketGuard {
     closed: AtomicBool::new(false),
+    message_mutex: Mutex::new(()),
     write_mutex: Mutex::new(()),
     stream: connection.new_ref(),
+    deflate,
   });
 
   let sender = WebsocketSender(guard.clone());
 
+  let now = Instant::now();
   let receiver = WebsocketReceiver {
     guard,
     state: Vec::new(),
     cursor: Default::default(),
     unhandled_messages: Default::default(),
+    max_frame_size: None,
+    max_message_size: None,
+    inflater: deflate.map(Inflater::new),
+    streamed: None,
+    ping_interval: None,
+    pong_timeout: None,
+    idle_timeout: None,
+    last_frame: now,
+    last_message: now,
+    last_ping: None,
+    unanswered_ping: None,
   };
 
   (sender, receiver)
@@ -63,31 +176,69 @@ impl WebsocketSender {
       WebsocketMessage::Binary(bin) => self.binary(bin),
       WebsocketMessage::Ping => self.ping(),
       WebsocketMessage::Pong => self.pong(),
+      WebsocketMessage::Close(None) => self.close(),
+      WebsocketMessage::Close(Some((code, reason))) => self.close_with(code, reason),
     }
   }
 
   /// Closes the Websocket sending the close frame.
   pub fn close(&self) -> TiiResult<()> {
-    let _g = unwrap_poison(self.0.write_mutex.lock())?;
-
-    if self.0.closed.swap(true, SeqCst) {
-      return Ok(()); //ALREADY CLOSED!
-    }
+    self.0.close(Vec::new())
+  }
 
-    Frame::new(Opcode::Close, Vec::new()).write_to(self.0.stream.as_stream_write())
+  /// Closes the Websocket sending a close frame with the given status code and reason.
+  ///
+  /// # Errors
+  /// If the status code must not be sent according to RFC 6455, if the reason is longer than 123 bytes
+  /// or if writing the close frame fails.
+  pub fn close_with(&self, code: u16, reason: impl AsRef<str>) -> TiiResult<()> {
+    self.0.close(close_payload(code, reason.as_ref())?)
   }
 
   /// Sends a binary message to the client
   pub fn binary(&self, message: impl Into<Vec<u8>>) -> TiiResult<()> {
-    let _g = unwrap_poison(self.0.write_mutex.lock())?;
-    Frame::new(Opcode::Binary, message.into()).write_to(self.0.stream.as_stream_write())
+    self.0.send_message(Opcode::Binary, message.into().as_slice())
   }
 
   /// Sends a text message to the client
   pub fn text(&self, message: impl ToString) -> TiiResult<()> {
-    let _g = unwrap_poison(self.0.write_mutex.lock())?;
-    Frame::new(Opcode::Text, message.to_string().into_bytes())
-      .write_to(self.0.stream.as_stream_write())
+    self.0.send_message(Opcode::Text, message.to_string().as_bytes())
+  }
+
+  /// Starts a text message that is sent in fragments of `fragment_size` bytes.
+  /// The caller must only write valid UTF-8, the client closes the web socket otherwise.
+  /// See `WebsocketMessageWriter` for details.
+  pub fn text_writer(&self, fragment_size: usize) -> TiiResult<WebsocketMessageWriter<'_>> {
+    self.message_writer(Opcode::Text, fragment_size)
+  }
+
+  /// Starts a binary message that is sent in fragments of `fragment_size` bytes.
+  /// See `WebsocketMessageWriter` for details.
+  pub fn binary_writer(&self, fragment_size: usize) -> TiiResult<WebsocketMessageWriter<'_>> {
+    self.message_writer(Opcode::Binary, fragment_size)
+  }
+
+  fn message_writer(
+    &self,
+    opcode: Opcode,
+    fragment_size: usize,
+  ) -> TiiResult<WebsocketMessageWriter<'_>> {
+    let message_guard = unwrap_poison(self.0.message_mutex.lock())?;
+    let deflater = self
+      .0
+      .deflate
+      .map(|deflate| MessageDeflater::new(deflate.server_window_bits()))
+      .transpose()?;
+
+    Ok(WebsocketMessageWriter {
+      guard: &self.0,
+      _message_guard: message_guard,
+      opcode,
+      fragment_size: fragment_size.max(1),
+      buffer: Vec::new(),
+      deflater,
+      finished: false,
+    })
   }
 
   /// Sends a ping to the client.
@@ -99,7 +250,7 @@ impl WebsocketSender {
   /// Sends a pong message to the client.
   pub fn pong(&self) -> TiiResult<()> {
     let _g = unwrap_poison(self.0.write_mutex.lock())?;
-    Frame::new(Opcode::Ping, Vec::new()).write_to(self.0.stream.as_stream_write())
+    Frame::new(Opcode::Pong, Vec::new()).write_to(self.0.stream.as_stream_write())
   }
 
   /// Attempts to get the peer address of this stream.
@@ -115,6 +266,31 @@ pub struct WebsocketReceiver {
   state: Vec<Frame>,
   cursor: Cursor<Vec<u8>>,
   unhandled_messages: VecDeque<WebsocketMessage>,
+  max_frame_size: Option<u64>,
+  max_message_size: Option<u64>,
+  /// Set if `permessage-deflate` was negotiated during the handshake.
+  inflater: Option<Inflater>,
+  /// Set while a message is read with `read_message_stream`.
+  streamed: Option<StreamedMessage>,
+  ping_interval: Option<Duration>,
+  pong_timeout: Option<Duration>,
+  idle_timeout: Option<Duration>,
+  /// When the last frame of any kind was received.
+  last_frame: Instant,
+  /// When the last text, binary or continuation frame was received.
+  last_message: Instant,
+  /// When the last ping was sent, reset when a frame is received.
+  last_ping: Option<Instant>,
+  /// When the first ping was sent that the client has not answered yet.
+  unanswered_ping: Option<Instant>,
+}
+
+/// State of a message that is read frame by frame.
+#[derive(Debug, Clone, Copy)]
+struct StreamedMessage {
+  /// Sum of the payload sizes of the frames received so far.
+  size: u64,
+  compressed: bool,
 }
 
 /// Return enum for the fn WebsocketReceiver::read_message_timeout
@@ -131,13 +307,76 @@ pub enum ReadMessageTimeoutResult {
 impl WebsocketReceiver {
   /// Closes the Websocket sending the close frame to the client.
   pub fn close(&self) -> TiiResult<()> {
-    let _g = unwrap_poison(self.guard.write_mutex.lock())?;
+    self.guard.close(Vec::new())
+  }
 
-    if self.guard.closed.swap(true, SeqCst) {
-      return Ok(()); //ALREADY CLOSED!
-    }
+  /// Closes the Websocket sending a close frame with the given status code and reason to the client.
+  ///
+  /// # Errors
+  /// If the status code must not be sent according to RFC 6455, if the reason is longer than 123 bytes
+  /// or if writing the close frame fails.
+  pub fn close_with(&self, code: u16, reason: impl AsRef<str>) -> TiiResult<()> {
+    self.guard.close(close_payload(code, reason.as_ref())?)
+  }
+
+  /// Sets the maximum payload size of a single frame the client may send.
+  /// If the client sends a larger frame then the web socket is closed with status 1009 (Message Too Big)
+  /// before the payload is read. None means unlimited.
+  pub fn set_max_frame_size(&mut self, max_frame_size: Option<u64>) {
+    self.max_frame_size = max_frame_size;
+  }
+
+  /// Returns the maximum payload size of a single frame the client may send.
+  pub fn max_frame_size(&self) -> Option<u64> {
+    self.max_frame_size
+  }
+
+  /// Sets the maximum size of a message the client may send. For fragmented messages this is
+  /// the sum of the payloads of all frames of the message.
+  /// If the client sends a larger message then the web socket is closed with status 1009 (Message Too Big)
+  /// before the payload of the offending frame is read. None means unlimited.
+  pub fn set_max_message_size(&mut self, max_message_size: Option<u64>) {
+    self.max_message_size = max_message_size;
+  }
+
+  /// Returns the maximum size of a message the client may send.
+  pub fn max_message_size(&self) -> Option<u64> {
+    self.max_message_size
+  }
+
+  /// Sets the interval in which pings are sent to the client while no frames are received.
+  /// Pings are only sent while waiting for the next frame in one of the read functions. None disables pings.
+  pub fn set_ping_interval(&mut self, ping_interval: Option<Duration>) {
+    self.ping_interval = ping_interval;
+  }
+
+  /// Returns the interval in which pings are sent to the client while no frames are received.
+  pub fn ping_interval(&self) -> Option<Duration> {
+    self.ping_interval
+  }
 
-    Frame::new(Opcode::Close, Vec::new()).write_to(self.guard.stream.as_stream_write())
+  /// Sets how long the client may take to answer a ping. Any frame received from the client counts as an answer.
+  /// If the client does not answer in time then the web socket is closed with status 1011 (Internal Error)
+  /// and the read function fails with `RequestHeadParsingError::WebSocketPongTimeout`. None means unlimited.
+  pub fn set_pong_timeout(&mut self, pong_timeout: Option<Duration>) {
+    self.pong_timeout = pong_timeout;
+  }
+
+  /// Returns how long the client may take to answer a ping.
+  pub fn pong_timeout(&self) -> Option<Duration> {
+    self.pong_timeout
+  }
+
+  /// Sets how long the client may go without sending a text or binary message. Pings and pongs do not count.
+  /// If the client stays idle for longer then the web socket is closed with status 1001 (Going Away)
+  /// and the read function fails with `RequestHeadParsingError::WebSocketIdleTimeout`. None means unlimited.
+  pub fn set_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+    self.idle_timeout = idle_timeout;
+  }
+
+  /// Returns how long the client may go without sending a text or binary message.
+  pub fn idle_timeout(&self) -> Option<Duration> {
+    self.idle_timeout
   }
 
   /// If the WebsocketReceiver is used with the "io::Read" trait then
@@ -148,7 +387,8 @@ impl WebsocketReceiver {
   }
 
   /// receive the next complete message.
-  /// Ok(None) indicates that the web socket is closed.
+  /// If the client closes the web socket then `WebsocketMessage::Close` is returned once,
+  /// after that Ok(None) indicates that the web socket is closed.
   pub fn read_message(&mut self) -> TiiResult<Option<WebsocketMessage>> {
     if let Some(message) = self.unhandled_messages.pop_front() {
       return Ok(Some(message));
@@ -178,113 +418,623 @@ impl WebsocketReceiver {
       return Ok(ReadMessageTimeoutResult::Message(message));
     }
 
+    self.skip_streamed_message()?;
+
     if self.guard.stream.available() == 0 {
       if self.guard.closed.load(SeqCst) {
         return Ok(ReadMessageTimeoutResult::Closed);
       }
 
-      let old_timeout = self.guard.stream.get_read_timeout()?.as_ref().cloned();
-      if let Err(err) = self.guard.stream.set_read_timeout(timeout) {
-        self.guard.closed.store(true, SeqCst);
-        error_log!("WebsocketReceiver::read_message_timeout error setting timeout for 1st byte of next frame {}", &err);
-        return Err(TiiError::from(err));
+      let deadline = timeout.map(|timeout| Instant::now() + timeout);
+      if !self.wait_for_frame(deadline)? {
+        return Ok(ReadMessageTimeoutResult::Timeout);
       }
-      let res = self.guard.stream.ensure_readable();
-      let res2 = self.guard.stream.set_read_timeout(old_timeout);
+    }
 
-      if let Err(err) = res2 {
-        self.guard.closed.store(true, SeqCst);
-        error_log!("WebsocketReceiver::read_message_timeout error setting timeout back to read timeout after waiting for 1st byte of next frame {}", &err);
-        return Err(TiiError::from(err));
+    match self.read_next_frame() {
+      Ok(Some(message)) => Ok(ReadMessageTimeoutResult::Message(message)),
+      Ok(None) => Ok(ReadMessageTimeoutResult::Closed),
+      Err(err) => Err(err),
+    }
+  }
+
+  /// Waits until the first byte of the next frame is received.
+  /// Returns false if the deadline expired first, None waits without a deadline.
+  ///
+  /// Sends pings in the configured interval and closes the web socket if the client
+  /// misses the pong timeout or the idle timeout while waiting.
+  fn wait_for_frame(&mut self, deadline: Option<Instant>) -> TiiResult<bool> {
+    loop {
+      if self.guard.stream.available() != 0 {
+        return Ok(true);
+      }
+
+      let now = Instant::now();
+      let pong_deadline =
+        self.unanswered_ping.zip(self.pong_timeout).map(|(sent, timeout)| sent + timeout);
+      if pong_deadline.is_some_and(|pong_deadline| now >= pong_deadline) {
+        if self.frame_already_received()? {
+          return Ok(true);
+        }
+        return Err(self.fail(CLOSE_INTERNAL_ERROR, RequestHeadParsingError::WebSocketPongTimeout));
       }
 
-      if let Err(err) = res {
-        if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) {
-          return Ok(ReadMessageTimeoutResult::Timeout);
+      let idle_deadline = self.idle_timeout.map(|timeout| self.last_message + timeout);
+      if idle_deadline.is_some_and(|idle_deadline| now >= idle_deadline) {
+        if self.frame_already_received()? {
+          return Ok(true);
         }
+        return Err(self.fail(CLOSE_GOING_AWAY, RequestHeadParsingError::WebSocketIdleTimeout));
+      }
+
+      let next_ping =
+        self.ping_interval.map(|interval| self.last_ping.unwrap_or(self.last_frame) + interval);
+      if next_ping.is_some_and(|next_ping| now >= next_ping) {
+        self.send_ping()?;
+        self.last_ping = Some(now);
+        self.unanswered_ping.get_or_insert(now);
+        continue;
+      }
+
+      if deadline.is_some_and(|deadline| now >= deadline) {
+        return Ok(false);
+      }
+
+      let wait_until =
+        [deadline, pong_deadline, idle_deadline, next_ping].into_iter().flatten().min();
+      //A read timeout of zero is an error for most streams.
+      let timeout = wait_until
+        .map(|wait_until| wait_until.saturating_duration_since(now).max(Duration::from_millis(1)));
+      if self.wait_readable(timeout)? {
+        return Ok(true);
+      }
+    }
+  }
+
+  /// Checks if the next frame was received by the OS while nobody was reading from the web socket.
+  /// The handler may have been busy for longer than a timeout while the client did answer in time.
+  fn frame_already_received(&mut self) -> TiiResult<bool> {
+    self.wait_readable(Some(Duration::from_millis(1)))
+  }
+
+  /// Waits for the first byte of the next frame with the given read timeout.
+  /// Returns false if the timeout expired first.
+  fn wait_readable(&mut self, timeout: Option<Duration>) -> TiiResult<bool> {
+    let old_timeout = self.guard.stream.get_read_timeout()?.as_ref().cloned();
+    if let Err(err) = self.guard.stream.set_read_timeout(timeout) {
+      self.guard.closed.store(true, SeqCst);
+      error_log!(
+        "WebsocketReceiver::wait_readable error setting timeout for 1st byte of next frame {}",
+        &err
+      );
+      return Err(TiiError::from(err));
+    }
+    let res = self.guard.stream.ensure_readable();
+    let res2 = self.guard.stream.set_read_timeout(old_timeout);
+
+    if let Err(err) = res2 {
+      self.guard.closed.store(true, SeqCst);
+      error_log!("WebsocketReceiver::wait_readable error setting timeout back to read timeout after waiting for 1st byte of next frame {}", &err);
+      return Err(TiiError::from(err));
+    }
+
+    match res {
+      //Ok(false) means EOF, the next read reports it.
+      Ok(_) => Ok(true),
+      Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+      Err(err) => {
         self.guard.closed.store(true, SeqCst);
-        error_log!("WebsocketReceiver::read_message_timeout error while waiting for 1st byte of next frame {}", &err);
-        return Err(TiiError::from(err));
+        error_log!(
+          "WebsocketReceiver::wait_readable error while waiting for 1st byte of next frame {}",
+          &err
+        );
+        Err(TiiError::from(err))
       }
     }
+  }
 
-    match self.read_next_frame() {
-      Ok(Some(message)) => Ok(ReadMessageTimeoutResult::Message(message)),
-      Ok(None) => Ok(ReadMessageTimeoutResult::Closed),
-      Err(err) => Err(err),
+  /// Sends a keep-alive ping to the client.
+  fn send_ping(&self) -> TiiResult<()> {
+    trace_log!("WebsocketReceiver::send_ping");
+    let _g = unwrap_poison(self.guard.write_mutex.lock())?;
+    Frame::new(Opcode::Ping, Vec::new()).write_to(self.guard.stream.as_stream_write()).inspect_err(
+      |e| {
+        self.guard.closed.store(true, SeqCst);
+        error_log!("WebsocketReceiver::send_ping error: {}", e);
+      },
+    )
+  }
+
+  /// Closes the web socket with the given status code because the client violated the protocol
+  /// and returns the error that describes the violation.
+  fn fail(&mut self, code: u16, error: RequestHeadParsingError) -> TiiError {
+    warn_log!(
+      "WebsocketReceiver::read_next_frame closing with status {} because of {:?}",
+      code,
+      &error
+    );
+    self.state.clear();
+    self.streamed = None;
+    match self.guard.close(code.to_be_bytes().to_vec()) {
+      Ok(()) => TiiError::RequestHeadParsing(error),
+      Err(err) => err,
+    }
+  }
+
+  /// Checks a frame whose header was read before its payload is read.
+  /// Closes the web socket with status 1002 (Protocol Error) if the frame is not allowed at this point
+  /// and with status 1009 (Message Too Big) if it exceeds the configured limits.
+  /// Control frames only count against the frame limit since they are not part of the message.
+  /// For compressed messages the limits apply to the compressed size, the decompressed size is checked by `inflate`.
+  fn check_frame(&mut self, frame: &Frame) -> TiiResult<()> {
+    //RSV1 marks a compressed message, it is only set on the first frame of the message.
+    let compression_allowed =
+      self.inflater.is_some() && !frame.is_control() && frame.opcode != Opcode::Continuation;
+    if frame.rsv[1] || frame.rsv[2] || (frame.rsv[0] && !compression_allowed) {
+      return Err(
+        self.fail(CLOSE_PROTOCOL_ERROR, RequestHeadParsingError::WebSocketReservedBitsSet),
+      );
+    }
+
+    let in_message = !self.state.is_empty() || self.streamed.is_some();
+    if frame.is_control() {
+      if !frame.fin || frame.length > 125 {
+        return Err(
+          self.fail(CLOSE_PROTOCOL_ERROR, RequestHeadParsingError::InvalidWebSocketControlFrame),
+        );
+      }
+    } else if (frame.opcode == Opcode::Continuation) != in_message {
+      //Either a continuation without a message or a new message before the last one was finished.
+      return Err(
+        self.fail(CLOSE_PROTOCOL_ERROR, RequestHeadParsingError::UnexpectedWebSocketOpcode),
+      );
+    }
+
+    let mut size = frame.length;
+    let mut exceeded = self.max_frame_size.is_some_and(|max| size > max);
+    if !exceeded && !frame.is_control() {
+      let streamed_size = self.streamed.map(|streamed| streamed.size).unwrap_or_default();
+      size = self.state.iter().map(|f| f.length).fold(size, u64::saturating_add);
+      size = size.saturating_add(streamed_size);
+      exceeded = self.max_message_size.is_some_and(|max| size > max);
+    }
+
+    if exceeded {
+      return Err(
+        self.fail(CLOSE_MESSAGE_TOO_BIG, RequestHeadParsingError::WebSocketMessageTooBig(size)),
+      );
+    }
+
+    Ok(())
+  }
+
+  /// Decompresses the payload of a message compressed with `permessage-deflate`.
+  /// A message may be decompressed in fragments, `first` and `fin` mark the first and the last fragment.
+  /// Closes the web socket with status 1007 (Invalid Payload) if the payload cannot be decompressed
+  /// and with status 1009 (Message Too Big) if the decompressed message exceeds the maximum message size.
+  /// The maximum frame size or a built-in default limits the decompressed message if there is no maximum message size.
+  fn inflate(&mut self, payload: &[u8], first: bool, fin: bool) -> TiiResult<Vec<u8>> {
+    let max_message_size =
+      self.max_message_size.or(self.max_frame_size).unwrap_or(DEFAULT_MAX_INFLATED_SIZE);
+    let Some(inflater) = self.inflater.as_mut() else {
+      return Err(
+        self.fail(CLOSE_PROTOCOL_ERROR, RequestHeadParsingError::WebSocketReservedBitsSet),
+      );
+    };
+
+    if first {
+      inflater.begin_message();
+    }
+
+    match inflater.inflate_fragment(payload, fin, max_message_size) {
+      Ok(message) => Ok(message),
+      Err(InflateError::TooBig(size)) => {
+        Err(self.fail(CLOSE_MESSAGE_TOO_BIG, RequestHeadParsingError::WebSocketMessageTooBig(size)))
+      }
+      Err(InflateError::Invalid(err)) => {
+        trace_log!("WebsocketReceiver::inflate invalid compressed payload: {}", &err);
+        Err(
+          self.fail(
+            CLOSE_INVALID_PAYLOAD,
+            RequestHeadParsingError::InvalidWebSocketCompressedPayload,
+          ),
+        )
+      }
     }
   }
 
+  /// Handles a close frame sent by the client.
+  /// Completes the close handshake by echoing the status code, as specified in [RFC 6455 Section 5.5.1](https://datatracker.ietf.org/doc/html/rfc6455#section-5.5.1).
+  fn handle_close(&mut self, payload: Vec<u8>) -> TiiResult<Option<WebsocketMessage>> {
+    let reason = match parse_close_payload(&payload) {
+      Ok(reason) => reason,
+      Err(code) => {
+        return Err(self.fail(code, RequestHeadParsingError::InvalidWebSocketClosePayload(payload)))
+      }
+    };
+
+    let pending = !self.state.is_empty() || self.streamed.is_some();
+    self.state.clear();
+    self.streamed = None;
+    self
+      .guard
+      .close(reason.as_ref().map(|(code, _)| code.to_be_bytes().to_vec()).unwrap_or_default())?;
+
+    if pending {
+      return Err(TiiError::RequestHeadParsing(
+        RequestHeadParsingError::WebSocketClosedDuringPendingMessage,
+      ));
+    }
+
+    Ok(Some(WebsocketMessage::Close(reason)))
+  }
+
+  /// Reads the next frame and checks it.
+  fn read_frame(&mut self) -> TiiResult<Frame> {
+    if self.ping_interval.is_some() || self.idle_timeout.is_some() {
+      self.wait_for_frame(None)?;
+    }
+
+    let guard = self.guard.clone();
+    let as_read = guard.stream.as_stream_read();
+    let mut frame = match Frame::header_from_stream(as_read) {
+      Ok(frame) => frame,
+      Err(TiiError::RequestHeadParsing(RequestHeadParsingError::InvalidWebSocketOpcode)) => {
+        return Err(
+          self.fail(CLOSE_PROTOCOL_ERROR, RequestHeadParsingError::InvalidWebSocketOpcode),
+        );
+      }
+      Err(e) => {
+        self.guard.closed.store(true, SeqCst);
+        error_log!("WebsocketReceiver::read_frame Frame::header_from_stream error: {}", &e);
+        return Err(e);
+      }
+    };
+
+    self.check_frame(&frame)?;
+
+    frame.read_payload(as_read).inspect_err(|e| {
+      self.guard.closed.store(true, SeqCst);
+      error_log!("WebsocketReceiver::read_frame Frame::read_payload error: {}", e);
+    })?;
+
+    let now = Instant::now();
+    self.last_frame = now;
+    self.last_ping = None;
+    self.unanswered_ping = None;
+    if !frame.is_control() {
+      self.last_message = now;
+    }
+
+    Ok(frame)
+  }
+
   /// Attempts to read a message from the given stream.
   ///
-  /// Silently responds to pings with pongs, as specified in [RFC 6455 Section 5.5.2](https://datatracker.ietf.org/doc/html/rfc6455#section-5.5.2).
+  /// Pings and pongs are returned as messages even if they arrive between the frames of a fragmented message.
   fn read_next_frame(&mut self) -> TiiResult<Option<WebsocketMessage>> {
+    self.skip_streamed_message()?;
     if self.guard.closed.load(SeqCst) {
       return Ok(None);
     }
 
-    let as_read = self.guard.stream.as_stream_read();
     // Keep reading frames until we get the finish frame
     while self.state.last().map(|f| !f.fin).unwrap_or(true) {
-      let frame = Frame::from_stream(as_read).inspect_err(|e| {
-        self.guard.closed.store(true, SeqCst);
-        error_log!("WebsocketReceiver::read_next_frame Frame::from_stream error: {}", e);
-      })?;
-
-      if frame.opcode == Opcode::Ping {
-        return Ok(Some(WebsocketMessage::Ping));
+      let frame = self.read_frame()?;
+      match frame.opcode {
+        Opcode::Ping => return Ok(Some(WebsocketMessage::Ping)),
+        Opcode::Pong => return Ok(Some(WebsocketMessage::Pong)),
+        Opcode::Close => return self.handle_close(frame.payload),
+        _ => self.state.push(frame),
       }
+    }
+
+    let frames = mem::take(&mut self.state);
+    let first_frame = unwrap_some(frames.first());
+    let frame_type = first_frame.opcode;
+    let compressed = first_frame.rsv[0];
+
+    let size = frames.iter().map(|f| f.payload.len()).sum();
+    let mut payload = Vec::with_capacity(size);
+    for frame in frames {
+      payload.extend_from_slice(frame.payload.as_slice());
+    }
+
+    if compressed {
+      payload = self.inflate(&payload, true, true)?;
+    }
 
-      if frame.opcode == Opcode::Pong {
-        return Ok(Some(WebsocketMessage::Pong));
+    match frame_type {
+      Opcode::Text => match String::from_utf8(payload) {
+        Ok(payload) => Ok(Some(WebsocketMessage::Text(payload))),
+        Err(e) => Err(self.fail(
+          CLOSE_INVALID_PAYLOAD,
+          RequestHeadParsingError::WebSocketTextMessageIsNotUtf8(e.into_bytes()),
+        )),
+      },
+      Opcode::Binary => Ok(Some(WebsocketMessage::Binary(payload))),
+      _ => Err(self.fail(CLOSE_PROTOCOL_ERROR, RequestHeadParsingError::UnexpectedWebSocketOpcode)),
+    }
+  }
+}
+
+impl WebsocketReceiver {
+  /// Starts reading the next text or binary message frame by frame instead of waiting for the whole message.
+  /// Returns None once the web socket is closed.
+  ///
+  /// Pings, pongs and the close message of the client that arrive while the message is read
+  /// are queued, see `unhandled`. If the returned reader is dropped before the message was read completely
+  /// then the rest of the message is skipped by the next read.
+  pub fn read_message_stream(&mut self) -> TiiResult<Option<WebsocketMessageReader<'_>>> {
+    self.skip_streamed_message()?;
+    let Some(frame) = self.read_data_frame()? else {
+      return Ok(None);
+    };
+
+    let text = frame.opcode == Opcode::Text;
+    self.streamed = Some(StreamedMessage { size: 0, compressed: frame.rsv[0] });
+    let (fragment, fin) = self.read_fragment(frame, true)?;
+
+    let mut reader = WebsocketMessageReader {
+      receiver: self,
+      text,
+      fragment: Cursor::new(Vec::new()),
+      utf8_remainder: Vec::new(),
+      finished: false,
+    };
+    reader.accept(fragment, fin)?;
+    Ok(Some(reader))
+  }
+
+  /// Reads frames until the next text, binary or continuation frame.
+  /// Control frames are queued. Returns None if the web socket is closed.
+  fn read_data_frame(&mut self) -> TiiResult<Option<Frame>> {
+    loop {
+      if self.guard.closed.load(SeqCst) {
+        return Ok(None);
       }
 
-      if frame.opcode == Opcode::Close {
-        self.guard.closed.store(true, SeqCst);
-        if self.state.is_empty() {
+      let frame = self.read_frame()?;
+      match frame.opcode {
+        Opcode::Ping => self.unhandled_messages.push_back(WebsocketMessage::Ping),
+        Opcode::Pong => self.unhandled_messages.push_back(WebsocketMessage::Pong),
+        Opcode::Close => {
+          if let Some(message) = self.handle_close(frame.payload)? {
+            self.unhandled_messages.push_back(message);
+          }
           return Ok(None);
         }
+        _ => return Ok(Some(frame)),
+      }
+    }
+  }
+
+  /// Returns the payload of a frame of the streamed message and whether it was the last frame.
+  fn read_fragment(&mut self, frame: Frame, first: bool) -> TiiResult<(Vec<u8>, bool)> {
+    let fin = frame.fin;
+    let Some(streamed) = self.streamed.as_mut() else {
+      return Err(
+        self.fail(CLOSE_PROTOCOL_ERROR, RequestHeadParsingError::UnexpectedWebSocketOpcode),
+      );
+    };
+
+    streamed.size = streamed.size.saturating_add(frame.length);
+    let compressed = streamed.compressed;
+    if fin {
+      self.streamed = None;
+    }
 
-        return Err(TiiError::RequestHeadParsing(
+    if compressed {
+      return Ok((self.inflate(&frame.payload, first, fin)?, fin));
+    }
+
+    Ok((frame.payload, fin))
+  }
+
+  /// Reads the next frame of the streamed message.
+  fn read_next_fragment(&mut self) -> TiiResult<(Vec<u8>, bool)> {
+    match self.read_data_frame()? {
+      Some(frame) => self.read_fragment(frame, false),
+      None => {
+        self.streamed = None;
+        Err(TiiError::RequestHeadParsing(
           RequestHeadParsingError::WebSocketClosedDuringPendingMessage,
-        ));
+        ))
       }
+    }
+  }
+
+  /// Skips the rest of a message whose reader was dropped before the message was read completely.
+  fn skip_streamed_message(&mut self) -> TiiResult<()> {
+    while self.streamed.is_some() {
+      self.read_next_fragment()?;
+    }
+    Ok(())
+  }
+}
 
-      self.state.push(frame);
+/// Reads a single text or binary message frame by frame. Created by `WebsocketReceiver::read_message_stream`.
+///
+/// The payload of each frame is returned as soon as the frame was received.
+/// Text messages are checked for valid UTF-8 as they arrive, invalid UTF-8 closes the web socket with status 1007.
+#[derive(Debug)]
+pub struct WebsocketMessageReader<'a> {
+  receiver: &'a mut WebsocketReceiver,
+  text: bool,
+  /// The unread part of the payload of the last frame.
+  fragment: Cursor<Vec<u8>>,
+  /// The bytes of a character that was split between two frames.
+  utf8_remainder: Vec<u8>,
+  finished: bool,
+}
+
+impl WebsocketMessageReader<'_> {
+  /// Returns true if this is a text message, false if it is a binary message.
+  pub fn is_text(&self) -> bool {
+    self.text
+  }
+
+  /// Returns true once the last frame of the message was received.
+  /// The payload of the last frame may not have been read yet.
+  pub fn is_finished(&self) -> bool {
+    self.finished
+  }
+
+  fn accept(&mut self, fragment: Vec<u8>, fin: bool) -> TiiResult<()> {
+    self.finished = fin;
+    if self.text && !self.check_utf8(&fragment, fin) {
+      return Err(self.receiver.fail(
+        CLOSE_INVALID_PAYLOAD,
+        RequestHeadParsingError::WebSocketTextMessageIsNotUtf8(fragment),
+      ));
     }
 
-    let frames = mem::take(&mut self.state);
-    let frame_type = unwrap_some(frames.first()).opcode;
+    self.fragment = Cursor::new(fragment);
+    Ok(())
+  }
 
-    let size = frames.iter().map(|f| f.payload.len()).sum();
-    let mut payload = Vec::with_capacity(size);
+  /// Checks that the fragment continues the text of the previous fragments with valid UTF-8.
+  fn check_utf8(&mut self, fragment: &[u8], fin: bool) -> bool {
+    let mut rest = fragment;
+    while !self.utf8_remainder.is_empty() {
+      let Some((byte, tail)) = rest.split_first() else {
+        break;
+      };
+      rest = tail;
+      self.utf8_remainder.push(*byte);
+      match std::str::from_utf8(&self.utf8_remainder) {
+        Ok(_) => self.utf8_remainder.clear(),
+        Err(err) if err.error_len().is_none() => {}
+        Err(_) => return false,
+      }
+    }
 
-    for (idx, frame) in frames.into_iter().enumerate() {
-      if idx != 0 && frame.opcode != Opcode::Continuation {
-        return Err(TiiError::RequestHeadParsing(
-          RequestHeadParsingError::UnexpectedWebSocketOpcode,
-        ));
+    match std::str::from_utf8(rest) {
+      Ok(_) => {}
+      Err(err) if err.error_len().is_none() => {
+        self.utf8_remainder = rest.get(err.valid_up_to()..).unwrap_or_default().to_vec();
       }
-      payload.extend_from_slice(frame.payload.as_slice());
+      Err(_) => return false,
     }
 
-    match frame_type {
-      Opcode::Text => {
-        let payload = String::from_utf8(payload).map_err(|e| {
-          self.guard.closed.store(true, SeqCst);
-          TiiError::RequestHeadParsing(RequestHeadParsingError::WebSocketTextMessageIsNotUtf8(
-            e.into_bytes(),
-          ))
-        })?;
-
-        Ok(Some(WebsocketMessage::Text(payload)))
+    !fin || self.utf8_remainder.is_empty()
+  }
+}
+
+impl Read for WebsocketMessageReader<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+      let count = self.fragment.read(buf)?;
+      if count != 0 || buf.is_empty() || self.finished {
+        return Ok(count);
       }
-      Opcode::Binary => Ok(Some(WebsocketMessage::Binary(payload))),
-      _ => {
-        self.guard.closed.store(true, SeqCst);
-        Err(TiiError::RequestHeadParsing(RequestHeadParsingError::UnexpectedWebSocketOpcode))
+
+      let (fragment, fin) = self.receiver.read_next_fragment()?;
+      self.accept(fragment, fin)?;
+    }
+  }
+}
+
+/// Writes a single text or binary message as a sequence of frames.
+/// Created by `WebsocketSender::text_writer` or `WebsocketSender::binary_writer`.
+///
+/// Written data is sent whenever a full fragment is buffered or when the writer is flushed.
+/// The last frame is sent by `finish` or when the writer is dropped.
+/// Pings, pongs and close frames can be sent with a clone of the `WebsocketSender` while the message is written.
+/// Other messages wait until the writer is finished, sending one on the thread that holds the writer deadlocks.
+#[derive(Debug)]
+pub struct WebsocketMessageWriter<'a> {
+  guard: &'a WebSocketGuard,
+  _message_guard: MutexGuard<'a, ()>,
+  /// Text or binary for the first frame, continuation for the following frames.
+  opcode: Opcode,
+  fragment_size: usize,
+  buffer: Vec<u8>,
+  deflater: Option<MessageDeflater>,
+  finished: bool,
+}
+
+impl WebsocketMessageWriter<'_> {
+  /// Sends the buffered data as the last frame of the message.
+  pub fn finish(mut self) -> TiiResult<()> {
+    self.finish_message()
+  }
+
+  fn finish_message(&mut self) -> TiiResult<()> {
+    if self.finished {
+      return Ok(());
+    }
+    self.finished = true;
+    let fragment = mem::take(&mut self.buffer);
+    self.send_fragment(&fragment, true)
+  }
+
+  fn send_fragment(&mut self, fragment: &[u8], fin: bool) -> TiiResult<()> {
+    if self.guard.closed.load(SeqCst) {
+      return Err(TiiError::from_io_kind(ErrorKind::ConnectionReset));
+    }
+
+    let mut frame = match self.deflater.as_mut() {
+      Some(deflater) => {
+        let mut frame = Frame::new(self.opcode, deflater.compress_fragment(fragment, fin)?);
+        frame.rsv[0] = self.opcode != Opcode::Continuation;
+        frame
+      }
+      None => Frame::new(self.opcode, fragment.to_vec()),
+    };
+    frame.fin = fin;
+    self.opcode = Opcode::Continuation;
+
+    let _g = unwrap_poison(self.guard.write_mutex.lock())?;
+    frame.write_to(self.guard.stream.as_stream_write()).inspect_err(|e| {
+      self.guard.closed.store(true, SeqCst);
+      error_log!("WebsocketMessageWriter::send_fragment error: {}", e);
+    })
+  }
+}
+
+impl Write for WebsocketMessageWriter<'_> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if self.finished {
+      return Err(io::Error::new(
+        ErrorKind::InvalidInput,
+        "web socket message is already finished",
+      ));
+    }
+
+    let mut data = buf;
+    while self.buffer.len() + data.len() > self.fragment_size {
+      let (head, tail) = data.split_at(self.fragment_size - self.buffer.len());
+      data = tail;
+      if self.buffer.is_empty() {
+        self.send_fragment(head, false)?;
+      } else {
+        self.buffer.extend_from_slice(head);
+        let fragment = mem::take(&mut self.buffer);
+        self.send_fragment(&fragment, false)?;
       }
     }
+
+    self.buffer.extend_from_slice(data);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if self.finished || self.buffer.is_empty() {
+      return Ok(());
+    }
+
+    let fragment = mem::take(&mut self.buffer);
+    self.send_fragment(&fragment, false)?;
+    Ok(())
+  }
+}
+
+impl Drop for WebsocketMessageWriter<'_> {
+  fn drop(&mut self) {
+    if let Err(err) = self.finish_message() {
+      warn_log!("WebsocketMessageWriter::drop error: {}", &err);
+    }
   }
 }
 
@@ -326,11 +1076,10 @@ impl Write for WebsocketSender {
     if self.0.closed.load(SeqCst) {
       return Err(io::Error::from(ErrorKind::ConnectionReset));
     }
-    Frame::write_unowned_payload_frame(self.0.stream.as_stream_write(), Opcode::Binary, buf)
-      .inspect_err(|e| {
-        self.0.closed.store(true, SeqCst);
-        error_log!("WebsocketSender::write error: {}", e);
-      })?;
+    self.0.send_message(Opcode::Binary, buf).inspect_err(|e| {
+      self.0.closed.store(true, SeqCst);
+      error_log!("WebsocketSender::write error: {}", e);
+    })?;
     Ok(buf.len())
   }
 
@@ -32,7 +32,7 @@ mod inner {
       Duration::from_secs(30),
     )?;
     stream.set_write_timeout(Some(Duration::from_secs(5)))?;
-    stream.write_all("GET / HTTP/1.1\r\n\r\n".as_bytes())?;
+    stream.write_all("GET / HTTP/1.1\r\nConnection: close\r\n\r\n".as_bytes())?;
     stream.flush()?;
     stream.set_read_timeout(Some(Duration::from_secs(5)))?;
     let mut response = Vec::new();
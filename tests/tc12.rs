@@ -24,5 +24,5 @@ pub fn tc12() {
   assert_eq!(err.kind(), io::ErrorKind::InvalidData);
   assert_eq!(err.to_string(), "HeaderValueMissing");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "");
+  assert_eq!(data, "HTTP/1.1 400 Bad Request\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
 }
@@ -32,5 +32,5 @@ pub fn tc17() {
   server.handle_connection(con).unwrap();
   assert_eq!(COUNTER.load(std::sync::atomic::Ordering::SeqCst), 1);
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nOkay!");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nOkay!");
 }
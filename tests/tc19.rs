@@ -24,6 +24,6 @@ pub fn tc19() {
   let data = stream.copy_written_data_to_string();
   assert_eq!(
     data,
-    "HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 21\r\n\r\n123451234567890123456"
+    "HTTP/1.1 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 21\r\n\r\n123451234567890123456"
   );
 }
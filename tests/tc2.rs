@@ -28,6 +28,6 @@ pub fn tc2() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nOkay!");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nOkay!");
   assert_eq!(COUNTER.load(std::sync::atomic::Ordering::SeqCst), 1);
 }
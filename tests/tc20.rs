@@ -27,7 +27,7 @@ pub fn tc20() {
   let data = stream.copy_written_data_to_string();
   assert_eq!(
     data,
-    "HTTP/1.1 200 OK\r\nFubar: Dubar\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nOkay!"
+    "HTTP/1.1 200 OK\r\nFubar: Dubar\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nOkay!"
   );
   assert_eq!(COUNTER.load(std::sync::atomic::Ordering::SeqCst), 1);
 }
@@ -43,7 +43,7 @@ pub fn tc26() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nOkay!");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nOkay!");
 
   let stream = MockStream::with_str(
     "GET /dummy HTTP/1.1\r\nHdr: test\r\nAccept: text/plain;q=0.5, application/json;q=0.6\r\n\r\n",
@@ -51,7 +51,7 @@ pub fn tc26() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: Close\r\nContent-Length: 7\r\n\r\n\"Nice!\"");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: Keep-Alive\r\nContent-Length: 7\r\n\r\n\"Nice!\"");
 
   let stream = MockStream::with_str(
     "GET /dummy HTTP/1.1\r\nHdr: test\r\nAccept: text/*;q=0.5, application/json;q=0.6\r\n\r\n",
@@ -59,7 +59,7 @@ pub fn tc26() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: Close\r\nContent-Length: 7\r\n\r\n\"Nice!\"");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: Keep-Alive\r\nContent-Length: 7\r\n\r\n\"Nice!\"");
 
   let stream = MockStream::with_str(
     "GET /dummy HTTP/1.1\r\nHdr: test\r\nAccept: text/plain;q=0.7, application/*;q=0.6\r\n\r\n",
@@ -67,7 +67,7 @@ pub fn tc26() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nOkay!");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nOkay!");
 
   let stream = MockStream::with_str(
     "GET /dummy HTTP/1.1\r\nHdr: test\r\nAccept: text/plain;q=0.5, application/*;q=0.6\r\n\r\n",
@@ -75,7 +75,7 @@ pub fn tc26() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: Close\r\nContent-Length: 7\r\n\r\n\"Nice!\"");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: Keep-Alive\r\nContent-Length: 7\r\n\r\n\"Nice!\"");
 
   let stream = MockStream::with_str(
     "GET /dummy HTTP/1.1\r\nHdr: test\r\nAccept: text/*;q=0.7, application/json;q=0.6\r\n\r\n",
@@ -83,12 +83,12 @@ pub fn tc26() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nOkay!");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nOkay!");
 
   //It's not clear what to do, so in this case we pick the first endpoint!
   let stream = MockStream::with_str("GET /dummy HTTP/1.1\r\nHdr: test\r\nAccept: */*\r\n\r\n");
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nOkay!");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nOkay!");
 }
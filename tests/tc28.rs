@@ -34,23 +34,26 @@ pub fn tc28() {
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 10\r\n\r\nOkay! 1234");
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 10\r\n\r\nOkay! 1234"
+  );
 
   let stream = MockStream::with_str("GET /dummy/p1/p2/abc/hello/world HTTP/1.1\r\n\r\n");
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 404 Not Found\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
+  assert_eq!(data, "HTTP/1.1 404 Not Found\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n");
 
   let stream = MockStream::with_str("GET /dummy/p1/p2/01234/hello/world HTTP/1.1\r\n\r\n");
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 404 Not Found\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
+  assert_eq!(data, "HTTP/1.1 404 Not Found\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n");
 
   let stream = MockStream::with_str("GET /dummy/p1/p2/0/hello/world HTTP/1.1\r\n\r\n");
   let con = stream.to_stream();
   server.handle_connection(con).expect("ERROR");
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 7\r\n\r\nOkay! 0");
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 7\r\n\r\nOkay! 0");
 }
@@ -41,12 +41,18 @@ pub fn tc32() {
   let con = stream.to_stream();
   server.handle_connection(con).unwrap();
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 204 No Content\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
 
   let stream = MockStream::with_str("GET /bla HTTP/1.1\r\nAccept: application/json\r\n\r\n");
   let con = stream.to_stream();
   server.handle_connection(con).unwrap();
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 406 Not Acceptable\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 406 Not Acceptable\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
   assert_eq!(COUNTER.load(Ordering::SeqCst), 1);
 }
@@ -50,7 +50,10 @@ pub fn tc33() {
   let con = stream.to_stream();
   server.handle_connection(con).unwrap();
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 204 No Content\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
 
   let stream = MockStream::with_str(
     "GET /bla HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}",
@@ -60,7 +63,7 @@ pub fn tc33() {
   let data = stream.copy_written_data_to_string();
   assert_eq!(
     data,
-    "HTTP/1.1 415 Unsupported Media Type\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+    "HTTP/1.1 415 Unsupported Media Type\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
   );
   assert_eq!(COUNTER.load(Ordering::SeqCst), 1);
 }
@@ -39,12 +39,18 @@ pub fn tc36() {
   let con = stream.to_stream();
   server.handle_connection(con).unwrap();
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 204 No Content\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
 
   let stream =
     MockStream::with_str("GET /dummy2 HTTP/1.1\r\nAccept: text/html\r\nHdr: test\r\n\r\n");
   let con = stream.to_stream();
   server.handle_connection(con).unwrap();
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "HTTP/1.1 406 Not Acceptable\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 406 Not Acceptable\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
 }
@@ -23,5 +23,5 @@ pub fn tc4() {
   assert_eq!(err.to_string(), "MethodNotSupportedByHttpVersion(Http09, Post)");
 
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "");
+  assert_eq!(data, "HTTP/1.1 400 Bad Request\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
 }
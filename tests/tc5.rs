@@ -23,5 +23,8 @@ pub fn tc5() {
   assert_eq!(err.to_string(), "HttpVersionNotSupported(\"HTTP/1.2\")");
 
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "");
+  assert_eq!(
+    data,
+    "HTTP/1.1 505 HTTP Version Not Supported\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
 }
@@ -21,7 +21,7 @@ pub fn tc52() {
     _ => panic!("unexpected error: {err:?}"),
   }
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "");
+  assert_eq!(data, "HTTP/1.1 400 Bad Request\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
 }
 
 #[test]
@@ -39,7 +39,7 @@ pub fn tc52_b() {
     _ => panic!("unexpected error: {err:?}"),
   }
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "");
+  assert_eq!(data, "HTTP/1.1 400 Bad Request\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
 }
 
 #[test]
@@ -57,7 +57,7 @@ pub fn tc52_c() {
     _ => panic!("unexpected error: {err:?}"),
   }
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "");
+  assert_eq!(data, "HTTP/1.1 400 Bad Request\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
 }
 
 #[test]
@@ -75,5 +75,5 @@ pub fn tc52_d() {
     _ => panic!("unexpected error: {err:?}"),
   }
   let data = stream.copy_written_data_to_string();
-  assert_eq!(data, "");
+  assert_eq!(data, "HTTP/1.1 400 Bad Request\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n");
 }
@@ -0,0 +1,232 @@
+use crate::mock_stream::MockStream;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use tii::{MimeType, TiiResult};
+use tii::{RequestContext, Response, ServerBuilder};
+
+mod mock_stream;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn dummy_route(ctx: &RequestContext) -> TiiResult<Response> {
+  COUNTER.fetch_add(1, SeqCst);
+  let data = ctx.request_body().unwrap().read_to_vec()?;
+  Ok(Response::ok(data, MimeType::TextPlain))
+}
+
+fn dummy_route_no_counter(ctx: &RequestContext) -> TiiResult<Response> {
+  let data = ctx.request_body().unwrap().read_to_vec()?;
+  Ok(Response::ok(data.len().to_string(), MimeType::TextPlain))
+}
+
+#[test]
+pub fn tc71_content_length_too_large() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(4))
+    .unwrap()
+    .router(|rt| rt.route_any("/counted", dummy_route))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("POST /counted HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\n12345GET /counted HTTP/1.1\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+  assert_eq!(COUNTER.load(SeqCst), 0);
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 413 Content Too Large\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc71_content_length_within_limit() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(5))
+    .unwrap()
+    .router(|rt| rt.route_any("/dummy", dummy_route_no_counter))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("POST /dummy HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\n12345GET /404 HTTP/1.1\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(data, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 1\r\n\r\n5HTTP/1.1 404 Not Found\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n");
+}
+
+#[test]
+pub fn tc71_route_override_larger() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(2))
+    .unwrap()
+    .router(|rt| rt.post("/dummy").max_request_body_size(10).endpoint(dummy_route_no_counter))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(
+    "POST /dummy HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\n12345",
+  );
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 1\r\n\r\n5"
+  );
+}
+
+#[test]
+pub fn tc71_route_override_smaller() {
+  let server = ServerBuilder::default()
+    .router(|rt| rt.post("/dummy").max_request_body_size(4).endpoint(dummy_route_no_counter))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(
+    "POST /dummy HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\n12345",
+  );
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 413 Content Too Large\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc71_chunked_too_large() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(8))
+    .unwrap()
+    .router(|rt| rt.route_any("/dummy", dummy_route_no_counter))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("POST /dummy HTTP/1.1\r\nConnection: keep-alive\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n12345\r\n5\r\n12345\r\n0\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 413 Content Too Large\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc71_chunked_within_limit() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(10))
+    .unwrap()
+    .router(|rt| rt.route_any("/dummy", dummy_route_no_counter))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("POST /dummy HTTP/1.1\r\nConnection: keep-alive\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n12345\r\n5\r\n12345\r\n0\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 2\r\n\r\n10"
+  );
+}
+
+#[test]
+pub fn tc71_gzip_decompressed_too_large() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(1024))
+    .unwrap()
+    .router(|rt| rt.route_any("/dummy", dummy_route_no_counter))
+    .expect("ERR")
+    .build();
+
+  let mut data = Vec::<u8>::new();
+  data.extend_from_slice(b"POST /dummy HTTP/1.1\r\n");
+  data.extend_from_slice(b"Content-Encoding: gzip\r\n");
+  data.extend_from_slice(b"Content-Length: 96\r\n");
+  data.extend_from_slice(b"Connection: keep-alive\r\n");
+  data.extend_from_slice(b"\r\n");
+  //python3 -c "import gzip; open('tc71.gz','wb').write(gzip.compress(b'\0'*65536, mtime=0))"
+  data.extend_from_slice(include_bytes!("./tc71.gz").as_ref());
+
+  let stream = MockStream::with_slice(data.as_slice());
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 413 Content Too Large\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc71_gzip_decompressed_within_limit() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(65536))
+    .unwrap()
+    .router(|rt| rt.route_any("/dummy", dummy_route_no_counter))
+    .expect("ERR")
+    .build();
+
+  let mut data = Vec::<u8>::new();
+  data.extend_from_slice(b"POST /dummy HTTP/1.1\r\n");
+  data.extend_from_slice(b"Content-Encoding: gzip\r\n");
+  data.extend_from_slice(b"Content-Length: 96\r\n");
+  data.extend_from_slice(b"Connection: keep-alive\r\n");
+  data.extend_from_slice(b"\r\n");
+  data.extend_from_slice(include_bytes!("./tc71.gz").as_ref());
+
+  let stream = MockStream::with_slice(data.as_slice());
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\n65536"
+  );
+}
+
+#[test]
+pub fn tc71_expect_continue_too_large() {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(Some(4))
+    .unwrap()
+    .router(|rt| {
+      rt.route_post("/dummy", dummy_route_no_counter)?
+        .post("/larger")
+        .max_request_body_size(10)
+        .endpoint(dummy_route_no_counter)
+    })
+    .expect("ERR")
+    .build();
+
+  //The client waits for 100 Continue and never sends the body.
+  let stream = MockStream::with_str(
+    "POST /dummy HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n",
+  );
+  server.handle_connection(stream.to_stream()).unwrap();
+  assert_eq!(
+    stream.copy_written_data_to_string(),
+    "HTTP/1.1 413 Content Too Large\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+
+  //Routes with a larger limit still receive the body once the client stops waiting.
+  let stream = MockStream::with_str(
+    "POST /larger HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n12345",
+  );
+  server.handle_connection(stream.to_stream()).unwrap();
+  assert_eq!(
+    stream.copy_written_data_to_string(),
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\nContent-Length: 1\r\n\r\n5"
+  );
+}
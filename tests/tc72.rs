@@ -0,0 +1,146 @@
+use crate::mock_stream::MockStream;
+use std::sync::Mutex;
+use tii::{
+  RequestContext, RequestHeadParsingError, RouterBuilder, ServerBuilder, TiiResult,
+  WebsocketMessage, WebsocketReceiver, WebsocketSender,
+};
+
+mod mock_stream;
+
+const HANDSHAKE: &[u8] = b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+
+const CLOSE_1009: &[u8] = &[0x88, 0x02, 0x03, 0xF1];
+
+fn masked_frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+  assert!(payload.len() < 126);
+  let mut frame = vec![first_byte, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+  frame.extend_from_slice(payload);
+  frame
+}
+
+fn run(
+  max_frame_size: Option<u64>,
+  max_message_size: Option<u64>,
+  frames: &[u8],
+) -> (Vec<u8>, TiiResult<Option<WebsocketMessage>>) {
+  run_with(
+    |rt| {
+      rt.with_websocket_max_frame_size(max_frame_size)?
+        .with_websocket_max_message_size(max_message_size)
+    },
+    frames,
+  )
+}
+
+fn run_with(
+  limits: impl FnOnce(RouterBuilder) -> TiiResult<RouterBuilder>,
+  frames: &[u8],
+) -> (Vec<u8>, TiiResult<Option<WebsocketMessage>>) {
+  let result = std::sync::Arc::new(Mutex::new(None));
+  let result_clone = result.clone();
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      limits(rt)?.ws_route_get(
+        "/ws",
+        move |_: &RequestContext, mut rx: WebsocketReceiver, _: WebsocketSender| -> TiiResult<()> {
+          loop {
+            let result = rx.read_message();
+            if matches!(result, Ok(Some(WebsocketMessage::Ping))) {
+              continue;
+            }
+            *result_clone.lock().unwrap() = Some(result);
+            return Ok(());
+          }
+        },
+      )
+    })
+    .expect("ERR")
+    .build();
+
+  let mut data = HANDSHAKE.to_vec();
+  data.extend_from_slice(frames);
+  let stream = MockStream::with_slice(data.as_slice());
+  server.handle_connection(stream.to_stream()).unwrap();
+
+  let written = stream.copy_written_data();
+  let header_end = written.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+  assert!(written.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+
+  let result = result.lock().unwrap().take().unwrap();
+  (written[header_end..].to_vec(), result)
+}
+
+fn assert_too_big(result: TiiResult<Option<WebsocketMessage>>, size: u64) {
+  let err = result.unwrap_err();
+  assert_eq!(
+    err.downcast_ref::<RequestHeadParsingError>(),
+    Some(&RequestHeadParsingError::WebSocketMessageTooBig(size))
+  );
+}
+
+fn assert_text(result: TiiResult<Option<WebsocketMessage>>, text: &str) {
+  match result.unwrap() {
+    Some(WebsocketMessage::Text(txt)) => assert_eq!(txt, text),
+    other => panic!("{other:?}"),
+  }
+}
+
+#[test]
+pub fn tc72_frame_within_limit() {
+  let (written, result) = run(Some(5), Some(5), &masked_frame(0x81, b"hello"));
+  assert_text(result, "hello");
+  //Dropping the receiver and sender sends the normal close frame.
+  assert_eq!(written, vec![0x88, 0x00]);
+}
+
+#[test]
+pub fn tc72_frame_too_large() {
+  let (written, result) = run(Some(4), None, &masked_frame(0x81, b"hello"));
+  assert_too_big(result, 5);
+  assert_eq!(written, CLOSE_1009);
+}
+
+#[test]
+pub fn tc72_declared_length_too_large() {
+  //Declares a payload of u64::MAX bytes but never sends it.
+  let frame = [0x82, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0];
+  let (written, result) = run(Some(1024), None, &frame);
+  assert_too_big(result, u64::MAX);
+  assert_eq!(written, CLOSE_1009);
+}
+
+#[test]
+pub fn tc72_fragmented_message_too_large() {
+  let mut frames = masked_frame(0x01, b"hel");
+  frames.extend(masked_frame(0x80, b"lo"));
+  let (written, result) = run(Some(3), Some(4), &frames);
+  assert_too_big(result, 5);
+  assert_eq!(written, CLOSE_1009);
+}
+
+#[test]
+pub fn tc72_fragmented_message_within_limit() {
+  let mut frames = masked_frame(0x01, b"hel");
+  //Pings are control frames and do not count towards the message.
+  frames.extend(masked_frame(0x89, b"ping"));
+  frames.extend(masked_frame(0x80, b"lo"));
+  let (_, result) = run(Some(4), Some(5), &frames);
+  assert_text(result, "hello");
+}
+
+#[test]
+pub fn tc72_default_limits() {
+  //Declares a payload of 16 MiB + 1 bytes but never sends it.
+  let frame = [0x82, 0xFF, 0, 0, 0, 0, 0x01, 0, 0, 0x01, 0, 0, 0, 0];
+  let (written, result) = run_with(Ok, &frame);
+  assert_too_big(result, 0x100_0001);
+  assert_eq!(written, CLOSE_1009);
+
+  //A fragment of exactly 16 MiB is allowed, the message it starts is not.
+  let mut frames = vec![0x02, 0xFF, 0, 0, 0, 0, 0x01, 0, 0, 0, 0, 0, 0, 0];
+  frames.resize(frames.len() + 0x100_0000, 0);
+  frames.extend(masked_frame(0x80, b"x"));
+  let (written, result) = run_with(Ok, &frames);
+  assert_too_big(result, 0x100_0001);
+  assert_eq!(written, CLOSE_1009);
+}
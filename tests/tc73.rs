@@ -0,0 +1,109 @@
+use crate::mock_stream::MockStream;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+use tii::{MimeType, ResponseBody, TiiResult};
+use tii::{RequestContext, Response, ServerBuilder};
+
+mod mock_stream;
+
+fn get_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("Hello", MimeType::TextPlain))
+}
+
+fn head_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::no_content())
+}
+
+#[test]
+pub fn tc73_head_falls_back_to_get() {
+  let server =
+    ServerBuilder::default().router(|rt| rt.route_get("/dummy", get_route)).expect("ERR").build();
+
+  let stream = MockStream::with_str("HEAD /dummy HTTP/1.1\r\nConnection: keep-alive\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\nContent-Length: 5\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc73_explicit_head_route_wins() {
+  let server = ServerBuilder::default()
+    .router(|rt| rt.route_get("/dummy", get_route)?.route_head("/dummy", head_route))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("HEAD /dummy HTTP/1.1\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc73_head_fallback_disabled() {
+  let server = ServerBuilder::default()
+    .router(|rt| rt.with_head_fallback_to_get(false)?.route_get("/dummy", get_route))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("HEAD /dummy HTTP/1.1\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert!(data.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{data}");
+}
+
+#[test]
+pub fn tc73_head_fallback_not_found() {
+  let server =
+    ServerBuilder::default().router(|rt| rt.route_get("/dummy", get_route)).expect("ERR").build();
+
+  let stream = MockStream::with_str("HEAD /other HTTP/1.1\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(data, "HTTP/1.1 404 Not Found\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n");
+}
+
+static STREAMED: AtomicBool = AtomicBool::new(false);
+
+fn streaming_route(request: &RequestContext) -> TiiResult<Response> {
+  assert_eq!(request.get_method(), &tii::HttpMethod::Head);
+  Ok(Response::ok(
+    ResponseBody::chunked(|sink| {
+      STREAMED.store(true, SeqCst);
+      sink.write_all(b"Hello")
+    }),
+    MimeType::TextPlain,
+  ))
+}
+
+#[test]
+pub fn tc73_head_fallback_does_not_run_stream() {
+  let server = ServerBuilder::default()
+    .router(|rt| rt.route_get("/dummy", streaming_route))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("HEAD /dummy HTTP/1.1\r\n\r\n");
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+
+  let data = stream.copy_written_data_to_string();
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\nTransfer-Encoding: chunked\r\n\r\n"
+  );
+  assert!(!STREAMED.load(SeqCst));
+}
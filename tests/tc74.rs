@@ -0,0 +1,230 @@
+use crate::mock_stream::MockStream;
+use std::time::Duration;
+use tii::{CorsPolicy, HttpMethod, MimeType, TiiResult};
+use tii::{RequestContext, Response, ServerBuilder, UserError};
+
+mod mock_stream;
+
+fn dummy_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("Hello", MimeType::TextPlain))
+}
+
+fn options_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("Options", MimeType::TextPlain))
+}
+
+fn serve(server: &tii::Server, request: &str) -> String {
+  let stream = MockStream::with_str(request);
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+  stream.copy_written_data_to_string()
+}
+
+#[test]
+pub fn tc74_automatic_options() {
+  let server = ServerBuilder::default()
+    .router(|rt| rt.route_get("/dummy", dummy_route)?.route_post("/dummy", dummy_route))
+    .expect("ERR")
+    .build();
+
+  let data = serve(&server, "OPTIONS /dummy HTTP/1.1\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\nAllow: GET, HEAD, POST, OPTIONS\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
+
+  let data = serve(&server, "DELETE /dummy HTTP/1.1\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD, POST, OPTIONS\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc74_automatic_options_unknown_path() {
+  let server =
+    ServerBuilder::default().router(|rt| rt.route_get("/dummy", dummy_route)).expect("ERR").build();
+
+  let data = serve(&server, "OPTIONS /other HTTP/1.1\r\n\r\n");
+  assert_eq!(data, "HTTP/1.1 404 Not Found\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n");
+}
+
+#[test]
+pub fn tc74_explicit_options_route_wins() {
+  let server = ServerBuilder::default()
+    .router(|rt| rt.route_get("/dummy", dummy_route)?.route_options("/dummy", options_route))
+    .expect("ERR")
+    .build();
+
+  let data = serve(&server, "OPTIONS /dummy HTTP/1.1\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 7\r\n\r\nOptions"
+  );
+}
+
+#[test]
+pub fn tc74_automatic_options_disabled() {
+  let server = ServerBuilder::default()
+    .router(|rt| rt.with_automatic_options(false)?.route_get("/dummy", dummy_route))
+    .expect("ERR")
+    .build();
+
+  let data = serve(&server, "OPTIONS /dummy HTTP/1.1\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+fn cors_server() -> tii::Server {
+  ServerBuilder::default()
+    .router(|rt| {
+      rt.with_cors_policy(
+        CorsPolicy::new()
+          .with_allowed_origin("https://example.com")
+          .with_allowed_header("Content-Type")
+          .with_allowed_header("X-Custom")
+          .with_exposed_header("X-Result")
+          .with_credentials(true)
+          .with_max_age(Duration::from_secs(600)),
+      )?
+      .route_get("/dummy", dummy_route)?
+      .route_put("/dummy", dummy_route)
+    })
+    .expect("ERR")
+    .build()
+}
+
+#[test]
+pub fn tc74_cors_preflight() {
+  let data = serve(&cors_server(), "OPTIONS /dummy HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: PUT\r\nAccess-Control-Request-Headers: x-custom, content-type\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\n\
+    Vary: Access-Control-Request-Method, Access-Control-Request-Headers\r\n\
+    Access-Control-Allow-Methods: GET, HEAD, PUT, OPTIONS\r\n\
+    Access-Control-Allow-Headers: Content-Type, X-Custom\r\n\
+    Access-Control-Max-Age: 600\r\n\
+    Vary: Origin\r\n\
+    Access-Control-Allow-Origin: https://example.com\r\n\
+    Access-Control-Allow-Credentials: true\r\n\
+    Connection: Keep-Alive\r\n\
+    Content-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc74_cors_preflight_origin_not_allowed() {
+  let data = serve(&cors_server(), "OPTIONS /dummy HTTP/1.1\r\nOrigin: https://evil.com\r\nAccess-Control-Request-Method: PUT\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\n\
+    Vary: Access-Control-Request-Method, Access-Control-Request-Headers\r\n\
+    Vary: Origin\r\n\
+    Connection: Keep-Alive\r\n\
+    Content-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc74_cors_preflight_header_not_allowed() {
+  let data = serve(&cors_server(), "OPTIONS /dummy HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: PUT\r\nAccess-Control-Request-Headers: x-other\r\n\r\n");
+  assert!(!data.contains("Access-Control-Allow-Methods"), "{data}");
+}
+
+#[test]
+pub fn tc74_cors_preflight_method_not_allowed() {
+  let data = serve(&cors_server(), "OPTIONS /dummy HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: DELETE\r\n\r\n");
+  assert!(!data.contains("Access-Control-Allow-Methods"), "{data}");
+}
+
+#[test]
+pub fn tc74_cors_simple_request() {
+  let data = serve(&cors_server(), "GET /dummy HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\n\
+    Content-Type: text/plain\r\n\
+    Vary: Origin\r\n\
+    Access-Control-Allow-Origin: https://example.com\r\n\
+    Access-Control-Allow-Credentials: true\r\n\
+    Access-Control-Expose-Headers: X-Result\r\n\
+    Connection: Keep-Alive\r\n\
+    Content-Length: 5\r\n\r\nHello"
+  );
+}
+
+#[test]
+pub fn tc74_cors_any_origin() {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.with_cors_policy(CorsPolicy::new().with_any_origin().with_allowed_method(HttpMethod::Get))?
+        .route_get("/dummy", dummy_route)
+    })
+    .expect("ERR")
+    .build();
+
+  let data = serve(&server, "GET /dummy HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\n\
+    Content-Type: text/plain\r\n\
+    Access-Control-Allow-Origin: *\r\n\
+    Connection: Keep-Alive\r\n\
+    Content-Length: 5\r\n\r\nHello"
+  );
+
+  let data = serve(
+    &server,
+    "OPTIONS /dummy HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: GET\r\n\r\n",
+  );
+  assert_eq!(
+    data,
+    "HTTP/1.1 204 No Content\r\n\
+    Vary: Access-Control-Request-Method, Access-Control-Request-Headers\r\n\
+    Access-Control-Allow-Methods: GET\r\n\
+    Access-Control-Allow-Origin: *\r\n\
+    Connection: Keep-Alive\r\n\
+    Content-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc74_cors_not_found_has_headers() {
+  let data = serve(&cors_server(), "GET /other HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 404 Not Found\r\n"), "{data}");
+  assert!(data.contains("Access-Control-Allow-Origin: https://example.com\r\n"), "{data}");
+}
+
+#[test]
+pub fn tc74_cors_policy_set_twice() {
+  let result = ServerBuilder::default().router(|rt| {
+    rt.with_cors_policy(CorsPolicy::new().with_any_origin())?
+      .with_cors_policy(CorsPolicy::new().with_any_origin())
+  });
+  let err = result.err().unwrap();
+  assert!(
+    matches!(err.downcast_ref::<UserError>(), Some(UserError::CorsPolicyAlreadySet)),
+    "{err}"
+  );
+}
+
+#[test]
+pub fn tc74_cors_preflight_path_parameters() {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.with_cors_policy(CorsPolicy::new().with_any_origin())?
+        .route_get("/items/{id}", dummy_route)?
+        .route_delete("/items/{id}", dummy_route)?
+        .route_post("/items", dummy_route)
+    })
+    .expect("ERR")
+    .build();
+
+  let data = serve(
+    &server,
+    "OPTIONS /items/5 HTTP/1.1\r\nOrigin: https://example.com\r\nAccess-Control-Request-Method: DELETE\r\n\r\n",
+  );
+  assert!(data.contains("Access-Control-Allow-Methods: GET, HEAD, DELETE, OPTIONS\r\n"), "{data}");
+}
@@ -0,0 +1,114 @@
+use crate::mock_stream::MockStream;
+use std::io::Cursor;
+use tii::{MimeType, ResponseBody, TiiResult};
+use tii::{RequestContext, Response, ServerBuilder};
+
+mod mock_stream;
+
+const CONTENT: &[u8] = b"0123456789abcdefghij";
+
+fn file_route(_: &RequestContext) -> TiiResult<Response> {
+  Response::ok(ResponseBody::from_file(Cursor::new(CONTENT))?, MimeType::TextPlain)
+    .with_header("ETag", "\"v1\"")
+}
+
+fn serve(request: &str) -> String {
+  let server =
+    ServerBuilder::default().router(|rt| rt.route_get("/file", file_route)).expect("ERR").build();
+
+  let stream = MockStream::with_str(request);
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+  stream.copy_written_data_to_string()
+}
+
+#[test]
+pub fn tc75_no_range() {
+  let data = serve("GET /file HTTP/1.1\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nETag: \"v1\"\r\nAccept-Ranges: bytes\r\nConnection: Keep-Alive\r\nContent-Length: 20\r\n\r\n0123456789abcdefghij"
+  );
+}
+
+#[test]
+pub fn tc75_single_range() {
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=2-5\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 206 Partial Content\r\nContent-Type: text/plain\r\nETag: \"v1\"\r\nAccept-Ranges: bytes\r\nContent-Range: bytes 2-5/20\r\nConnection: Keep-Alive\r\nContent-Length: 4\r\n\r\n2345"
+  );
+}
+
+#[test]
+pub fn tc75_suffix_and_open_ranges() {
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=-3\r\n\r\n");
+  assert!(data.contains("Content-Range: bytes 17-19/20\r\n"), "{data}");
+  assert!(data.ends_with("\r\n\r\nhij"), "{data}");
+
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=15-\r\n\r\n");
+  assert!(data.contains("Content-Range: bytes 15-19/20\r\n"), "{data}");
+  assert!(data.ends_with("\r\n\r\nfghij"), "{data}");
+}
+
+#[test]
+pub fn tc75_multiple_ranges() {
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=0-1, 10-12\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{data}");
+
+  let boundary = data
+    .split("Content-Type: multipart/byteranges; boundary=")
+    .nth(1)
+    .and_then(|rest| rest.split("\r\n").next())
+    .unwrap();
+
+  let body = format!(
+    "--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/20\r\n\r\n01\r\n\
+    --{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 10-12/20\r\n\r\nabc\r\n\
+    --{boundary}--\r\n"
+  );
+  assert!(data.contains(&format!("Content-Length: {}\r\n", body.len())), "{data}");
+  assert!(data.ends_with(&format!("\r\n\r\n{body}")), "{data}");
+}
+
+#[test]
+pub fn tc75_overlapping_ranges() {
+  //Overlapping and adjacent ranges are merged into a single range.
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=4-6, 0-3, 2-5\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{data}");
+  assert!(data.contains("Content-Range: bytes 0-6/20\r\n"), "{data}");
+  assert!(data.ends_with("\r\n\r\n0123456"), "{data}");
+}
+
+#[test]
+pub fn tc75_not_satisfiable() {
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=20-30\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 416 Requested Range Not Satisfiable\r\nAccept-Ranges: bytes\r\nContent-Range: bytes */20\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc75_malformed_range_is_ignored() {
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=5-2\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 200 OK\r\n"), "{data}");
+  assert!(data.ends_with("\r\n\r\n0123456789abcdefghij"), "{data}");
+}
+
+#[test]
+pub fn tc75_if_range() {
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: \"v1\"\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{data}");
+
+  let data = serve("GET /file HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: \"v2\"\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 200 OK\r\n"), "{data}");
+  assert!(data.ends_with("\r\n\r\n0123456789abcdefghij"), "{data}");
+}
+
+#[test]
+pub fn tc75_head_range() {
+  let expected = "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nETag: \"v1\"\r\nAccept-Ranges: bytes\r\nConnection: Close\r\nContent-Length: 20\r\n\r\n";
+  assert_eq!(serve("HEAD /file HTTP/1.1\r\nRange: bytes=2-5\r\n\r\n"), expected);
+  assert_eq!(serve("HEAD /file HTTP/1.1\r\nRange: bytes=30-\r\n\r\n"), expected);
+}
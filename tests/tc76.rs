@@ -0,0 +1,211 @@
+use crate::mock_stream::MockStream;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tii::{etag_filter, precondition_filter, MimeType, TiiResult};
+use tii::{RequestContext, Response, ServerBuilder};
+
+mod mock_stream;
+
+fn hello_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("Hello", MimeType::TextPlain))
+}
+
+fn put_route(request: &RequestContext) -> TiiResult<Response> {
+  if let Some(failed) = request.evaluate_preconditions(Some("\"v1\""), None)? {
+    return Ok(failed);
+  }
+
+  Ok(Response::no_content())
+}
+
+fn serve(request: &str) -> String {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.with_response_filter(etag_filter)?
+        .route_get("/hello", hello_route)?
+        .route_put("/hello", put_route)?
+        .route_delete("/hello", |_: &RequestContext| Ok(Response::no_content()))
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(request);
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+  stream.copy_written_data_to_string()
+}
+
+fn etag() -> String {
+  let data = serve("GET /hello HTTP/1.1\r\n\r\n");
+  data.split("ETag: ").nth(1).and_then(|rest| rest.split("\r\n").next()).unwrap().to_string()
+}
+
+#[test]
+pub fn tc76_etag_filter() {
+  let etag = etag();
+  assert!(etag.starts_with('"') && etag.ends_with('"') && etag.len() > 2, "{etag}");
+
+  let data = serve("GET /hello HTTP/1.1\r\n\r\n");
+  assert_eq!(
+    data,
+    format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nETag: {etag}\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nHello")
+  );
+}
+
+#[test]
+pub fn tc76_if_none_match() {
+  let etag = etag();
+  let data = serve(&format!("GET /hello HTTP/1.1\r\nIf-None-Match: \"other\", {etag}\r\n\r\n"));
+  assert_eq!(
+    data,
+    format!(
+      "HTTP/1.1 304 Not Modified\r\nETag: {etag}\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+    )
+  );
+
+  let data = serve(&format!("HEAD /hello HTTP/1.1\r\nIf-None-Match: W/{etag}\r\n\r\n"));
+  assert!(data.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{data}");
+
+  let data = serve("GET /hello HTTP/1.1\r\nIf-None-Match: *\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{data}");
+
+  let data = serve("GET /hello HTTP/1.1\r\nIf-None-Match: \"other\"\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 200 OK\r\n"), "{data}");
+}
+
+#[test]
+pub fn tc76_if_match_on_get() {
+  let data = serve("GET /hello HTTP/1.1\r\nIf-Match: \"other\"\r\n\r\n");
+  assert_eq!(
+    data,
+    "HTTP/1.1 412 Precondition Failed\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc76_put_preconditions() {
+  let data = serve("PUT /hello HTTP/1.1\r\nIf-Match: \"v1\"\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 204 No Content\r\n"), "{data}");
+
+  let data = serve("PUT /hello HTTP/1.1\r\nIf-Match: \"v0\"\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 412 Precondition Failed\r\n"), "{data}");
+
+  let data = serve("PUT /hello HTTP/1.1\r\nIf-Match: W/\"v1\"\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 412 Precondition Failed\r\n"), "{data}");
+
+  let data = serve("PUT /hello HTTP/1.1\r\nIf-None-Match: *\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 412 Precondition Failed\r\n"), "{data}");
+}
+
+#[test]
+pub fn tc76_unsafe_preconditions_are_opt_in() {
+  //The DELETE endpoint does not evaluate the preconditions, so they are ignored.
+  let data = serve("DELETE /hello HTTP/1.1\r\nIf-Match: \"v0\"\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 204 No Content\r\n"), "{data}");
+
+  let data =
+    serve("DELETE /hello HTTP/1.1\r\nIf-Unmodified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 204 No Content\r\n"), "{data}");
+}
+
+fn validators(request: &RequestContext) -> TiiResult<(Option<String>, Option<SystemTime>)> {
+  if request.get_path() != "/hello" {
+    return Ok((None, None));
+  }
+
+  Ok((Some("\"v1\"".to_string()), Some(UNIX_EPOCH + Duration::from_secs(784111777))))
+}
+
+#[test]
+pub fn tc76_precondition_filter() {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.with_request_filter(precondition_filter(validators))?
+        .route_delete("/hello", |_: &RequestContext| Ok(Response::no_content()))?
+        .route_delete("/missing", |_: &RequestContext| Ok(Response::no_content()))
+    })
+    .expect("ERR")
+    .build();
+
+  let serve = |request: &str| {
+    let stream = MockStream::with_str(request);
+    server.handle_connection(stream.to_stream()).unwrap();
+    stream.copy_written_data_to_string()
+  };
+
+  let cases = [
+    ("DELETE /hello HTTP/1.1\r\n\r\n", "HTTP/1.1 204 No Content"),
+    ("DELETE /hello HTTP/1.1\r\nIf-Match: \"v1\"\r\n\r\n", "HTTP/1.1 204 No Content"),
+    ("DELETE /hello HTTP/1.1\r\nIf-Match: \"v0\"\r\n\r\n", "HTTP/1.1 412 Precondition Failed"),
+    ("DELETE /hello HTTP/1.1\r\nIf-None-Match: *\r\n\r\n", "HTTP/1.1 412 Precondition Failed"),
+    (
+      "DELETE /hello HTTP/1.1\r\nIf-Unmodified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+      "HTTP/1.1 204 No Content",
+    ),
+    (
+      "DELETE /hello HTTP/1.1\r\nIf-Unmodified-Since: Sun, 06 Nov 1994 08:49:36 GMT\r\n\r\n",
+      "HTTP/1.1 412 Precondition Failed",
+    ),
+    ("DELETE /missing HTTP/1.1\r\nIf-Match: *\r\n\r\n", "HTTP/1.1 412 Precondition Failed"),
+    ("DELETE /missing HTTP/1.1\r\nIf-None-Match: *\r\n\r\n", "HTTP/1.1 204 No Content"),
+  ];
+
+  for (request, expected) in cases {
+    let data = serve(request);
+    assert!(data.starts_with(&format!("{expected}\r\n")), "{request:?} {data}");
+  }
+}
+
+#[cfg(feature = "extras")]
+mod serve_file {
+  use crate::mock_stream::MockStream;
+  use tii::extras::builtin_endpoints::serve_file;
+  use tii::ServerBuilder;
+
+  fn serve(request: &str) -> String {
+    let server = ServerBuilder::default()
+      .router(|rt| rt.route_get("/license", serve_file("test_files/IMAGES/LICENSE")))
+      .expect("ERR")
+      .build();
+
+    let stream = MockStream::with_str(request);
+    server.handle_connection(stream.to_stream()).unwrap();
+    stream.copy_written_data_to_string()
+  }
+
+  fn header(data: &str, name: &str) -> String {
+    data
+      .split(&format!("\r\n{name}: "))
+      .nth(1)
+      .and_then(|rest| rest.split("\r\n").next())
+      .unwrap()
+      .to_string()
+  }
+
+  #[test]
+  pub fn tc76_serve_file_validators() {
+    let data = serve("GET /license HTTP/1.1\r\n\r\n");
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"), "{data}");
+    let etag = header(&data, "ETag");
+    let last_modified = header(&data, "Last-Modified");
+
+    let data = serve(&format!("GET /license HTTP/1.1\r\nIf-None-Match: {etag}\r\n\r\n"));
+    assert_eq!(
+      data,
+      format!("HTTP/1.1 304 Not Modified\r\nETag: {etag}\r\nLast-Modified: {last_modified}\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n")
+    );
+
+    let data =
+      serve(&format!("GET /license HTTP/1.1\r\nIf-Modified-Since: {last_modified}\r\n\r\n"));
+    assert!(data.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{data}");
+
+    let data =
+      serve("GET /license HTTP/1.1\r\nIf-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n");
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"), "{data}");
+
+    //If-None-Match takes precedence over If-Modified-Since
+    let data = serve(&format!(
+      "GET /license HTTP/1.1\r\nIf-None-Match: \"other\"\r\nIf-Modified-Since: {last_modified}\r\n\r\n"
+    ));
+    assert!(data.starts_with("HTTP/1.1 200 OK\r\n"), "{data}");
+  }
+}
@@ -0,0 +1,121 @@
+use crate::mock_stream::MockStream;
+use std::io::Read;
+use tii::{MimeType, MultipartForm, MultipartFormDeserializer, MultipartReader, TiiResult};
+use tii::{RequestContext, Response, ServerBuilder};
+
+mod mock_stream;
+
+const BODY: &str = "--XyZ\r\n\
+Content-Disposition: form-data; name=\"title\"\r\n\
+\r\n\
+Hello World\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"upload\"; filename=\"notes.txt\"\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+line1\r\nline2\r\n--Xy\r\n\
+--XyZ--\r\n";
+
+fn form_route(_: &RequestContext, form: &MultipartForm) -> TiiResult<Response> {
+  assert_eq!(form.fields().len(), 2);
+  assert_eq!(form.get_text("title"), Some("Hello World"));
+
+  let upload = form.get_field("upload").unwrap();
+  assert_eq!(upload.filename(), Some("notes.txt"));
+  assert_eq!(upload.content_type().map(|ct| ct.mime()), Some(&MimeType::TextPlain));
+  assert_eq!(upload.get_header("content-type"), Some("text/plain"));
+  assert_eq!(upload.data(), b"line1\r\nline2\r\n--Xy");
+  assert_eq!(form.files().count(), 1);
+
+  Ok(Response::ok("OK", MimeType::TextPlain))
+}
+
+fn streaming_route(request: &RequestContext) -> TiiResult<Response> {
+  let content_type = request.get_content_type().unwrap();
+  let body = request.request_body().unwrap();
+  let mut reader = MultipartReader::from_request_body(content_type, body)?;
+
+  let mut names = Vec::new();
+  while let Some(mut part) = reader.next_part()? {
+    names.push(part.name().unwrap_or_default().to_string());
+    if part.filename().is_some() {
+      let mut first = [0u8; 5];
+      part.read_exact(&mut first)?;
+      assert_eq!(&first, b"line1");
+      //The rest of the part is skipped by next_part.
+    }
+  }
+
+  Ok(Response::ok(names.join(","), MimeType::TextPlain))
+}
+
+fn form_data() -> MimeType {
+  MimeType::parse("multipart/form-data").unwrap()
+}
+
+fn serve(path: &str, content_type: &str, body: &str) -> String {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.post("/form")
+        .consumes(form_data())
+        .entity_endpoint(form_route, MultipartFormDeserializer::new())?
+        .post("/limited")
+        .consumes(form_data())
+        .entity_endpoint(form_route, MultipartFormDeserializer::new().with_max_part_size(11))?
+        .route_post("/stream", streaming_route)
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(
+    format!(
+      "POST {path} HTTP/1.1\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+      body.len()
+    )
+    .as_str(),
+  );
+  server.handle_connection(stream.to_stream()).unwrap();
+  stream.copy_written_data_to_string()
+}
+
+#[test]
+pub fn tc77_multipart_form() {
+  let data = serve("/form", "multipart/form-data; boundary=XyZ", BODY);
+  assert_eq!(
+    data,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 2\r\n\r\nOK"
+  );
+}
+
+#[test]
+pub fn tc77_multipart_quoted_boundary() {
+  let data = serve("/form", "multipart/form-data; charset=utf-8; boundary=\"XyZ\"", BODY);
+  assert!(data.starts_with("HTTP/1.1 200 OK\r\n"), "{data}");
+}
+
+#[test]
+pub fn tc77_multipart_streaming() {
+  let data = serve("/stream", "multipart/form-data; boundary=XyZ", BODY);
+  assert!(data.ends_with("\r\n\r\ntitle,upload"), "{data}");
+}
+
+#[test]
+pub fn tc77_multipart_part_too_large() {
+  let data = serve("/limited", "multipart/form-data; boundary=XyZ", BODY);
+  assert_eq!(
+    data,
+    "HTTP/1.1 413 Content Too Large\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc77_multipart_malformed() {
+  let data = serve("/form", "multipart/form-data; boundary=XyZ", "--XyZ\r\nno header\r\n\r\n");
+  assert!(data.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{data}");
+
+  let data = serve("/form", "multipart/form-data; boundary=XyZ", &BODY[..BODY.len() - 9]);
+  assert!(data.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{data}");
+
+  let data = serve("/form", "multipart/form-data", BODY);
+  assert!(data.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{data}");
+}
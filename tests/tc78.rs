@@ -0,0 +1,109 @@
+use crate::mock_stream::MockStream;
+use tii::{MimeType, TiiResult, UrlEncodedForm};
+use tii::{RequestContext, Response, ServerBuilder};
+
+mod mock_stream;
+
+fn form_data() -> MimeType {
+  MimeType::parse("application/x-www-form-urlencoded").unwrap()
+}
+
+fn helper_route(request: &RequestContext) -> TiiResult<Response> {
+  let form = request.read_urlencoded_form()?.unwrap();
+  let name = form.get_param("name").unwrap_or_default().to_string();
+  let age = form.parse_param_or::<u32, _>("age", 0)?;
+  let tags = form.get_params("tag").join(",");
+  Ok(Response::ok(format!("{name}|{age}|{tags}"), MimeType::TextPlain))
+}
+
+fn entity_route(_: &RequestContext, form: &UrlEncodedForm) -> TiiResult<Response> {
+  Ok(Response::ok(format!("{:?}", form.get_all_params()), MimeType::TextPlain))
+}
+
+fn serve(path: &str, content_type: &str, body: &str) -> String {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.post("/helper")
+        .consumes(form_data())
+        .endpoint(helper_route)?
+        .post("/entity")
+        .consumes(form_data())
+        .entity_endpoint(entity_route, UrlEncodedForm::from_request_body)?
+        .post("/limited")
+        .consumes(form_data())
+        .max_request_body_size(8)
+        .endpoint(helper_route)
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(
+    format!(
+      "POST {path} HTTP/1.1\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+      body.len()
+    )
+    .as_str(),
+  );
+  server.handle_connection(stream.to_stream()).unwrap();
+  stream.copy_written_data_to_string()
+}
+
+#[test]
+pub fn tc78_form_helper() {
+  let data = serve(
+    "/helper",
+    "application/x-www-form-urlencoded",
+    "name=J%C3%BCrgen+M%2B&age=42&tag=a&tag=b",
+  );
+  assert!(data.ends_with("\r\n\r\nJürgen M+|42|a,b"), "{data}");
+}
+
+#[test]
+pub fn tc78_form_entity() {
+  let data = serve("/entity", "application/x-www-form-urlencoded", "a=1&b=");
+  assert!(data.ends_with("\r\n\r\n[(\"a\", \"1\"), (\"b\", \"\")]"), "{data}");
+}
+
+#[test]
+pub fn tc78_form_charset() {
+  let data =
+    serve("/helper", "application/x-www-form-urlencoded; charset=ISO-8859-1", "name=J%FCrgen");
+  assert!(data.ends_with("\r\n\r\nJürgen|0|"), "{data}");
+
+  let data = serve("/helper", "application/x-www-form-urlencoded", "name=J%FCrgen");
+  assert!(data.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{data}");
+
+  let data = serve("/helper", "application/x-www-form-urlencoded; charset=koi8-r", "name=x");
+  assert!(data.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{data}");
+}
+
+#[test]
+pub fn tc78_form_malformed() {
+  let data = serve("/helper", "application/x-www-form-urlencoded", "name=a b");
+  assert!(data.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{data}");
+}
+
+#[test]
+pub fn tc78_form_too_large() {
+  let data = serve("/limited", "application/x-www-form-urlencoded", "name=abcdefgh");
+  assert!(data.starts_with("HTTP/1.1 413 Content Too Large\r\n"), "{data}");
+}
+
+fn query_route(request: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok(format!("{:?}", request.get_query()), MimeType::TextPlain))
+}
+
+#[test]
+pub fn tc78_query_and_form_plus_decoding() {
+  //Query strings decode %2B after percent decoding, so it becomes a space just like +.
+  let server =
+    ServerBuilder::default().router(|rt| rt.route_get("/query", query_route)).expect("ERR").build();
+  let stream = MockStream::with_str("GET /query?a=1+2%2B3 HTTP/1.1\r\n\r\n");
+  server.handle_connection(stream.to_stream()).unwrap();
+  let data = stream.copy_written_data_to_string();
+  assert!(data.ends_with("\r\n\r\n[(\"a\", \"1 2 3\")]"), "{data}");
+
+  //Form bodies decode %2B as a plus.
+  let data = serve("/entity", "application/x-www-form-urlencoded", "a=1+2%2B3");
+  assert!(data.ends_with("\r\n\r\n[(\"a\", \"1 2+3\")]"), "{data}");
+}
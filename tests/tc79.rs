@@ -0,0 +1,157 @@
+use crate::mock_stream::MockStream;
+use libflate::{gzip, zlib};
+use std::io::Read;
+use tii::{
+  CompressionFilter, ContentCoding, HttpHeaderName, MimeType, RequestContext, Response,
+  ResponseBody, ServerBuilder, TiiResult,
+};
+
+mod mock_stream;
+
+fn text() -> String {
+  "Hello World! ".repeat(200)
+}
+
+fn text_route(_: &RequestContext) -> TiiResult<Response> {
+  Response::ok(text(), MimeType::TextPlain).with_header(HttpHeaderName::ETag, "\"v1\"")
+}
+
+fn small_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("Hello", MimeType::TextPlain))
+}
+
+fn image_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok(text(), MimeType::ImagePng))
+}
+
+fn gzipped_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok(ResponseBody::from_data_with_gzip_in_memory(text()), MimeType::TextPlain))
+}
+
+fn chunked_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok(
+    ResponseBody::chunked(|sink| {
+      sink.write_all(text().as_bytes())?;
+      sink.write_all(text().as_bytes())
+    }),
+    MimeType::TextPlain,
+  ))
+}
+
+fn serve_with(filter: CompressionFilter, request: &str) -> (String, Vec<u8>) {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.route_get("/text", text_route)?
+        .route_get("/small", small_route)?
+        .route_get("/image", image_route)?
+        .route_get("/gzipped", gzipped_route)?
+        .route_get("/chunked", chunked_route)?
+        .with_response_filter(filter)
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(request);
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+  let data = stream.copy_written_data();
+  let split = data.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+  (String::from_utf8(data[..split + 4].to_vec()).unwrap(), data[split + 4..].to_vec())
+}
+
+fn serve(request: &str) -> (String, Vec<u8>) {
+  serve_with(CompressionFilter::new(), request)
+}
+
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+  let mut result = Vec::new();
+  loop {
+    let line_end = body.windows(2).position(|w| w == b"\r\n").unwrap();
+    let size = usize::from_str_radix(std::str::from_utf8(&body[..line_end]).unwrap(), 16).unwrap();
+    body = &body[line_end + 2..];
+    if size == 0 {
+      return result;
+    }
+    result.extend_from_slice(&body[..size]);
+    body = &body[size + 2..];
+  }
+}
+
+#[test]
+pub fn tc79_gzip() {
+  let (head, body) = serve("GET /text HTTP/1.1\r\nAccept-Encoding: deflate;q=0.5, gzip\r\n\r\n");
+  assert_eq!(head, format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nVary: Accept-Encoding\r\nETag: \"v1-gzip\"\r\nConnection: Keep-Alive\r\nContent-Length: {}\r\nContent-Encoding: gzip\r\n\r\n", body.len()));
+  let mut decoded = String::new();
+  gzip::Decoder::new(body.as_slice()).unwrap().read_to_string(&mut decoded).unwrap();
+  assert_eq!(decoded, text());
+}
+
+#[test]
+pub fn tc79_deflate() {
+  let (head, body) = serve("GET /text HTTP/1.1\r\nAccept-Encoding: gzip;q=0.5, deflate\r\n\r\n");
+  assert!(head.contains("\r\nContent-Encoding: deflate\r\n"), "{head}");
+  let mut decoded = String::new();
+  zlib::Decoder::new(body.as_slice()).unwrap().read_to_string(&mut decoded).unwrap();
+  assert_eq!(decoded, text());
+
+  let filter = CompressionFilter::new().with_codings([ContentCoding::Deflate, ContentCoding::Gzip]);
+  let (head, _) =
+    serve_with(filter, "GET /text HTTP/1.1\r\nAccept-Encoding: gzip, deflate\r\n\r\n");
+  assert!(head.contains("\r\nContent-Encoding: deflate\r\n"), "{head}");
+}
+
+#[test]
+pub fn tc79_not_accepted() {
+  for accept in ["", "Accept-Encoding: identity\r\n", "Accept-Encoding: gzip;q=0, br\r\n"] {
+    let (head, body) = serve(&format!("GET /text HTTP/1.1\r\n{accept}\r\n"));
+    assert_eq!(head, format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nETag: \"v1\"\r\nVary: Accept-Encoding\r\nConnection: Keep-Alive\r\nContent-Length: {}\r\n\r\n", text().len()));
+    assert_eq!(body, text().as_bytes());
+  }
+}
+
+#[test]
+pub fn tc79_not_eligible() {
+  let (head, body) = serve("GET /small HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n");
+  assert_eq!(
+    head,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\n"
+  );
+  assert_eq!(body, b"Hello");
+
+  let (head, body) = serve("GET /image HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n");
+  assert!(!head.contains("Content-Encoding") && !head.contains("Vary"), "{head}");
+  assert_eq!(body, text().as_bytes());
+
+  let (head, body) = serve_with(
+    CompressionFilter::new().with_mime_type(MimeType::ImagePng),
+    "GET /image HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+  );
+  assert!(head.contains("\r\nContent-Encoding: gzip\r\n"), "{head}");
+  assert_ne!(body, text().as_bytes());
+
+  let (head, body) = serve("GET /gzipped HTTP/1.1\r\nAccept-Encoding: deflate\r\n\r\n");
+  assert!(head.contains("\r\nContent-Encoding: gzip\r\n") && !head.contains("Vary"), "{head}");
+  let mut decoded = String::new();
+  gzip::Decoder::new(body.as_slice()).unwrap().read_to_string(&mut decoded).unwrap();
+  assert_eq!(decoded, text());
+}
+
+#[test]
+pub fn tc79_chunked() {
+  let (head, body) = serve("GET /chunked HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n");
+  assert_eq!(head, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nVary: Accept-Encoding\r\nConnection: Keep-Alive\r\nTransfer-Encoding: chunked\r\nContent-Encoding: gzip\r\n\r\n");
+  let mut decoded = String::new();
+  gzip::Decoder::new(dechunk(&body).as_slice()).unwrap().read_to_string(&mut decoded).unwrap();
+  assert_eq!(decoded, text().repeat(2));
+}
+
+#[test]
+pub fn tc79_if_none_match() {
+  let (head, _) =
+    serve("GET /text HTTP/1.1\r\nAccept-Encoding: gzip\r\nIf-None-Match: \"v1-gzip\"\r\n\r\n");
+  assert!(head.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{head}");
+
+  let (head, _) =
+    serve("GET /text HTTP/1.1\r\nAccept-Encoding: gzip\r\nIf-None-Match: \"v1\"\r\n\r\n");
+  assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+}
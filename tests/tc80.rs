@@ -0,0 +1,129 @@
+use crate::mock_stream::MockStream;
+use libflate::{gzip, zlib};
+use std::io::Write;
+use tii::{MimeType, RequestContext, Response, ServerBuilder, TiiResult};
+
+mod mock_stream;
+
+const JSON: &[u8] = b"{ \"mydummy\" : \"json\" }\n";
+
+fn echo_route(ctx: &RequestContext) -> TiiResult<Response> {
+  let data = ctx.request_body().unwrap().read_to_vec()?;
+  Ok(Response::ok(data, MimeType::TextPlain))
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+  let mut encoder = zlib::Encoder::new(Vec::new()).unwrap();
+  encoder.write_all(data).unwrap();
+  encoder.finish().into_result().unwrap()
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+  let mut encoder = gzip::Encoder::new(Vec::new()).unwrap();
+  encoder.write_all(data).unwrap();
+  encoder.finish().into_result().unwrap()
+}
+
+fn serve(max_size: Option<u64>, request: &[u8]) -> String {
+  let server = ServerBuilder::default()
+    .with_max_request_body_size(max_size)
+    .unwrap()
+    .router(|rt| rt.route_any("/echo", echo_route))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_slice(request);
+  let con = stream.to_stream();
+  server.handle_connection(con).unwrap();
+  stream.copy_written_data_to_string()
+}
+
+fn request(headers: &str, body: &[u8]) -> Vec<u8> {
+  let mut data = Vec::<u8>::new();
+  data.extend_from_slice(b"POST /echo HTTP/1.1\r\nConnection: keep-alive\r\n");
+  data.extend_from_slice(headers.as_bytes());
+  data.extend_from_slice(b"\r\n");
+  data.extend_from_slice(body);
+  data.extend_from_slice(b"GET /404 HTTP/1.1\r\n\r\n");
+  data
+}
+
+fn chunked(data: &[u8]) -> Vec<u8> {
+  let mut result = Vec::new();
+  for chunk in data.chunks(7) {
+    result.extend_from_slice(format!("{:X}\r\n", chunk.len()).as_bytes());
+    result.extend_from_slice(chunk);
+    result.extend_from_slice(b"\r\n");
+  }
+  result.extend_from_slice(b"0\r\n\r\n");
+  result
+}
+
+fn expected_echo() -> String {
+  format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Keep-Alive\r\nContent-Length: {}\r\n\r\n{}HTTP/1.1 404 Not Found\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n", JSON.len(), std::str::from_utf8(JSON).unwrap())
+}
+
+#[test]
+pub fn tc80_deflate_content_length() {
+  let body = deflate(JSON);
+  let data = serve(
+    None,
+    &request(&format!("Content-Encoding: deflate\r\nContent-Length: {}\r\n", body.len()), &body),
+  );
+  assert_eq!(data, expected_echo());
+}
+
+#[test]
+pub fn tc80_deflate_chunked() {
+  let body = chunked(&deflate(JSON));
+  let data =
+    serve(None, &request("Content-Encoding: deflate\r\nTransfer-Encoding: chunked\r\n", &body));
+  assert_eq!(data, expected_echo());
+
+  let data = serve(None, &request("Transfer-Encoding: deflate, chunked\r\n", &body));
+  assert_eq!(data, expected_echo());
+}
+
+#[test]
+pub fn tc80_stacked() {
+  //gzip was applied first, so deflate has to be decoded first.
+  let body = deflate(&gzip(JSON));
+  let data = serve(
+    None,
+    &request(
+      &format!("Content-Encoding: gzip, identity, deflate\r\nContent-Length: {}\r\n", body.len()),
+      &body,
+    ),
+  );
+  assert_eq!(data, expected_echo());
+
+  let body = chunked(&gzip(&deflate(JSON)));
+  let data = serve(
+    None,
+    &request("Content-Encoding: deflate, x-gzip\r\nTransfer-Encoding: chunked\r\n", &body),
+  );
+  assert_eq!(data, expected_echo());
+}
+
+#[test]
+pub fn tc80_deflate_decompressed_too_large() {
+  let body = deflate(&[0u8; 65536]);
+  let data = serve(
+    Some(1024),
+    &request(&format!("Content-Encoding: deflate\r\nContent-Length: {}\r\n", body.len()), &body),
+  );
+  assert_eq!(
+    data,
+    "HTTP/1.1 413 Content Too Large\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+}
+
+#[test]
+pub fn tc80_unsupported_encoding() {
+  let server =
+    ServerBuilder::default().router(|rt| rt.route_any("/echo", echo_route)).expect("ERR").build();
+  let stream =
+    MockStream::with_slice(&request("Content-Encoding: gzip, br\r\nContent-Length: 1\r\n", b"a"));
+  let con = stream.to_stream();
+  assert!(server.handle_connection(con).is_err());
+}
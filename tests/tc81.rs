@@ -0,0 +1,125 @@
+use tii::testing::TestClient;
+use tii::{
+  CompressionFilter, MimeType, RequestContext, Response, ServerBuilder, TiiResult,
+  WebsocketMessage, WebsocketReceiver, WebsocketSender,
+};
+
+fn echo_route(request: &RequestContext) -> TiiResult<Response> {
+  let body = request.request_body().map(|body| body.read_to_vec()).transpose()?.unwrap_or_default();
+  let name = request.get_query_param("name").unwrap_or("nobody").to_string();
+  Response::ok(body, MimeType::TextPlain)
+    .with_header("X-Name", name)?
+    .with_header("X-Method", request.get_method().as_str())
+}
+
+fn big_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("text ".repeat(1000), MimeType::TextPlain))
+}
+
+fn ws_route(_: &RequestContext, mut rx: WebsocketReceiver, tx: WebsocketSender) -> TiiResult<()> {
+  while let Some(message) = rx.read_message()? {
+    match message {
+      WebsocketMessage::Text(text) => tx.text(format!("echo {text}"))?,
+      WebsocketMessage::Binary(binary) => {
+        tx.binary(binary.into_iter().rev().collect::<Vec<_>>())?
+      }
+      WebsocketMessage::Ping => tx.ping()?,
+      WebsocketMessage::Pong | WebsocketMessage::Close(_) => {}
+    }
+  }
+  tx.close()
+}
+
+fn client() -> TestClient {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.route_any("/echo", echo_route)?
+        .route_get("/big", big_route)?
+        .ws_route_get("/ws", ws_route)?
+        .with_response_filter(CompressionFilter::new())
+    })
+    .expect("ERR")
+    .build();
+
+  TestClient::new(server)
+}
+
+#[test]
+pub fn tc81_requests() {
+  let mut client = client();
+
+  let response = client.get("/echo").with_query_param("name", "a b&c").send().unwrap();
+  assert_eq!(response.get_status_code_number(), 200);
+  assert_eq!(response.get_header("x-name"), Some("a b&c"));
+  assert_eq!(response.get_header("Connection"), Some("Keep-Alive"));
+  assert_eq!(response.get_body(), b"");
+
+  let response = client.post("/echo").with_body("hello").send().unwrap();
+  assert_eq!(response.get_header("X-Method"), Some("POST"));
+  assert_eq!(response.get_body_text(), Some("hello"));
+
+  let response = client
+    .put("/echo")
+    .with_body("chunked gzip")
+    .with_chunked(true)
+    .with_gzip(true)
+    .send()
+    .unwrap();
+  assert_eq!(response.get_header("X-Method"), Some("PUT"));
+  assert_eq!(response.get_body_text(), Some("chunked gzip"));
+
+  let response = client.head("/echo").send().unwrap();
+  assert_eq!(response.get_status_code_number(), 200);
+  assert_eq!(response.get_body(), b"");
+
+  let response = client.get("/missing").send().unwrap();
+  assert_eq!(response.get_status_code_number(), 404);
+
+  client.close().unwrap();
+}
+
+#[test]
+pub fn tc81_decoded_body() {
+  let mut client = client();
+  let response = client.get("/big").with_header("Accept-Encoding", "gzip").send().unwrap();
+  assert_eq!(response.get_header("Content-Encoding"), Some("gzip"));
+  assert!(response.get_raw_body().len() < 1000);
+  assert_eq!(response.get_body_text(), Some("text ".repeat(1000).as_str()));
+}
+
+#[test]
+pub fn tc81_connection_close() {
+  let mut client = client();
+  let response = client.get("/echo").with_header("Connection", "close").send().unwrap();
+  assert_eq!(response.get_header("Connection"), Some("Close"));
+
+  let response = client.get("/echo").send().unwrap();
+  assert_eq!(response.get_status_code_number(), 200);
+}
+
+#[test]
+pub fn tc81_websocket() {
+  let mut client = client();
+  let mut ws = client.get("/ws").websocket().unwrap();
+  assert_eq!(ws.get_handshake_response().get_status_code_number(), 101);
+
+  ws.text("hello").unwrap();
+  assert!(
+    matches!(ws.read_message().unwrap(), Some(WebsocketMessage::Text(text)) if text == "echo hello")
+  );
+
+  ws.binary(vec![1, 2, 3]).unwrap();
+  assert!(
+    matches!(ws.read_message().unwrap(), Some(WebsocketMessage::Binary(data)) if data == vec![3, 2, 1])
+  );
+
+  ws.ping().unwrap();
+  assert!(matches!(ws.read_message().unwrap(), Some(WebsocketMessage::Ping)));
+
+  ws.close().unwrap();
+
+  let response = client.get("/echo").send().unwrap();
+  assert_eq!(response.get_status_code_number(), 200);
+
+  assert!(client.get("/echo").websocket().is_err());
+}
@@ -0,0 +1,358 @@
+#[cfg(feature = "extras")]
+mod inner {
+  use std::io::{BufRead, BufReader, Read, Write};
+  use std::net::{TcpListener, TcpStream};
+  use std::sync::Arc;
+  use std::thread::sleep;
+  use std::time::Duration;
+  use tii::extras::{Connector, HttpClient, TcpConnector};
+  use tii::{
+    CompressionFilter, MimeType, RequestBodyError, RequestContext, Response, ResponseBody, Server,
+    ServerBuilder, TiiResult,
+  };
+
+  fn echo_route(request: &RequestContext) -> TiiResult<Response> {
+    let body =
+      request.request_body().map(|body| body.read_to_vec()).transpose()?.unwrap_or_default();
+    Response::ok(body, MimeType::TextPlain)
+      .with_header("X-Method", request.get_method().as_str())?
+      .with_header("X-Peer", request.peer_address())?
+      .with_header("X-Auth", request.get_header("Authorization").unwrap_or("none"))
+  }
+
+  fn big_route(_: &RequestContext) -> TiiResult<Response> {
+    Ok(Response::ok("text ".repeat(1000), MimeType::TextPlain))
+  }
+
+  fn chunked_route(_: &RequestContext) -> TiiResult<Response> {
+    Ok(Response::ok(
+      ResponseBody::chunked(|sink| {
+        for index in 0..100 {
+          sink.write_all(format!("chunk {index}\n").as_bytes())?;
+        }
+        Ok(())
+      }),
+      MimeType::TextPlain,
+    ))
+  }
+
+  fn endless_route(_: &RequestContext) -> TiiResult<Response> {
+    Ok(Response::ok(
+      ResponseBody::streamed(|sink| loop {
+        sink.write_all(&[b'x'; 0x1000])?;
+      }),
+      MimeType::TextPlain,
+    ))
+  }
+
+  fn server() -> Arc<Server> {
+    ServerBuilder::builder_arc(|builder| {
+      builder
+        .router(|rt| {
+          rt.route_any("/echo", echo_route)?
+            .route_get("/big", big_route)?
+            .route_get("/chunked", chunked_route)?
+            .route_get("/endless", endless_route)?
+            .route_get("/found", |_: &RequestContext| Ok(Response::found_no_body("echo")))?
+            .route_post("/see-other", |_: &RequestContext| {
+              Ok(Response::see_other_no_body("/echo"))
+            })?
+            .route_post("/temporary", |_: &RequestContext| {
+              Ok(Response::temporary_redirect_no_body("/echo"))
+            })?
+            .route_get("/loop", |_: &RequestContext| Ok(Response::found_no_body("/loop")))?
+            .with_response_filter(CompressionFilter::new())
+        })?
+        .with_keep_alive_timeout(Some(Duration::from_millis(500)))?
+        .ok()
+    })
+    .unwrap()
+  }
+
+  pub fn requests() {
+    let connector = TcpConnector::start_unpooled("127.0.0.1:28882", server()).unwrap();
+    let client = HttpClient::new();
+
+    let response = client.get("http://127.0.0.1:28882/echo").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 200);
+    assert_eq!(response.get_header("x-method"), Some("GET"));
+    assert_eq!(response.get_body(), b"");
+    let peer = response.get_header("X-Peer").unwrap().to_string();
+    assert_eq!(client.idle_connections(), 1);
+
+    let response = client.post("http://127.0.0.1:28882/echo").with_body("hello").send().unwrap();
+    assert_eq!(response.get_header("X-Method"), Some("POST"));
+    assert_eq!(response.get_body_text(), Some("hello"));
+    assert_eq!(response.get_header("X-Peer"), Some(peer.as_str()));
+
+    let response = client.head("http://127.0.0.1:28882/echo").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 200);
+    assert_eq!(response.get_body(), b"");
+
+    let response = client.get("http://127.0.0.1:28882/missing").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 404);
+    assert_eq!(client.idle_connections(), 1);
+
+    //The server closes the idle connection, the client reconnects.
+    client.get("http://127.0.0.1:28882/echo").send().unwrap();
+    assert_eq!(client.idle_connections(), 1);
+    sleep(Duration::from_millis(1000));
+    let response = client.get("http://127.0.0.1:28882/echo").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 200);
+
+    client.close_idle_connections();
+    assert_eq!(client.idle_connections(), 0);
+
+    let client = HttpClient::new().with_max_idle_connections(0);
+    client.get("http://127.0.0.1:28882/echo").send().unwrap();
+    assert_eq!(client.idle_connections(), 0);
+
+    assert!(client.get("http://127.0.0.1:28883/echo").send().is_err());
+    assert!(client.get("ftp://127.0.0.1:28882/echo").send().is_err());
+
+    connector.shutdown_and_join(None);
+  }
+
+  pub fn decoding() {
+    let connector = TcpConnector::start_unpooled("127.0.0.1:28884", server()).unwrap();
+    let client = HttpClient::new();
+
+    let response = client.get("http://127.0.0.1:28884/big").send().unwrap();
+    assert_eq!(response.get_header("Content-Encoding"), Some("gzip"));
+    assert_eq!(response.get_body_text(), Some("text ".repeat(1000).as_str()));
+
+    let response = client
+      .get("http://127.0.0.1:28884/big")
+      .with_header("Accept-Encoding", "deflate")
+      .unwrap()
+      .send()
+      .unwrap();
+    assert_eq!(response.get_header("Content-Encoding"), Some("deflate"));
+    assert_eq!(response.get_body_text(), Some("text ".repeat(1000).as_str()));
+
+    let expected = (0..100).map(|index| format!("chunk {index}\n")).collect::<String>();
+    for accept in ["identity", "gzip"] {
+      let response = client
+        .get("http://127.0.0.1:28884/chunked")
+        .with_header("Accept-Encoding", accept)
+        .unwrap()
+        .send()
+        .unwrap();
+      assert_eq!(response.get_header("Transfer-Encoding"), Some("chunked"));
+      assert_eq!(response.get_body_text(), Some(expected.as_str()));
+    }
+    assert_eq!(client.idle_connections(), 1);
+    client.close_idle_connections();
+
+    let client =
+      HttpClient::new().with_max_response_body_size(Some(1000)).with_max_idle_connections(0);
+    assert!(client.get("http://127.0.0.1:28884/big").send().is_err());
+    //Bodies that end when the server closes the connection are not read beyond the limit.
+    let err = client
+      .get("http://127.0.0.1:28884/endless")
+      .with_header("Accept-Encoding", "identity")
+      .unwrap()
+      .send()
+      .unwrap_err();
+    assert!(matches!(
+      err.downcast_ref::<RequestBodyError>(),
+      Some(RequestBodyError::TooLarge(1000))
+    ));
+
+    connector.shutdown_and_join(None);
+  }
+
+  pub fn redirects() {
+    let connector = TcpConnector::start_unpooled("127.0.0.1:28885", server()).unwrap();
+    let client = HttpClient::new();
+
+    let response = client.get("http://127.0.0.1:28885/found").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 200);
+    assert_eq!(response.get_url(), "http://127.0.0.1:28885/echo");
+
+    let response = client
+      .post("http://127.0.0.1:28885/see-other")
+      .with_header("Authorization", "secret")
+      .unwrap()
+      .with_body("hello")
+      .send()
+      .unwrap();
+    assert_eq!(response.get_header("X-Method"), Some("GET"));
+    assert_eq!(response.get_header("X-Auth"), Some("secret"));
+    assert_eq!(response.get_body(), b"");
+
+    let response =
+      client.post("http://127.0.0.1:28885/temporary").with_body("hello").send().unwrap();
+    assert_eq!(response.get_header("X-Method"), Some("POST"));
+    assert_eq!(response.get_body_text(), Some("hello"));
+
+    assert!(client.get("http://127.0.0.1:28885/loop").send().is_err());
+    client.close_idle_connections();
+
+    let client = HttpClient::new().with_max_redirects(0).with_max_idle_connections(0);
+    let response = client.get("http://127.0.0.1:28885/found").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 302);
+    assert_eq!(response.get_header("Location"), Some("echo"));
+
+    connector.shutdown_and_join(None);
+  }
+
+  /// Reads a request and returns its request line, None if the client closed the connection.
+  fn read_request(reader: &mut BufReader<TcpStream>) -> Option<String> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).unwrap() == 0 {
+      return None;
+    }
+    let mut length = 0;
+    loop {
+      let mut line = String::new();
+      reader.read_line(&mut line).unwrap();
+      if line == "\r\n" {
+        break;
+      }
+      if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+        length = value.trim().parse().unwrap();
+      }
+    }
+    reader.read_exact(&mut vec![0; length]).unwrap();
+    Some(request_line.trim_end().to_string())
+  }
+
+  pub fn retries() {
+    //The server answers the first request of a connection and closes it after reading the second one.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+      let mut requests = Vec::new();
+      for _ in 0..3 {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let Some(request) = read_request(&mut reader) else { continue };
+        requests.push(request);
+        (&stream).write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+        requests.extend(read_request(&mut reader));
+      }
+      requests
+    });
+
+    let client = HttpClient::new();
+    client.get(format!("{url}/a")).send().unwrap();
+    //The server may have processed the request, it is not sent again.
+    assert!(client.post(format!("{url}/b")).with_body("hi").send().is_err());
+    client.get(format!("{url}/c")).send().unwrap();
+    //Idempotent requests are sent again on a new connection.
+    client.get(format!("{url}/d")).send().unwrap();
+    drop(client);
+
+    assert_eq!(
+      server.join().unwrap(),
+      vec![
+        "GET /a HTTP/1.1",
+        "POST /b HTTP/1.1",
+        "GET /c HTTP/1.1",
+        "GET /d HTTP/1.1",
+        "GET /d HTTP/1.1"
+      ]
+    );
+  }
+
+  pub fn connection_tokens() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let responses = [
+      ("HTTP/1.1 200 OK\r\nConnection: close, TE\r\nContent-Length: 0\r\n\r\n", 0),
+      ("HTTP/1.1 200 OK\r\nConnection: TE,Close\r\nContent-Length: 0\r\n\r\n", 0),
+      ("HTTP/1.0 200 OK\r\nConnection: TE, Keep-Alive\r\nContent-Length: 0\r\n\r\n", 1),
+      (
+        "HTTP/1.1 200 OK\r\nConnection: TE\r\nConnection: keep-alive\r\nContent-Length: 0\r\n\r\n",
+        1,
+      ),
+    ];
+    let server = std::thread::spawn(move || {
+      for (response, _) in responses {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        read_request(&mut reader).unwrap();
+        (&stream).write_all(response.as_bytes()).unwrap();
+      }
+    });
+
+    for (_, idle) in responses {
+      let client = HttpClient::new();
+      client.get(format!("{url}/a")).send().unwrap();
+      assert_eq!(client.idle_connections(), idle);
+    }
+    server.join().unwrap();
+  }
+
+  pub fn invalid_headers() {
+    let client = HttpClient::new();
+    let request = || client.get("http://127.0.0.1:1/");
+    assert!(request().with_header("X-Custom", "value").is_ok());
+    assert!(request().with_header("X-Custom", "a\r\nX-Injected: b").is_err());
+    assert!(request().with_header("X-Custom", "a\nb").is_err());
+    assert!(request().with_header("X-Custom", "a\rb").is_err());
+    assert!(request().with_header("X Custom", "value").is_err());
+    assert!(request().with_header("X-Custom:", "value").is_err());
+    assert!(request().with_header("X-Custom\r\nX-Injected", "value").is_err());
+    assert!(request().with_header("", "value").is_err());
+  }
+
+  #[cfg(unix)]
+  pub fn unix() {
+    let path = std::env::temp_dir().join(format!("tii-tc82-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let connector = tii::extras::UnixConnector::start_unpooled(&path, server()).unwrap();
+    let client = HttpClient::new().with_max_idle_connections(0);
+
+    let url = format!("http+unix://{}/echo", urlencoding::encode(&path.to_string_lossy()));
+    let response = client.post(url).with_body("unix").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 200);
+    assert_eq!(response.get_body_text(), Some("unix"));
+
+    connector.shutdown_and_join(None);
+    let _ = std::fs::remove_file(&path);
+  }
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc82_requests() {
+  inner::requests();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc82_decoding() {
+  inner::decoding();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc82_redirects() {
+  inner::redirects();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc82_retries() {
+  inner::retries();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc82_connection_tokens() {
+  inner::connection_tokens();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc82_invalid_headers() {
+  inner::invalid_headers();
+}
+
+#[cfg(all(feature = "extras", unix))]
+#[test]
+pub fn tc82_unix() {
+  inner::unix();
+}
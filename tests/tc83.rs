@@ -0,0 +1,198 @@
+#[cfg(feature = "extras")]
+mod inner {
+  use std::io::{Read, Write};
+  use std::net::TcpListener;
+  use std::sync::Arc;
+  use std::thread::sleep;
+  use std::time::Duration;
+  use tii::extras::{Connector, HttpClient, ReverseProxy, TcpConnector};
+  use tii::testing::TestClient;
+  use tii::{MimeType, RequestContext, Response, ResponseBody, Server, ServerBuilder, TiiResult};
+
+  fn echo_route(request: &RequestContext) -> TiiResult<Response> {
+    let body =
+      request.request_body().map(|body| body.read_to_vec()).transpose()?.unwrap_or_default();
+    let mut headers = request
+      .iter_headers()
+      .map(|header| format!("{}: {}", header.name, header.value))
+      .collect::<Vec<_>>()
+      .join("\n");
+    headers.push('\n');
+    Response::ok(
+      ResponseBody::chunked(move |sink| sink.write_all(headers.as_bytes())),
+      MimeType::TextPlain,
+    )
+    .with_header("X-Upstream-Path", request.get_raw_status_line())?
+    .with_header("X-Upstream-Body", String::from_utf8_lossy(&body))?
+    .with_header("Keep-Alive", "timeout=5")
+  }
+
+  fn big_route(_: &RequestContext) -> TiiResult<Response> {
+    Ok(Response::ok("big ".repeat(10000), MimeType::TextPlain))
+  }
+
+  fn slow_route(_: &RequestContext) -> TiiResult<Response> {
+    sleep(Duration::from_millis(1000));
+    Ok(Response::no_content())
+  }
+
+  fn upstream() -> Arc<Server> {
+    ServerBuilder::builder_arc(|builder| {
+      builder.router(|rt| {
+        rt.route_any("/v1/echo/*", echo_route)?
+          .route_get("/v1/big", big_route)?
+          .route_get("/v1/slow", slow_route)?
+          .route_get("/v1/missing", |_: &RequestContext| Ok(Response::not_found_no_body()))
+      })
+    })
+    .unwrap()
+  }
+
+  fn proxy(upstream: &str) -> Arc<Server> {
+    let upstream = upstream.to_string();
+    ServerBuilder::builder_arc(move |builder| {
+      builder.router(move |rt| {
+        rt.route_any(
+          "/api/*",
+          ReverseProxy::new(&upstream)?
+            .with_strip_prefix("/api")
+            .with_timeout(Some(Duration::from_millis(300))),
+        )
+      })
+    })
+    .unwrap()
+  }
+
+  pub fn forward() {
+    let upstream = TcpConnector::start_unpooled("127.0.0.1:28886", upstream()).unwrap();
+    let connector =
+      TcpConnector::start_unpooled("127.0.0.1:28887", proxy("http://127.0.0.1:28886/v1/")).unwrap();
+    let client = HttpClient::new().with_max_idle_connections(0);
+
+    let response = client
+      .post("http://127.0.0.1:28887/api/echo/a%20b?x=1&y=%26")
+      .with_header("Connection", "X-Hop")
+      .unwrap()
+      .with_header("X-Hop", "hop")
+      .unwrap()
+      .with_header("X-End", "end")
+      .unwrap()
+      .with_header("X-Forwarded-For", "10.0.0.1")
+      .unwrap()
+      .with_body("hello")
+      .send()
+      .unwrap();
+    assert_eq!(response.get_status_code_number(), 200);
+    assert_eq!(
+      response.get_header("X-Upstream-Path"),
+      Some("POST /v1/echo/a%20b?x=1&y=%26 HTTP/1.1")
+    );
+    assert_eq!(response.get_header("X-Upstream-Body"), Some("hello"));
+    assert_eq!(response.get_header("Keep-Alive"), None);
+    assert_eq!(response.get_header("Transfer-Encoding"), Some("chunked"));
+
+    let headers = response.get_body_text().unwrap();
+    assert!(headers.contains("Host: 127.0.0.1:28886\n"), "{headers}");
+    assert!(headers.contains("X-End: end\n"), "{headers}");
+    assert!(!headers.contains("X-Hop"), "{headers}");
+    assert!(headers.contains("X-Forwarded-For: 10.0.0.1, 127.0.0.1\n"), "{headers}");
+    assert!(headers.contains("X-Forwarded-Host: 127.0.0.1:28887\n"), "{headers}");
+    assert!(headers.contains("X-Forwarded-Proto: http\n"), "{headers}");
+    assert!(
+      headers.contains("Forwarded: for=127.0.0.1;proto=http;host=\"127.0.0.1:28887\"\n"),
+      "{headers}"
+    );
+    assert!(headers.contains("Content-Length: 5\n"), "{headers}");
+
+    let response = client.get("http://127.0.0.1:28887/api/big").send().unwrap();
+    assert_eq!(response.get_header("Content-Type"), Some("text/plain"));
+    assert_eq!(response.get_body_text(), Some("big ".repeat(10000).as_str()));
+
+    let response = client.head("http://127.0.0.1:28887/api/big").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 200);
+    assert_eq!(response.get_header("Content-Length"), Some("40000"));
+    assert_eq!(response.get_body(), b"");
+
+    let response = client.get("http://127.0.0.1:28887/api/missing").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 404);
+
+    let response = client.get("http://127.0.0.1:28887/api/slow").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 504);
+
+    connector.shutdown_and_join(None);
+    upstream.shutdown_and_join(None);
+  }
+
+  pub fn chunked_request() {
+    let upstream = TcpConnector::start_unpooled("127.0.0.1:28888", upstream()).unwrap();
+    let mut client = TestClient::new(proxy("http://127.0.0.1:28888/v1"));
+
+    let response = client
+      .put("/api/echo/chunked")
+      .with_body("chunked gzip")
+      .with_chunked(true)
+      .with_gzip(true)
+      .send()
+      .unwrap();
+    assert_eq!(response.get_header("X-Upstream-Body"), Some("chunked gzip"));
+    let headers = response.get_body_text().unwrap();
+    assert!(headers.contains("Transfer-Encoding: chunked\n"), "{headers}");
+    assert!(!headers.contains("Content-Encoding"), "{headers}");
+
+    upstream.shutdown_and_join(None);
+  }
+
+  pub fn not_modified() {
+    let listener = TcpListener::bind("127.0.0.1:28890").unwrap();
+    let upstream = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut head = Vec::new();
+      while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0u8];
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+      }
+      stream
+        .write_all(b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nContent-Length: 1234\r\n\r\n")
+        .unwrap();
+    });
+
+    let mut client = TestClient::new(proxy("http://127.0.0.1:28890/v1"));
+    let response = client.get("/api/echo/x").with_header("If-None-Match", "\"v1\"").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 304);
+    assert_eq!(response.get_header("ETag"), Some("\"v1\""));
+    assert_eq!(response.get_header("Content-Length"), Some("1234"));
+    assert_eq!(response.get_body(), b"");
+    upstream.join().unwrap();
+  }
+
+  pub fn unreachable() {
+    let mut client = TestClient::new(proxy("http://127.0.0.1:28889"));
+    let response = client.get("/api/echo/x").send().unwrap();
+    assert_eq!(response.get_status_code_number(), 502);
+  }
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc83_forward() {
+  inner::forward();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc83_chunked_request() {
+  inner::chunked_request();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc83_not_modified() {
+  inner::not_modified();
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc83_unreachable() {
+  inner::unreachable();
+}
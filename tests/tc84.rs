@@ -0,0 +1,123 @@
+use std::time::Duration;
+use tii::testing::TestClient;
+use tii::{sse_endpoint, CompressionFilter, Response, ServerBuilder, SseSender};
+
+fn events(last_event_id: Option<&str>, sender: &SseSender<'_>) -> std::io::Result<()> {
+  sender.retry(Duration::from_millis(1500))?;
+  sender.comment("resuming")?;
+  sender.event(
+    Some("1"),
+    Some("greeting"),
+    format!("after {}", last_event_id.unwrap_or("none")),
+  )?;
+  sender.event(None, None, "line1\nline2\r\nline3")?;
+  sender.data("plain")?;
+  assert!(sender.event(Some("a\nb"), None, "x").is_err());
+  assert!(sender.is_connected());
+  Ok(())
+}
+
+fn client() -> TestClient {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.route_get("/events", sse_endpoint(None, events))?
+        .route_get("/ping", |_: &tii::RequestContext| {
+          Ok(Response::event_stream(Some(Duration::from_millis(50)), |sender| {
+            std::thread::sleep(Duration::from_millis(300));
+            sender.data("done")
+          }))
+        })?
+        .with_response_filter(CompressionFilter::new())
+    })
+    .expect("ERR")
+    .build();
+
+  TestClient::new(server)
+}
+
+#[test]
+pub fn tc84_events() {
+  let mut client = client();
+
+  let response = client.get("/events").with_header("Accept-Encoding", "gzip").send().unwrap();
+  assert_eq!(response.get_status_code_number(), 200);
+  assert_eq!(response.get_header("Content-Type"), Some("text/event-stream"));
+  assert_eq!(response.get_header("Cache-Control"), Some("no-cache"));
+  assert_eq!(response.get_header("Transfer-Encoding"), Some("chunked"));
+  assert_eq!(response.get_header("Content-Encoding"), None);
+  assert_eq!(
+    response.get_body_text(),
+    Some(
+      "retry: 1500\n\n: resuming\n\nid: 1\nevent: greeting\ndata: after none\n\n\
+       data: line1\ndata: line2\ndata: line3\n\ndata: plain\n\n"
+    )
+  );
+
+  let response = client.get("/events").with_header("last-event-id", "41").send().unwrap();
+  assert!(response.get_body_text().unwrap().contains("data: after 41\n"));
+}
+
+#[test]
+pub fn tc84_keep_alive() {
+  let mut client = client();
+
+  let response = client.get("/ping").send().unwrap();
+  let body = response.get_body_text().unwrap();
+  assert!(body.starts_with(":\n\n:\n\n"), "{body}");
+  assert!(body.ends_with(":\n\ndata: done\n\n"), "{body}");
+}
+
+#[cfg(feature = "extras")]
+mod inner {
+  use std::io::{Read, Write};
+  use std::net::TcpStream;
+  use std::sync::mpsc;
+  use std::sync::Mutex;
+  use std::time::Duration;
+  use tii::extras::{Connector, TcpConnector};
+  use tii::{Response, ServerBuilder};
+
+  pub fn disconnect() {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let server = ServerBuilder::builder_arc(move |builder| {
+      builder.router(move |rt| {
+        rt.route_get("/events", move |_: &tii::RequestContext| {
+          let tx = tx.lock().unwrap().clone();
+          Ok(Response::event_stream(Some(Duration::from_millis(20)), move |sender| {
+            let error = loop {
+              if !sender.is_connected() {
+                break sender.data("late").unwrap_err();
+              }
+              std::thread::sleep(Duration::from_millis(10));
+            };
+            tx.send(error.kind()).unwrap();
+            Err(error)
+          }))
+        })
+      })
+    })
+    .unwrap();
+    let connector = TcpConnector::start_unpooled("127.0.0.1:28890", server).unwrap();
+
+    let mut stream = TcpStream::connect("127.0.0.1:28890").unwrap();
+    stream.write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut buffer = [0u8; 1024];
+    let mut received = Vec::new();
+    while !received.windows(8).any(|window| window == b"3\r\n:\n\n\r\n") {
+      let count = stream.read(&mut buffer).unwrap();
+      assert_ne!(count, 0);
+      received.extend_from_slice(&buffer[..count]);
+    }
+    drop(stream);
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(10)).unwrap(), std::io::ErrorKind::BrokenPipe);
+    connector.shutdown_and_join(None);
+  }
+}
+
+#[cfg(feature = "extras")]
+#[test]
+pub fn tc84_disconnect() {
+  inner::disconnect();
+}
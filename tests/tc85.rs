@@ -0,0 +1,156 @@
+use crate::mock_stream::MockStream;
+use crate::ws_frames::masked_frame;
+use std::sync::{Arc, Mutex};
+use tii::{
+  RequestContext, RequestHeadParsingError, ServerBuilder, TiiError, TiiResult, WebsocketMessage,
+  WebsocketReceiver, WebsocketSender,
+};
+
+mod mock_stream;
+mod ws_frames;
+
+const HANDSHAKE: &[u8] = b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+
+const CLOSE_1002: &[u8] = &[0x88, 0x02, 0x03, 0xEA];
+const CLOSE_1007: &[u8] = &[0x88, 0x02, 0x03, 0xEF];
+
+/// Returns the bytes the server wrote after the handshake, the messages the handler received
+/// and the error that ended the handler loop.
+fn run(frames: &[u8]) -> (Vec<u8>, Vec<WebsocketMessage>, Option<TiiError>) {
+  let result = Arc::new(Mutex::new((Vec::new(), None)));
+  let result_clone = result.clone();
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.ws_route_get(
+        "/ws",
+        move |_: &RequestContext,
+              mut rx: WebsocketReceiver,
+              tx: WebsocketSender|
+              -> TiiResult<()> {
+          let mut result = result_clone.lock().unwrap();
+          loop {
+            match rx.read_message() {
+              Ok(Some(message)) => {
+                match &message {
+                  WebsocketMessage::Ping => tx.pong()?,
+                  WebsocketMessage::Text(text) if text == "close" => {
+                    assert!(tx.close_with(1005, "reserved").is_err());
+                    assert!(tx.close_with(4000, "x".repeat(124)).is_err());
+                    tx.close_with(4000, "done")?;
+                  }
+                  _ => {}
+                }
+                result.0.push(message);
+              }
+              Ok(None) => return Ok(()),
+              Err(err) => {
+                result.1 = Some(err);
+                return Ok(());
+              }
+            }
+          }
+        },
+      )
+    })
+    .expect("ERR")
+    .build();
+
+  let mut data = HANDSHAKE.to_vec();
+  data.extend_from_slice(frames);
+  let stream = MockStream::with_slice(data.as_slice());
+  let _ = server.handle_connection(stream.to_stream());
+
+  let written = stream.copy_written_data();
+  let header_end = written.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+  assert!(written.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+
+  let (messages, error) = std::mem::take(&mut *result.lock().unwrap());
+  (written[header_end..].to_vec(), messages, error)
+}
+
+fn assert_error(error: Option<TiiError>, expected: RequestHeadParsingError) {
+  assert_eq!(error.unwrap().downcast_ref::<RequestHeadParsingError>(), Some(&expected));
+}
+
+#[test]
+pub fn tc85_client_close_with_reason() {
+  let mut frames = masked_frame(0x81, b"hi");
+  frames.extend(masked_frame(0x88, b"\x03\xE9bye"));
+  let (written, messages, error) = run(&frames);
+  assert!(error.is_none());
+  assert!(matches!(&messages[0], WebsocketMessage::Text(text) if text == "hi"));
+  assert!(matches!(&messages[1], WebsocketMessage::Close(Some((1001, reason))) if reason == "bye"));
+  assert_eq!(messages.len(), 2);
+  //The status code is echoed.
+  assert_eq!(written, vec![0x88, 0x02, 0x03, 0xE9]);
+}
+
+#[test]
+pub fn tc85_client_close_without_reason() {
+  let (written, messages, error) = run(&masked_frame(0x88, b""));
+  assert!(error.is_none());
+  assert!(matches!(messages.as_slice(), [WebsocketMessage::Close(None)]));
+  assert_eq!(written, vec![0x88, 0x00]);
+}
+
+#[test]
+pub fn tc85_server_close_with_reason() {
+  let (written, messages, error) = run(&masked_frame(0x81, b"close"));
+  assert!(error.is_none());
+  assert_eq!(messages.len(), 1);
+  assert_eq!(written, b"\x88\x06\x0F\xA0done");
+}
+
+#[test]
+pub fn tc85_pong() {
+  let (written, messages, _) = run(&masked_frame(0x89, b""));
+  assert!(matches!(messages.as_slice(), [WebsocketMessage::Ping]));
+  assert_eq!(written, vec![0x8A, 0x00]);
+}
+
+#[test]
+pub fn tc85_protocol_errors() {
+  let (written, _, error) = run(&masked_frame(0x83, b""));
+  assert_error(error, RequestHeadParsingError::InvalidWebSocketOpcode);
+  assert_eq!(written, CLOSE_1002);
+
+  let (written, _, error) = run(&masked_frame(0x80, b"lost"));
+  assert_error(error, RequestHeadParsingError::UnexpectedWebSocketOpcode);
+  assert_eq!(written, CLOSE_1002);
+
+  let mut frames = masked_frame(0x01, b"hel");
+  frames.extend(masked_frame(0x81, b"lo"));
+  let (written, _, error) = run(&frames);
+  assert_error(error, RequestHeadParsingError::UnexpectedWebSocketOpcode);
+  assert_eq!(written, CLOSE_1002);
+
+  let (written, _, error) = run(&masked_frame(0x09, b""));
+  assert_error(error, RequestHeadParsingError::InvalidWebSocketControlFrame);
+  assert_eq!(written, CLOSE_1002);
+
+  let (written, _, error) = run(&masked_frame(0xC1, b"deflated"));
+  assert_error(error, RequestHeadParsingError::WebSocketReservedBitsSet);
+  assert_eq!(written, CLOSE_1002);
+
+  let (written, _, error) = run(&masked_frame(0x88, b"\x03"));
+  assert_error(error, RequestHeadParsingError::InvalidWebSocketClosePayload(vec![0x03]));
+  assert_eq!(written, CLOSE_1002);
+
+  let (written, _, error) = run(&masked_frame(0x88, b"\x03\xED"));
+  assert_error(error, RequestHeadParsingError::InvalidWebSocketClosePayload(vec![0x03, 0xED]));
+  assert_eq!(written, CLOSE_1002);
+}
+
+#[test]
+pub fn tc85_invalid_utf8() {
+  let (written, _, error) = run(&masked_frame(0x81, b"\xFF"));
+  assert_error(error, RequestHeadParsingError::WebSocketTextMessageIsNotUtf8(vec![0xFF]));
+  assert_eq!(written, CLOSE_1007);
+
+  let (written, _, error) = run(&masked_frame(0x88, b"\x03\xE8\xFF"));
+  assert_error(
+    error,
+    RequestHeadParsingError::InvalidWebSocketClosePayload(vec![0x03, 0xE8, 0xFF]),
+  );
+  assert_eq!(written, CLOSE_1007);
+}
@@ -0,0 +1,288 @@
+use crate::mock_stream::MockStream;
+use crate::ws_frames::{masked_frame, parse_frames};
+use libflate::zlib::{EncodeOptions, Encoder, FlushMode};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use tii::{
+  PerMessageDeflate, RequestContext, RequestHeadParsingError, ServerBuilder, TiiError, TiiResult,
+  WebsocketMessage, WebsocketReceiver, WebsocketSender,
+};
+
+mod mock_stream;
+mod ws_frames;
+
+const HANDSHAKE: &str = "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n";
+
+/// Compresses messages like a client that keeps its compression context.
+struct Compressor(Encoder<Vec<u8>>);
+
+impl Compressor {
+  fn new() -> Self {
+    let mut encoder =
+      Encoder::with_options(Vec::new(), EncodeOptions::new().flush_mode(FlushMode::Sync)).unwrap();
+    //Remove the zlib header.
+    encoder.as_inner_mut().clear();
+    Self(encoder)
+  }
+
+  fn compress(&mut self, data: &[u8]) -> Vec<u8> {
+    self.0.write_all(data).unwrap();
+    self.0.flush().unwrap();
+    let mut compressed = std::mem::take(self.0.as_inner_mut());
+    assert!(compressed.ends_with(&[0x00, 0x00, 0xFF, 0xFF]));
+    compressed.truncate(compressed.len() - 4);
+    compressed
+  }
+}
+
+fn decompress(data: &[u8]) -> Vec<u8> {
+  let mut data = data.to_vec();
+  //Empty uncompressed block + empty final block.
+  data.extend_from_slice(&[0x00, 0x00, 0xFF, 0xFF, 0x03, 0x00]);
+  let mut result = Vec::new();
+  libflate::deflate::Decoder::new(data.as_slice()).read_to_end(&mut result).unwrap();
+  result
+}
+
+struct Outcome {
+  head: String,
+  frames: Vec<(u8, Vec<u8>)>,
+  messages: Vec<WebsocketMessage>,
+  error: Option<TiiError>,
+}
+
+/// Runs a websocket connection that echoes text and binary messages with a maximum message size of 100 bytes.
+fn run(config: Option<PerMessageDeflate>, extensions: &[&str], frames: &[u8]) -> Outcome {
+  run_with_limits(config, extensions, frames, None, Some(100))
+}
+
+/// Runs a websocket connection that echoes text and binary messages.
+fn run_with_limits(
+  config: Option<PerMessageDeflate>,
+  extensions: &[&str],
+  frames: &[u8],
+  max_frame_size: Option<u64>,
+  max_message_size: Option<u64>,
+) -> Outcome {
+  let result = Arc::new(Mutex::new((Vec::new(), None)));
+  let result_clone = result.clone();
+  let handler =
+    move |_: &RequestContext, mut rx: WebsocketReceiver, tx: WebsocketSender| -> TiiResult<()> {
+      let mut result = result_clone.lock().unwrap();
+      loop {
+        match rx.read_message() {
+          Ok(Some(message)) => {
+            if message.bytes().is_some() {
+              tx.send(message.clone())?;
+            }
+            result.0.push(message);
+          }
+          Ok(None) => return Ok(()),
+          Err(err) => {
+            result.1 = Some(err);
+            return Ok(());
+          }
+        }
+      }
+    };
+
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      let route = rt
+        .with_websocket_max_frame_size(max_frame_size)?
+        .with_websocket_max_message_size(max_message_size)?
+        .ws_get("/ws");
+      match config {
+        Some(config) => route.permessage_deflate(config).endpoint(handler),
+        None => route.endpoint(handler),
+      }
+    })
+    .expect("ERR")
+    .build();
+
+  let mut data = HANDSHAKE.to_string();
+  for extension in extensions {
+    data.push_str(&format!("Sec-WebSocket-Extensions: {extension}\r\n"));
+  }
+  data.push_str("\r\n");
+  let mut data = data.into_bytes();
+  data.extend_from_slice(frames);
+  let stream = MockStream::with_slice(data.as_slice());
+  let _ = server.handle_connection(stream.to_stream());
+
+  let written = stream.copy_written_data();
+  let header_end = written.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+  let head = String::from_utf8(written[..header_end].to_vec()).unwrap();
+  assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+
+  let (messages, error) = std::mem::take(&mut *result.lock().unwrap());
+  Outcome { head, frames: parse_frames(&written[header_end..]), messages, error }
+}
+
+fn negotiated(config: Option<PerMessageDeflate>, extensions: &[&str]) -> Option<String> {
+  let outcome = run(config, extensions, &masked_frame(0x88, b""));
+  outcome
+    .head
+    .lines()
+    .find_map(|line| line.strip_prefix("Sec-WebSocket-Extensions: "))
+    .map(ToString::to_string)
+}
+
+fn assert_error(error: Option<TiiError>, expected: RequestHeadParsingError) {
+  assert_eq!(error.unwrap().downcast_ref::<RequestHeadParsingError>(), Some(&expected));
+}
+
+#[test]
+pub fn tc86_negotiation() {
+  let config = PerMessageDeflate::new();
+  assert_eq!(negotiated(None, &["permessage-deflate"]), None);
+  assert_eq!(negotiated(Some(config), &[]), None);
+  assert_eq!(negotiated(Some(config), &["x-webkit-deflate-frame"]), None);
+  assert_eq!(negotiated(Some(config), &["permessage-deflate; unknown"]), None);
+  assert_eq!(
+    negotiated(Some(config), &["permessage-deflate; client_max_window_bits"]),
+    Some("permessage-deflate".to_string())
+  );
+  assert_eq!(
+    negotiated(Some(config), &["permessage-deflate; server_no_context_takeover"]),
+    Some("permessage-deflate; server_no_context_takeover".to_string())
+  );
+
+  let config = PerMessageDeflate::new()
+    .with_server_no_context_takeover(true)
+    .with_client_no_context_takeover(true)
+    .with_server_max_window_bits(Some(12))
+    .with_client_max_window_bits(Some(10));
+  assert_eq!(
+    negotiated(
+      Some(config),
+      &[
+        "x-webkit-deflate-frame, permessage-deflate; server_max_window_bits=7",
+        "permessage-deflate; server_max_window_bits=\"14\"; client_max_window_bits"
+      ]
+    ),
+    Some(
+      "permessage-deflate; server_no_context_takeover; client_no_context_takeover; \
+       server_max_window_bits=12; client_max_window_bits=10"
+        .to_string()
+    )
+  );
+  //The client did not indicate that it supports client_max_window_bits.
+  assert_eq!(
+    negotiated(Some(config), &["permessage-deflate; server_max_window_bits=9"]),
+    Some(
+      "permessage-deflate; server_no_context_takeover; client_no_context_takeover; \
+       server_max_window_bits=9"
+        .to_string()
+    )
+  );
+}
+
+#[test]
+pub fn tc86_round_trip() {
+  let mut compressor = Compressor::new();
+  let mut frames = masked_frame(0xC1, &compressor.compress(b"hello hello hello hello"));
+  frames.extend(masked_frame(0x82, b"raw"));
+  let fragmented = compressor.compress(&[7u8; 90]);
+  let (first, second) = fragmented.split_at(fragmented.len() / 2);
+  frames.extend(masked_frame(0x42, first));
+  frames.extend(masked_frame(0x89, b""));
+  frames.extend(masked_frame(0x80, second));
+  frames.extend(masked_frame(0xC1, &compressor.compress(b"hello again")));
+  frames.extend(masked_frame(0x88, b""));
+
+  let outcome = run(Some(PerMessageDeflate::new()), &["permessage-deflate"], &frames);
+  assert!(outcome.error.is_none());
+  assert!(
+    matches!(&outcome.messages[0], WebsocketMessage::Text(text) if text == "hello hello hello hello")
+  );
+  assert!(matches!(&outcome.messages[1], WebsocketMessage::Binary(data) if data == b"raw"));
+  assert!(matches!(&outcome.messages[2], WebsocketMessage::Ping));
+  assert!(matches!(&outcome.messages[3], WebsocketMessage::Binary(data) if data == &[7u8; 90]));
+  assert!(matches!(&outcome.messages[4], WebsocketMessage::Text(text) if text == "hello again"));
+  assert!(matches!(&outcome.messages[5], WebsocketMessage::Close(None)));
+
+  //Every echoed message is compressed, the close frame is not.
+  let frames = outcome.frames;
+  assert_eq!(frames.len(), 5);
+  assert_eq!(frames[0].0, 0xC1);
+  assert_eq!(decompress(&frames[0].1), b"hello hello hello hello");
+  assert_eq!(frames[1].0, 0xC2);
+  assert_eq!(decompress(&frames[1].1), b"raw");
+  assert_eq!(frames[2].0, 0xC2);
+  assert_eq!(decompress(&frames[2].1), vec![7u8; 90]);
+  assert!(frames[2].1.len() < 90);
+  assert_eq!(frames[3].0, 0xC1);
+  assert_eq!(decompress(&frames[3].1), b"hello again");
+  assert_eq!(frames[4], (0x88, Vec::new()));
+}
+
+#[test]
+pub fn tc86_no_context_takeover() {
+  let config = PerMessageDeflate::new().with_client_no_context_takeover(true);
+  let mut frames = masked_frame(0xC1, &Compressor::new().compress(b"first"));
+  frames.extend(masked_frame(0xC1, &Compressor::new().compress(b"second")));
+  let outcome = run(Some(config), &["permessage-deflate"], &frames);
+  assert!(matches!(&outcome.messages[0], WebsocketMessage::Text(text) if text == "first"));
+  assert!(matches!(&outcome.messages[1], WebsocketMessage::Text(text) if text == "second"));
+}
+
+#[test]
+pub fn tc86_errors() {
+  let config = Some(PerMessageDeflate::new());
+  let offer: &[&str] = &["permessage-deflate"];
+  let close_1002 = (0x88, vec![0x03, 0xEA]);
+
+  //RSV1 is only allowed on the first frame of a message.
+  let mut frames = masked_frame(0x41, &Compressor::new().compress(b"abc"));
+  frames.extend(masked_frame(0xC0, b""));
+  let outcome = run(config, offer, &frames);
+  assert_error(outcome.error, RequestHeadParsingError::WebSocketReservedBitsSet);
+  assert_eq!(outcome.frames, vec![close_1002.clone()]);
+
+  let outcome = run(config, offer, &masked_frame(0xC9, b""));
+  assert_error(outcome.error, RequestHeadParsingError::WebSocketReservedBitsSet);
+  assert_eq!(outcome.frames, vec![close_1002.clone()]);
+
+  //Compressed messages are not allowed if the extension was not negotiated.
+  let outcome = run(config, &[], &masked_frame(0xC1, &Compressor::new().compress(b"abc")));
+  assert_error(outcome.error, RequestHeadParsingError::WebSocketReservedBitsSet);
+  assert_eq!(outcome.frames, vec![close_1002]);
+
+  let outcome = run(config, offer, &masked_frame(0xC1, &[0xFF, 0xFF, 0xFF]));
+  assert_error(outcome.error, RequestHeadParsingError::InvalidWebSocketCompressedPayload);
+  assert_eq!(outcome.frames, vec![(0x88, vec![0x03, 0xEF])]);
+
+  //The maximum message size applies to the decompressed message.
+  let compressed = Compressor::new().compress(&[0u8; 1000]);
+  assert!(compressed.len() < 100);
+  let outcome = run(config, offer, &masked_frame(0xC2, &compressed));
+  assert!(matches!(
+    outcome.error.unwrap().downcast_ref::<RequestHeadParsingError>(),
+    Some(RequestHeadParsingError::WebSocketMessageTooBig(size)) if *size > 100
+  ));
+  assert_eq!(outcome.frames, vec![(0x88, vec![0x03, 0xF1])]);
+}
+
+#[test]
+pub fn tc86_decompression_limit() {
+  let config = Some(PerMessageDeflate::new());
+  let offer: &[&str] = &["permessage-deflate"];
+  let assert_too_big = |outcome: Outcome, max: u64| {
+    assert!(matches!(
+      outcome.error.unwrap().downcast_ref::<RequestHeadParsingError>(),
+      Some(RequestHeadParsingError::WebSocketMessageTooBig(size)) if *size > max
+    ));
+    assert_eq!(outcome.frames, vec![(0x88, vec![0x03, 0xF1])]);
+  };
+
+  //Without a maximum message size the maximum frame size limits the decompressed message.
+  let frames = masked_frame(0xC2, &Compressor::new().compress(&[0u8; 1000]));
+  assert_too_big(run_with_limits(config, offer, &frames, Some(100), None), 100);
+
+  //Without any limit a built-in maximum of 16 MiB applies.
+  let bomb = Compressor::new().compress(&vec![0u8; 0x100_0000 + 1]);
+  assert!(bomb.len() < 0x10000, "{}", bomb.len());
+  let frames = masked_frame(0xC2, &bomb);
+  assert_too_big(run_with_limits(config, offer, &frames, None, None), 0x100_0000);
+}
@@ -0,0 +1,117 @@
+use crate::mock_stream::MockStream;
+use std::sync::{Arc, Mutex};
+use tii::{RequestContext, Response, ServerBuilder, StatusCode, TiiError, TiiResult};
+use tii::{WebSocketHandshakeError, WebsocketReceiver, WebsocketSender};
+
+mod mock_stream;
+
+/// Returns the response head and the subprotocol the endpoint saw.
+fn handshake(path: &str, protocols: &[&str]) -> (String, Option<Option<String>>) {
+  let seen = Arc::new(Mutex::new(None));
+  let seen_clone = seen.clone();
+  let handler =
+    move |request: &RequestContext, _: WebsocketReceiver, _: WebsocketSender| -> TiiResult<()> {
+      *seen_clone.lock().unwrap() = Some(request.websocket_protocol().map(ToString::to_string));
+      Ok(())
+    };
+
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.ws_get("/required")
+        .protocol("graphql-transport-ws")
+        .protocol("custom")
+        .require_protocol(true)
+        .endpoint(handler.clone())?
+        .begin_ws_get("/optional", |route| route.protocol("custom").endpoint(handler))
+    })
+    .expect("ERR")
+    .build();
+
+  let mut data = format!("GET {path} HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n");
+  for protocol in protocols {
+    data.push_str(&format!("Sec-WebSocket-Protocol: {protocol}\r\n"));
+  }
+  data.push_str("\r\n");
+  let stream = MockStream::with_str(data.as_str());
+  let _ = server.handle_connection(stream.to_stream());
+
+  let written = stream.copy_written_data();
+  let header_end = written.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+  let head = String::from_utf8(written[..header_end].to_vec()).unwrap();
+  let seen = seen.lock().unwrap().take();
+  (head, seen)
+}
+
+fn protocol_header(head: &str) -> Option<&str> {
+  head.lines().find_map(|line| line.strip_prefix("Sec-WebSocket-Protocol: "))
+}
+
+#[test]
+pub fn tc87_select_first_offered() {
+  let (head, seen) = handshake("/required", &["custom, graphql-transport-ws"]);
+  assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+  assert_eq!(protocol_header(&head), Some("custom"));
+  assert_eq!(seen, Some(Some("custom".to_string())));
+
+  let (head, seen) = handshake("/required", &["unknown", "graphql-transport-ws,custom"]);
+  assert_eq!(protocol_header(&head), Some("graphql-transport-ws"));
+  assert_eq!(seen, Some(Some("graphql-transport-ws".to_string())));
+}
+
+#[test]
+pub fn tc87_required() {
+  let (head, seen) = handshake("/required", &["unknown"]);
+  assert!(head.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{head}");
+  assert_eq!(seen, None);
+
+  let (head, seen) = handshake("/required", &[]);
+  assert!(head.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{head}");
+  assert_eq!(seen, None);
+
+  //Subprotocols are case-sensitive.
+  let (head, _) = handshake("/required", &["Custom"]);
+  assert!(head.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{head}");
+}
+
+#[test]
+pub fn tc87_optional() {
+  let (head, seen) = handshake("/optional", &["graphql-transport-ws"]);
+  assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+  assert_eq!(protocol_header(&head), None);
+  assert_eq!(seen, Some(None));
+
+  let (head, seen) = handshake("/optional", &["custom"]);
+  assert_eq!(protocol_header(&head), Some("custom"));
+  assert_eq!(seen, Some(Some("custom".to_string())));
+}
+
+fn error_handler(_: &mut RequestContext, error: TiiError) -> TiiResult<Response> {
+  match error.downcast_ref::<WebSocketHandshakeError>() {
+    Some(WebSocketHandshakeError::UnsupportedProtocol(offered)) => {
+      Ok(Response::new(StatusCode::BadRequest).with_body(format!("offered: {offered}")))
+    }
+    _ => Err(error),
+  }
+}
+
+#[test]
+pub fn tc87_required_error() {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.with_error_handler(error_handler)?
+        .ws_get("/required")
+        .protocol("custom")
+        .require_protocol(true)
+        .endpoint(|_: &RequestContext, _: WebsocketReceiver, _: WebsocketSender| -> TiiResult<()> {
+          Ok(())
+        })
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str("GET /required HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: a\r\nSec-WebSocket-Protocol: b\r\n\r\n");
+  let _ = server.handle_connection(stream.to_stream());
+  let data = stream.copy_written_data_to_string();
+  assert!(data.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{data}");
+  assert!(data.ends_with("\r\n\r\noffered: a, b"), "{data}");
+}
@@ -0,0 +1,184 @@
+use crate::mock_stream::MockStream;
+use crate::ws_frames::{after_handshake, masked_frame, parse_frames};
+use libflate::zlib::{EncodeOptions, Encoder, FlushMode};
+use std::io::{Read, Write};
+use tii::{
+  PerMessageDeflate, RequestContext, RequestHeadParsingError, ServerBuilder, TiiResult,
+  WebsocketMessage, WebsocketReceiver, WebsocketSender,
+};
+
+mod mock_stream;
+mod ws_frames;
+
+const HANDSHAKE: &str = "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+
+/// Runs the handler on a websocket connection and returns the frames written by the server.
+fn run<F>(deflate: bool, frames: &[u8], handler: F) -> Vec<(u8, Vec<u8>)>
+where
+  F: Fn(&RequestContext, WebsocketReceiver, WebsocketSender) -> TiiResult<()>
+    + Send
+    + Sync
+    + 'static,
+{
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      let route = rt.ws_get("/ws");
+      match deflate {
+        true => route.permessage_deflate(PerMessageDeflate::new()).endpoint(handler),
+        false => route.endpoint(handler),
+      }
+    })
+    .expect("ERR")
+    .build();
+
+  let mut data = HANDSHAKE.as_bytes().to_vec();
+  data.extend_from_slice(frames);
+  let stream = MockStream::with_slice(data.as_slice());
+  server.handle_connection(stream.to_stream()).unwrap();
+  parse_frames(after_handshake(&stream.copy_written_data()))
+}
+
+/// Returns the payloads of the individual reads.
+fn read_chunks(reader: &mut impl Read) -> std::io::Result<Vec<Vec<u8>>> {
+  let mut chunks = Vec::new();
+  let mut buffer = [0u8; 64];
+  loop {
+    match reader.read(&mut buffer)? {
+      0 => return Ok(chunks),
+      count => chunks.push(buffer[..count].to_vec()),
+    }
+  }
+}
+
+#[test]
+pub fn tc88_read_stream() {
+  let mut frames = masked_frame(0x01, b"h\xC3");
+  frames.extend(masked_frame(0x89, b""));
+  frames.extend(masked_frame(0x00, b"\xA9llo"));
+  frames.extend(masked_frame(0x80, b"!"));
+  frames.extend(masked_frame(0x82, b"abc"));
+  frames.extend(masked_frame(0x02, b"skip"));
+  frames.extend(masked_frame(0x80, b"ped"));
+  frames.extend(masked_frame(0x81, b"after"));
+  frames.extend(masked_frame(0x88, b""));
+
+  let written = run(false, &frames, |_, mut rx, _| {
+    let mut reader = rx.read_message_stream()?.unwrap();
+    assert!(reader.is_text());
+    assert_eq!(
+      read_chunks(&mut reader)?,
+      vec![b"h\xC3".to_vec(), b"\xA9llo".to_vec(), b"!".to_vec()]
+    );
+    assert!(reader.is_finished());
+    assert!(matches!(rx.unhandled(), Some(WebsocketMessage::Ping)));
+
+    let mut reader = rx.read_message_stream()?.unwrap();
+    assert!(!reader.is_text());
+    assert_eq!(read_chunks(&mut reader)?, vec![b"abc".to_vec()]);
+
+    //The rest of a message is skipped if the reader is dropped early.
+    let mut reader = rx.read_message_stream()?.unwrap();
+    let mut buffer = [0u8; 2];
+    reader.read_exact(&mut buffer)?;
+    assert!(!reader.is_finished());
+    drop(reader);
+
+    assert!(matches!(rx.read_message()?, Some(WebsocketMessage::Text(text)) if text == "after"));
+    assert!(rx.read_message_stream()?.is_none());
+    assert!(matches!(rx.unhandled(), Some(WebsocketMessage::Close(None))));
+    Ok(())
+  });
+  assert_eq!(written, vec![(0x88, Vec::new())]);
+}
+
+#[test]
+pub fn tc88_read_stream_invalid_utf8() {
+  for frames in [
+    [masked_frame(0x01, b"\xC3"), masked_frame(0x80, b"(")].concat(),
+    masked_frame(0x81, b"ok\xC3"),
+  ] {
+    let written = run(false, &frames, |_, mut rx, _| {
+      let error = rx.read_message_stream().and_then(|reader| {
+        read_chunks(&mut reader.unwrap())?;
+        Ok(())
+      });
+      assert!(matches!(
+        error.unwrap_err().downcast_ref::<RequestHeadParsingError>(),
+        Some(RequestHeadParsingError::WebSocketTextMessageIsNotUtf8(_))
+      ));
+      Ok(())
+    });
+    assert_eq!(written, vec![(0x88, vec![0x03, 0xEF])]);
+  }
+}
+
+#[test]
+pub fn tc88_write_stream() {
+  let written = run(false, &[], |_, _, tx| {
+    let pinger = tx.clone();
+    let mut writer = tx.binary_writer(4)?;
+    writer.write_all(b"0123456789")?;
+    pinger.ping()?;
+    writer.flush()?;
+    writer.write_all(b"")?;
+    writer.finish()?;
+
+    let mut writer = tx.text_writer(100)?;
+    writer.write_all(b"hi")?;
+    drop(writer);
+
+    tx.binary_writer(3)?.write_all(b"abcdef")?;
+    tx.close()
+  });
+
+  assert_eq!(
+    written,
+    vec![
+      (0x02, b"0123".to_vec()),
+      (0x00, b"4567".to_vec()),
+      (0x89, Vec::new()),
+      (0x00, b"89".to_vec()),
+      (0x80, Vec::new()),
+      (0x81, b"hi".to_vec()),
+      (0x02, b"abc".to_vec()),
+      (0x80, b"def".to_vec()),
+      (0x88, Vec::new()),
+    ]
+  );
+}
+
+#[test]
+pub fn tc88_deflate() {
+  let mut encoder =
+    Encoder::with_options(Vec::new(), EncodeOptions::new().flush_mode(FlushMode::Sync)).unwrap();
+  encoder.write_all(b"compressed compressed compressed").unwrap();
+  encoder.flush().unwrap();
+  let compressed = encoder.into_inner();
+  //Remove the zlib header and the tail of the sync flush.
+  let compressed = &compressed[2..compressed.len() - 4];
+  let (first, second) = compressed.split_at(compressed.len() / 2);
+  let mut frames = masked_frame(0x42, first);
+  frames.extend(masked_frame(0x80, second));
+
+  let written = run(true, &frames, |_, mut rx, tx| {
+    let mut reader = rx.read_message_stream()?.unwrap();
+    let mut message = Vec::new();
+    reader.read_to_end(&mut message)?;
+    assert_eq!(message, b"compressed compressed compressed");
+
+    let mut writer = tx.text_writer(10)?;
+    writer.write_all(b"hello hello hello hello")?;
+    writer.finish()?;
+    tx.close()
+  });
+
+  //Only the first frame of the message has RSV1 set.
+  let first_bytes: Vec<u8> = written.iter().map(|(first, _)| *first).collect();
+  assert_eq!(first_bytes, vec![0x41, 0x00, 0x80, 0x88]);
+
+  let mut payload: Vec<u8> = written[..3].iter().flat_map(|(_, payload)| payload.clone()).collect();
+  payload.extend_from_slice(&[0x00, 0x00, 0xFF, 0xFF, 0x03, 0x00]);
+  let mut message = Vec::new();
+  libflate::deflate::Decoder::new(payload.as_slice()).read_to_end(&mut message).unwrap();
+  assert_eq!(message, b"hello hello hello hello");
+}
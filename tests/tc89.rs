@@ -0,0 +1,210 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tii::{
+  ReadMessageTimeoutResult, RequestContext, RequestHeadParsingError, ServerBuilder, TiiError,
+  TiiResult, WebsocketMessage, WebsocketReceiver, WebsocketRouteBuilder, WebsocketSender,
+};
+
+mod ws_frames;
+
+const HANDSHAKE: &[u8] = b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+
+type Outcome = Arc<Mutex<(Vec<WebsocketMessage>, Option<TiiError>)>>;
+
+struct Client {
+  stream: TcpStream,
+  server: JoinHandle<()>,
+  outcome: Outcome,
+}
+
+impl Client {
+  fn masked_frame(&mut self, first_byte: u8, payload: &[u8]) {
+    self.stream.write_all(&ws_frames::masked_frame(first_byte, payload)).unwrap();
+  }
+
+  fn read_frame(&mut self) -> (u8, Vec<u8>) {
+    let mut header = [0u8; 2];
+    self.stream.read_exact(&mut header).unwrap();
+    assert!(header[1] < 126);
+    let mut payload = vec![0u8; header[1] as usize];
+    self.stream.read_exact(&mut payload).unwrap();
+    (header[0], payload)
+  }
+
+  fn finish(self) -> (Vec<WebsocketMessage>, Option<TiiError>) {
+    drop(self.stream);
+    self.server.join().unwrap();
+    std::mem::take(&mut *self.outcome.lock().unwrap())
+  }
+}
+
+/// Connects to a websocket route that collects the messages it receives until the web socket is closed.
+/// The handler is busy for the given duration after every message.
+fn connect(
+  route: fn(WebsocketRouteBuilder) -> WebsocketRouteBuilder,
+  read_timeout: Option<Duration>,
+  busy: Duration,
+) -> Client {
+  let outcome: Outcome = Arc::new(Mutex::new((Vec::new(), None)));
+  let outcome_clone = outcome.clone();
+  let handler =
+    move |_: &RequestContext, mut rx: WebsocketReceiver, _: WebsocketSender| -> TiiResult<()> {
+      let mut outcome = outcome_clone.lock().unwrap();
+      loop {
+        let result = match read_timeout {
+          Some(timeout) => rx.read_message_timeout(Some(timeout)).map(|res| match res {
+            ReadMessageTimeoutResult::Message(message) => Some(message),
+            ReadMessageTimeoutResult::Timeout => Some(WebsocketMessage::Text("timeout".into())),
+            ReadMessageTimeoutResult::Closed => None,
+          }),
+          None => rx.read_message(),
+        };
+        match result {
+          Ok(Some(message)) => {
+            outcome.0.push(message);
+            std::thread::sleep(busy);
+          }
+          Ok(None) => return Ok(()),
+          Err(err) => {
+            outcome.1 = Some(err);
+            return Ok(());
+          }
+        }
+      }
+    };
+
+  let server = ServerBuilder::default()
+    .router(|rt| route(rt.ws_get("/ws")).endpoint(handler))
+    .expect("ERR")
+    .build();
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let address = listener.local_addr().unwrap();
+  let server = std::thread::spawn(move || {
+    let (stream, _) = listener.accept().unwrap();
+    let _ = server.handle_connection(stream);
+  });
+
+  let mut stream = TcpStream::connect(address).unwrap();
+  stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+  stream.write_all(HANDSHAKE).unwrap();
+  let mut head = Vec::new();
+  while !head.ends_with(b"\r\n\r\n") {
+    let mut byte = [0u8];
+    stream.read_exact(&mut byte).unwrap();
+    head.push(byte[0]);
+  }
+  assert!(head.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+
+  Client { stream, server, outcome }
+}
+
+fn assert_error(error: Option<TiiError>, expected: RequestHeadParsingError) {
+  assert_eq!(error.unwrap().downcast_ref::<RequestHeadParsingError>(), Some(&expected));
+}
+
+#[test]
+pub fn tc89_pong_timeout() {
+  let mut client = connect(
+    |route| route.ping_interval(Duration::from_millis(50)).pong_timeout(Duration::from_millis(30)),
+    None,
+    Duration::ZERO,
+  );
+
+  //Answering the ping keeps the web socket open.
+  assert_eq!(client.read_frame(), (0x89, Vec::new()));
+  client.masked_frame(0x8A, b"");
+  assert_eq!(client.read_frame(), (0x89, Vec::new()));
+  //Not answering closes it once the pong timeout expires.
+  assert_eq!(client.read_frame(), (0x88, vec![0x03, 0xF3]));
+
+  let (messages, error) = client.finish();
+  assert!(matches!(messages.as_slice(), [WebsocketMessage::Pong]));
+  assert_error(error, RequestHeadParsingError::WebSocketPongTimeout);
+}
+
+#[test]
+pub fn tc89_idle_timeout() {
+  let mut client = connect(
+    |route| route.ping_interval(Duration::from_millis(30)).idle_timeout(Duration::from_millis(200)),
+    None,
+    Duration::ZERO,
+  );
+
+  client.masked_frame(0x81, b"hi");
+  //Pongs keep the connection alive but do not count as activity.
+  let mut pings = 0;
+  loop {
+    match client.read_frame() {
+      (0x89, _) => {
+        pings += 1;
+        client.masked_frame(0x8A, b"");
+      }
+      close => {
+        assert_eq!(close, (0x88, vec![0x03, 0xE9]));
+        break;
+      }
+    }
+  }
+  assert!(pings >= 2, "{pings}");
+
+  let (messages, error) = client.finish();
+  assert!(matches!(messages.first(), Some(WebsocketMessage::Text(text)) if text == "hi"));
+  assert_error(error, RequestHeadParsingError::WebSocketIdleTimeout);
+}
+
+#[test]
+pub fn tc89_read_message_timeout() {
+  let mut client = connect(
+    |route| route.ping_interval(Duration::from_millis(100)),
+    Some(Duration::from_millis(40)),
+    Duration::ZERO,
+  );
+
+  //The timeout of read_message_timeout still expires while pings are scheduled.
+  assert_eq!(client.read_frame(), (0x89, Vec::new()));
+  client.masked_frame(0x88, b"");
+  assert_eq!(client.read_frame(), (0x88, Vec::new()));
+
+  let (messages, error) = client.finish();
+  assert!(error.is_none());
+  let timeouts = messages
+    .iter()
+    .filter(|message| matches!(message, WebsocketMessage::Text(text) if text == "timeout"))
+    .count();
+  assert!(timeouts >= 2, "{timeouts}");
+  assert!(matches!(messages.last(), Some(WebsocketMessage::Close(None))));
+}
+
+#[test]
+pub fn tc89_pong_received_while_busy() {
+  let mut client = connect(
+    |route| route.ping_interval(Duration::from_millis(50)).pong_timeout(Duration::from_millis(100)),
+    Some(Duration::from_millis(60)),
+    Duration::from_millis(300),
+  );
+
+  //The handler is busy after its read timed out, the pong waits in the OS buffer until after the pong timeout.
+  assert_eq!(client.read_frame(), (0x89, Vec::new()));
+  std::thread::sleep(Duration::from_millis(50));
+  client.masked_frame(0x8A, b"");
+  client.masked_frame(0x88, b"");
+  loop {
+    match client.read_frame() {
+      (0x89, _) => continue,
+      close => {
+        assert_eq!(close, (0x88, Vec::new()));
+        break;
+      }
+    }
+  }
+
+  let (messages, error) = client.finish();
+  assert!(error.is_none(), "{error:?}");
+  assert!(matches!(messages.first(), Some(WebsocketMessage::Text(text)) if text == "timeout"));
+  assert!(messages.iter().any(|message| matches!(message, WebsocketMessage::Pong)));
+  assert!(matches!(messages.last(), Some(WebsocketMessage::Close(None))));
+}
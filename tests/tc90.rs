@@ -0,0 +1,119 @@
+use crate::mock_stream::MockStream;
+use std::time::Duration;
+use tii::{MimeType, RequestContext, Response, ResponseBody, ServerBuilder, StatusCode, TiiResult};
+
+mod mock_stream;
+
+fn dummy_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("Okay!", MimeType::TextPlain))
+}
+
+fn chunked_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(
+    Response::new(StatusCode::OK).with_body(ResponseBody::chunked(|sink| sink.write_all(b"Okay!"))),
+  )
+}
+
+/// Sends the requests over a single connection and returns the raw responses.
+fn run(builder: ServerBuilder, requests: &[&str]) -> Vec<String> {
+  let server = builder
+    .router(|rt| rt.route_get("/dummy", dummy_route)?.route_get("/chunked", chunked_route))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(requests.concat().as_str());
+  server.handle_connection(stream.to_stream()).expect("ERR");
+  let data = stream.copy_written_data_to_string();
+  let mut responses: Vec<String> =
+    data.split("HTTP/1.").skip(1).map(|response| format!("HTTP/1.{response}")).collect();
+  if let Some(last) = responses.last_mut() {
+    *last = last.trim_end().to_string();
+  }
+  responses
+}
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+  response.lines().find_map(|line| line.strip_prefix(format!("{name}: ").as_str()))
+}
+
+#[test]
+pub fn tc90_http11_persistent_by_default() {
+  let responses = run(
+    ServerBuilder::default(),
+    &["GET /dummy HTTP/1.1\r\n\r\n", "GET /dummy HTTP/1.1\r\nConnection: Upgrade, close\r\n\r\n"],
+  );
+  assert_eq!(responses.len(), 2);
+  assert_eq!(header(&responses[0], "Connection"), Some("Keep-Alive"));
+  assert_eq!(header(&responses[0], "Keep-Alive"), None);
+  assert_eq!(header(&responses[1], "Connection"), Some("Close"));
+
+  //The connection is closed after the client asked for it.
+  let responses = run(
+    ServerBuilder::default(),
+    &["GET /dummy HTTP/1.1\r\nConnection: CLOSE\r\n\r\n", "GET /dummy HTTP/1.1\r\n\r\n"],
+  );
+  assert_eq!(responses.len(), 1);
+  assert_eq!(header(&responses[0], "Connection"), Some("Close"));
+}
+
+#[test]
+pub fn tc90_http10_opt_in() {
+  let responses =
+    run(ServerBuilder::default(), &["GET /dummy HTTP/1.0\r\n\r\n", "GET /dummy HTTP/1.0\r\n\r\n"]);
+  assert_eq!(responses.len(), 1);
+  assert_eq!(header(&responses[0], "Connection"), None);
+
+  let responses = run(
+    ServerBuilder::default(),
+    &[
+      "GET /dummy HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n",
+      "GET /dummy HTTP/1.0\r\nConnection: foo, keep-alive\r\n\r\n",
+      "GET /dummy HTTP/1.0\r\n\r\n",
+      "GET /dummy HTTP/1.0\r\n\r\n",
+    ],
+  );
+  assert_eq!(responses.len(), 3);
+  assert!(responses[0].starts_with("HTTP/1.0 200 OK\r\n"));
+  assert_eq!(header(&responses[0], "Connection"), Some("Keep-Alive"));
+  assert_eq!(header(&responses[1], "Connection"), Some("Keep-Alive"));
+  assert_eq!(header(&responses[2], "Connection"), None);
+
+  //A body without a length can only be delimited by closing the connection.
+  let responses = run(
+    ServerBuilder::default(),
+    &[
+      "GET /chunked HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+      "GET /dummy HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+    ],
+  );
+  assert_eq!(responses.len(), 1);
+  assert_eq!(header(&responses[0], "Connection"), None);
+}
+
+#[test]
+pub fn tc90_keep_alive_hints() {
+  let builder = ServerBuilder::default()
+    .with_keep_alive_timeout(Some(Duration::from_secs(5)))
+    .unwrap()
+    .with_max_requests_per_connection(Some(2))
+    .unwrap();
+  assert_eq!(builder.build().max_requests_per_connection(), Some(2));
+
+  let builder = ServerBuilder::default()
+    .with_keep_alive_timeout(Some(Duration::from_secs(5)))
+    .unwrap()
+    .with_max_requests_per_connection(Some(2))
+    .unwrap();
+  let request = "GET /dummy HTTP/1.1\r\n\r\n";
+  let responses = run(builder, &[request, request, request]);
+  assert_eq!(responses.len(), 2);
+  assert_eq!(header(&responses[0], "Connection"), Some("Keep-Alive"));
+  assert_eq!(header(&responses[0], "Keep-Alive"), Some("timeout=5, max=1"));
+  assert_eq!(header(&responses[1], "Connection"), Some("Close"));
+  assert_eq!(header(&responses[1], "Keep-Alive"), None);
+
+  let builder =
+    ServerBuilder::default().with_keep_alive_timeout(Some(Duration::from_secs(30))).unwrap();
+  let responses = run(builder, &["GET /dummy HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"]);
+  assert_eq!(header(&responses[0], "Keep-Alive"), Some("timeout=30"));
+}
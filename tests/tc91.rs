@@ -0,0 +1,195 @@
+use crate::mock_stream::MockStream;
+use libflate::{gzip, zlib};
+use std::io::{Cursor, Read};
+use tii::{
+  MimeType, MimeTypeWithCharset, RequestContext, Response, ResponseBody, ServerBuilder, TiiResult,
+};
+
+mod mock_stream;
+
+const DATA: &[u8] = b"Hello World";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Framing {
+  Length,
+  Chunked,
+  Close,
+}
+
+type BodyCase = (&'static str, fn() -> ResponseBody, Framing);
+
+/// Every kind of response body with the framing it gets for http 1.1 clients.
+fn bodies() -> Vec<BodyCase> {
+  vec![
+    ("entity", || ResponseBody::from_entity(DATA.to_vec(), serialize), Framing::Length),
+    ("data", || ResponseBody::from_slice(DATA), Framing::Length),
+    ("static", || ResponseBody::from_static_slice(DATA), Framing::Length),
+    ("text", || ResponseBody::from_string("Hello World"), Framing::Length),
+    ("file", || ResponseBody::from_file(Cursor::new(DATA)).unwrap(), Framing::Length),
+    ("file range", || ResponseBody::from_file_range(Cursor::new(DATA), 0, 11), Framing::Length),
+    ("stream", || ResponseBody::streamed(|sink| sink.write_all(DATA)), Framing::Close),
+    ("chunked", || ResponseBody::chunked(|sink| sink.write_all(DATA)), Framing::Chunked),
+    ("events", || ResponseBody::event_stream(None, |tx| tx.data("Hello World")), Framing::Chunked),
+    ("gzip data", || ResponseBody::from_data_with_gzip_in_memory(DATA), Framing::Length),
+    ("gzip file", gzip_file, Framing::Length),
+    ("gzip stream", || ResponseBody::chunked_gzip(|sink| sink.write_all(DATA)), Framing::Chunked),
+    (
+      "gzip chunked file",
+      || ResponseBody::from_file_with_chunked_gzip(Cursor::new(DATA)),
+      Framing::Chunked,
+    ),
+    ("deflate data", || ResponseBody::from_data_with_deflate_in_memory(DATA), Framing::Length),
+    (
+      "deflate stream",
+      || ResponseBody::chunked_deflate(|sink| sink.write_all(DATA)),
+      Framing::Chunked,
+    ),
+    (
+      "deflate chunked file",
+      || ResponseBody::from_file_with_chunked_deflate(Cursor::new(DATA)),
+      Framing::Chunked,
+    ),
+  ]
+}
+
+fn serialize(_: &MimeTypeWithCharset, data: Vec<u8>) -> TiiResult<Vec<u8>> {
+  Ok(data)
+}
+
+fn gzip_file() -> ResponseBody {
+  let mut encoder = gzip::Encoder::new(Vec::new()).unwrap();
+  std::io::Write::write_all(&mut encoder, DATA).unwrap();
+  let data = encoder.finish().into_result().unwrap();
+  ResponseBody::from_externally_gzipped_file(Cursor::new(data)).unwrap()
+}
+
+struct ParsedResponse {
+  head: String,
+  body: Vec<u8>,
+}
+
+impl ParsedResponse {
+  fn header(&self, name: &str) -> Option<&str> {
+    self.head.lines().find_map(|line| line.strip_prefix(format!("{name}: ").as_str()))
+  }
+
+  fn decoded_body(&self) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    match self.header("Content-Encoding") {
+      Some("gzip") => gzip::Decoder::new(self.body.as_slice()).unwrap().read_to_end(&mut decoded),
+      Some("deflate") => {
+        zlib::Decoder::new(self.body.as_slice()).unwrap().read_to_end(&mut decoded)
+      }
+      _ => return self.body.clone(),
+    }
+    .unwrap();
+    decoded
+  }
+}
+
+/// Splits the written data into the responses, a response without length or chunks ends at the end of the data.
+fn parse_responses(mut data: &[u8]) -> Vec<ParsedResponse> {
+  let mut responses = Vec::new();
+  while !data.is_empty() {
+    let head_end = data.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8(data[..head_end].to_vec()).unwrap();
+    data = &data[head_end..];
+    let mut response = ParsedResponse { head, body: Vec::new() };
+    if let Some(length) = response.header("Content-Length") {
+      let (body, rest) = data.split_at(length.parse().unwrap());
+      response.body = body.to_vec();
+      data = rest;
+    } else if response.header("Transfer-Encoding") == Some("chunked") {
+      loop {
+        let line_end = data.windows(2).position(|w| w == b"\r\n").unwrap();
+        let size =
+          usize::from_str_radix(std::str::from_utf8(&data[..line_end]).unwrap(), 16).unwrap();
+        let chunk = &data[line_end + 2..line_end + 2 + size];
+        response.body.extend_from_slice(chunk);
+        data = &data[line_end + 4 + size..];
+        if size == 0 {
+          break;
+        }
+      }
+    } else {
+      response.body = data.to_vec();
+      data = &[];
+    }
+    responses.push(response);
+  }
+  responses
+}
+
+fn serve(body: fn() -> ResponseBody, version: &str) -> Vec<ParsedResponse> {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.route_get("/", move |_: &RequestContext| -> TiiResult<Response> {
+        Ok(Response::ok(body(), MimeType::TextPlain))
+      })
+    })
+    .expect("ERR")
+    .build();
+
+  let request = format!("GET / {version}\r\nConnection: keep-alive\r\n\r\n");
+  let stream = MockStream::with_str(request.repeat(2).as_str());
+  server.handle_connection(stream.to_stream()).expect("ERR");
+  parse_responses(&stream.copy_written_data())
+}
+
+fn expected_body(name: &str) -> &'static [u8] {
+  match name {
+    "events" => b"data: Hello World\n\n",
+    _ => DATA,
+  }
+}
+
+#[test]
+pub fn tc91_http11_framing() {
+  for (name, body, framing) in bodies() {
+    let responses = serve(body, "HTTP/1.1");
+    let response = &responses[0];
+    assert!(response.head.starts_with("HTTP/1.1 200 OK\r\n"), "{name}");
+    assert_eq!(response.decoded_body(), expected_body(name), "{name}");
+    match framing {
+      Framing::Length => {
+        assert!(response.header("Content-Length").is_some(), "{name}");
+        assert_eq!(response.header("Connection"), Some("Keep-Alive"), "{name}");
+        assert_eq!(responses.len(), 2, "{name}");
+      }
+      Framing::Chunked => {
+        assert_eq!(response.header("Transfer-Encoding"), Some("chunked"), "{name}");
+        assert_eq!(response.header("Connection"), Some("Keep-Alive"), "{name}");
+        assert_eq!(responses.len(), 2, "{name}");
+      }
+      Framing::Close => {
+        assert_eq!(response.header("Content-Length"), None, "{name}");
+        assert_eq!(response.header("Transfer-Encoding"), None, "{name}");
+        assert_eq!(response.header("Connection"), Some("Close"), "{name}");
+        assert_eq!(responses.len(), 1, "{name}");
+      }
+    }
+  }
+}
+
+#[test]
+pub fn tc91_http10_framing() {
+  for (name, body, framing) in bodies() {
+    let responses = serve(body, "HTTP/1.0");
+    let response = &responses[0];
+    assert!(response.head.starts_with("HTTP/1.0 200 OK\r\n"), "{name}");
+    assert_eq!(response.header("Transfer-Encoding"), None, "{name}");
+    assert_eq!(response.decoded_body(), expected_body(name), "{name}");
+    match framing {
+      Framing::Length => {
+        assert!(response.header("Content-Length").is_some(), "{name}");
+        assert_eq!(response.header("Connection"), Some("Keep-Alive"), "{name}");
+        assert_eq!(responses.len(), 2, "{name}");
+      }
+      Framing::Chunked | Framing::Close => {
+        assert_eq!(response.header("Content-Length"), None, "{name}");
+        assert_eq!(response.header("Connection"), None, "{name}");
+        assert_eq!(responses.len(), 1, "{name}");
+      }
+    }
+  }
+}
@@ -0,0 +1,157 @@
+use crate::mock_stream::MockStream;
+use std::sync::{Arc, Mutex};
+use tii::{
+  CompressionFilter, HttpHeader, HttpHeaderName, MimeType, RequestContext, Response, ResponseBody,
+  ServerBuilder, TiiResult,
+};
+
+mod mock_stream;
+
+type Seen = Arc<Mutex<Vec<(Option<Vec<HttpHeader>>, Vec<u8>)>>>;
+
+/// Sends the request to an endpoint that reads the request body and returns what it saw and the written response.
+fn upload(request: &[u8]) -> (Seen, String) {
+  let seen: Seen = Arc::new(Mutex::new(Vec::new()));
+  let seen_clone = seen.clone();
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.route_post("/upload", move |ctx: &RequestContext| -> TiiResult<Response> {
+        let body = ctx.request_body().unwrap();
+        //Trailers are not known before the body has been read.
+        assert_eq!(body.trailers()?, None);
+        let data = body.read_to_vec()?;
+        seen_clone.lock().unwrap().push((body.trailers()?, data));
+        Ok(Response::no_content())
+      })
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_slice(request);
+  let _ = server.handle_connection(stream.to_stream());
+  (seen, stream.copy_written_data_to_string())
+}
+
+#[test]
+pub fn tc92_request_trailers() {
+  let (seen, response) = upload(b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHello\r\n0\r\nChecksum: abc\r\nX-Status:0\r\n\r\nPOST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nHi\r\n0\r\n\r\n");
+  assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"), "{response}");
+  let seen = seen.lock().unwrap();
+  assert_eq!(seen.len(), 2);
+  assert_eq!(
+    seen[0],
+    (
+      Some(vec![HttpHeader::new("Checksum", "abc"), HttpHeader::new("X-Status", "0")]),
+      b"Hello".to_vec()
+    )
+  );
+  assert_eq!(seen[1], (Some(Vec::new()), b"Hi".to_vec()));
+
+  //Bodies with a length have no trailers.
+  let (seen, _) = upload(b"POST /upload HTTP/1.1\r\nContent-Length: 2\r\n\r\nHi");
+  assert_eq!(seen.lock().unwrap()[0], (None, b"Hi".to_vec()));
+}
+
+#[test]
+pub fn tc92_request_trailers_encoded() {
+  let gz = include_bytes!("./tc53.gz").as_ref();
+  let mut request =
+    b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Encoding: gzip\r\n\r\n2B\r\n"
+      .to_vec();
+  request.extend_from_slice(gz);
+  request.extend_from_slice(b"\r\n0\r\nChecksum: abc\r\n\r\n");
+
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.route_post("/upload", |ctx: &RequestContext| -> TiiResult<Response> {
+        let body = ctx.request_body().unwrap();
+        assert_eq!(body.read_to_vec()?, b"{ \"mydummy\" : \"json\" }\n");
+        assert_eq!(body.trailer("checksum")?, Some("abc".to_string()));
+        Ok(Response::no_content())
+      })
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_slice(request.as_slice());
+  server.handle_connection(stream.to_stream()).unwrap();
+  assert!(stream.copy_written_data_to_string().starts_with("HTTP/1.1 204 No Content\r\n"));
+}
+
+#[test]
+pub fn tc92_request_trailers_malformed() {
+  for trailer in [&b"Checksum abc\r\n\r\n"[..], b"Check sum: abc\r\n\r\n", b": abc\r\n\r\n"] {
+    let mut request =
+      b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nHi\r\n0\r\n".to_vec();
+    request.extend_from_slice(trailer);
+    let (seen, _) = upload(&request);
+    assert!(seen.lock().unwrap().is_empty());
+  }
+}
+
+fn download(request: &str) -> String {
+  let server = ServerBuilder::default()
+    .router(|rt| {
+      rt.route_get("/download", |_: &RequestContext| -> TiiResult<Response> {
+        let body = ResponseBody::chunked(|sink| {
+          sink.write_all(b"Hello")?;
+          assert!(sink.set_trailer("Content-Length", "5").is_err());
+          assert!(sink.set_trailer("Checksum", "a\r\nb").is_err());
+          assert!(sink.set_trailer("X-Unannounced", "1").is_err());
+          sink.set_trailer("Checksum", "old")?;
+          sink.set_trailer("Checksum", "abc")?;
+          sink.set_trailer("X-Status", "0")
+        })
+        .with_trailer("Checksum")?
+        .with_trailer(HttpHeaderName::from("X-Status"))?;
+        Ok(Response::ok(body, MimeType::TextPlain))
+      })?
+      .with_response_filter(CompressionFilter::new())
+    })
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(request);
+  server.handle_connection(stream.to_stream()).unwrap();
+  stream.copy_written_data_to_string()
+}
+
+#[test]
+pub fn tc92_response_trailers() {
+  let response = download("GET /download HTTP/1.1\r\nConnection: close\r\n\r\n");
+  assert_eq!(response, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nVary: Accept-Encoding\r\nConnection: Close\r\nTransfer-Encoding: chunked\r\nTrailer: Checksum, X-Status\r\n\r\n5\r\nHello\r\n0\r\nChecksum: abc\r\nX-Status: 0\r\n\r\n");
+
+  //Compressed bodies keep their trailers.
+  let response =
+    download("GET /download HTTP/1.1\r\nConnection: close\r\nAccept-Encoding: gzip\r\n\r\n");
+  assert!(response.contains("\r\nContent-Encoding: gzip\r\n"), "{response}");
+  assert!(response.contains("\r\nTrailer: Checksum, X-Status\r\n"), "{response}");
+  assert!(response.ends_with("\r\n0\r\nChecksum: abc\r\nX-Status: 0\r\n\r\n"), "{response}");
+
+  //Http 1.0 clients get neither chunks nor trailers.
+  let response = download("GET /download HTTP/1.0\r\n\r\n");
+  assert_eq!(
+    response,
+    "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\nVary: Accept-Encoding\r\n\r\nHello"
+  );
+}
+
+#[test]
+pub fn tc92_invalid_trailer_names() {
+  for name in [
+    "Content-Length",
+    "Transfer-Encoding",
+    "Trailer",
+    "Content-Encoding",
+    "Content-Type",
+    "Host",
+    "authorization",
+    "Set-Cookie",
+    "Cache-Control",
+    "Connection",
+    "Max-Forwards",
+    "Bad Name",
+  ] {
+    assert!(ResponseBody::chunked(|_| Ok(())).with_trailer(name).is_err(), "{name}");
+  }
+}
@@ -0,0 +1,198 @@
+use crate::mock_stream::MockStream;
+use tii::{
+  HttpVersion, MimeType, RequestContext, RequestHeadParsingError, Response, ServerBuilder,
+  TiiResult,
+};
+
+mod mock_stream;
+
+fn dummy_route(ctx: &RequestContext) -> TiiResult<Response> {
+  if let Some(body) = ctx.request_body() {
+    body.read_to_vec()?;
+  }
+  Ok(Response::ok("Okay!", MimeType::TextPlain))
+}
+
+fn serve(builder: ServerBuilder, request: &str) -> TiiResult<String> {
+  let server = builder.router(|rt| rt.route_any("/dummy", dummy_route)).expect("ERR").build();
+
+  let stream = MockStream::with_str(request);
+  server.handle_connection(stream.to_stream())?;
+  Ok(stream.copy_written_data_to_string())
+}
+
+fn assert_rejects(builder: ServerBuilder, request: &str, expected: RequestHeadParsingError) {
+  let err = serve(builder, request).unwrap_err();
+  assert_eq!(err.downcast_ref::<RequestHeadParsingError>(), Some(&expected), "{request:?}");
+}
+
+fn strict() -> ServerBuilder {
+  ServerBuilder::default().with_strict_parsing(true).unwrap()
+}
+
+/// The request is rejected in strict mode and accepted by the default lenient parser.
+fn assert_strict_rejects(request: &str, expected: RequestHeadParsingError) {
+  assert_rejects(strict(), request, expected);
+  let response = serve(ServerBuilder::default(), request).unwrap();
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+}
+
+#[test]
+pub fn tc93_valid_requests() {
+  let server = strict().with_min_http_version(HttpVersion::Http11).unwrap().build();
+  assert!(server.strict_parsing());
+  assert_eq!(server.min_http_version(), HttpVersion::Http11);
+  assert!(!ServerBuilder::default().build().strict_parsing());
+
+  let response = serve(
+    strict(),
+    "\r\nPOST /dummy HTTP/1.1\r\nX-Custom_Header!: a b\r\nContent-Length: 2\r\nContent-Length: 2\r\n\r\nhi",
+  )
+  .unwrap();
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+}
+
+#[test]
+pub fn tc93_content_length_with_transfer_encoding() {
+  assert_strict_rejects(
+    "POST /dummy HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n",
+    RequestHeadParsingError::ContentLengthWithTransferEncoding,
+  );
+}
+
+#[test]
+pub fn tc93_invalid_transfer_encoding() {
+  let values = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+  assert_strict_rejects(
+    "POST /dummy HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: gzip\r\n\r\n2\r\nhi\r\n0\r\n\r\n",
+    RequestHeadParsingError::InvalidTransferEncoding(values(&["chunked", "gzip"])),
+  );
+  assert_rejects(
+    strict(),
+    "POST /dummy HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n",
+    RequestHeadParsingError::InvalidTransferEncoding(values(&["chunked, gzip"])),
+  );
+  assert_rejects(
+    strict(),
+    "POST /dummy HTTP/1.1\r\nTransfer-Encoding: chunked, chunked\r\n\r\n",
+    RequestHeadParsingError::InvalidTransferEncoding(values(&["chunked, chunked"])),
+  );
+
+  let response = serve(
+    strict(),
+    "POST /dummy HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n",
+  )
+  .unwrap();
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+}
+
+#[test]
+pub fn tc93_conflicting_content_length() {
+  assert_rejects(
+    strict(),
+    "POST /dummy HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\nhi!",
+    RequestHeadParsingError::ConflictingContentLength(vec!["2".to_string(), "3".to_string()]),
+  );
+  assert_rejects(
+    strict(),
+    "POST /dummy HTTP/1.1\r\nContent-Length: 2, 3\r\n\r\nhi!",
+    RequestHeadParsingError::ConflictingContentLength(vec!["2".to_string(), "3".to_string()]),
+  );
+}
+
+#[test]
+pub fn tc93_obs_fold() {
+  assert_rejects(
+    strict(),
+    "GET /dummy HTTP/1.1\r\nX-Folded: a\r\n b\r\n\r\n",
+    RequestHeadParsingError::HeaderObsFold,
+  );
+}
+
+#[test]
+pub fn tc93_whitespace_before_colon() {
+  assert_rejects(
+    strict(),
+    "GET /dummy HTTP/1.1\r\nContent-Length : 0\r\n\r\n",
+    RequestHeadParsingError::HeaderWhitespaceBeforeColon,
+  );
+}
+
+#[test]
+pub fn tc93_bare_line_feed() {
+  assert_strict_rejects("\nGET /dummy HTTP/1.1\r\n\r\n", RequestHeadParsingError::BareLineFeed);
+  assert_rejects(strict(), "GET /dummy HTTP/1.1\n\r\n", RequestHeadParsingError::BareLineFeed);
+  assert_rejects(
+    strict(),
+    "GET /dummy HTTP/1.1\r\nX-Custom: a\n\r\n",
+    RequestHeadParsingError::BareLineFeed,
+  );
+  assert_rejects(strict(), "GET /dummy HTTP/1.1\r\n\n", RequestHeadParsingError::BareLineFeed);
+}
+
+#[test]
+pub fn tc93_bare_carriage_return() {
+  assert_rejects(
+    strict(),
+    "GET /dummy HTTP/1.1\r\nX-Custom: a\rb\r\n\r\n",
+    RequestHeadParsingError::BareCarriageReturn,
+  );
+  assert_rejects(
+    strict(),
+    "GET\r /dummy HTTP/1.1\r\n\r\n",
+    RequestHeadParsingError::BareCarriageReturn,
+  );
+}
+
+#[test]
+pub fn tc93_invalid_method_token() {
+  assert_rejects(
+    strict(),
+    "G[T /dummy HTTP/1.1\r\n\r\n",
+    RequestHeadParsingError::InvalidMethodToken("G[T".to_string()),
+  );
+}
+
+#[test]
+pub fn tc93_invalid_header_name_token() {
+  assert_strict_rejects(
+    "GET /dummy HTTP/1.1\r\nX-Cust{om}: a\r\n\r\n",
+    RequestHeadParsingError::InvalidHeaderNameToken("X-Cust{om}".to_string()),
+  );
+}
+
+#[test]
+pub fn tc93_malformed_header_line() {
+  assert_rejects(
+    strict(),
+    "GET /dummy HTTP/1.1\r\nX-Custom a\r\n\r\n",
+    RequestHeadParsingError::MalformedHeaderLine("X-Custom a".to_string()),
+  );
+}
+
+#[test]
+pub fn tc93_disabled_http_versions() {
+  let min_11 = || ServerBuilder::default().with_min_http_version(HttpVersion::Http11).unwrap();
+  let min_10 = || ServerBuilder::default().with_min_http_version(HttpVersion::Http10).unwrap();
+
+  assert_rejects(
+    min_11(),
+    "GET /dummy HTTP/1.0\r\n\r\n",
+    RequestHeadParsingError::HttpVersionDisabled(HttpVersion::Http10),
+  );
+  assert_rejects(
+    min_11(),
+    "GET /dummy\r\n",
+    RequestHeadParsingError::HttpVersionDisabled(HttpVersion::Http09),
+  );
+  assert_rejects(
+    min_10(),
+    "GET /dummy\r\n",
+    RequestHeadParsingError::HttpVersionDisabled(HttpVersion::Http09),
+  );
+
+  let response = serve(min_10(), "GET /dummy HTTP/1.0\r\n\r\n").unwrap();
+  assert!(response.starts_with("HTTP/1.0 200 OK\r\n"), "{response}");
+  let response = serve(min_11(), "GET /dummy HTTP/1.1\r\n\r\n").unwrap();
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+}
@@ -0,0 +1,124 @@
+use crate::mock_stream::MockStream;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use tii::{
+  HttpVersion, MimeType, RequestContext, RequestHeadParsingError, Response, ServerBuilder,
+  StatusCode, TiiError, TiiResult,
+};
+
+mod mock_stream;
+
+fn dummy_route(_: &RequestContext) -> TiiResult<Response> {
+  Ok(Response::ok("Okay!", MimeType::TextPlain))
+}
+
+/// Returns the response written for the request, handling the request must fail.
+fn serve(builder: ServerBuilder, request: &str) -> String {
+  let server = builder
+    .with_max_head_buffer_size(0x100)
+    .unwrap()
+    .router(|rt| rt.route_any("/dummy", dummy_route))
+    .expect("ERR")
+    .build();
+
+  let stream = MockStream::with_str(request);
+  server.handle_connection(stream.to_stream()).unwrap_err();
+  stream.copy_written_data_to_string()
+}
+
+fn status_line(builder: ServerBuilder, request: &str) -> String {
+  let response = serve(builder, request);
+  assert!(response.ends_with("\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"), "{response}");
+  response.lines().next().unwrap_or_default().to_string()
+}
+
+#[test]
+pub fn tc94_default_status_codes() {
+  let long = "a".repeat(0x100);
+  let long_query = format!("GET /dummy?a={long} HTTP/1.1\r\n\r\n");
+  let long_header = format!("GET /dummy HTTP/1.1\r\nX-Header: {long}\r\n\r\n");
+  let cases = [
+    ("GET /dummy\tHTTP/1.1\r\n\r\n", "HTTP/1.1 400 Bad Request"),
+    ("GET /dummy HTTP/1.1\r\nContent-Length: abc\r\n\r\n", "HTTP/1.1 400 Bad Request"),
+    (long_query.as_str(), "HTTP/1.1 414 Request-URI Too Long"),
+    (long_header.as_str(), "HTTP/1.1 431 Request Header Fields Too Large"),
+    ("GET /dummy HTTP/2.0\r\n\r\n", "HTTP/1.1 505 HTTP Version Not Supported"),
+    ("POST /dummy HTTP/1.1\r\nTransfer-Encoding: br\r\n\r\n", "HTTP/1.1 501 Not Implemented"),
+    (
+      "POST /dummy HTTP/1.1\r\nContent-Encoding: foo\r\n\r\n",
+      "HTTP/1.1 415 Unsupported Media Type",
+    ),
+  ];
+
+  for (request, expected) in cases {
+    assert_eq!(status_line(ServerBuilder::default(), request), expected, "{request:?}");
+  }
+
+  let builder = ServerBuilder::default().with_min_http_version(HttpVersion::Http11).unwrap();
+  assert_eq!(
+    status_line(builder, "GET /dummy HTTP/1.0\r\n\r\n"),
+    "HTTP/1.1 505 HTTP Version Not Supported"
+  );
+
+  assert_eq!(
+    status_line(ServerBuilder::default(), "GET /dummy HTTP/1.0\r\nHdr\r\n\r\n"),
+    "HTTP/1.0 400 Bad Request"
+  );
+
+  let builder = ServerBuilder::default().with_strict_parsing(true).unwrap();
+  assert_eq!(
+    status_line(builder, "GET /dummy HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n"),
+    "HTTP/1.1 400 Bad Request"
+  );
+}
+
+fn custom_handler(error: &TiiError) -> TiiResult<Option<Response>> {
+  Ok(match error.downcast_ref::<RequestHeadParsingError>() {
+    Some(RequestHeadParsingError::StatusLineNoWhitespace) => None,
+    Some(err) => Some(Response::new(StatusCode::BadRequest).with_body(format!("{err}"))),
+    None => return Err(TiiError::from_io_kind(error.kind())),
+  })
+}
+
+#[test]
+pub fn tc94_custom_handler() {
+  let builder = || ServerBuilder::default().with_parse_error_handler(custom_handler).unwrap();
+
+  assert_eq!(
+    serve(builder(), "GET /dummy HTTP/1.1\r\nHdr\r\n\r\n"),
+    "HTTP/1.1 400 Bad Request\r\nConnection: Close\r\nContent-Length: 18\r\n\r\nHeaderValueMissing"
+  );
+  assert_eq!(serve(builder(), "GET/dummyHTTP/1.1\r\n\r\n"), "");
+}
+
+#[test]
+pub fn tc94_head_read_timeout() {
+  let server = ServerBuilder::default()
+    .with_read_timeout(Some(Duration::from_millis(100)))
+    .unwrap()
+    .router(|rt| rt.route_any("/dummy", dummy_route))
+    .expect("ERR")
+    .build();
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let address = listener.local_addr().unwrap();
+  let handle = std::thread::spawn(move || {
+    let (stream, _) = listener.accept().unwrap();
+    server.handle_connection(stream).unwrap_err()
+  });
+
+  let mut client = TcpStream::connect(address).unwrap();
+  client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+  client.write_all(b"GET /dummy HTTP/1.1\r\nHdr: ").unwrap();
+  let mut response = String::new();
+  client.read_to_string(&mut response).unwrap();
+  assert_eq!(
+    response,
+    "HTTP/1.1 408 Request Timeout\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n"
+  );
+  assert!(matches!(
+    handle.join().unwrap().kind(),
+    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+  ));
+}
@@ -0,0 +1,44 @@
+#![allow(dead_code)]
+//! Raw web socket frames for tests that need frames `tii::testing::TestWebSocket` does not send,
+//! for example frames with reserved bits or fragments interleaved with control frames.
+
+/// Builds a client frame. The masking key is zero, so the payload is sent as is.
+pub fn masked_frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+  let mut frame = vec![first_byte];
+  match payload.len() {
+    len @ 0..=125 => frame.push(0x80 | len as u8),
+    len @ 126..=0xFFFF => {
+      frame.push(0x80 | 126);
+      frame.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+    len => {
+      frame.push(0x80 | 127);
+      frame.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+  }
+  frame.extend_from_slice(&[0, 0, 0, 0]);
+  frame.extend_from_slice(payload);
+  frame
+}
+
+/// Returns the data written by the server after the handshake response.
+pub fn after_handshake(written: &[u8]) -> &[u8] {
+  let header_end = written.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+  &written[header_end..]
+}
+
+/// Splits the unmasked frames written by the server into their first byte and payload.
+pub fn parse_frames(mut data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+  let mut frames = Vec::new();
+  while let [first, length, rest @ ..] = data {
+    let (length, rest) = match *length {
+      126 => (u16::from_be_bytes([rest[0], rest[1]]) as usize, &rest[2..]),
+      127 => (u64::from_be_bytes(rest[..8].try_into().unwrap()) as usize, &rest[8..]),
+      length => (length as usize, rest),
+    };
+    let (payload, rest) = rest.split_at(length);
+    frames.push((*first, payload.to_vec()));
+    data = rest;
+  }
+  frames
+}